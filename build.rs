@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // use the bundled protoc so builds do not need one installed
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    println!("cargo:rerun-if-changed=proto/domain_searcher.proto");
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/domain_searcher.proto"], &["proto"])?;
+    Ok(())
+}
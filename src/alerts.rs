@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::json;
+use tokio::time;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::config::{AlertAction, AlertMetric, AlertRule, Config, LiveConfig};
+use crate::notify::{self, WebhookFormat};
+use crate::progress::Progress;
+use crate::service::{ScanControl, ScanState};
+
+// Rule states by name for /stats/ and /metrics, which have no handle on the
+// evaluating task; rules removed on reload are dropped at the next evaluation
+static RULES: Mutex<BTreeMap<String, AlertStatus>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Ok,
+    /// The condition holds, not yet for the rule's `for`
+    Pending,
+    /// The actions ran; stays until the condition no longer holds
+    Firing,
+}
+
+/// Where a rule of `alerts.rules` stands, in /stats/
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct AlertStatus {
+    pub state: AlertState,
+    /// The metric over the last evaluated interval; absent before the first
+    pub value: Option<f64>,
+    /// When the state began, unix seconds
+    pub since_unix: u64,
+}
+
+pub fn stats() -> BTreeMap<String, AlertStatus> {
+    RULES.lock().clone()
+}
+
+// counters of the main scan at the start of an interval
+struct Mark {
+    at: Instant,
+    checked: i64,
+    found: i64,
+    retried: i64,
+    errors: BTreeMap<&'static str, i64>,
+}
+
+impl Mark {
+    fn of(prog: &Progress) -> Self {
+        let (_, checked, found, _) = prog.snapshot();
+        Self {
+            at: Instant::now(),
+            checked,
+            found,
+            retried: prog.retries().1,
+            errors: prog.errors().into_iter().collect(),
+        }
+    }
+}
+
+// The metric of a rule between two marks
+fn measure(rule: &AlertRule, a: &Mark, b: &Mark) -> f64 {
+    let secs = b.at.duration_since(a.at).as_secs_f64().max(f64::EPSILON);
+    let errors: i64 = b
+        .errors
+        .iter()
+        .filter(|(k, _)| rule.causes.is_empty() || rule.causes.iter().any(|c| c == *k))
+        .map(|(k, n)| n - a.errors.get(k).copied().unwrap_or(0))
+        .sum();
+    match rule.metric {
+        AlertMetric::ErrorPercent => {
+            // a re-check that is scheduled is an attempt that did not count as checked
+            let attempts = (b.checked - a.checked) + (b.retried - a.retried);
+            if attempts > 0 {
+                errors as f64 * 100.0 / attempts as f64
+            } else {
+                0.0
+            }
+        }
+        AlertMetric::ErrorsPerSec => errors as f64 / secs,
+        AlertMetric::Speed => (b.checked - a.checked) as f64 / secs,
+        AlertMetric::FoundPerHour => (b.found - a.found) as f64 * 3600.0 / secs,
+    }
+}
+
+/// Evaluate `alerts.rules` against the main scan every `alerts.interval`
+/// until the process exits. Only intervals the scan ran and had work in
+/// count: while it is paused or stopped the rules start over, and an idle
+/// interval (between loop passes, outside the schedule) is skipped.
+pub fn spawn(config: LiveConfig, prog: Progress, control: ScanControl, client: Client) {
+    tokio::spawn(async move {
+        let mut mark: Option<Mark> = None;
+        loop {
+            time::sleep(config.current().alerts.interval).await;
+            let cfg = config.current();
+            let next = Mark::of(&prog);
+            let prev = mark.take();
+            let now = now_unix();
+            let mut rules = RULES.lock();
+            rules.retain(|name, _| cfg.alerts.rules.iter().any(|r| &r.name == name));
+            for rule in &cfg.alerts.rules {
+                rules.entry(rule.name.clone()).or_insert(AlertStatus {
+                    state: AlertState::Ok,
+                    value: None,
+                    since_unix: now,
+                });
+            }
+            if control.state() != ScanState::Running {
+                // a rule that paused the scan can pause it again after a resume;
+                // a firing one says it resolved, as a webhook receiver waits for that
+                for rule in &cfg.alerts.rules {
+                    let Some(st) = rules.get_mut(&rule.name).filter(|st| st.state != AlertState::Ok) else { continue };
+                    if st.state == AlertState::Firing {
+                        resolved(rule, st.value.unwrap_or(0.0), &cfg, &client);
+                    }
+                    st.state = AlertState::Ok;
+                    st.since_unix = now;
+                }
+                continue;
+            }
+            let Some(prev) = prev.filter(|p| next.checked != p.checked || next.retried != p.retried || prog.queue().0 > 0) else {
+                mark = Some(next);
+                continue;
+            };
+            let secs = next.at.duration_since(prev.at).as_secs();
+            for rule in &cfg.alerts.rules {
+                let Some(st) = rules.get_mut(&rule.name) else { continue };
+                let value = measure(rule, &prev, &next);
+                st.value = Some(value);
+                let (threshold, above) = rule.threshold();
+                let holds = if above { value > threshold } else { value < threshold };
+                match (st.state, holds) {
+                    (AlertState::Ok, true) => {
+                        // it held over the whole interval
+                        st.state = AlertState::Pending;
+                        st.since_unix = now.saturating_sub(secs);
+                    }
+                    (AlertState::Pending | AlertState::Firing, false) => {
+                        if st.state == AlertState::Firing {
+                            resolved(rule, value, &cfg, &client);
+                        }
+                        st.state = AlertState::Ok;
+                        st.since_unix = now;
+                    }
+                    _ => {}
+                }
+                if st.state == AlertState::Pending && now.saturating_sub(st.since_unix) >= rule.hold.as_secs() {
+                    st.state = AlertState::Firing;
+                    st.since_unix = now;
+                    fire(rule, value, &cfg, &control, &client);
+                }
+            }
+            mark = Some(next);
+        }
+    });
+}
+
+fn describe(rule: &AlertRule, value: f64) -> String {
+    let (threshold, above) = rule.threshold();
+    let mut out = format!(
+        "{} {:.2} {} {}",
+        rule.metric.as_str(),
+        value,
+        if above { "above" } else { "below" },
+        threshold
+    );
+    if !rule.hold.is_zero() {
+        out.push_str(&format!(" for {:?}", rule.hold));
+    }
+    out
+}
+
+fn fire(rule: &AlertRule, value: f64, cfg: &Config, control: &ScanControl, client: &Client) {
+    for action in &rule.actions {
+        match action {
+            AlertAction::Log => warn!("alerts: {} firing: {}", rule.name, describe(rule, value)),
+            AlertAction::Webhook => send(rule, "firing", value, cfg, client),
+            AlertAction::Pause => {
+                if control.pause() == ScanState::Paused {
+                    warn!("alerts: {} paused the scan; POST /control/resume continues it", rule.name);
+                }
+            }
+        }
+    }
+}
+
+fn resolved(rule: &AlertRule, value: f64, cfg: &Config, client: &Client) {
+    if rule.actions.contains(&AlertAction::Log) {
+        info!("alerts: {} resolved: {} {:.2}", rule.name, rule.metric.as_str(), value);
+    }
+    if rule.actions.contains(&AlertAction::Webhook) {
+        send(rule, "resolved", value, cfg, client);
+    }
+}
+
+// in the background: retries must not hold up the other rules
+fn send(rule: &AlertRule, state: &'static str, value: f64, cfg: &Config, client: &Client) {
+    let (threshold, above) = rule.threshold();
+    let text = match state {
+        "firing" => format!("Alert {} firing: {}", rule.name, describe(rule, value)),
+        _ => format!("Alert {} resolved: {} {:.2}", rule.name, rule.metric.as_str(), value),
+    };
+    let json = json!({
+        "event": "alert",
+        "rule": rule.name,
+        "state": state,
+        "metric": rule.metric.as_str(),
+        "value": value,
+        "condition": if above { "above" } else { "below" },
+        "threshold": threshold,
+        "for_secs": rule.hold.as_secs(),
+    });
+    let (endpoints, hook, client) = (cfg.alerts.webhook.clone(), cfg.notifications.webhook.clone(), client.clone());
+    tokio::spawn(async move {
+        for ep in &endpoints {
+            let body = match ep.format {
+                WebhookFormat::Json => json.clone(),
+                WebhookFormat::Slack | WebhookFormat::Telegram => json!({ "text": text }),
+                WebhookFormat::Discord => json!({ "content": text }),
+            };
+            if !notify::post(&client, &ep.url, &body, &hook).await {
+                warn!("alerts: giving up on the {} alert for {}", state, notify::host_of(&ep.url));
+            }
+        }
+    });
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::store::{extract_tld, DomainStore};
+
+/// GET /analytics/: how the stored domains are distributed, to pick the
+/// lengths, alphabet and TLDs of the next scan.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct Analytics {
+    /// Domains counted
+    pub total: u64,
+    /// By length of the name before the TLD, shortest first
+    pub by_length: Vec<LengthCount>,
+    /// By TLD, most domains first
+    pub by_tld: Vec<KeyCount>,
+    pub by_class: ClassCounts,
+    /// Most common name prefixes of `prefix_len` characters
+    pub top_prefixes: Vec<KeyCount>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LengthCount {
+    pub length: usize,
+    pub count: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct KeyCount {
+    pub key: String,
+    pub count: u64,
+}
+
+/// Names by character class; every name is in exactly one
+#[derive(Default, Serialize, utoipa::ToSchema)]
+pub struct ClassCounts {
+    /// Letters only
+    pub letters: u64,
+    /// Digits only
+    pub digits: u64,
+    /// Letters and digits
+    pub alphanumeric: u64,
+    /// Containing a hyphen
+    pub hyphenated: u64,
+    /// Punycode ("xn--") names
+    pub idn: u64,
+}
+
+impl ClassCounts {
+    fn add(&mut self, name: &str) {
+        let slot = if name.starts_with("xn--") {
+            &mut self.idn
+        } else if name.contains('-') {
+            &mut self.hyphenated
+        } else if name.bytes().all(|b| b.is_ascii_digit()) {
+            &mut self.digits
+        } else if name.bytes().any(|b| b.is_ascii_digit()) {
+            &mut self.alphanumeric
+        } else {
+            &mut self.letters
+        };
+        *slot += 1;
+    }
+}
+
+/// Read every stored domain (of one TLD) and summarize them; blocking.
+pub fn summarize(store: &DomainStore, tld: Option<&str>, prefix_len: usize, top: usize) -> Analytics {
+    let mut total = 0u64;
+    let mut lengths: BTreeMap<usize, u64> = BTreeMap::new();
+    let mut tlds: HashMap<String, u64> = HashMap::new();
+    let mut prefixes: HashMap<String, u64> = HashMap::new();
+    let mut by_class = ClassCounts::default();
+    store.scan(tld, &mut |domain| {
+        // "abc.co.uk" counts as "abc" under "uk", like the storage files
+        let name = domain.split('.').next().unwrap_or(domain);
+        total += 1;
+        *lengths.entry(name.chars().count()).or_default() += 1;
+        *tlds.entry(extract_tld(domain).unwrap_or_default().to_string()).or_default() += 1;
+        by_class.add(name);
+        // names shorter than the prefix have none
+        let prefix: String = name.chars().take(prefix_len).collect();
+        if prefix.chars().count() == prefix_len {
+            *prefixes.entry(prefix).or_default() += 1;
+        }
+        true
+    });
+    Analytics {
+        total,
+        by_length: lengths.into_iter().map(|(length, count)| LengthCount { length, count }).collect(),
+        by_tld: ranked(tlds, usize::MAX),
+        by_class,
+        top_prefixes: ranked(prefixes, top),
+    }
+}
+
+// most first, ties alphabetically
+fn ranked(counts: HashMap<String, u64>, top: usize) -> Vec<KeyCount> {
+    let mut v: Vec<KeyCount> = counts.into_iter().map(|(key, count)| KeyCount { key, count }).collect();
+    v.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    v.truncate(top);
+    v
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::config::{ApiRateLimitConfig, LiveConfig};
+use crate::error::ApiError;
+
+/// How clients are told apart (`server.rate_limit.by`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientBy {
+    /// By API key; requests without a known key by IP
+    #[default]
+    Key,
+    /// By IP, whatever key is sent
+    Ip,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Key(String),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+// idle buckets are dropped once the table grows past this
+const PRUNE_AT: usize = 10_000;
+
+struct Buckets {
+    map: HashMap<Client, Bucket>,
+    prune_at: usize,
+}
+
+/// Per-client token buckets behind `server.rate_limit`, shared by all requests.
+#[derive(Clone)]
+pub struct ApiLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl Default for ApiLimiter {
+    fn default() -> Self {
+        Self {
+            buckets: Arc::new(Mutex::new(Buckets {
+                map: HashMap::new(),
+                prune_at: PRUNE_AT,
+            })),
+        }
+    }
+}
+
+impl ApiLimiter {
+    // take a token, or tell how long until the next one
+    fn take(&self, client: Client, rate: f64, burst: f64) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut b = self.buckets.lock();
+        if b.map.len() >= b.prune_at {
+            // a bucket idle long enough to refill is no different from a new one
+            let refill = Duration::from_secs_f64(burst / rate);
+            b.map.retain(|_, v| now.duration_since(v.last) < refill);
+            b.prune_at = (b.map.len() * 2).max(PRUNE_AT);
+        }
+        let bucket = b.map.entry(client).or_insert(Bucket { tokens: burst, last: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.last).as_secs_f64() * rate).min(burst);
+        bucket.last = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+// the peer address, or the last X-Forwarded-For hop when the proxy in front is trusted
+fn client_ip(cfg: &ApiRateLimitConfig, req: &Request) -> IpAddr {
+    if cfg.trust_forwarded_for {
+        let forwarded = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .next_back()
+            .and_then(|ip| ip.trim().parse().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |c| c.0.ip())
+}
+
+/// Middleware enforcing `server.rate_limit` from the live config; over the
+/// limit a client gets 429 with Retry-After.
+pub async fn limit(live: LiveConfig, limiter: ApiLimiter, req: Request, next: Next) -> Response {
+    let cfg = live.current();
+    let rl = &cfg.server.rate_limit;
+    if !rl.enabled {
+        return next.run(req).await;
+    }
+    let key = match rl.by {
+        ClientBy::Key => crate::auth::request_key(&cfg.auth, &req),
+        ClientBy::Ip => None,
+    };
+    let rate = key.and_then(|k| k.rate_limit).unwrap_or(rl.rate_per_second);
+    // rate_limit: 0 on a key exempts it
+    if rate <= 0.0 {
+        return next.run(req).await;
+    }
+    let burst = if rl.burst == 0 { rate.ceil() } else { rl.burst as f64 };
+    let client = match key {
+        Some(k) => Client::Key(k.key.0.trim().to_string()),
+        None => Client::Ip(client_ip(rl, &req)),
+    };
+    match limiter.take(client, rate, burst.max(1.0)) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let secs = (wait.as_secs_f64().ceil() as u64).max(1);
+            debug!("rate limit: {} {} turned away for {}s", req.method(), req.uri().path(), secs);
+            (
+                [(header::RETRY_AFTER, secs.to_string())],
+                ApiError::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", format!("too many requests, retry in {secs}s")),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use axum::extract::Request;
+use axum::http::{header, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::config::{ApiKey, AuthConfig, LiveConfig};
+use crate::error::ApiError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthRole {
+    #[default]
+    Read,
+    Write,
+}
+
+// GET/HEAD only read; anything else (control, reload, cluster reports) changes state
+fn required_role(method: &Method) -> AuthRole {
+    if method == Method::GET || method == Method::HEAD {
+        AuthRole::Read
+    } else {
+        AuthRole::Write
+    }
+}
+
+// "Authorization: Bearer <key>" or "X-API-Key: <key>"
+fn presented_key(req: &Request) -> Option<String> {
+    let h = req.headers();
+    if let Some(v) = h.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        if let Some(k) = v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")) {
+            return Some(k.trim().to_string());
+        }
+    }
+    if let Some(k) = h.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        return Some(k.trim().to_string());
+    }
+    // browsers cannot set headers on WebSocket requests
+    if req.uri().path().starts_with("/ws/") {
+        let query: Vec<(String, String)> = serde_urlencoded::from_str(req.uri().query()?).ok()?;
+        return query.into_iter().find(|(k, _)| k == "api_key").map(|(_, v)| v.trim().to_string());
+    }
+    None
+}
+
+// compare without leaking the matching prefix length through timing
+fn key_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn find_key<'a>(cfg: &'a AuthConfig, key: &str) -> Option<&'a ApiKey> {
+    // check every key so the lookup time does not depend on which one matched
+    cfg.keys
+        .iter()
+        .filter(|k| key_eq(k.key.0.trim(), key))
+        .fold(None, |best: Option<&ApiKey>, k| match best {
+            Some(b) if b.role >= k.role => Some(b),
+            _ => Some(k),
+        })
+}
+
+/// The configured key a request carries, if any.
+pub fn request_key<'a>(auth: &'a AuthConfig, req: &Request) -> Option<&'a ApiKey> {
+    find_key(auth, &presented_key(req)?)
+}
+
+/// Why a request was turned away
+pub enum Denied {
+    Missing,
+    Invalid,
+    ReadOnly,
+}
+
+impl Denied {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Denied::Missing => "missing API key",
+            Denied::Invalid => "invalid API key",
+            Denied::ReadOnly => "this API key is read-only",
+        }
+    }
+}
+
+/// Check a presented key for an action that needs `need`; Ok(None) when the
+/// action is open without a key.
+pub fn authorize<'a>(auth: &'a AuthConfig, key: Option<&str>, need: AuthRole) -> Result<Option<&'a ApiKey>, Denied> {
+    if !auth.enabled || (need == AuthRole::Read && auth.public_read) {
+        return Ok(None);
+    }
+    let key = key.ok_or(Denied::Missing)?;
+    match find_key(auth, key) {
+        None => Err(Denied::Invalid),
+        Some(k) if k.role < need => Err(Denied::ReadOnly),
+        Some(k) => Ok(Some(k)),
+    }
+}
+
+/// Middleware enforcing `auth` from the live config on every route.
+pub async fn require_key(live: LiveConfig, req: Request, next: Next) -> Response {
+    let cfg = live.current();
+    let need = required_role(req.method());
+    match authorize(&cfg.auth, presented_key(&req).as_deref(), need) {
+        Ok(None) => next.run(req).await,
+        Ok(Some(k)) => {
+            if need == AuthRole::Write {
+                debug!("auth: {} {} with key '{}'", req.method(), req.uri().path(), k.name);
+            }
+            next.run(req).await
+        }
+        Err(d @ Denied::ReadOnly) => ApiError::new(axum::http::StatusCode::FORBIDDEN, "forbidden", d.message()).into_response(),
+        Err(d) => unauthorized(d.message()),
+    }
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        ApiError::new(axum::http::StatusCode::UNAUTHORIZED, "unauthorized", message),
+    )
+        .into_response()
+}
+
+// Routes that steer the scans; with server.admin_addr set they are only
+// served there, as is every write
+const ADMIN_PREFIXES: [&str; 6] = ["/control/", "/schedule/", "/preview/", "/candidates/", "/scans", "/cluster/"];
+
+/// Middleware of the public listener when `server.admin_addr` splits off
+/// the admin surface: reads only, and no control or admin routes.
+pub async fn public_only(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if required_role(req.method()) == AuthRole::Write || ADMIN_PREFIXES.iter().any(|p| path.starts_with(p)) {
+        debug!("api: {} {} refused on the public address", req.method(), path);
+        return ApiError::not_found("no such endpoint").into_response();
+    }
+    next.run(req).await
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use reqwest::{Request, RequestBuilder, Response};
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::config::{BandwidthConfig, TrafficDirection};
+
+const HOUR: u64 = 3600;
+
+/// What the bytes were moved for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Check requests, redirects and the tls stage included
+    Http,
+    /// Lookups of the dns stage and the wildcard probes
+    Dns,
+    Rdap,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Http, Channel::Dns, Channel::Rdap];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Channel::Http => "http",
+            Channel::Dns => "dns",
+            Channel::Rdap => "rdap",
+        }
+    }
+}
+
+// Counted over all scans since start, like the connection counters: the cap
+// is on what the process sends and receives, not on one scan
+static TOTALS: [[AtomicU64; 2]; 3] = [const { [const { AtomicU64::new(0) }; 2] }; 3];
+static WINDOW: Mutex<Window> = Mutex::new(Window {
+    hour: 0,
+    sent: 0,
+    received: 0,
+    limit: 0,
+    direction: TrafficDirection::Both,
+    capped: None,
+});
+static HOURS_CAPPED: AtomicU64 = AtomicU64::new(0);
+
+// the current UTC hour's traffic, toward limits.bandwidth.per_hour
+struct Window {
+    hour: u64,
+    sent: u64,
+    received: u64,
+    // limits.bandwidth as the checks last read it, for /stats/
+    limit: u64,
+    direction: TrafficDirection,
+    // the last hour the cap was reached in, so it is logged once
+    capped: Option<u64>,
+}
+
+impl Window {
+    fn roll(&mut self, now: u64) {
+        if self.hour != now / HOUR {
+            self.hour = now / HOUR;
+            self.sent = 0;
+            self.received = 0;
+        }
+    }
+
+    fn used(&self) -> u64 {
+        match self.direction {
+            TrafficDirection::Both => self.sent + self.received,
+            TrafficDirection::Sent => self.sent,
+            TrafficDirection::Received => self.received,
+        }
+    }
+}
+
+/// Bytes sent and received
+#[derive(Clone, Copy, Debug, Default, Serialize, ToSchema)]
+pub struct Traffic {
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// Network traffic of the checks since start, over all scans, in /stats/.
+/// Estimated: request and response heads and the body bytes read for HTTP,
+/// query and answer sizes for DNS; TCP and TLS overhead is not counted
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct BandwidthStats {
+    /// By channel: http, dns and rdap
+    pub channels: BTreeMap<String, Traffic>,
+    /// Sum of the channels
+    pub total: Traffic,
+    /// In the current UTC hour
+    pub this_hour: Traffic,
+    /// limits.bandwidth.per_hour as the checks last read it; 0 for none
+    pub per_hour_limit: u64,
+    /// The limit is used up and checks wait for the next hour
+    pub capped: bool,
+    /// Hours in which the limit was reached since start
+    pub hours_capped: u64,
+}
+
+pub fn stats() -> BandwidthStats {
+    let channels: BTreeMap<String, Traffic> = Channel::ALL
+        .iter()
+        .map(|&ch| {
+            let t = &TOTALS[ch as usize];
+            let traffic = Traffic {
+                sent: t[0].load(Ordering::Relaxed),
+                received: t[1].load(Ordering::Relaxed),
+            };
+            (ch.as_str().to_string(), traffic)
+        })
+        .collect();
+    let total = channels.values().fold(Traffic::default(), |a, t| Traffic {
+        sent: a.sent + t.sent,
+        received: a.received + t.received,
+    });
+    let mut w = WINDOW.lock();
+    w.roll(now_unix());
+    BandwidthStats {
+        channels,
+        total,
+        this_hour: Traffic {
+            sent: w.sent,
+            received: w.received,
+        },
+        per_hour_limit: w.limit,
+        capped: w.limit > 0 && w.used() >= w.limit,
+        hours_capped: HOURS_CAPPED.load(Ordering::Relaxed),
+    }
+}
+
+/// Count traffic of a channel.
+pub fn record(ch: Channel, sent: u64, received: u64) {
+    let t = &TOTALS[ch as usize];
+    t[0].fetch_add(sent, Ordering::Relaxed);
+    t[1].fetch_add(received, Ordering::Relaxed);
+    let mut w = WINDOW.lock();
+    w.roll(now_unix());
+    w.sent += sent;
+    w.received += received;
+}
+
+/// How long checks have to wait for limits.bandwidth.per_hour; None while
+/// the hour has room left.
+pub fn over_limit(cfg: &BandwidthConfig) -> Option<Duration> {
+    let now = now_unix();
+    let mut w = WINDOW.lock();
+    w.roll(now);
+    w.limit = cfg.per_hour as u64;
+    w.direction = cfg.direction;
+    if w.limit == 0 || w.used() < w.limit {
+        return None;
+    }
+    let wait = Duration::from_secs(HOUR - now % HOUR);
+    if w.capped != Some(w.hour) {
+        w.capped = Some(w.hour);
+        HOURS_CAPPED.fetch_add(1, Ordering::Relaxed);
+        info!(
+            "bandwidth: limits.bandwidth.per_hour of {} used up, checks wait {}s for the next hour",
+            crate::human_bytes(cfg.per_hour as u64),
+            wait.as_secs()
+        );
+    }
+    Some(wait)
+}
+
+/// Send a request and count it with the head of its response; body bytes
+/// are counted by whoever reads them.
+pub async fn send(req: RequestBuilder, ch: Channel) -> reqwest::Result<Response> {
+    let (client, req) = req.build_split();
+    let req = req?;
+    let size = request_size(&req);
+    let res = client.execute(req).await;
+    match &res {
+        Ok(resp) => record(ch, size, head_size(resp)),
+        // nothing went out without a connection
+        Err(e) if e.is_connect() => {}
+        Err(_) => record(ch, size, 0),
+    }
+    res
+}
+
+// request line, Host, the headers and the body as HTTP/1.1 writes them; the
+// client's own defaults (Accept, Accept-Encoding) are not in the request yet
+fn request_size(req: &Request) -> u64 {
+    let url = req.url();
+    let line = req.method().as_str().len() + url.path().len() + url.query().map_or(0, |q| q.len() + 1) + " HTTP/1.1\r\n".len() + 1;
+    let host = "Host: \r\n".len() + url.host_str().map_or(0, str::len);
+    let headers: usize = req.headers().iter().map(|(k, v)| k.as_str().len() + v.len() + 4).sum();
+    let body = req.body().and_then(|b| b.as_bytes()).map_or(0, <[u8]>::len);
+    (line + host + headers + 2 + body) as u64
+}
+
+fn head_size(resp: &Response) -> u64 {
+    let headers: usize = resp.headers().iter().map(|(k, v)| k.as_str().len() + v.len() + 4).sum();
+    ("HTTP/1.1 200 OK\r\n".len() + headers + 2) as u64
+}
+
+fn now_unix() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::LiveConfig;
+
+const DAY: u64 = 86_400;
+
+/// Candidates the main scan has queued, saved with the resume state so
+/// limits.max_candidates and limits.budget hold across restarts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetState {
+    /// Over all passes since the state file was created
+    pub total: i64,
+    /// UTC day (days since the epoch) `today` belongs to
+    pub day: u64,
+    pub today: i64,
+    /// In the current pass, toward limits.max_candidates
+    pub pass: i64,
+}
+
+/// Whether the main scan may queue one more candidate
+pub enum Allowance {
+    Go,
+    /// limits.budget.total is used up
+    Exhausted,
+    /// limits.budget.per_day is used up until the next UTC day, this far off
+    Wait(Duration),
+}
+
+/// Counts queued candidates against limits.budget; the limits are read live.
+pub struct Budget {
+    config: LiveConfig,
+    state: Mutex<BudgetState>,
+}
+
+impl Budget {
+    pub fn new(config: LiveConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(BudgetState::default()),
+        }
+    }
+
+    /// Continue from a saved state (resume).
+    pub fn restore(&self, saved: BudgetState) {
+        let mut st = self.state.lock();
+        *st = saved;
+        roll_day(&mut st, now_unix());
+        if st.total > 0 {
+            info!("budget: restored {} queued in total, {} today, {} in the current pass", st.total, st.today, st.pass);
+        }
+    }
+
+    pub fn snapshot(&self) -> BudgetState {
+        *self.state.lock()
+    }
+
+    /// Candidates queued so far in the current pass.
+    pub fn pass_sent(&self) -> i64 {
+        self.state.lock().pass
+    }
+
+    /// A pass ended; the next one starts a fresh limits.max_candidates.
+    pub fn new_pass(&self) {
+        self.state.lock().pass = 0;
+    }
+
+    pub fn check(&self) -> Allowance {
+        let cfg = self.config.current().limits.budget.clone();
+        let now = now_unix();
+        let mut st = self.state.lock();
+        roll_day(&mut st, now);
+        if cfg.total > 0 && st.total >= cfg.total {
+            return Allowance::Exhausted;
+        }
+        if cfg.per_day > 0 && st.today >= cfg.per_day {
+            return Allowance::Wait(Duration::from_secs(DAY - now % DAY));
+        }
+        Allowance::Go
+    }
+
+    /// Whether limits.budget.total is used up.
+    pub fn exhausted(&self) -> bool {
+        matches!(self.check(), Allowance::Exhausted)
+    }
+
+    /// One candidate was queued.
+    pub fn record(&self) {
+        let mut st = self.state.lock();
+        roll_day(&mut st, now_unix());
+        st.total += 1;
+        st.today += 1;
+        st.pass += 1;
+    }
+}
+
+fn roll_day(st: &mut BudgetState, now: u64) {
+    let day = now / DAY;
+    if st.day != day {
+        st.day = day;
+        st.today = 0;
+    }
+}
+
+fn now_unix() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
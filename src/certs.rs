@@ -0,0 +1,203 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use ring::digest::{digest, SHA256};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{ring as provider, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use x509_parser::prelude::*;
+
+// verdicts kept for responses still in flight; the oldest go first
+const MAX_VERDICTS: usize = 16384;
+
+/// TLS certificate of the HTTPS response a domain was found with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(default)]
+pub struct CertInfo {
+    /// Subject common name
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names of the subject alternative name extension
+    pub sans: Vec<String>,
+    pub not_before_unix: u64,
+    pub not_after_unix: u64,
+    /// Issued by its own subject
+    pub self_signed: bool,
+    /// Chain, host name and validity period verified against the system roots
+    pub valid: bool,
+    /// Why it is not valid
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+}
+
+// (host, SHA-256 of the leaf) -> verification error, None when valid
+type Key = (String, [u8; 32]);
+
+#[derive(Default)]
+struct Verdicts {
+    by_key: HashMap<Key, Option<String>>,
+    order: VecDeque<Key>,
+}
+
+// Filled during handshakes, read once the response is in; the check client
+// runs its own verifier because reqwest only hands out the leaf certificate
+static VERDICTS: Lazy<Mutex<Verdicts>> = Lazy::new(Default::default);
+
+fn key(host: &str, der: &[u8]) -> Key {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(digest(&SHA256, der).as_ref());
+    (host.trim_end_matches('.').to_ascii_lowercase(), hash)
+}
+
+fn record(host: &str, der: &[u8], error: Option<String>) {
+    let k = key(host, der);
+    let mut v = VERDICTS.lock();
+    if v.by_key.insert(k.clone(), error).is_none() {
+        v.order.push_back(k);
+    }
+    while v.order.len() > MAX_VERDICTS {
+        if let Some(old) = v.order.pop_front() {
+            v.by_key.remove(&old);
+        }
+    }
+}
+
+// Standard webpki verification whose outcome is recorded per certificate;
+// with `accept_invalid` the handshake goes on regardless so the site can be inspected
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    accept_invalid: bool,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let res = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now);
+        record(&server_name.to_str(), end_entity, res.as_ref().err().map(|e| e.to_string()));
+        match res {
+            Err(_) if self.accept_invalid => Ok(ServerCertVerified::assertion()),
+            r => r,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// rustls settings for the check client: system roots, the recording verifier,
+/// no session resumption (a resumed session skips verification).
+pub fn client_tls(accept_invalid: bool) -> anyhow::Result<ClientConfig> {
+    let provider: Arc<CryptoProvider> = Arc::new(provider::default_provider());
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(system_roots()?), provider.clone())
+        .build()
+        .context("build certificate verifier")?;
+    let mut cfg = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(RecordingVerifier { inner, accept_invalid }))
+        .with_no_client_auth();
+    cfg.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    cfg.resumption = rustls::client::Resumption::disabled();
+    Ok(cfg)
+}
+
+/// rustls settings verifying servers against the system roots, for
+/// connections other than the checks (SMTP).
+pub fn plain_client_tls() -> anyhow::Result<ClientConfig> {
+    Ok(ClientConfig::builder_with_provider(Arc::new(provider::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(system_roots()?)
+        .with_no_client_auth())
+}
+
+fn system_roots() -> anyhow::Result<RootCertStore> {
+    let native = rustls_native_certs::load_native_certs();
+    if let Some(e) = native.errors.first() {
+        warn!("tls: some system root certificates could not be loaded: {e}");
+    }
+    let mut roots = RootCertStore::empty();
+    let (added, _) = roots.add_parsable_certificates(native.certs);
+    if added == 0 {
+        anyhow::bail!("no system root certificates found");
+    }
+    Ok(roots)
+}
+
+/// Certificate of an HTTPS response from the check client; None for plain HTTP.
+pub fn inspect(resp: &reqwest::Response) -> Option<CertInfo> {
+    let der = resp.extensions().get::<reqwest::tls::TlsInfo>()?.peer_certificate()?;
+    let host = resp.url().host_str().unwrap_or_default();
+    let verdict = VERDICTS.lock().by_key.get(&key(host, der)).cloned();
+    let mut info = match X509Certificate::from_der(der) {
+        Ok((_, cert)) => describe(&cert),
+        Err(e) => CertInfo {
+            error: format!("unparsable certificate: {e}"),
+            ..Default::default()
+        },
+    };
+    match verdict {
+        Some(None) => info.valid = info.error.is_empty(),
+        Some(Some(e)) => info.error = e,
+        None => info.error = "certificate was not verified".to_string(),
+    }
+    Some(info)
+}
+
+fn describe(cert: &X509Certificate) -> CertInfo {
+    let mut sans = Vec::new();
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for n in &san.value.general_names {
+            if let GeneralName::DNSName(d) = n {
+                sans.push(d.to_string());
+            }
+        }
+    }
+    let validity = cert.validity();
+    CertInfo {
+        subject: cert
+            .subject()
+            .iter_common_name()
+            .find_map(|cn| cn.as_str().ok())
+            .unwrap_or_default()
+            .to_string(),
+        issuer: cert.issuer().to_string(),
+        sans,
+        not_before_unix: validity.not_before.timestamp().max(0) as u64,
+        not_after_unix: validity.not_after.timestamp().max(0) as u64,
+        self_signed: cert.subject().as_raw() == cert.issuer().as_raw(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,728 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::Instant;
+
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::error::ProtoErrorKind;
+use hickory_resolver::proto::op::ResponseCode;
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+use reqwest::header::{LOCATION, USER_AGENT};
+use reqwest::{Client, IntoUrl, Method, RequestBuilder, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tracing::field::Empty;
+use tracing::{debug, info_span, Instrument};
+
+use crate::bandwidth::{self, Channel};
+use crate::certs::{self, CertInfo};
+use crate::config::{CheckStrategy, Config, ContentMatchConfig, HTTPCheckConfig, SchemePolicy, StatusAction};
+use crate::dns::{AddressFamily, ResolverPool};
+use crate::http_pool;
+use crate::latency::{Stage, Timing};
+use crate::rdap::{Availability, RdapClient};
+use crate::store::FoundDomain;
+use crate::wildcard::WildcardFilter;
+
+mod pipeline;
+mod robots;
+mod success;
+
+use pipeline::Pipeline;
+pub use success::SuccessExpr;
+
+// Which scheme answered and with what status
+pub struct HttpHit {
+    pub status: u16,
+    pub scheme: &'static str,
+    /// Every scheme whose answer was accepted, `scheme` first; with
+    /// http_check.scheme_policy `first` only `scheme`
+    pub schemes: Vec<&'static str>,
+    pub final_url: String,
+    pub server: String,
+    pub response_ms: u64,
+    /// Empty unless http_check.match.extract_title is on
+    pub title: String,
+    /// Tag of the matching http_check.status_rules entry
+    pub tag: String,
+    /// For HTTPS answers; stored with http_check.tls.inspect
+    pub cert: Option<CertInfo>,
+    /// Body read for the http_check.match rules, if any
+    pub body: Option<Vec<u8>>,
+    /// Whether robots.txt allows crawling, with http_check.robots
+    pub robots: Option<bool>,
+    /// Every request made, in order
+    pub timings: Vec<Timing>,
+}
+
+// Why no scheme answered; transient failures are worth another attempt later
+pub struct HttpMiss {
+    pub error: String,
+    /// Class of the failure in `error`
+    pub kind: FailureKind,
+    pub transient: bool,
+    pub timings: Vec<Timing>,
+}
+
+/// Why a check did not find the domain, counted per label in /stats/ `errors`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The name does not exist
+    DnsNxdomain,
+    /// The name exists without records of the looked-up type (NODATA)
+    DnsNoRecords,
+    DnsServfail,
+    DnsRefused,
+    DnsTimeout,
+    /// I/O, protocol and other resolver errors
+    DnsOther,
+    /// Connection refused, reset or unreachable
+    HttpConnect,
+    /// TLS handshake or certificate errors
+    HttpTls,
+    HttpTimeout,
+    /// Answered with a status outside the accepted range
+    HttpStatus,
+    /// Accepted status, but the body failed the http_check.match rules
+    HttpContent,
+    /// Redirected off-host (http_check.reject_offsite_redirects) or too often
+    HttpRedirect,
+    /// Answered without a valid certificate (http_check.tls.require_valid)
+    HttpCert,
+    HttpOther,
+    /// A parking provider's fingerprint matched (required `parking` stage)
+    Parked,
+}
+
+impl FailureKind {
+    pub const ALL: [FailureKind; 15] = [
+        FailureKind::DnsNxdomain,
+        FailureKind::DnsNoRecords,
+        FailureKind::DnsServfail,
+        FailureKind::DnsRefused,
+        FailureKind::DnsTimeout,
+        FailureKind::DnsOther,
+        FailureKind::HttpConnect,
+        FailureKind::HttpTls,
+        FailureKind::HttpTimeout,
+        FailureKind::HttpStatus,
+        FailureKind::HttpContent,
+        FailureKind::HttpRedirect,
+        FailureKind::HttpCert,
+        FailureKind::HttpOther,
+        FailureKind::Parked,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::DnsNxdomain => "dns_nxdomain",
+            FailureKind::DnsNoRecords => "dns_no_records",
+            FailureKind::DnsServfail => "dns_servfail",
+            FailureKind::DnsRefused => "dns_refused",
+            FailureKind::DnsTimeout => "dns_timeout",
+            FailureKind::DnsOther => "dns_other",
+            FailureKind::HttpConnect => "http_connect",
+            FailureKind::HttpTls => "http_tls",
+            FailureKind::HttpTimeout => "http_timeout",
+            FailureKind::HttpStatus => "http_status",
+            FailureKind::HttpContent => "http_content",
+            FailureKind::HttpRedirect => "http_redirect",
+            FailureKind::HttpCert => "http_cert",
+            FailureKind::HttpOther => "http_other",
+            FailureKind::Parked => "parked",
+        }
+    }
+}
+
+// Outcome of running one domain through the check pipeline
+#[derive(Debug, Default, Serialize)]
+pub struct Verdict {
+    pub domain: String,
+    pub found: bool,
+    pub ips: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_error: Option<String>,
+    /// Resolved only to the zone's wildcard addresses; treated as unresolved
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub wildcard: bool,
+    /// Configured record types the domain has (dns.record_types)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub record_types: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<&'static str>,
+    /// Schemes whose answer was accepted (http_check.scheme_policy all)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schemes: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<CertInfo>,
+    /// Parking provider whose fingerprint matched (parking stage)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parked_by: Option<String>,
+    /// Address families the HTTP check succeeded over (http_check.per_family)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub http_families: Vec<&'static str>,
+    /// "allowed" or "disallowed": what robots.txt says about crawling (http_check.robots)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robots: Option<&'static str>,
+    /// Country code of the addresses (geoip stage)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Network of the addresses and its organization (geoip stage)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Availability>,
+    /// Why the domain was not found: the DNS error, or the HTTP one once DNS matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    /// Not found because of a timeout, reset or SERVFAIL rather than a definite answer
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub transient: bool,
+    /// Address lookup and HTTP requests, for the latency histograms
+    #[serde(skip)]
+    pub timings: Vec<Timing>,
+}
+
+impl Verdict {
+    /// The storage record for a domain that passed the checks.
+    pub fn found_record(&self) -> Option<FoundDomain> {
+        if !self.found {
+            return None;
+        }
+        Some(FoundDomain {
+            http_status: self.http_status.unwrap_or_default(),
+            scheme: self.scheme.unwrap_or_default().to_string(),
+            schemes: self.schemes.iter().map(|s| s.to_string()).collect(),
+            final_url: self.final_url.clone().unwrap_or_default(),
+            server: self.server.clone().unwrap_or_default(),
+            response_ms: self.response_ms.unwrap_or_default(),
+            title: self.title.clone().unwrap_or_default(),
+            record_types: self.record_types.iter().map(|t| t.to_string()).collect(),
+            tag: self.tag.clone().unwrap_or_default(),
+            cert: self.cert.clone(),
+            parked_by: self.parked_by.clone().unwrap_or_default(),
+            http_families: self.http_families.iter().map(|f| f.to_string()).collect(),
+            robots: self.robots.unwrap_or_default().to_string(),
+            country: self.country.clone().unwrap_or_default(),
+            asn: self.asn.unwrap_or_default(),
+            as_org: self.as_org.clone().unwrap_or_default(),
+            ..FoundDomain::new(&self.domain, self.ips.clone())
+        })
+    }
+}
+
+/// Clients for the HTTP checks, by the address family they connect over
+#[derive(Clone)]
+pub struct CheckClients {
+    /// Whatever the system resolver offers, IPv6 and IPv4 raced
+    pub any: Client,
+    pub ipv4: Client,
+    pub ipv6: Client,
+}
+
+impl CheckClients {
+    /// The client a dns.address_family policy connects with
+    pub fn for_family(&self, family: AddressFamily) -> &Client {
+        match family {
+            AddressFamily::Ipv4 => &self.ipv4,
+            AddressFamily::Ipv6 => &self.ipv6,
+            AddressFamily::Any | AddressFamily::Both => &self.any,
+        }
+    }
+}
+
+/// Clients for the HTTP checks. Redirects are not followed by the clients but
+/// by `check_domain`, under the live http_check settings; certificates are
+/// verified by `certs` so they can be inspected.
+pub fn check_clients(cfg: &Config) -> anyhow::Result<CheckClients> {
+    // binding the unspecified address of one family keeps connections to it
+    let build = |local: Option<IpAddr>| -> anyhow::Result<Client> {
+        Ok(http_pool::tune(Client::builder(), cfg)
+            .connector_layer(http_pool::CountConnections)
+            .redirect(reqwest::redirect::Policy::none())
+            .use_preconfigured_tls(certs::client_tls(cfg.http_check.tls.accept_invalid)?)
+            .tls_info(true)
+            .local_address(local)
+            .build()?)
+    };
+    Ok(CheckClients {
+        any: build(None)?,
+        ipv4: build(Some(Ipv4Addr::UNSPECIFIED.into()))?,
+        ipv6: build(Some(Ipv6Addr::UNSPECIFIED.into()))?,
+    })
+}
+
+/// What a scan looks for (top-level `mode`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanMode {
+    /// Live sites through the `checks` pipeline (default DNS -> HTTP, RDAP for the rest)
+    #[default]
+    Live,
+    /// Unregistered names: no SOA in DNS and RDAP does not know them
+    Availability,
+}
+
+// The check pipeline (or SOA -> RDAP in availability mode) for a single
+// domain, without touching storage or counters
+pub struct Probe {
+    pipeline: Pipeline,
+    resolver: Arc<ResolverPool>,
+    rdap: Option<Arc<RdapClient>>,
+    mode: ScanMode,
+}
+
+impl Probe {
+    pub fn new(cfg: &Config, clients: CheckClients, resolver: ResolverPool, rdap: Option<Arc<RdapClient>>, wildcard: Option<WildcardFilter>) -> Self {
+        let resolver = Arc::new(resolver);
+        let pipeline = Pipeline::new(cfg, clients, resolver.clone(), rdap.clone(), wildcard);
+        Self {
+            pipeline,
+            resolver,
+            rdap,
+            mode: cfg.mode,
+        }
+    }
+
+    pub async fn evaluate(&self, domain: &str, hc: &HTTPCheckConfig) -> Verdict {
+        if self.mode == ScanMode::Availability {
+            let v = Verdict {
+                domain: domain.to_string(),
+                ..Default::default()
+            };
+            return self.evaluate_availability(v).await;
+        }
+        self.pipeline.run(domain, hc).await
+    }
+
+    // SOA -> RDAP: a name that exists in DNS is registered and needs no RDAP
+    // query; only NXDOMAIN names are looked up
+    async fn evaluate_availability(&self, mut v: Verdict) -> Verdict {
+        let started = Instant::now();
+        let exists = self.resolver.name_exists(&v.domain).await;
+        v.timings.push(Timing {
+            stage: Stage::Dns,
+            elapsed: started.elapsed(),
+            answered: exists.as_ref().err().is_none_or(|e| !dns_transient(e)),
+        });
+        match exists {
+            Ok(true) => {}
+            Ok(false) => {
+                if let Some(rdap) = self.rdap.as_ref() {
+                    v.availability = Some(rdap.lookup(&v.domain).await);
+                }
+            }
+            Err(e) => {
+                v.transient = dns_transient(&e);
+                v.failure = Some(dns_failure(&e));
+                v.dns_error = Some(e.to_string());
+            }
+        }
+        v
+    }
+}
+
+// NXDOMAIN / empty answers are definite; timeouts, I/O errors and SERVFAIL are not
+fn dns_transient(e: &ResolveError) -> bool {
+    match e.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => *response_code == ResponseCode::ServFail,
+        _ => true,
+    }
+}
+
+pub(crate) fn dns_failure(e: &ResolveError) -> FailureKind {
+    match e.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => match *response_code {
+            ResponseCode::NXDomain => FailureKind::DnsNxdomain,
+            ResponseCode::ServFail => FailureKind::DnsServfail,
+            ResponseCode::Refused => FailureKind::DnsRefused,
+            ResponseCode::NoError => FailureKind::DnsNoRecords,
+            _ => FailureKind::DnsOther,
+        },
+        ResolveErrorKind::Timeout => FailureKind::DnsTimeout,
+        ResolveErrorKind::Proto(p) if matches!(p.kind(), ProtoErrorKind::Timeout) => FailureKind::DnsTimeout,
+        _ => FailureKind::DnsOther,
+    }
+}
+
+// rustls reports handshake and certificate errors as InvalidData I/O errors
+fn http_failure(e: &reqwest::Error) -> FailureKind {
+    use std::error::Error;
+    if e.is_timeout() {
+        return FailureKind::HttpTimeout;
+    }
+    let mut src = e.source();
+    while let Some(err) = src {
+        if err.downcast_ref::<std::io::Error>().is_some_and(|io| io.kind() == std::io::ErrorKind::InvalidData) {
+            return FailureKind::HttpTls;
+        }
+        src = err.source();
+    }
+    if e.is_connect() {
+        FailureKind::HttpConnect
+    } else {
+        FailureKind::HttpOther
+    }
+}
+
+// timeouts and dropped connections, as opposed to refused connections or TLS errors
+fn http_transient(e: &reqwest::Error) -> bool {
+    use std::error::Error;
+    use std::io::ErrorKind;
+    if e.is_timeout() {
+        return true;
+    }
+    let mut src = e.source();
+    while let Some(err) = src {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::TimedOut | ErrorKind::BrokenPipe | ErrorKind::UnexpectedEof
+            );
+        }
+        src = err.source();
+    }
+    false
+}
+
+/// Try each scheme of http_check.scheme_policy (and retry) until one answers
+/// with an accepted status, or with `all` until each did or ran out of attempts;
+/// the error is the last rejection by a server that answered (status, content,
+/// redirect), else the last request error; transient if any attempt failed transiently.
+pub async fn check_domain(client: &Client, domain: &str, hc: &HTTPCheckConfig) -> Result<HttpHit, HttpMiss> {
+    let method = if hc.method.trim().is_empty() {
+        Method::GET
+    } else {
+        Method::from_bytes(hc.method.as_bytes()).unwrap_or(Method::GET)
+    };
+    let schemes: &[&'static str] = match (hc.scheme_policy, hc.try_https_first) {
+        (SchemePolicy::HttpsOnly, _) => &["https"],
+        (_, true) => &["https", "http"],
+        (_, false) => &["http", "https"],
+    };
+    // accepted answers, by scheme; `all` goes on after the first
+    let mut hits: Vec<HttpHit> = Vec::new();
+
+    let mut last_err = String::from("no attempts made");
+    let mut last_kind = FailureKind::HttpOther;
+    // a server answered but was rejected; later connection errors do not hide that
+    let mut answered = false;
+    let mut transient = false;
+    let mut timings = Vec::new();
+    for _attempt in 0..=hc.retry {
+        for &scheme in schemes {
+            if hits.iter().any(|h| h.scheme == scheme) {
+                continue;
+            }
+            let url = format!("{scheme}://{domain}/");
+            let start_url = Url::parse(&url).map_err(|e| HttpMiss {
+                error: e.to_string(),
+                kind: FailureKind::HttpOther,
+                transient: false,
+                timings: Vec::new(),
+            })?;
+            // politeness before a page is read for the match rules
+            let mut robots = None;
+            if hc.robots.enabled && hc.content.reads_body() {
+                robots = robots::visit(client, scheme, domain, hc).await;
+                if robots == Some(false) && hc.robots.skip_disallowed {
+                    debug!("robots.txt disallows {}", url);
+                    last_err = format!("{url}: robots.txt disallows crawling");
+                    last_kind = FailureKind::HttpContent;
+                    answered = true;
+                    continue;
+                }
+            }
+            let started = Instant::now();
+            let fetched = fetch_page(client, &method, start_url, domain, hc).await;
+            timings.push(Timing {
+                stage: Stage::Http,
+                elapsed: started.elapsed(),
+                answered: !matches!(fetched, Err(FetchError::Request(_))),
+            });
+            match fetched {
+                Ok(resp) => {
+                    let status = resp.status().as_u16();
+                    let (action, tag) = hc.status_action(status);
+                    if action == StatusAction::RetryLater {
+                        // e.g. 429/503: the server is there but not answering properly now
+                        last_err = format!("{url}: status {status}, re-check later");
+                        last_kind = FailureKind::HttpStatus;
+                        answered = true;
+                        transient = true;
+                        continue;
+                    }
+                    // kept for the tls stage even when http_check.tls does not ask for it
+                    let cert = if action != StatusAction::Reject { certs::inspect(&resp) } else { None };
+                    if action != StatusAction::Reject && hc.tls.require_valid && !cert.as_ref().is_some_and(|c| c.valid) {
+                        let why = cert.map_or_else(|| "no certificate (plain HTTP)".to_string(), |c| format!("certificate not valid: {}", c.error));
+                        debug!("certificate rejected: {} {}", url, why);
+                        last_err = format!("{url}: {why}");
+                        last_kind = FailureKind::HttpCert;
+                        answered = true;
+                        continue;
+                    }
+                    // Just check status, don't read body if not needed
+                    if action != StatusAction::Reject {
+                        let response_ms = started.elapsed().as_millis() as u64;
+                        let server = resp
+                            .headers()
+                            .get(reqwest::header::SERVER)
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+                        let final_url = resp.url().to_string();
+                        let mut title = String::new();
+                        let mut page = None;
+                        if hc.content.reads_body() {
+                            let checked = match read_body(resp, hc.body_limit).await {
+                                Ok(body) => {
+                                    let m = match_content(&body, &hc.content).map_err(|why| (why, FailureKind::HttpContent));
+                                    page = Some(body);
+                                    m
+                                }
+                                Err(e) => {
+                                    transient |= http_transient(&e);
+                                    Err((format!("reading body: {e}"), http_failure(&e)))
+                                }
+                            };
+                            match checked {
+                                Ok(t) => title = t,
+                                Err((why, kind)) => {
+                                    debug!("content rejected: {} {}", url, why);
+                                    last_err = format!("{url}: {why}");
+                                    last_kind = kind;
+                                    answered |= kind == FailureKind::HttpContent;
+                                    continue;
+                                }
+                            }
+                        }
+                        debug!("reachable: {} status={}", url, status);
+                        hits.push(HttpHit {
+                            status,
+                            scheme,
+                            schemes: Vec::new(),
+                            final_url,
+                            server,
+                            response_ms,
+                            title,
+                            tag: if action == StatusAction::StoreWithTag { tag.to_string() } else { String::new() },
+                            cert,
+                            body: page,
+                            robots,
+                            timings: Vec::new(),
+                        });
+                        if hc.scheme_policy != SchemePolicy::All || hits.len() == schemes.len() {
+                            break;
+                        }
+                        continue;
+                    }
+                    last_err = format!("{url}: status {status} not accepted");
+                    last_kind = FailureKind::HttpStatus;
+                    answered = true;
+                }
+                Err(FetchError::Request(e)) => {
+                    debug!("request error for {}: {}", url, e);
+                    transient |= http_transient(&e);
+                    if !answered {
+                        last_err = format!("{url}: {e}");
+                        last_kind = http_failure(&e);
+                    }
+                }
+                Err(FetchError::Redirect(why)) => {
+                    debug!("redirect rejected: {} {}", url, why);
+                    last_err = format!("{url}: {why}");
+                    last_kind = FailureKind::HttpRedirect;
+                    answered = true;
+                }
+            }
+        }
+        if !hits.is_empty() && (hc.scheme_policy != SchemePolicy::All || hits.len() == schemes.len()) {
+            break;
+        }
+    }
+    // the first scheme in order stands for the domain
+    hits.sort_by_key(|h| schemes.iter().position(|s| *s == h.scheme));
+    let accepted: Vec<&'static str> = hits.iter().map(|h| h.scheme).collect();
+    if let Some(mut hit) = hits.into_iter().next() {
+        hit.schemes = accepted;
+        hit.timings = timings;
+        return Ok(hit);
+    }
+    Err(HttpMiss {
+        error: last_err,
+        kind: last_kind,
+        transient,
+        timings,
+    })
+}
+
+// Why fetching a URL failed: the request itself, or the redirect policy
+enum FetchError {
+    Request(reqwest::Error),
+    Redirect(String),
+}
+
+// A check request with the configured timeout, User-Agent and headers;
+// counted for the connection reuse in /stats/
+fn check_request(client: &Client, method: Method, url: impl IntoUrl, hc: &HTTPCheckConfig) -> RequestBuilder {
+    http_pool::count_request();
+    let mut req = client.request(method, url).timeout(hc.timeout);
+    if !hc.user_agent.is_empty() {
+        req = req.header(USER_AGENT, hc.user_agent.as_str());
+    }
+    for (name, value) in &hc.headers {
+        req = req.header(name.as_str(), value.as_str());
+    }
+    req
+}
+
+// The check request of http_check.strategy: `method` as configured, or
+// HEAD with a GET after it when the server rejects HEAD (405, 501) or lets
+// it hang; the GET reads no more of the body than the check needs
+async fn fetch_page(client: &Client, method: &Method, url: Url, domain: &str, hc: &HTTPCheckConfig) -> Result<reqwest::Response, FetchError> {
+    // HEAD has no body for the match rules
+    if hc.strategy != CheckStrategy::HeadThenGet || hc.content.reads_body() {
+        return fetch(client, method, url, domain, hc).await;
+    }
+    let wait = if hc.head_timeout.is_zero() { hc.timeout } else { hc.head_timeout };
+    let why = match time::timeout(wait, fetch(client, &Method::HEAD, url.clone(), domain, hc)).await {
+        Ok(Ok(resp)) if matches!(resp.status(), StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) => {
+            format!("status {}", resp.status().as_u16())
+        }
+        Ok(Err(FetchError::Request(e))) if e.is_timeout() => "timed out".to_string(),
+        Err(_) => "timed out".to_string(),
+        // an answer, a refused connection or a redirect rejection: GET gets the same
+        Ok(done) => return done,
+    };
+    debug!("HEAD {}: {}, retrying with GET", url, why);
+    http_pool::count_head_fallback();
+    fetch(client, &Method::GET, url, domain, hc).await
+}
+
+// Send the request and follow redirects as http_check allows; the check
+// client leaves redirects to this so the settings apply on reload
+async fn fetch(client: &Client, method: &Method, mut url: Url, domain: &str, hc: &HTTPCheckConfig) -> Result<reqwest::Response, FetchError> {
+    let mut method = method.clone();
+    let mut hops = 0;
+    loop {
+        let mut req = check_request(client, method.clone(), url.clone(), hc);
+        // http_check.body goes with POST, PUT and the like, also after a 307/308
+        if !hc.body.is_empty() && method != Method::GET && method != Method::HEAD {
+            req = req.body(hc.body.clone());
+        }
+        let span = info_span!(
+            "http.attempt",
+            otel.kind = "client",
+            http.request.method = %method,
+            url.full = %url,
+            http.response.status_code = Empty,
+            error = Empty,
+        );
+        let sent = bandwidth::send(req, Channel::Http).instrument(span.clone()).await;
+        match &sent {
+            Ok(r) => span.record("http.response.status_code", r.status().as_u16()),
+            Err(e) => span.record("error", tracing::field::display(e)),
+        };
+        let resp = sent.map_err(FetchError::Request)?;
+        if !hc.follow_redirects || !resp.status().is_redirection() {
+            return Ok(resp);
+        }
+        // 304 and 3xx without a usable Location are judged as they are
+        let Some(next) = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .and_then(|l| url.join(l).ok())
+        else {
+            return Ok(resp);
+        };
+        if hops >= hc.max_redirects {
+            return Err(FetchError::Redirect(format!("more than {} redirects", hc.max_redirects)));
+        }
+        if hc.reject_offsite_redirects && !on_site(&next, domain) {
+            return Err(FetchError::Redirect(format!("redirects off-host to {next}")));
+        }
+        // 303 continues as GET, and so do 301/302 after a POST, as browsers do
+        let to_get = match resp.status() {
+            StatusCode::SEE_OTHER => method != Method::HEAD,
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => method == Method::POST,
+            _ => false,
+        };
+        if to_get {
+            method = Method::GET;
+        }
+        hops += 1;
+        url = next;
+    }
+}
+
+// the URL stays on the domain or one of its subdomains (www. included)
+fn on_site(url: &Url, domain: &str) -> bool {
+    url.host_str().is_some_and(|h| {
+        let h = h.trim_end_matches('.').to_ascii_lowercase();
+        h == domain || h.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.'))
+    })
+}
+
+// the first `limit` bytes of the body; the rest is never downloaded
+async fn read_body(mut resp: reqwest::Response, limit: usize) -> Result<Vec<u8>, reqwest::Error> {
+    let mut body = Vec::new();
+    while body.len() < limit {
+        let Some(chunk) = resp.chunk().await? else { break };
+        bandwidth::record(Channel::Http, 0, chunk.len() as u64);
+        body.extend_from_slice(&chunk[..chunk.len().min(limit - body.len())]);
+    }
+    Ok(body)
+}
+
+// Apply the http_check.match rules to a body; Ok carries the page title when
+// extraction is on, Err says which rule rejected it
+fn match_content(body: &[u8], m: &ContentMatchConfig) -> Result<String, String> {
+    if body.len() < m.min_body_size {
+        return Err(format!("body too small ({} bytes)", body.len()));
+    }
+    if let Some(p) = m.must_contain.iter().find(|p| !p.0.is_match(body)) {
+        return Err(format!("body does not match {:?}", p.0.as_str()));
+    }
+    if let Some(p) = m.must_not_contain.iter().find(|p| p.0.is_match(body)) {
+        return Err(format!("body matches {:?}", p.0.as_str()));
+    }
+    Ok(if m.extract_title { extract_title(body) } else { String::new() })
+}
+
+static TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+// <title> text with whitespace collapsed and the common entities decoded
+fn extract_title(body: &[u8]) -> String {
+    let Some(m) = TITLE.captures(body).and_then(|c| c.get(1)) else {
+        return String::new();
+    };
+    let text = String::from_utf8_lossy(m.as_bytes());
+    let title = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let title = title
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    title.chars().take(200).collect()
+}
@@ -0,0 +1,539 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::{Client, Method, Url};
+use tracing::field::Empty;
+use tracing::{error, info, info_span, Instrument};
+
+use super::{check_domain, check_request, dns_failure, dns_transient, fetch, http_failure, http_transient, read_body, CheckClients, FailureKind, HttpHit, HttpMiss, SuccessExpr, Verdict};
+use crate::bandwidth::{self, Channel};
+use crate::certs::{self, CertInfo};
+use crate::config::{CheckKind, Config, HTTPCheckConfig, RunWhen, SchemePolicy};
+use crate::dns::{AddressFamily, RecordKind, RecordMatch, ResolverPool};
+use crate::geoip::GeoIp;
+use crate::latency::{Stage, Timing};
+use crate::parking::Fingerprints;
+use crate::rdap::RdapClient;
+use crate::wildcard::WildcardFilter;
+
+/// What a stage made of the domain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// Nothing to decide, e.g. HTTP for a name without addresses
+    Skip,
+}
+
+/// A domain on its way through the stages
+pub struct CheckState {
+    pub verdict: Verdict,
+    /// Addresses from the dns stage; None when the pipeline has none, so
+    /// HTTP goes to the system resolver
+    addrs: Option<Vec<IpAddr>>,
+    /// dns.record_types other than A/AAAA matched, a hit without HTTP
+    other_records: bool,
+    /// Certificate of the http stage's HTTPS answer, whether stored or not
+    https_cert: Option<CertInfo>,
+    /// Body the http stage read for the match rules
+    page: Option<Vec<u8>>,
+}
+
+/// One step of the check pipeline (top-level `checks`). A stage fills in
+/// the verdict and says whether the domain passed it.
+pub trait CheckStage: Send + Sync {
+    fn run<'a>(&'a self, st: &'a mut CheckState, hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome>;
+}
+
+struct Step {
+    kind: CheckKind,
+    stage: Box<dyn CheckStage>,
+    when: RunWhen,
+    required: bool,
+}
+
+/// The configured stages of live mode, in order. A domain is found when
+/// the `success` expression holds, or without one when at least one stage
+/// passed and no required stage failed.
+pub struct Pipeline {
+    steps: Vec<Step>,
+    success: Option<SuccessExpr>,
+}
+
+impl Pipeline {
+    pub fn new(
+        cfg: &Config,
+        clients: CheckClients,
+        resolver: Arc<ResolverPool>,
+        rdap: Option<Arc<RdapClient>>,
+        mut wildcard: Option<WildcardFilter>,
+    ) -> Self {
+        let mut steps = Vec::new();
+        let mut names = Vec::new();
+        let family = resolver.address_family();
+        let client = clients.for_family(family).clone();
+        for c in cfg.check_stages() {
+            let stage: Box<dyn CheckStage> = match c.stage {
+                CheckKind::Dns => Box::new(DnsStage {
+                    resolver: resolver.clone(),
+                    wildcard: wildcard.take(),
+                }),
+                CheckKind::Http => Box::new(HttpStage {
+                    clients: clients.clone(),
+                    family,
+                }),
+                CheckKind::Tls => Box::new(TlsStage { client: client.clone() }),
+                // open only with rdap.enabled, which config validation asks for
+                CheckKind::Rdap => match &rdap {
+                    Some(r) => Box::new(RdapStage { rdap: r.clone() }),
+                    None => continue,
+                },
+                CheckKind::Parking => Box::new(ParkingStage {
+                    client: client.clone(),
+                    resolver: resolver.clone(),
+                    fingerprints: Fingerprints::new(&cfg.parking),
+                }),
+                // config validation checked the files exist; one that cannot be read is left out
+                CheckKind::Geoip => match GeoIp::open(&cfg.geoip) {
+                    Ok(geo) => Box::new(GeoIpStage { geo }),
+                    Err(e) => {
+                        error!("{e:#}; the geoip stage is left out");
+                        continue;
+                    }
+                },
+            };
+            names.push(c.stage.as_str());
+            steps.push(Step {
+                kind: c.stage,
+                stage,
+                when: c.when,
+                required: c.required,
+            });
+        }
+        info!("checks: {}", names.join(" -> "));
+        if let Some(expr) = &cfg.success {
+            info!("success: {expr:?}");
+        }
+        Self {
+            steps,
+            success: cfg.success.clone(),
+        }
+    }
+
+    pub async fn run(&self, domain: &str, hc: &HTTPCheckConfig) -> Verdict {
+        let span = info_span!("check", domain, found = Empty, failure = Empty);
+        let v = self.run_stages(domain, hc).instrument(span.clone()).await;
+        span.record("found", v.found);
+        if let Some(f) = v.failure {
+            span.record("failure", f.as_str());
+        }
+        v
+    }
+
+    async fn run_stages(&self, domain: &str, hc: &HTTPCheckConfig) -> Verdict {
+        let mut st = CheckState {
+            verdict: Verdict {
+                domain: domain.to_string(),
+                ..Default::default()
+            },
+            addrs: None,
+            other_records: false,
+            https_cert: None,
+            page: None,
+        };
+        let (mut passed, mut failed) = (false, false);
+        // what each stage that ran made of the domain, for `success`
+        let mut outcomes: Vec<(CheckKind, Outcome)> = Vec::new();
+        for step in &self.steps {
+            let due = match step.when {
+                RunWhen::Passing => !failed,
+                RunWhen::Failed => failed || !passed,
+                RunWhen::Always => true,
+            };
+            if !due {
+                continue;
+            }
+            // an optional stage does not get to explain why the domain was not found
+            let before = (st.verdict.failure, st.verdict.transient);
+            let outcome = step
+                .stage
+                .run(&mut st, hc)
+                .instrument(info_span!("check.stage", stage = step.kind.as_str()))
+                .await;
+            outcomes.push((step.kind, outcome));
+            match outcome {
+                Outcome::Pass => passed = true,
+                Outcome::Fail if step.required => failed = true,
+                Outcome::Fail => (st.verdict.failure, st.verdict.transient) = before,
+                Outcome::Skip => {}
+            }
+        }
+        let v = &mut st.verdict;
+        v.found = match &self.success {
+            Some(expr) => expr.eval(&|k| outcomes.iter().find(|(s, _)| *s == k).map(|(_, o)| *o)),
+            None => passed && !failed,
+        };
+        if v.found {
+            v.failure = None;
+            v.transient = false;
+        }
+        st.verdict
+    }
+}
+
+// Addresses (minus wildcard answers) and the other dns.record_types
+struct DnsStage {
+    resolver: Arc<ResolverPool>,
+    wildcard: Option<WildcardFilter>,
+}
+
+impl CheckStage for DnsStage {
+    fn run<'a>(&'a self, st: &'a mut CheckState, _hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome> {
+        async move {
+            let v = &mut st.verdict;
+            let domain = v.domain.as_str();
+            let mut addrs = Vec::new();
+            let started = Instant::now();
+            let looked_up = self.resolver.lookup_ip(domain).await;
+            v.timings.push(Timing {
+                stage: Stage::Dns,
+                elapsed: started.elapsed(),
+                answered: looked_up.as_ref().err().is_none_or(|e| !dns_transient(e)),
+            });
+            match looked_up {
+                Ok(ips) => {
+                    let wild = match &self.wildcard {
+                        Some(w) => w.is_wildcard_answer(domain, &ips, &self.resolver).await,
+                        None => false,
+                    };
+                    if wild {
+                        v.wildcard = true;
+                    } else {
+                        v.ips = ips.iter().map(|ip| ip.to_string()).collect();
+                        // dns.address_family both: one family is not enough
+                        if self.resolver.address_family().satisfied_by(&ips) {
+                            addrs = ips;
+                        } else {
+                            v.failure = Some(FailureKind::DnsNoRecords);
+                        }
+                    }
+                }
+                Err(e) => {
+                    v.transient = dns_transient(&e);
+                    v.failure = Some(dns_failure(&e));
+                    v.dns_error = Some(e.to_string());
+                }
+            }
+            let (types, mode) = self.resolver.record_types();
+            let mut matched: Vec<RecordKind> = Vec::new();
+            for &kind in types {
+                let present = match kind {
+                    RecordKind::A => addrs.iter().any(IpAddr::is_ipv4),
+                    RecordKind::Aaaa => addrs.iter().any(IpAddr::is_ipv6),
+                    _ => match self.resolver.has_records(domain, kind).await {
+                        Ok(found) => found,
+                        Err(e) => {
+                            v.transient |= dns_transient(&e);
+                            v.failure.get_or_insert(dns_failure(&e));
+                            v.dns_error.get_or_insert_with(|| e.to_string());
+                            false
+                        }
+                    },
+                };
+                if present && !matched.contains(&kind) {
+                    matched.push(kind);
+                }
+            }
+            let hit = match mode {
+                RecordMatch::Any => !matched.is_empty(),
+                RecordMatch::All => types.iter().all(|t| matched.contains(t)),
+            };
+            st.addrs = Some(addrs);
+            if !hit {
+                return Outcome::Fail;
+            }
+            v.record_types = matched.iter().map(|k| k.as_str()).collect();
+            v.failure = None;
+            st.other_records = matched.iter().any(|k| !k.is_address());
+            Outcome::Pass
+        }
+        .boxed()
+    }
+}
+
+// http_check requests, scheme by scheme; with http_check.per_family once
+// over each address family
+struct HttpStage {
+    clients: CheckClients,
+    family: AddressFamily,
+}
+
+impl HttpStage {
+    // Both families at once; the IPv4 answer is the one stored. Either one
+    // will do, unless dns.address_family asks for both.
+    async fn per_family(&self, v: &mut Verdict, hc: &HTTPCheckConfig) -> Result<HttpHit, HttpMiss> {
+        let (v4, v6) = futures_util::join!(
+            check_domain(&self.clients.ipv4, &v.domain, hc),
+            check_domain(&self.clients.ipv6, &v.domain, hc),
+        );
+        let label = |r: Result<HttpHit, HttpMiss>, family: &str| {
+            r.map_err(|mut m| {
+                m.error = format!("{family}: {}", m.error);
+                m
+            })
+        };
+        let (v4, v6) = (label(v4, "ipv4"), label(v6, "ipv6"));
+        v.http_families = [("ipv4", v4.is_ok()), ("ipv6", v6.is_ok())]
+            .into_iter()
+            .filter_map(|(f, ok)| ok.then_some(f))
+            .collect();
+        let need_both = self.family == AddressFamily::Both;
+        match (v4, v6) {
+            (Ok(mut hit), Ok(other)) => {
+                hit.timings.extend(other.timings);
+                Ok(hit)
+            }
+            (Ok(mut hit), Err(miss)) | (Err(miss), Ok(mut hit)) if !need_both => {
+                hit.timings.extend(miss.timings);
+                Ok(hit)
+            }
+            (Ok(hit), Err(mut miss)) | (Err(mut miss), Ok(hit)) => {
+                miss.timings.extend(hit.timings);
+                Err(miss)
+            }
+            (Err(mut miss), Err(other)) => {
+                miss.timings.extend(other.timings);
+                miss.transient |= other.transient;
+                Err(miss)
+            }
+        }
+    }
+}
+
+impl CheckStage for HttpStage {
+    fn run<'a>(&'a self, st: &'a mut CheckState, hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome> {
+        async move {
+            if st.addrs.as_ref().is_some_and(|a| a.is_empty()) {
+                return Outcome::Skip;
+            }
+            let v = &mut st.verdict;
+            let checked = if hc.per_family {
+                self.per_family(v, hc).await
+            } else {
+                check_domain(self.clients.for_family(self.family), &v.domain, hc).await
+            };
+            match checked {
+                Ok(hit) => {
+                    v.http_status = Some(hit.status);
+                    v.scheme = Some(hit.scheme);
+                    if hc.scheme_policy == SchemePolicy::All {
+                        v.schemes = hit.schemes.clone();
+                    }
+                    v.final_url = Some(hit.final_url);
+                    v.server = Some(hit.server);
+                    v.response_ms = Some(hit.response_ms);
+                    v.title = Some(hit.title).filter(|t| !t.is_empty());
+                    v.tag = Some(hit.tag).filter(|t| !t.is_empty());
+                    // a status rule's tag wins
+                    if v.tag.is_none() && !hit.schemes.contains(&"https") {
+                        v.tag = Some(hc.http_only_tag.trim().to_string()).filter(|t| !t.is_empty());
+                    }
+                    v.cert = hit.cert.clone().filter(|_| hc.tls.inspect);
+                    v.timings.extend(hit.timings);
+                    st.https_cert = hit.cert;
+                    st.page = hit.body;
+                    v.robots = hit.robots.map(|allowed| if allowed { "allowed" } else { "disallowed" });
+                    Outcome::Pass
+                }
+                Err(miss) => {
+                    v.timings.extend(miss.timings);
+                    v.http_error = Some(miss.error);
+                    // MX/NS/TXT matches are hits without it
+                    if st.other_records {
+                        return Outcome::Skip;
+                    }
+                    v.transient = miss.transient;
+                    v.failure = Some(miss.kind);
+                    Outcome::Fail
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+// A valid certificate on port 443: the one the http stage got over HTTPS,
+// else one HEAD request of its own
+struct TlsStage {
+    client: Client,
+}
+
+impl CheckStage for TlsStage {
+    fn run<'a>(&'a self, st: &'a mut CheckState, hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome> {
+        async move {
+            if st.addrs.as_ref().is_some_and(|a| a.is_empty()) {
+                return Outcome::Skip;
+            }
+            let v = &mut st.verdict;
+            let cert = match st.https_cert.take() {
+                Some(c) => c,
+                None => {
+                    let url = format!("https://{}/", v.domain);
+                    let started = Instant::now();
+                    let sent = bandwidth::send(check_request(&self.client, Method::HEAD, &url, hc), Channel::Http).await;
+                    v.timings.push(Timing {
+                        stage: Stage::Http,
+                        elapsed: started.elapsed(),
+                        answered: sent.is_ok(),
+                    });
+                    let resp = match sent {
+                        Ok(r) => r,
+                        Err(e) => {
+                            v.transient = http_transient(&e);
+                            v.failure = Some(http_failure(&e));
+                            v.http_error = Some(format!("{url}: {e}"));
+                            return Outcome::Fail;
+                        }
+                    };
+                    match certs::inspect(&resp) {
+                        Some(c) => c,
+                        None => {
+                            v.failure = Some(FailureKind::HttpCert);
+                            v.http_error = Some(format!("{url}: no certificate"));
+                            return Outcome::Fail;
+                        }
+                    }
+                }
+            };
+            let valid = cert.valid;
+            if !valid {
+                v.failure = Some(FailureKind::HttpCert);
+                v.http_error = Some(format!("https://{}/: certificate not valid: {}", v.domain, cert.error));
+            }
+            v.cert = Some(cert);
+            if valid {
+                Outcome::Pass
+            } else {
+                Outcome::Fail
+            }
+        }
+        .boxed()
+    }
+}
+
+// Registered vs. available, for what did not answer
+struct RdapStage {
+    rdap: Arc<RdapClient>,
+}
+
+impl CheckStage for RdapStage {
+    fn run<'a>(&'a self, st: &'a mut CheckState, _hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome> {
+        async move {
+            let v = &mut st.verdict;
+            // subdomains of a parent zone are not registrations
+            if crate::store::parent_zone(&v.domain).is_some() {
+                return Outcome::Skip;
+            }
+            if v.ips.is_empty() || !self.rdap.only_unresolved() {
+                v.availability = Some(self.rdap.lookup(&v.domain).await);
+            }
+            Outcome::Skip
+        }
+        .boxed()
+    }
+}
+
+// Parking provider fingerprints: nameservers, the host the site ends up on
+// and the page body, read by the http stage or fetched here. Fails for a
+// parked domain, which as an optional stage only marks it.
+struct ParkingStage {
+    client: Client,
+    resolver: Arc<ResolverPool>,
+    fingerprints: Fingerprints,
+}
+
+impl ParkingStage {
+    // Final URL and the first body_limit bytes of the site's page, following
+    // redirects as http_check does; None when it cannot be fetched
+    async fn page(&self, v: &mut Verdict, hc: &HTTPCheckConfig) -> Option<(Url, Vec<u8>)> {
+        let start = v.final_url.clone().unwrap_or_else(|| format!("http://{}/", v.domain));
+        let url = Url::parse(&start).ok()?;
+        let started = Instant::now();
+        let fetched = fetch(&self.client, &Method::GET, url, &v.domain, hc).await;
+        v.timings.push(Timing {
+            stage: Stage::Http,
+            elapsed: started.elapsed(),
+            answered: fetched.is_ok(),
+        });
+        let resp = fetched.ok()?;
+        let url = resp.url().clone();
+        let body = read_body(resp, hc.body_limit).await.ok()?;
+        Some((url, body))
+    }
+}
+
+impl CheckStage for ParkingStage {
+    fn run<'a>(&'a self, st: &'a mut CheckState, hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome> {
+        async move {
+            let fp = &self.fingerprints;
+            let v = &mut st.verdict;
+            let mut provider = None;
+            if fp.uses_nameservers() {
+                if let Ok(ns) = self.resolver.ns_names(&v.domain).await {
+                    provider = fp.by_nameservers(&ns);
+                }
+            }
+            let host = |u: &str| Url::parse(u).ok().and_then(|u| u.host_str().map(str::to_string));
+            if provider.is_none() {
+                if let Some(h) = v.final_url.as_deref().and_then(host) {
+                    provider = fp.by_host(&h);
+                }
+            }
+            // no addresses: nothing to fetch
+            let reachable = !st.addrs.as_ref().is_some_and(|a| a.is_empty());
+            if provider.is_none() && fp.uses_body() && reachable {
+                let page = match st.page.take() {
+                    Some(body) => Some((None, body)),
+                    None => self.page(v, hc).await.map(|(u, b)| (Some(u), b)),
+                };
+                if let Some((url, body)) = page {
+                    provider = url
+                        .and_then(|u| u.host_str().and_then(|h| fp.by_host(h)))
+                        .or_else(|| fp.by_body(&body));
+                }
+            }
+            let Some(name) = provider else {
+                return Outcome::Skip;
+            };
+            v.parked_by = Some(name.to_string());
+            v.failure = Some(FailureKind::Parked);
+            v.transient = false;
+            Outcome::Fail
+        }
+        .boxed()
+    }
+}
+
+// Country and network of the addresses the dns stage found
+struct GeoIpStage {
+    geo: GeoIp,
+}
+
+impl CheckStage for GeoIpStage {
+    fn run<'a>(&'a self, st: &'a mut CheckState, _hc: &'a HTTPCheckConfig) -> BoxFuture<'a, Outcome> {
+        async move {
+            if let Some(addrs) = st.addrs.as_deref().filter(|a| !a.is_empty()) {
+                let info = self.geo.lookup(addrs);
+                let v = &mut st.verdict;
+                v.country = info.country;
+                v.asn = info.asn;
+                v.as_org = info.as_org;
+            }
+            Outcome::Skip
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use reqwest::{Client, Method, Url};
+use tokio::time;
+use tracing::debug;
+
+use super::{fetch, read_body};
+use crate::config::{HTTPCheckConfig, RobotsConfig};
+
+// RFC 9309: crawlers read at least the first 500 KiB
+const MAX_SIZE: usize = 500 * 1024;
+
+// robots.txt verdicts by "scheme://host"; shared by the scans and verify
+// rounds, which build their own clients but visit the same sites
+static SITES: Lazy<Mutex<HashMap<String, Site>>> = Lazy::new(Default::default);
+
+struct Site {
+    allowed: bool,
+    crawl_delay: Duration,
+    fetched: Instant,
+    // the next request to the site waits for this
+    next: Instant,
+}
+
+/// Read the site's robots.txt (cached for http_check.robots.cache_ttl) and
+/// wait out its Crawl-delay before the page request. Some(false) when it
+/// disallows crawling `/`, None when it could not be fetched.
+pub(super) async fn visit(client: &Client, scheme: &str, domain: &str, hc: &HTTPCheckConfig) -> Option<bool> {
+    let cfg = &hc.robots;
+    let key = format!("{scheme}://{domain}");
+    let cached = SITES
+        .lock()
+        .get(&key)
+        .filter(|s| s.fetched.elapsed() < cfg.cache_ttl)
+        .map(|s| s.allowed);
+    let allowed = match cached {
+        Some(a) => a,
+        None => {
+            let (allowed, crawl_delay) = read_robots(client, &key, domain, hc).await?;
+            remember(key.clone(), allowed, crawl_delay, cfg);
+            allowed
+        }
+    };
+    // no request follows
+    if !allowed && cfg.skip_disallowed {
+        return Some(false);
+    }
+    let at = {
+        let mut sites = SITES.lock();
+        let site = sites.get_mut(&key)?;
+        let at = site.next.max(Instant::now());
+        site.next = at + site.crawl_delay.min(cfg.max_crawl_delay);
+        at
+    };
+    if at > Instant::now() {
+        debug!("robots: waiting {}ms for the crawl delay of {}", (at - Instant::now()).as_millis(), key);
+        time::sleep_until(at.into()).await;
+    }
+    Some(allowed)
+}
+
+// Whether `/` may be crawled and the Crawl-delay; a server error counts as
+// disallowing everything and a missing file (4xx) as allowing it, as RFC 9309 says
+async fn read_robots(client: &Client, base: &str, domain: &str, hc: &HTTPCheckConfig) -> Option<(bool, Duration)> {
+    let url = Url::parse(&format!("{base}/robots.txt")).ok()?;
+    let resp = match fetch(client, &Method::GET, url, domain, hc).await {
+        Ok(r) => r,
+        Err(_) => {
+            debug!("robots: {}/robots.txt could not be fetched", base);
+            return None;
+        }
+    };
+    let status = resp.status();
+    if status.is_server_error() {
+        return Some((false, Duration::ZERO));
+    }
+    if !status.is_success() {
+        return Some((true, Duration::ZERO));
+    }
+    let body = read_body(resp, MAX_SIZE).await.ok()?;
+    let (allowed, crawl_delay) = parse(&String::from_utf8_lossy(&body), &product_token(&hc.user_agent));
+    debug!("robots: {} allowed={} crawl_delay={:?}", base, allowed, crawl_delay);
+    Some((allowed, crawl_delay))
+}
+
+fn remember(key: String, allowed: bool, crawl_delay: Duration, cfg: &RobotsConfig) {
+    let mut sites = SITES.lock();
+    if sites.len() >= cfg.max_entries && !sites.contains_key(&key) {
+        sites.retain(|_, s| s.fetched.elapsed() < cfg.cache_ttl);
+        // all still fresh: make room for a while instead of scanning again on every insert
+        if sites.len() >= cfg.max_entries {
+            let drop: Vec<String> = sites.keys().take(cfg.max_entries / 10 + 1).cloned().collect();
+            for k in drop {
+                sites.remove(&k);
+            }
+        }
+    }
+    let now = Instant::now();
+    sites.insert(
+        key,
+        Site {
+            allowed,
+            crawl_delay,
+            fetched: now,
+            // the robots.txt request was one
+            next: now + crawl_delay.min(cfg.max_crawl_delay),
+        },
+    );
+}
+
+// "MyScanner/1.0 (+https://...)" -> "myscanner"; robots.txt groups name products
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    // (allow, path pattern)
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+// The rules of the groups naming `agent`, else of the `*` groups: whether
+// `/` is allowed (longest match wins, Allow on a tie) and the Crawl-delay
+fn parse(body: &str, agent: &str) -> (bool, Duration) {
+    let mut groups: Vec<Group> = Vec::new();
+    // consecutive User-agent lines share one group
+    let mut in_agents = false;
+    for line in body.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some((key, value)) = line.split_once(':') else { continue };
+        let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+        if key == "user-agent" {
+            if !in_agents {
+                groups.push(Group::default());
+                in_agents = true;
+            }
+            if let Some(g) = groups.last_mut() {
+                g.agents.push(value.to_ascii_lowercase());
+            }
+            continue;
+        }
+        in_agents = false;
+        let Some(g) = groups.last_mut() else { continue };
+        match key.as_str() {
+            // an empty Disallow allows everything, the same as no rule
+            "allow" | "disallow" if !value.is_empty() => g.rules.push((key == "allow", value.to_string())),
+            "crawl-delay" => {
+                g.crawl_delay = value.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0).map(Duration::from_secs_f64);
+            }
+            _ => {}
+        }
+    }
+    let named: Vec<&Group> = groups
+        .iter()
+        .filter(|g| !agent.is_empty() && g.agents.iter().any(|a| a == agent))
+        .collect();
+    let applying = if named.is_empty() {
+        groups.iter().filter(|g| g.agents.iter().any(|a| a == "*")).collect()
+    } else {
+        named
+    };
+    let best = applying
+        .iter()
+        .flat_map(|g| g.rules.iter())
+        .filter(|(_, pattern)| matches(pattern, "/"))
+        .max_by_key(|(allow, pattern)| (pattern.len(), *allow));
+    let allowed = best.is_none_or(|(allow, _)| *allow);
+    let crawl_delay = applying.iter().find_map(|g| g.crawl_delay).unwrap_or_default();
+    (allowed, crawl_delay)
+}
+
+// robots.txt path pattern: a prefix with `*` for any run of characters and
+// a trailing `$` for the end of the path
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        // the last piece of an anchored pattern has to end the path
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::pipeline::Outcome;
+use crate::config::CheckKind;
+
+/// When a domain counts as found (top-level `success`), as a boolean
+/// expression over the stage outcomes, e.g. `dns.any AND (http.ok OR tls.valid)`.
+#[derive(Clone, PartialEq, Eq)]
+pub enum SuccessExpr {
+    /// The stage ran and passed (`dns`, `http.ok`, `dns.any`, `tls.valid`)
+    Passed(CheckKind),
+    /// The stage ran and failed (`http.failed`, `parking.parked`)
+    Failed(CheckKind),
+    Not(Box<SuccessExpr>),
+    And(Box<SuccessExpr>, Box<SuccessExpr>),
+    Or(Box<SuccessExpr>, Box<SuccessExpr>),
+}
+
+impl SuccessExpr {
+    pub fn parse(src: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(src)?;
+        let mut p = Parser { tokens, pos: 0 };
+        let expr = p.or()?;
+        if let Some(t) = p.tokens.get(p.pos) {
+            anyhow::bail!("unexpected {t:?}");
+        }
+        Ok(expr)
+    }
+
+    /// `outcome` is what a stage made of the domain, None when it did not run.
+    pub fn eval(&self, outcome: &dyn Fn(CheckKind) -> Option<Outcome>) -> bool {
+        match self {
+            SuccessExpr::Passed(k) => outcome(*k) == Some(Outcome::Pass),
+            SuccessExpr::Failed(k) => outcome(*k) == Some(Outcome::Fail),
+            SuccessExpr::Not(e) => !e.eval(outcome),
+            SuccessExpr::And(a, b) => a.eval(outcome) && b.eval(outcome),
+            SuccessExpr::Or(a, b) => a.eval(outcome) || b.eval(outcome),
+        }
+    }
+
+    /// Stages the expression refers to.
+    pub fn stages(&self) -> Vec<CheckKind> {
+        match self {
+            SuccessExpr::Passed(k) | SuccessExpr::Failed(k) => vec![*k],
+            SuccessExpr::Not(e) => e.stages(),
+            SuccessExpr::And(a, b) | SuccessExpr::Or(a, b) => {
+                let mut v = a.stages();
+                v.extend(b.stages());
+                v
+            }
+        }
+    }
+}
+
+// printed back in the canonical form, for logs and config comparison
+impl fmt::Debug for SuccessExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SuccessExpr::Passed(k) => write!(f, "{}.ok", k.as_str()),
+            SuccessExpr::Failed(k) => write!(f, "{}.failed", k.as_str()),
+            SuccessExpr::Not(e) => write!(f, "NOT {e:?}"),
+            SuccessExpr::And(a, b) => write!(f, "({a:?} AND {b:?})"),
+            SuccessExpr::Or(a, b) => write!(f, "({a:?} OR {b:?})"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SuccessExpr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        SuccessExpr::parse(&s).map_err(|e| serde::de::Error::custom(format!("success: {e:#}")))
+    }
+}
+
+impl Serialize for SuccessExpr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{self:?}"))
+    }
+}
+
+impl utoipa::PartialSchema for SuccessExpr {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        utoipa::openapi::schema::ObjectBuilder::new()
+            .schema_type(utoipa::openapi::schema::Type::String)
+            .examples(["dns.any AND (http.ok OR tls.valid)"])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for SuccessExpr {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Atom(String),
+}
+
+fn tokenize(src: &str) -> anyhow::Result<Vec<Token>> {
+    let mut out = Vec::new();
+    let mut chars = src.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '!' => {
+                chars.next();
+                out.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Not,
+                });
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    anyhow::bail!("use {c}{c} or {}", if c == '&' { "AND" } else { "OR" });
+                }
+                out.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            _ if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == '.') {
+                    word.push(c);
+                    chars.next();
+                }
+                out.push(match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Atom(word.to_ascii_lowercase()),
+                });
+            }
+            _ => anyhow::bail!("unexpected {c:?}"),
+        }
+    }
+    Ok(out)
+}
+
+// or  := and (OR and)*
+// and := not (AND not)*
+// not := NOT not | ( or ) | atom
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<&Token> {
+        let t = self.tokens.get(self.pos);
+        self.pos += 1;
+        t
+    }
+
+    fn eat(&mut self, t: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(t) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> anyhow::Result<SuccessExpr> {
+        let mut e = self.and()?;
+        while self.eat(&Token::Or) {
+            e = SuccessExpr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    fn and(&mut self) -> anyhow::Result<SuccessExpr> {
+        let mut e = self.not()?;
+        while self.eat(&Token::And) {
+            e = SuccessExpr::And(Box::new(e), Box::new(self.not()?));
+        }
+        Ok(e)
+    }
+
+    fn not(&mut self) -> anyhow::Result<SuccessExpr> {
+        match self.next() {
+            Some(Token::Not) => Ok(SuccessExpr::Not(Box::new(self.not()?))),
+            Some(Token::Open) => {
+                let e = self.or()?;
+                if !self.eat(&Token::Close) {
+                    anyhow::bail!("missing ')'");
+                }
+                Ok(e)
+            }
+            Some(Token::Atom(a)) => {
+                let a = a.clone();
+                atom(&a)
+            }
+            Some(t) => anyhow::bail!("unexpected {t:?}"),
+            None => anyhow::bail!("expression ends too early"),
+        }
+    }
+}
+
+// `stage` or `stage.state`; dns.any, tls.valid and parking.parked read as
+// the checks put them
+fn atom(a: &str) -> anyhow::Result<SuccessExpr> {
+    let (name, state) = a.split_once('.').unwrap_or((a, "ok"));
+    let Some(kind) = CheckKind::ALL.into_iter().find(|k| k.as_str() == name) else {
+        anyhow::bail!("unknown stage {name:?}");
+    };
+    match (kind, state) {
+        (_, "ok") | (CheckKind::Dns, "any") | (CheckKind::Tls, "valid") => Ok(SuccessExpr::Passed(kind)),
+        (_, "failed") | (CheckKind::Parking, "parked") => Ok(SuccessExpr::Failed(kind)),
+        _ => anyhow::bail!("unknown state {a:?}, use {name}.ok or {name}.failed"),
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::RangeInclusive;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::generator::GenerationOrder;
+
+// labels tracked at most; older positions are dropped and resume falls back to seeking the label
+const MAX_TRACKED: usize = 1 << 20;
+
+/// Generator position of the saved resume point: restoring `index` makes the
+/// generator produce `label` next, and `tld` is where the resume domain sits
+/// in the TLD list, so resume needs neither a replay nor a name comparison.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Unicode form, as generated
+    pub label: String,
+    /// See CandidateGenerator::checkpoint; for brute force its length is the label length
+    pub index: Vec<usize>,
+    pub tld: usize,
+    /// Set for the orders that run a block of labels per TLD (generator.order)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<BlockPosition>,
+}
+
+/// Block a label belongs to, with the settings that cut it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockPosition {
+    /// Generator position of the block's first label
+    pub start: Vec<usize>,
+    /// Labels of the block before this one
+    pub n: usize,
+    pub order: GenerationOrder,
+    pub batch: usize,
+}
+
+impl BlockPosition {
+    /// The block was cut the way `order` and `batch` cut it.
+    pub fn fits(&self, order: GenerationOrder, batch: usize) -> bool {
+        self.order == order && (!matches!(order, GenerationOrder::RoundRobin | GenerationOrder::Priority) || self.batch == batch)
+    }
+}
+
+// Positions of generated labels the resume position has not passed yet,
+// oldest first. A label's position only depends on the generator settings, so
+// entries stay valid across passes.
+#[derive(Default)]
+pub struct CheckpointLog {
+    // `tld` is filled in by lookup
+    labels: Mutex<VecDeque<Checkpoint>>,
+    cursors: Mutex<Cursors>,
+    // cursor labels the pass has not reached yet; their pairs keep the cursor
+    held: Mutex<BTreeSet<String>>,
+}
+
+impl CheckpointLog {
+    /// Start from the cursors of the saved state.
+    pub fn with_cursors(cursors: Cursors) -> Self {
+        Self { cursors: Mutex::new(cursors), ..Default::default() }
+    }
+
+    /// Move the cursors of `tlds` x `lengths` to the resume domain `domain`
+    /// (ACE) and return them all for saving. Pairs whose cursor the pass has
+    /// not reached yet stay where they are.
+    pub fn cursors(&self, domain: &str, tlds: &[String], lengths: RangeInclusive<usize>) -> Cursors {
+        let held = self.held.lock();
+        let mut g = self.cursors.lock();
+        g.record(domain, tlds, lengths, |c| held.contains(&c.label));
+        g.clone()
+    }
+
+    /// Remember where a label was generated from.
+    pub fn push(&self, position: Checkpoint) {
+        if position.index.is_empty() {
+            return;
+        }
+        let mut g = self.labels.lock();
+        if g.len() >= MAX_TRACKED {
+            g.pop_front();
+        }
+        g.push_back(position);
+    }
+
+    /// Checkpoint for the resume domain `domain` (ACE) given the generator's
+    /// TLD list; labels before it are forgotten. None when its label is no
+    /// longer tracked.
+    pub fn lookup(&self, domain: &str, tlds: &[String]) -> Option<Checkpoint> {
+        let unicode = idna::domain_to_unicode(domain).0;
+        let (label, tld) = unicode.split_once('.')?;
+        let tld = tlds.iter().position(|t| t.strip_prefix('.') == Some(tld))?;
+        let mut g = self.labels.lock();
+        let at = g.iter().position(|cp| cp.label == label)?;
+        g.drain(..at);
+        Some(Checkpoint { tld, ..g.front()?.clone() })
+    }
+}
+
+/// Resume position of one (label length, TLD) pair in `label` order: the
+/// labels the generator produces before `label` are done for it, and
+/// `label` itself when `done` is set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub label: String,
+    pub done: bool,
+}
+
+/// Cursors by TLD (Unicode form with the dot, as in candidate_suffixes) and
+/// label length. Pairs the current settings do not produce are kept, so
+/// changing the TLD list or the lengths between runs only starts the new
+/// pairs from the beginning.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<CursorGroup>", into = "Vec<CursorGroup>")]
+pub struct Cursors(BTreeMap<String, BTreeMap<usize, Cursor>>);
+
+// Saved form: pairs with the same cursor and lengths share one entry
+#[derive(Serialize, Deserialize)]
+struct CursorGroup {
+    label: String,
+    #[serde(default)]
+    done: bool,
+    lengths: Vec<usize>,
+    tlds: Vec<String>,
+}
+
+impl From<Vec<CursorGroup>> for Cursors {
+    fn from(groups: Vec<CursorGroup>) -> Self {
+        let mut out = Cursors::default();
+        for g in groups {
+            for tld in g.tlds {
+                let by_len = out.0.entry(tld).or_default();
+                for &len in &g.lengths {
+                    by_len.insert(len, Cursor { label: g.label.clone(), done: g.done });
+                }
+            }
+        }
+        out
+    }
+}
+
+impl From<Cursors> for Vec<CursorGroup> {
+    fn from(cursors: Cursors) -> Self {
+        let mut groups: BTreeMap<(String, bool, Vec<usize>), Vec<String>> = BTreeMap::new();
+        for (tld, by_len) in cursors.0 {
+            let mut per: BTreeMap<(String, bool), Vec<usize>> = BTreeMap::new();
+            for (len, c) in by_len {
+                per.entry((c.label, c.done)).or_default().push(len);
+            }
+            for ((label, done), lengths) in per {
+                groups.entry((label, done, lengths)).or_default().push(tld.clone());
+            }
+        }
+        groups
+            .into_iter()
+            .map(|((label, done, lengths), tlds)| CursorGroup { label, done, lengths, tlds })
+            .collect()
+    }
+}
+
+impl Cursors {
+    /// Cursors for a single resume domain (ACE), as format v1 state files and
+    /// explicit start points give it.
+    pub fn at(domain: &str, tlds: &[String], lengths: RangeInclusive<usize>) -> Self {
+        let mut out = Cursors::default();
+        out.advance(domain, tlds, lengths);
+        out
+    }
+
+    /// Move the cursors of `tlds` x `lengths` to the resume domain `domain` (ACE).
+    pub fn advance(&mut self, domain: &str, tlds: &[String], lengths: RangeInclusive<usize>) {
+        self.record(domain, tlds, lengths, |_| false);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, tld: &str, length: usize) -> Option<&Cursor> {
+        self.0.get(tld)?.get(&length)
+    }
+
+    // Everything up to `domain` was done for the pairs of `tlds` x `lengths`:
+    // its label for its TLD and the ones listed before it. A TLD no longer
+    // listed leaves the label to do for all of them.
+    fn record(&mut self, domain: &str, tlds: &[String], lengths: RangeInclusive<usize>, keep: impl Fn(&Cursor) -> bool) {
+        let unicode = idna::domain_to_unicode(&domain.trim().to_lowercase()).0;
+        let Some((label, tld)) = unicode.split_once('.') else {
+            return;
+        };
+        let at = tlds.iter().position(|t| t.strip_prefix('.') == Some(tld));
+        for (ti, t) in tlds.iter().enumerate() {
+            let by_len = self.0.entry(t.clone()).or_default();
+            for len in lengths.clone() {
+                if by_len.get(&len).is_some_and(&keep) {
+                    continue;
+                }
+                let done = at.is_some_and(|a| ti <= a);
+                by_len.insert(len, Cursor { label: label.to_string(), done });
+            }
+        }
+    }
+
+    /// The label every pair of `tlds` x `lengths` has its cursor at; None
+    /// when some pair has none or they differ, so the pass has to start over.
+    pub fn common_label(&self, tlds: &[String], lengths: RangeInclusive<usize>) -> Option<&str> {
+        let mut common = None;
+        for t in tlds {
+            for len in lengths.clone() {
+                let label = self.get(t, len)?.label.as_str();
+                if common.is_some_and(|c| c != label) {
+                    return None;
+                }
+                common = Some(label);
+            }
+        }
+        common
+    }
+
+    /// Distinct cursor labels.
+    pub fn labels(&self) -> BTreeSet<String> {
+        self.0.values().flat_map(|m| m.values().map(|c| c.label.clone())).collect()
+    }
+
+    /// Drop the cursors at `label`.
+    pub fn forget(&mut self, label: &str) {
+        for by_len in self.0.values_mut() {
+            by_len.retain(|_, c| c.label != label);
+        }
+        self.0.retain(|_, m| !m.is_empty());
+    }
+}
+
+/// Skips the (label, TLD) pairs the cursors cover while a pass catches up
+/// with them, and tells `log` which cursors it has reached.
+pub struct CursorSkip<'a> {
+    cursors: Cursors,
+    pending: BTreeSet<String>,
+    log: Option<&'a CheckpointLog>,
+}
+
+impl<'a> CursorSkip<'a> {
+    pub fn new(cursors: Cursors, log: Option<&'a CheckpointLog>) -> Self {
+        let pending = cursors.labels();
+        if let Some(l) = log {
+            *l.held.lock() = pending.clone();
+        }
+        Self { cursors, pending, log }
+    }
+
+    /// Which TLDs of `tlds` are already done for `label`, the next label of
+    /// the pass; empty once every cursor is reached.
+    pub fn covered(&mut self, label: &str, tlds: &[String]) -> Vec<bool> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        let len = label.chars().count();
+        let out = tlds
+            .iter()
+            .map(|t| match self.cursors.get(t, len) {
+                Some(c) if self.pending.contains(&c.label) => c.label != label || c.done,
+                _ => false,
+            })
+            .collect();
+        if self.pending.remove(label) {
+            if let Some(l) = self.log {
+                l.held.lock().remove(label);
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::dedup::{fnv1a, Dedup};
+use crate::domain::Domain;
+use crate::notify::Notifier;
+use crate::store::{DomainStore, FoundDomain};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterRole {
+    #[default]
+    Standalone,
+    Coordinator,
+    Worker,
+}
+
+/// Partition of the label space a candidate belongs to, for generators that
+/// cannot split their keyspace into ranges (`CandidateGenerator::shard`).
+pub fn shard_of(label: &str, shards: u32) -> u32 {
+    (fnv1a(label.as_bytes()) % shards.max(1) as u64) as u32
+}
+
+// Workers must enumerate the same keyspace as the coordinator expects
+pub fn generator_fingerprint(cfg: &Config) -> String {
+    let g = &cfg.generator;
+    let key = format!(
+        "{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{}|{}|{}|{}|{}|{}|{:?}|{:?}",
+        g.mode,
+        g.wordlists,
+        g.masks,
+        g.typos,
+        g.prefixes,
+        g.suffixes,
+        g.alphabet,
+        g.min_length,
+        g.max_length,
+        g.allow_hyphen,
+        g.forbid_leading_hyphen,
+        g.forbid_trailing_hyphen,
+        g.forbid_double_hyphen,
+        g.strategy,
+        g.sample
+    );
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+// -------- wire format --------
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LeaseReq {
+    pub worker: String,
+    pub fingerprint: String,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Lease {
+    pub shard: u32,
+    pub shards: u32,
+    pub ttl_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CompleteReq {
+    pub worker: String,
+    pub shard: u32,
+}
+
+// Found domains from a worker; an empty report is a lease heartbeat
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportReq {
+    pub worker: String,
+    pub shard: Option<u32>,
+    #[serde(default)]
+    pub domains: Vec<FoundDomain>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LeaseInfo {
+    shard: u32,
+    worker: String,
+    expires_in_secs: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ClusterStatus {
+    shards: u32,
+    pending: usize,
+    done: usize,
+    leased: Vec<LeaseInfo>,
+}
+
+pub enum LeaseError {
+    FingerprintMismatch,
+}
+
+// -------- coordinator --------
+
+#[derive(Clone)]
+enum Shard {
+    Pending,
+    Leased { worker: String, expires: Instant },
+    Done,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CoordinatorState {
+    shards: u32,
+    done: Vec<u32>,
+}
+
+// Hands out shards to workers and stores what they report
+pub struct Coordinator {
+    shards: Mutex<Vec<Shard>>,
+    ttl: Duration,
+    fingerprint: String,
+    store: DomainStore,
+    dedup: Option<Arc<Dedup>>,
+    notifier: Notifier,
+    state_path: PathBuf,
+}
+
+impl Coordinator {
+    pub fn new(cfg: &Config, store: DomainStore, dedup: Option<Arc<Dedup>>, notifier: Notifier) -> Arc<Self> {
+        let n = cfg.cluster.shards.max(1);
+        let state_path = PathBuf::from(&cfg.storage.dir).join("cluster.json");
+        let mut shards = vec![Shard::Pending; n as usize];
+        // keep finished shards across restarts as long as the partitioning is the same
+        if let Ok(st) = std::fs::read(&state_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|b| Ok(serde_json::from_slice::<CoordinatorState>(&b)?))
+        {
+            if st.shards == n {
+                for i in st.done.into_iter().filter(|i| *i < n) {
+                    shards[i as usize] = Shard::Done;
+                }
+            }
+        }
+        let done = shards.iter().filter(|s| matches!(s, Shard::Done)).count();
+        info!("cluster: coordinator with {} shards ({} already done)", n, done);
+        Arc::new(Self {
+            shards: Mutex::new(shards),
+            ttl: cfg.cluster.lease_ttl,
+            fingerprint: generator_fingerprint(cfg),
+            store,
+            dedup,
+            notifier,
+            state_path,
+        })
+    }
+
+    /// Lease the next pending (or expired) shard; None when all are taken or done.
+    pub fn lease(&self, req: &LeaseReq) -> Result<Option<Lease>, LeaseError> {
+        if req.fingerprint != self.fingerprint {
+            return Err(LeaseError::FingerprintMismatch);
+        }
+        let mut shards = self.shards.lock();
+        let total = shards.len() as u32;
+        let now = Instant::now();
+        let free = shards.iter().position(|s| match s {
+            Shard::Pending => true,
+            Shard::Leased { expires, .. } => *expires <= now,
+            Shard::Done => false,
+        });
+        let Some(i) = free else { return Ok(None) };
+        shards[i] = Shard::Leased {
+            worker: req.worker.clone(),
+            expires: now + self.ttl,
+        };
+        info!("cluster: shard {} leased to {}", i, req.worker);
+        Ok(Some(Lease {
+            shard: i as u32,
+            shards: total,
+            ttl_secs: self.ttl.as_secs(),
+        }))
+    }
+
+    pub fn complete(&self, req: &CompleteReq) -> bool {
+        let mut shards = self.shards.lock();
+        let Some(s) = shards.get_mut(req.shard as usize) else {
+            return false;
+        };
+        match s {
+            Shard::Leased { worker, .. } if *worker == req.worker => {
+                *s = Shard::Done;
+                info!("cluster: shard {} completed by {}", req.shard, req.worker);
+            }
+            // late completion of an expired lease still counts
+            Shard::Pending => *s = Shard::Done,
+            Shard::Done => {}
+            Shard::Leased { .. } => return false,
+        }
+        let done: Vec<u32> = shards
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| matches!(s, Shard::Done))
+            .map(|(i, _)| i as u32)
+            .collect();
+        let st = CoordinatorState {
+            shards: shards.len() as u32,
+            done,
+        };
+        if let Err(e) = serde_json::to_vec(&st)
+            .map_err(anyhow::Error::from)
+            .and_then(|b| Ok(std::fs::write(&self.state_path, b)?))
+        {
+            warn!("cluster: failed to save {}: {}", self.state_path.display(), e);
+        }
+        true
+    }
+
+    pub async fn report(&self, req: ReportReq) {
+        if let Some(i) = req.shard {
+            // renew the lease if this worker still holds it
+            if let Some(Shard::Leased { worker, expires }) = self.shards.lock().get_mut(i as usize) {
+                if *worker == req.worker {
+                    *expires = Instant::now() + self.ttl;
+                }
+            }
+        }
+        for rec in req.domains {
+            if self.dedup.as_ref().is_some_and(|d| !d.first_store("found", &rec.domain)) {
+                continue;
+            }
+            self.notifier.found(&rec);
+            self.store.add(rec).await;
+        }
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        let shards = self.shards.lock();
+        let now = Instant::now();
+        let mut st = ClusterStatus {
+            shards: shards.len() as u32,
+            pending: 0,
+            done: 0,
+            leased: vec![],
+        };
+        for (i, s) in shards.iter().enumerate() {
+            match s {
+                Shard::Leased { worker, expires } if *expires > now => st.leased.push(LeaseInfo {
+                    shard: i as u32,
+                    worker: worker.clone(),
+                    expires_in_secs: (*expires - now).as_secs(),
+                }),
+                Shard::Done => st.done += 1,
+                _ => st.pending += 1,
+            }
+        }
+        st
+    }
+}
+
+// -------- worker side --------
+
+// Talks to the coordinator; found domains are buffered and sent in batches
+pub struct ClusterClient {
+    http: Client,
+    base: String,
+    worker: String,
+    fingerprint: String,
+    api_key: String,
+    current: Mutex<Option<u32>>,
+    // candidates of the current shard queued and not checked yet
+    pending: Mutex<HashSet<Domain>>,
+    outbox: Mutex<Vec<FoundDomain>>,
+}
+
+impl ClusterClient {
+    pub fn new(cfg: &Config, http: Client) -> Arc<Self> {
+        let c = &cfg.cluster;
+        let worker = if c.worker_id.trim().is_empty() {
+            default_worker_id()
+        } else {
+            c.worker_id.trim().to_string()
+        };
+        info!("cluster: worker '{}' using coordinator {}", worker, c.coordinator_url);
+        let this = Arc::new(Self {
+            http,
+            base: c.coordinator_url.trim().trim_end_matches('/').to_string(),
+            worker,
+            fingerprint: generator_fingerprint(cfg),
+            api_key: c.api_key.0.trim().to_string(),
+            current: Mutex::new(None),
+            pending: Mutex::new(HashSet::new()),
+            outbox: Mutex::new(Vec::new()),
+        });
+        // batch reports and keep the lease alive
+        let reporter = Arc::downgrade(&this);
+        let heartbeat = (c.lease_ttl / 3).max(Duration::from_secs(1));
+        tokio::spawn(async move {
+            let mut last_sent = Instant::now();
+            loop {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                let Some(this) = reporter.upgrade() else { break };
+                if !this.outbox.lock().is_empty() || last_sent.elapsed() >= heartbeat {
+                    this.flush().await;
+                    last_sent = Instant::now();
+                }
+            }
+        });
+        this
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        let req = self.http.post(format!("{}{}", self.base, path));
+        if self.api_key.is_empty() {
+            req
+        } else {
+            req.bearer_auth(&self.api_key)
+        }
+    }
+
+    pub async fn lease(&self) -> anyhow::Result<Option<Lease>> {
+        let resp = self
+            .post("/cluster/lease")
+            .json(&LeaseReq {
+                worker: self.worker.clone(),
+                fingerprint: self.fingerprint.clone(),
+            })
+            .send()
+            .await?;
+        match resp.status() {
+            StatusCode::NO_CONTENT => Ok(None),
+            StatusCode::CONFLICT => anyhow::bail!("coordinator rejected lease: generator config differs"),
+            _ => {
+                let lease: Lease = resp.error_for_status()?.json().await?;
+                *self.current.lock() = Some(lease.shard);
+                Ok(Some(lease))
+            }
+        }
+    }
+
+    pub async fn complete(&self, shard: u32) -> anyhow::Result<()> {
+        self.flush().await;
+        self.post("/cluster/complete")
+            .json(&CompleteReq {
+                worker: self.worker.clone(),
+                shard,
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        *self.current.lock() = None;
+        Ok(())
+    }
+
+    /// A candidate of the current shard went into the queue.
+    pub fn queued(&self, domain: &Domain) {
+        self.pending.lock().insert(domain.clone());
+    }
+
+    /// A candidate is checked for good (not scheduled for a re-check).
+    pub fn checked(&self, domain: &Domain) {
+        self.pending.lock().remove(domain);
+    }
+
+    /// Candidates of the current shard still to be checked.
+    pub fn shard_pending(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    pub fn report(&self, rec: FoundDomain) {
+        self.outbox.lock().push(rec);
+    }
+
+    /// Send buffered results; they are kept for the next attempt on failure.
+    pub async fn flush(&self) {
+        let domains = std::mem::take(&mut *self.outbox.lock());
+        let req = ReportReq {
+            worker: self.worker.clone(),
+            shard: *self.current.lock(),
+            domains,
+        };
+        let res = self
+            .post("/cluster/report")
+            .json(&req)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = res {
+            warn!("cluster: report to coordinator failed: {}", e);
+            let mut outbox = self.outbox.lock();
+            let mut pending = req.domains;
+            pending.append(&mut outbox);
+            *outbox = pending;
+        }
+    }
+}
+
+fn default_worker_id() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| format!("worker-{}", std::process::id()))
+}
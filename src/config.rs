@@ -1,30 +1,267 @@
-use std::{fs, path::Path, time::Duration};
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc, time::Duration};
 
 use serde::de::Visitor;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_yaml as yaml;
+use tokio::sync::watch;
 
 use anyhow::Context;
-use tracing::info;
+use tracing::{info, warn};
+use utoipa::openapi::schema::{AdditionalProperties, Array, ArrayBuilder, ObjectBuilder, OneOfBuilder, Schema, Type};
+use utoipa::openapi::{Ref, RefOr};
+use utoipa::{PartialSchema, ToSchema};
 
-#[derive(Clone, Debug, Deserialize)]
+use crate::api_limit::ClientBy;
+use crate::auth::AuthRole;
+use crate::checker::{FailureKind, ScanMode, SuccessExpr};
+use crate::cluster::ClusterRole;
+use crate::dns::{AddressFamily, RecordKind, RecordMatch};
+use crate::generator::{GenerationOrder, GenerationStrategy, GeneratorMode, TypoKind};
+use crate::notify::WebhookFormat;
+use crate::sources::SourceKind;
+use crate::stats::StatsSchema;
+use crate::store::StorageBackendKind;
+
+mod schema;
+
+pub use schema::{json_schema, unknown_keys};
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct Config {
     #[allow(dead_code)]
     pub version: i32,
+    /// live (default): reachable sites; availability: unregistered names
+    #[serde(default)]
+    pub mode: ScanMode,
+    /// Check pipeline of live mode, in order; empty means dns, http (, rdap)
+    #[serde(default)]
+    pub checks: Vec<CheckStageConfig>,
+    /// When a live-mode domain is found, e.g. `dns.any AND (http.ok OR tls.valid)`;
+    /// unset: a stage passed and no required stage failed
+    #[serde(default)]
+    pub success: Option<SuccessExpr>,
     pub generator: GeneratorConfig,
     pub limits: LimitsConfig,
     #[serde(rename = "http_check")]
     pub http_check: HTTPCheckConfig,
     pub run: RunConfig,
+    /// Start scans at cron times instead of once or in a loop
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
     pub storage: StorageConfig,
+    /// Message brokers every found domain is published to
+    #[serde(default)]
+    pub sinks: SinksConfig,
+    #[serde(default)]
+    pub rdap: RdapConfig,
+    #[serde(default)]
+    pub dns: DnsConfig,
+    #[serde(default)]
+    pub dedup: DedupConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub wildcard: WildcardConfig,
+    /// Fingerprints of the `parking` check stage
+    #[serde(default)]
+    pub parking: ParkingConfig,
+    /// Databases of the `geoip` check stage
+    #[serde(default)]
+    pub geoip: GeoIpConfig,
+    #[serde(default)]
+    pub ingest: IngestConfig,
+    #[serde(default)]
+    pub source: SourceConfig,
+    /// Periodic re-check of the found domains
+    #[serde(default)]
+    pub verify: VerifyConfig,
+    /// Snapshots of the main scan's counters for GET /stats/history
+    #[serde(default)]
+    pub stats_history: StatsHistoryConfig,
+    /// Rules on the main scan's rates and what to do when one holds
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    /// OpenTelemetry span export
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Named scan configurations started through POST /scans/
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+impl Config {
+    /// The check pipeline of live mode: `checks`, or dns -> http (-> rdap
+    /// when enabled) when it is not set.
+    pub fn check_stages(&self) -> Vec<CheckStageConfig> {
+        if !self.checks.is_empty() {
+            return self.checks.clone();
+        }
+        let mut stages = vec![CheckKind::Dns, CheckKind::Http];
+        if self.rdap.enabled {
+            stages.push(CheckKind::Rdap);
+        }
+        stages.into_iter().map(CheckStageConfig::new).collect()
+    }
+}
+
+/// A stage of the check pipeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckKind {
+    /// Address (and dns.record_types) lookup with the wildcard filter
+    Dns,
+    /// http_check requests
+    Http,
+    /// Certificate of the HTTPS answer, fetched when the http stage did not get one
+    Tls,
+    /// Registered vs. available; classifies, never decides `found`
+    Rdap,
+    /// Parking provider fingerprints (nameservers, redirect hosts, page body)
+    Parking,
+    /// Country and ASN of the addresses; records, never decides `found`
+    Geoip,
+}
+
+impl CheckKind {
+    pub const ALL: [CheckKind; 6] = [CheckKind::Dns, CheckKind::Http, CheckKind::Tls, CheckKind::Rdap, CheckKind::Parking, CheckKind::Geoip];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CheckKind::Dns => "dns",
+            CheckKind::Http => "http",
+            CheckKind::Tls => "tls",
+            CheckKind::Rdap => "rdap",
+            CheckKind::Parking => "parking",
+            CheckKind::Geoip => "geoip",
+        }
+    }
+}
+
+/// When a stage runs, given the outcome of the stages before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunWhen {
+    /// No required stage has failed so far
+    Passing,
+    /// The domain is not found so far: a required stage failed or none passed
+    Failed,
+    Always,
+}
+
+/// One entry of `checks`: a stage name, or `{stage, when, required}`
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckStageConfig {
+    pub stage: CheckKind,
+    /// Default `passing`; `failed` for rdap
+    pub when: RunWhen,
+    /// A failure of this stage makes the domain not found (default true,
+    /// false for parking); optional stages only record what they saw
+    pub required: bool,
+}
+
+impl CheckStageConfig {
+    pub fn new(stage: CheckKind) -> Self {
+        Self {
+            stage,
+            when: if stage == CheckKind::Rdap { RunWhen::Failed } else { RunWhen::Passing },
+            // parked domains are stored, marked as such, unless asked otherwise
+            required: stage != CheckKind::Parking,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CheckStageConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Full {
+            stage: CheckKind,
+            when: Option<RunWhen>,
+            required: Option<bool>,
+        }
+        // "dns" or {stage: dns, ...}
+        let full = match yaml::Value::deserialize(deserializer)? {
+            v @ yaml::Value::String(_) => Full {
+                stage: yaml::from_value(v).map_err(serde::de::Error::custom)?,
+                when: None,
+                required: None,
+            },
+            v => yaml::from_value::<Full>(v).map_err(serde::de::Error::custom)?,
+        };
+        let mut c = CheckStageConfig::new(full.stage);
+        c.when = full.when.unwrap_or(c.when);
+        c.required = full.required.unwrap_or(c.required);
+        Ok(c)
+    }
+}
+
+impl utoipa::PartialSchema for CheckStageConfig {
+    fn schema() -> RefOr<Schema> {
+        let full = ObjectBuilder::new()
+            .property("stage", Ref::from_schema_name(CheckKind::name()))
+            .required("stage")
+            .property("when", Ref::from_schema_name(RunWhen::name()))
+            .property("required", ObjectBuilder::new().schema_type(Type::Boolean))
+            .additional_properties(Some(AdditionalProperties::FreeForm(false)));
+        OneOfBuilder::new()
+            .item(Ref::from_schema_name(CheckKind::name()))
+            .item(full)
+            .description(Some("A stage name, or {stage, when, required}"))
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for CheckStageConfig {
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((CheckKind::name().into(), CheckKind::schema()));
+        schemas.push((RunWhen::name().into(), RunWhen::schema()));
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct GeneratorConfig {
+    #[serde(default)]
+    pub mode: GeneratorMode,
+    #[serde(default)]
+    pub wordlists: Vec<String>,
+    /// Mask mode patterns, e.g. "shop?l?l?d" (see generator::parse_mask)
+    #[serde(default)]
+    pub masks: Vec<String>,
+    /// Seeds and variant kinds for typos mode
+    #[serde(default)]
+    pub typos: TyposConfig,
+    /// The n-gram model of markov mode
+    #[serde(default)]
+    pub markov: MarkovConfig,
+    /// Prepended / appended to every generated label (any mode)
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub suffixes: Vec<String>,
     #[serde(default)]
     pub tlds: Vec<String>,
     #[serde(default)]
     pub tlds_file: String,
+    /// How often a tlds_file URL is fetched again; added TLDs join the
+    /// running scan. 0 turns the refresh off
+    #[serde(default = "default_tlds_refresh", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub tlds_refresh: Duration,
+    /// Zones whose subdomains are generated ("example.com" gives
+    /// "<label>.example.com"), next to the TLDs; stored under the zone
+    #[serde(default)]
+    pub parent_zones: Vec<String>,
     pub min_length: i32,
     pub max_length: i32,
     #[serde(default)]
@@ -37,46 +274,2126 @@ pub struct GeneratorConfig {
     pub forbid_trailing_hyphen: bool,
     #[serde(default)]
     pub forbid_double_hyphen: bool,
+    /// The whole keyspace, or a seeded random share of it (`sample`)
+    #[serde(default)]
+    pub strategy: GenerationStrategy,
+    #[serde(default)]
+    pub sample: SampleConfig,
+    /// How labels and TLDs are combined
+    #[serde(default)]
+    pub order: GenerationOrder,
+    /// Labels per TLD turn with order: round_robin
+    #[serde(default = "default_order_batch")]
+    pub order_batch: usize,
+    /// Labels and full domains never to generate, one per line (path or URL)
+    #[serde(default)]
+    pub exclude_file: String,
+    /// Generated labels matching any of these are skipped
+    #[serde(default)]
+    pub exclude_regex: Vec<String>,
+    /// Label scores for order: priority and min_score
+    #[serde(default)]
+    pub scoring: ScoringConfig,
+}
+
+/// Brand names whose typo variants `mode: typos` generates
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TyposConfig {
+    /// Labels or domains ("example", "example.com"); the first label is used
+    #[serde(default)]
+    pub seeds: Vec<String>,
+    #[serde(default = "default_typo_kinds")]
+    pub kinds: Vec<TypoKind>,
+}
+
+impl Default for TyposConfig {
+    fn default() -> Self {
+        Self {
+            seeds: Vec::new(),
+            kinds: default_typo_kinds(),
+        }
+    }
+}
+
+fn default_typo_kinds() -> Vec<TypoKind> {
+    TypoKind::ALL.to_vec()
+}
+
+/// Character n-gram model `mode: markov` generates labels from, most likely first
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct MarkovConfig {
+    /// Known-good labels or domains (the first label is used) to train on,
+    /// one per line; paths or URLs
+    #[serde(default)]
+    pub corpus: Vec<String>,
+    /// Trained model file: loaded instead of reading `corpus` when it
+    /// exists, written after training otherwise; empty keeps it in memory
+    #[serde(default)]
+    pub model: String,
+    /// Characters of context the next one is predicted from
+    #[serde(default = "default_markov_order")]
+    pub order: usize,
+    /// Labels generated before the generator ends
+    #[serde(default = "default_markov_count")]
+    pub count: usize,
+}
+
+impl Default for MarkovConfig {
+    fn default() -> Self {
+        Self {
+            corpus: Vec::new(),
+            model: String::new(),
+            order: default_markov_order(),
+            count: default_markov_count(),
+        }
+    }
+}
+
+fn default_markov_order() -> usize {
+    3
+}
+
+fn default_markov_count() -> usize {
+    100_000
+}
+
+/// Share of the keyspace `strategy: random_sample` generates
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct SampleConfig {
+    /// Fraction of the labels of each length, in (0, 1]
+    #[serde(default = "default_sample_rate")]
+    pub rate: f64,
+    /// Picks the labels and their order; the same seed gives the same sample
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl Default for SampleConfig {
+    fn default() -> Self {
+        Self {
+            rate: default_sample_rate(),
+            seed: 0,
+        }
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    0.01
+}
+
+fn default_tlds_refresh() -> Duration {
+    Duration::from_secs(24 * 3600)
+}
+
+fn default_order_batch() -> usize {
+    1000
+}
+
+// How generated labels are rated (see score::Scorer)
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ScoringConfig {
+    /// Word lists (paths or URLs); labels made of these words score higher
+    #[serde(default)]
+    pub dictionary: Vec<String>,
+    /// Labels scoring below this (0-100) are skipped in every order; 0 keeps all
+    #[serde(default)]
+    pub min_score: f64,
+    #[serde(default)]
+    pub weights: ScoreWeights,
+}
+
+/// Relative weight of each feature in the score
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ScoreWeights {
+    #[serde(default = "default_weight_pronounceable")]
+    pub pronounceable: f64,
+    #[serde(default = "default_weight_dictionary")]
+    pub dictionary: f64,
+    #[serde(default = "default_weight_length")]
+    pub length: f64,
+    #[serde(default = "default_weight_digits")]
+    pub digits: f64,
+    #[serde(default = "default_weight_hyphens")]
+    pub hyphens: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            pronounceable: default_weight_pronounceable(),
+            dictionary: default_weight_dictionary(),
+            length: default_weight_length(),
+            digits: default_weight_digits(),
+            hyphens: default_weight_hyphens(),
+        }
+    }
+}
+
+fn default_weight_pronounceable() -> f64 {
+    3.0
+}
+
+fn default_weight_dictionary() -> f64 {
+    3.0
+}
+
+fn default_weight_length() -> f64 {
+    2.0
+}
+
+fn default_weight_digits() -> f64 {
+    1.0
+}
+
+fn default_weight_hyphens() -> f64 {
+    1.0
+}
+
+// A named scan: `generator` keys override the top-level generator section
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ProfileConfig {
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub generator: yaml::Mapping,
+    /// Candidates for one run; 0 means limits.max_candidates
+    #[serde(default)]
+    pub max_candidates: i32,
+    /// Write found domains to `<storage.dir>/namespaces/<namespace>/` instead
+    /// of the main store; empty keeps them with the main scan's
+    #[serde(default)]
+    pub namespace: String,
+}
+
+impl ProfileConfig {
+    /// The configuration a scan of this profile runs with.
+    pub fn resolve(&self, base: &Config) -> anyhow::Result<Config> {
+        let mut gen = match yaml::to_value(&base.generator)? {
+            yaml::Value::Mapping(m) => m,
+            _ => unreachable!("generator serializes to a mapping"),
+        };
+        for (k, v) in &self.generator {
+            gen.insert(k.clone(), v.clone());
+        }
+        let mut cfg = base.clone();
+        cfg.generator = yaml::from_value(yaml::Value::Mapping(gen))?;
+        if self.max_candidates > 0 {
+            cfg.limits.max_candidates = self.max_candidates;
+        }
+        Ok(cfg)
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct LimitsConfig {
     pub concurrency: i32,
     pub rate_per_second: i32,
+    /// Token-bucket capacity; 0 means "same as rate_per_second"
+    #[serde(default)]
+    pub burst: i32,
     pub max_candidates: i32,
+    /// Tighter caps for the domains of some TLDs, keyed by TLD (".io")
+    #[serde(default)]
+    pub per_tld: BTreeMap<String, TldLimitConfig>,
+    /// Candidates the main scan may queue over passes and restarts
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Network traffic the checks may use per hour
+    #[serde(default)]
+    pub bandwidth: BandwidthConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct BudgetConfig {
+    /// In total, e.g. "1M"; the scan ends once it is used up. 0 for no limit
+    #[serde(default, deserialize_with = "de_count")]
+    #[schema(schema_with = count_schema)]
+    pub total: i64,
+    /// Per UTC day, e.g. "100k"; the scan waits for the next day. 0 for no limit
+    #[serde(default, deserialize_with = "de_count")]
+    #[schema(schema_with = count_schema)]
+    pub per_day: i64,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct BandwidthConfig {
+    /// Bytes per UTC hour, e.g. "2GB"; once used up checks wait for the next
+    /// hour. 0 for no limit
+    #[serde(default, deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub per_hour: usize,
+    /// What counts toward per_hour
+    #[serde(default)]
+    pub direction: TrafficDirection,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficDirection {
+    /// Sent and received
+    #[default]
+    Both,
+    /// Requests and queries only, for metered egress
+    Sent,
+    Received,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TldLimitConfig {
+    /// Checks of this TLD in flight at once; 0 leaves it to limits.concurrency
+    #[serde(default)]
+    pub concurrency: i32,
+    /// 0 leaves it to limits.rate_per_second
+    #[serde(default)]
+    pub rate_per_second: i32,
+    /// Token-bucket capacity; 0 means "same as rate_per_second"
+    #[serde(default)]
+    pub burst: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct HTTPCheckConfig {
-    #[serde(deserialize_with = "de_duration")]
+    #[serde(deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
     pub timeout: Duration,
     #[serde(default)]
     pub retry: u32,
     #[serde(default)]
     pub method: String,
+    /// `method` sends every check with `method`; `head_then_get` asks with
+    /// HEAD and falls back to GET when the server rejects or ignores it
+    #[serde(default)]
+    pub strategy: CheckStrategy,
+    /// How long a HEAD may take before GET is tried instead; 0 waits `timeout`
+    #[serde(default, deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub head_timeout: Duration,
+    /// User-Agent of the check requests; empty sends none
+    #[serde(default)]
+    pub user_agent: String,
+    /// Extra request headers, e.g. Accept-Language
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Request body, for methods that carry one (POST, PUT, ...)
+    #[serde(default)]
+    pub body: String,
+    /// Shorthand for a single `store` rule; used only when status_rules is empty
+    #[serde(default)]
     pub accept_status_min: i32,
+    #[serde(default)]
     pub accept_status_max: i32,
+    /// First rule whose statuses match decides; statuses no rule lists are rejected
+    #[serde(default)]
+    pub status_rules: Vec<StatusRule>,
     #[serde(default)]
     pub try_https_first: bool,
+    /// Which schemes are requested and which answers count
+    #[serde(default)]
+    pub scheme_policy: SchemePolicy,
+    /// Tag for found domains whose HTTPS request was not accepted, only plain
+    /// HTTP; empty leaves them untagged
+    #[serde(default)]
+    pub http_only_tag: String,
+    /// Most of a response body read for the `match` rules, e.g. "32KB"
+    #[serde(default = "default_body_limit", deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub body_limit: usize,
+    #[serde(default, rename = "match")]
+    pub content: ContentMatchConfig,
+    /// Follow 3xx answers; when off they are judged by their own status
+    #[serde(default = "default_true")]
+    pub follow_redirects: bool,
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+    /// Reject domains that redirect to a host outside the domain (parking, for-sale pages)
+    #[serde(default)]
+    pub reject_offsite_redirects: bool,
+    /// Check over IPv4 and over IPv6 and store which answered
+    #[serde(default)]
+    pub per_family: bool,
+    #[serde(default)]
+    pub tls: TlsCheckConfig,
+    #[serde(default)]
+    pub robots: RobotsConfig,
+    #[serde(default)]
+    pub client: HttpClientConfig,
+}
+
+/// Connection pool of the HTTP clients (http_check.client); read when the
+/// clients are built
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct HttpClientConfig {
+    /// Idle connections kept per host; 0 means limits.concurrency
+    #[serde(default)]
+    pub pool_max_idle_per_host: usize,
+    /// Idle connections are closed after this; 0 keeps them until the server closes them
+    #[serde(default = "default_pool_idle_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub pool_idle_timeout: Duration,
+    /// Limit on connection setup (DNS, TCP, TLS); 0 leaves it to `timeout`
+    #[serde(default, deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub connect_timeout: Duration,
+    /// Negotiate HTTP/2 over TLS; off speaks HTTP/1.1 only
+    #[serde(default = "default_true")]
+    pub http2: bool,
+    /// TCP keepalive interval; 0 turns it off
+    #[serde(default = "default_tcp_keepalive", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub tcp_keepalive: Duration,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 0,
+            pool_idle_timeout: default_pool_idle_timeout(),
+            connect_timeout: Duration::ZERO,
+            http2: true,
+            tcp_keepalive: default_tcp_keepalive(),
+        }
+    }
+}
+
+fn default_pool_idle_timeout() -> Duration {
+    Duration::from_secs(90)
+}
+
+fn default_tcp_keepalive() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// Certificate inspection of HTTPS answers (http_check.tls)
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TlsCheckConfig {
+    /// Store issuer, SANs, validity and verification result with HTTPS results
+    #[serde(default)]
+    pub inspect: bool,
+    /// Only accept answers over HTTPS with a valid certificate
+    #[serde(default)]
+    pub require_valid: bool,
+    /// Finish handshakes with invalid certificates instead of failing them;
+    /// read when the check client is built
+    #[serde(default)]
+    pub accept_invalid: bool,
+}
+
+/// robots.txt politeness of the content checks (http_check.robots)
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RobotsConfig {
+    /// Read /robots.txt before a page is fetched for the `match` rules,
+    /// wait out its Crawl-delay and store whether it disallows crawling
+    #[serde(default)]
+    pub enabled: bool,
+    /// Don't fetch pages robots.txt disallows; they fail the content check
+    #[serde(default)]
+    pub skip_disallowed: bool,
+    /// How long a fetched robots.txt is used
+    #[serde(default = "default_robots_cache_ttl", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub cache_ttl: Duration,
+    /// Sites whose robots.txt is kept at once
+    #[serde(default = "default_robots_max_entries")]
+    pub max_entries: usize,
+    /// Longest Crawl-delay waited for; longer ones are cut to it, 0 ignores them
+    #[serde(default = "default_max_crawl_delay", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub max_crawl_delay: Duration,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            skip_disallowed: false,
+            cache_ttl: default_robots_cache_ttl(),
+            max_entries: default_robots_max_entries(),
+            max_crawl_delay: default_max_crawl_delay(),
+        }
+    }
+}
+
+fn default_robots_cache_ttl() -> Duration {
+    Duration::from_secs(24 * 3600)
+}
+
+fn default_robots_max_entries() -> usize {
+    100_000
+}
+
+fn default_max_crawl_delay() -> Duration {
+    Duration::from_secs(10)
+}
+
+impl HTTPCheckConfig {
+    /// What to do with a response of this status, and the tag to store it with.
+    pub fn status_action(&self, status: u16) -> (StatusAction, &str) {
+        if self.status_rules.is_empty() {
+            let s = status as i32;
+            let ok = s >= self.accept_status_min && s <= self.accept_status_max;
+            return (if ok { StatusAction::Store } else { StatusAction::Reject }, "");
+        }
+        self.status_rules
+            .iter()
+            .find(|r| r.status.iter().any(|&(lo, hi)| (lo..=hi).contains(&status)))
+            .map_or((StatusAction::Reject, ""), |r| (r.action, r.tag.as_str()))
+    }
+}
+
+/// Schemes of the HTTP check (http_check.scheme_policy)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemePolicy {
+    /// Both, in try_https_first order, up to the first accepted answer
+    #[default]
+    First,
+    /// Both, stored as `schemes` with every one that was accepted
+    All,
+    /// HTTPS only; plain HTTP is never requested
+    HttpsOnly,
+}
+
+/// How the HTTP check requests a page (http_check.strategy)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStrategy {
+    /// One request with http_check.method
+    #[default]
+    Method,
+    /// HEAD first; GET when the answer is 405 or 501 or does not come within
+    /// head_timeout. Checks that need the body go straight to GET
+    HeadThenGet,
+}
+
+/// What an http_check.status_rules entry does with a matching response
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusAction {
+    /// Found: store the domain
+    #[default]
+    Store,
+    /// Found: store it with the rule's tag (e.g. "protected" for 401/403)
+    StoreWithTag,
+    /// Not found now; re-check it later through the retry queue
+    RetryLater,
+    /// Not found
+    Reject,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct StatusRule {
+    /// Codes and inclusive ranges: [200-299, 401, 403] or "5xx"
+    #[serde(deserialize_with = "de_statuses", serialize_with = "ser_statuses")]
+    #[schema(schema_with = statuses_schema)]
+    pub status: Vec<(u16, u16)>,
+    #[serde(default)]
+    pub action: StatusAction,
+    #[serde(default)]
+    pub tag: String,
+}
+
+fn de_statuses<'de, D>(deserializer: D) -> Result<Vec<(u16, u16)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // YAML reads 401 as a number and 200-299 as a string
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Spec {
+        Code(u16),
+        Text(String),
+    }
+    let specs = match Option::<Vec<Spec>>::deserialize(deserializer)? {
+        Some(v) => v,
+        None => return Ok(Vec::new()),
+    };
+    specs
+        .into_iter()
+        .map(|s| match s {
+            Spec::Code(c) => Ok((c, c)),
+            Spec::Text(t) => parse_status_range(&t).map_err(serde::de::Error::custom),
+        })
+        .collect()
+}
+
+// written back as in the file: 404, "200-299"
+fn ser_statuses<S: serde::Serializer>(status: &[(u16, u16)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(status.iter().map(|&(lo, hi)| match lo == hi {
+        true => yaml::Value::from(lo),
+        false => yaml::Value::from(format!("{lo}-{hi}")),
+    }))
+}
+
+fn statuses_schema() -> Array {
+    let code = ObjectBuilder::new().schema_type(Type::Integer).minimum(Some(100)).maximum(Some(999));
+    let text = ObjectBuilder::new().schema_type(Type::String).examples(["200-299", "5xx"]);
+    ArrayBuilder::new().items(OneOfBuilder::new().item(code).item(text)).build()
+}
+
+fn parse_status_range(s: &str) -> Result<(u16, u16), String> {
+    let s = s.trim().to_lowercase();
+    let bad = || format!("invalid status {s:?}, expected e.g. 404, 200-299 or 4xx");
+    if let Some(class) = s.strip_suffix("xx") {
+        let c: u16 = class.parse().map_err(|_| bad())?;
+        return if (1..=9).contains(&c) { Ok((c * 100, c * 100 + 99)) } else { Err(bad()) };
+    }
+    match s.split_once('-') {
+        Some((lo, hi)) => {
+            let (lo, hi) = (lo.trim().parse().map_err(|_| bad())?, hi.trim().parse().map_err(|_| bad())?);
+            if lo > hi {
+                return Err(bad());
+            }
+            Ok((lo, hi))
+        }
+        None => s.parse().map(|c| (c, c)).map_err(|_| bad()),
+    }
+}
+
+fn default_body_limit() -> usize {
+    64 * 1024
+}
+
+fn default_max_redirects() -> usize {
+    10
+}
+
+// Body checks for accepted responses, to tell real sites from parked pages and
+// registrar placeholders; the body is only read when one of them is set
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ContentMatchConfig {
+    /// Every pattern must match the body
+    #[serde(default)]
+    pub must_contain: Vec<Pattern>,
+    /// No pattern may match the body
+    #[serde(default)]
+    pub must_not_contain: Vec<Pattern>,
+    /// Shorter bodies are rejected
+    #[serde(default, deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub min_body_size: usize,
+    /// Keep the page <title> with the stored domain
+    #[serde(default)]
+    pub extract_title: bool,
+}
+
+impl ContentMatchConfig {
+    pub fn reads_body(&self) -> bool {
+        !self.must_contain.is_empty() || !self.must_not_contain.is_empty() || self.min_body_size > 0 || self.extract_title
+    }
+}
+
+// Regex from the config, compiled when the config is loaded
+#[derive(Clone, Debug, utoipa::ToSchema)]
+#[schema(value_type = String)]
+pub struct Pattern(pub regex::bytes::Regex);
+
+impl Serialize for Pattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        regex::bytes::Regex::new(&s).map(Pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RunConfig {
+    /// Start another pass when one ends
+    #[serde(default, rename = "loop", alias = "loop_")]
+    pub loop_: bool,
+    /// How long shutdown waits for in-flight checks before abandoning them
+    #[serde(default = "default_drain_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub drain_timeout: Duration,
+}
+
+fn default_drain_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// When scheduled scan runs start and what ends them; also the body of PUT /schedule/
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ScheduleConfig {
+    /// Cron expression in UTC: "min hour day month weekday", or with a
+    /// leading seconds field; empty disables the schedule
+    #[serde(default)]
+    #[schema(example = "0 3 * * *")]
+    pub cron: String,
+    /// A run is cut off after this long (e.g. "2h"); 0 runs until the pass ends
+    #[serde(default, deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String, example = "2h")]
+    pub max_duration: Duration,
+    /// Candidates queued per run; 0 uses limits.max_candidates
+    #[serde(default)]
+    pub max_candidates: i64,
+}
+
+impl ScheduleConfig {
+    /// The parsed cron expression; None when no schedule is set.
+    pub fn parse(&self) -> anyhow::Result<Option<cron::Schedule>> {
+        let expr = self.cron.trim();
+        if expr.is_empty() {
+            return Ok(None);
+        }
+        // the cron crate wants seconds first; classic crontab lines have 5 fields
+        let expr = if expr.split_whitespace().count() == 5 {
+            format!("0 {expr}")
+        } else {
+            expr.to_string()
+        };
+        let schedule = expr
+            .parse::<cron::Schedule>()
+            .with_context(|| format!("invalid cron expression {:?}", self.cron))?;
+        Ok(Some(schedule))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.max_candidates < 0 {
+            anyhow::bail!("max_candidates must be >= 0");
+        }
+        if let Some(s) = self.parse()? {
+            if s.upcoming(chrono::Utc).next().is_none() {
+                anyhow::bail!("cron expression {:?} never fires again", self.cron);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct StorageConfig {
+    pub dir: String,
+    #[serde(default)]
+    pub backend: StorageBackendKind,
+    /// SQLite database path; defaults to "<dir>/domains.sqlite"
+    #[serde(default)]
+    pub sqlite_path: String,
+    #[serde(default)]
+    pub resume: bool,
+    #[serde(default)]
+    pub state_file: String,
+    /// Journal of candidates checked past the resume position; makes resume exact
+    #[serde(default = "default_true")]
+    pub journal: bool,
+    /// Defaults to "<dir>/journal.log"
+    #[serde(default)]
+    pub journal_file: String,
+    /// Bucket settings for backend: s3
+    #[serde(default)]
+    pub s3: S3Config,
+    /// Rolling of the per-TLD files of backend: text
+    #[serde(default)]
+    pub rotation: RotationConfig,
+    /// Queue between the candidate source and the checks
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// Memory held by found domains on their way to the backend
+    #[serde(default)]
+    pub buffer: StoreBufferConfig,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct StoreBufferConfig {
+    /// The writer flushes once its buffered records reach this size; 0 flushes by count and time only
+    #[serde(default = "default_buffer_soft_limit", deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub soft_limit: usize,
+    /// Checks wait to add a found domain while records of this size are queued or buffered; 0 for no limit
+    #[serde(default = "default_buffer_hard_limit", deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub hard_limit: usize,
+}
+
+impl Default for StoreBufferConfig {
+    fn default() -> Self {
+        Self {
+            soft_limit: default_buffer_soft_limit(),
+            hard_limit: default_buffer_hard_limit(),
+        }
+    }
+}
+
+fn default_buffer_soft_limit() -> usize {
+    4 << 20
+}
+
+fn default_buffer_hard_limit() -> usize {
+    64 << 20
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct QueueConfig {
+    #[serde(default)]
+    pub kind: QueueKind,
+    /// Candidates queued before the source waits for the checks
+    #[serde(default = "default_queue_capacity")]
+    pub capacity: usize,
+    /// Directory of kind: disk; defaults to "<dir>/queue"
+    #[serde(default)]
+    pub dir: String,
+    /// Start a new segment file of kind: disk at this size
+    #[serde(default = "default_queue_segment_size", deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub segment_size: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            kind: QueueKind::default(),
+            capacity: default_queue_capacity(),
+            dir: String::new(),
+            segment_size: default_queue_segment_size(),
+        }
+    }
+}
+
+fn default_queue_capacity() -> usize {
+    10000
+}
+
+fn default_queue_segment_size() -> usize {
+    4 << 20
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueKind {
+    /// A channel; queued candidates are generated again after a restart
+    #[default]
+    Memory,
+    /// A segment log under queue.dir that survives a crash
+    Disk,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RotationConfig {
+    #[serde(default)]
+    pub period: RotationPeriod,
+    /// Roll a TLD's files once they hold this much; 0 for no limit
+    #[serde(default, deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub max_size: usize,
+    /// gzip rolled segments
+    #[serde(default)]
+    pub compress: bool,
+}
+
+impl RotationConfig {
+    pub fn enabled(&self) -> bool {
+        self.period != RotationPeriod::Never || self.max_size > 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationPeriod {
+    #[default]
+    Never,
+    /// A new segment for every UTC day
+    Daily,
+}
+
+// S3-compatible object storage (AWS, MinIO, R2, ...) for backend: s3
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct S3Config {
+    #[serde(default)]
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// Defaults to https://s3.<region>.amazonaws.com
+    #[serde(default)]
+    pub endpoint: String,
+    /// Bucket in the path instead of the host name (MinIO and most self-hosted stores)
+    #[serde(default)]
+    pub path_style: bool,
+    /// Key prefix of the result objects, e.g. "scans/eu-1/"
+    #[serde(default)]
+    pub prefix: String,
+    /// Default to AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY / AWS_SESSION_TOKEN
+    #[serde(default)]
+    pub access_key_id: String,
+    #[serde(default)]
+    pub secret_access_key: Secret,
+    #[serde(default)]
+    pub session_token: Secret,
+    /// A TLD's open shard object is rewritten on every flush until it reaches this size
+    #[serde(default = "default_s3_shard_size", deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub shard_size: usize,
+    /// or this age; then a new dated shard is started
+    #[serde(default = "default_s3_shard_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub shard_interval: Duration,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            bucket: String::new(),
+            region: default_s3_region(),
+            endpoint: String::new(),
+            path_style: false,
+            prefix: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: Secret::default(),
+            session_token: Secret::default(),
+            shard_size: default_s3_shard_size(),
+            shard_interval: default_s3_shard_interval(),
+        }
+    }
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_s3_shard_size() -> usize {
+    4 << 20
+}
+
+fn default_s3_shard_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct DnsConfig {
+    /// Upstream servers, see dns::parse_server; empty means Google public DNS
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// One resolver per server with queries spread round-robin; otherwise a
+    /// single resolver that picks among all servers itself
+    #[serde(default = "default_true")]
+    pub round_robin: bool,
+    #[serde(default = "default_dns_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub timeout: Duration,
+    #[serde(default = "default_dns_attempts")]
+    pub attempts: i32,
+    /// Record types that make a domain a hit; MX/NS/TXT matches need no HTTP answer
+    #[serde(default = "default_record_types")]
+    pub record_types: Vec<RecordKind>,
+    #[serde(default)]
+    pub record_match: RecordMatch,
+    /// A, AAAA, both or either (default) for a domain to resolve
+    #[serde(default)]
+    pub address_family: AddressFamily,
+    #[serde(default)]
+    pub batch: DnsBatchConfig,
+    #[serde(default)]
+    pub negative_cache: NegativeCacheConfig,
+    #[serde(default)]
+    pub positive_cache: PositiveCacheConfig,
+    #[serde(default)]
+    pub consensus: DnsConsensusConfig,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            servers: vec![],
+            round_robin: true,
+            timeout: default_dns_timeout(),
+            attempts: default_dns_attempts(),
+            record_types: default_record_types(),
+            record_match: RecordMatch::Any,
+            address_family: AddressFamily::Any,
+            batch: DnsBatchConfig::default(),
+            negative_cache: NegativeCacheConfig::default(),
+            positive_cache: PositiveCacheConfig::default(),
+            consensus: DnsConsensusConfig::default(),
+        }
+    }
+}
+
+// Every lookup asked of several resolvers at once; a name resolves only when
+// a quorum of them has an answer
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct DnsConsensusConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Voting resolvers, same format as `servers`; empty uses `servers`
+    #[serde(default)]
+    pub resolvers: Vec<String>,
+    /// Resolvers that must agree on an answer (or on its absence)
+    #[serde(default = "default_dns_quorum")]
+    pub quorum: usize,
+}
+
+impl Default for DnsConsensusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolvers: vec![],
+            quorum: default_dns_quorum(),
+        }
+    }
+}
+
+impl DnsConsensusConfig {
+    /// The voting resolvers: `resolvers`, else `dns.servers`.
+    pub fn voters<'a>(&'a self, servers: &'a [String]) -> Vec<&'a str> {
+        let list = if self.resolvers.iter().any(|s| !s.trim().is_empty()) { &self.resolvers } else { servers };
+        list.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).collect()
+    }
+}
+
+// A/AAAA lookups as raw UDP queries pipelined on one socket per server
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct DnsBatchConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Queries written out per wakeup of the sender
+    #[serde(default = "default_dns_batch_size")]
+    pub size: usize,
+    /// Queries waiting for an answer at once, over all servers
+    #[serde(default = "default_dns_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+impl Default for DnsBatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: default_dns_batch_size(),
+            max_in_flight: default_dns_max_in_flight(),
+        }
+    }
+}
+
+// NXDOMAIN names remembered so they are not looked up again within the ttl
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct NegativeCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_negative_ttl", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub ttl: Duration,
+    /// Names kept over all shards; the least recently used go first
+    #[serde(default = "default_negative_max_entries")]
+    pub max_entries: usize,
+    /// Separately locked parts of the cache
+    #[serde(default = "default_negative_shards")]
+    pub shards: usize,
+    /// Save the cache in storage.dir on shutdown and load it at startup
+    #[serde(default)]
+    pub persist: bool,
+}
+
+impl Default for NegativeCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl: default_negative_ttl(),
+            max_entries: default_negative_max_entries(),
+            shards: default_negative_shards(),
+            persist: false,
+        }
+    }
+}
+
+// Answers with records remembered for their TTL
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct PositiveCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Longest an answer is kept, whatever its TTL
+    #[serde(default = "default_positive_max_ttl", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub max_ttl: Duration,
+    /// Answers kept over all shards; the least recently used go first
+    #[serde(default = "default_positive_max_entries")]
+    pub max_entries: usize,
+    /// Separately locked parts of the cache
+    #[serde(default = "default_negative_shards")]
+    pub shards: usize,
+}
+
+impl Default for PositiveCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_ttl: default_positive_max_ttl(),
+            max_entries: default_positive_max_entries(),
+            shards: default_negative_shards(),
+        }
+    }
+}
+
+fn default_positive_max_ttl() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_positive_max_entries() -> usize {
+    100_000
+}
+
+fn default_negative_ttl() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_negative_max_entries() -> usize {
+    1_000_000
+}
+
+fn default_negative_shards() -> usize {
+    16
+}
+
+fn default_dns_quorum() -> usize {
+    2
+}
+
+fn default_dns_batch_size() -> usize {
+    64
+}
+
+fn default_dns_max_in_flight() -> usize {
+    4096
+}
+
+fn default_dns_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_record_types() -> Vec<RecordKind> {
+    vec![RecordKind::A, RecordKind::Aaaa]
+}
+
+fn default_dns_attempts() -> i32 {
+    2
+}
+
+// Skip candidates whose only addresses are their zone's wildcard answer
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct WildcardConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Random labels resolved per zone on its first candidate
+    #[serde(default = "default_wildcard_probes")]
+    pub probes: usize,
+}
+
+impl Default for WildcardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            probes: default_wildcard_probes(),
+        }
+    }
+}
+
+fn default_wildcard_probes() -> usize {
+    3
+}
+
+// Tell parked domains from live sites (the `parking` check stage)
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ParkingConfig {
+    /// Start from the built-in provider list (Sedo, Bodis, ParkingCrew, ...)
+    #[serde(default = "default_true")]
+    pub builtin: bool,
+    /// Fingerprints on top of the built-in ones
+    #[serde(default)]
+    pub providers: Vec<ParkingProvider>,
+}
+
+impl Default for ParkingConfig {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            providers: Vec::new(),
+        }
+    }
+}
+
+/// A parking provider; any one of its fingerprints marks a domain as parked
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ParkingProvider {
+    /// Stored as `parked_by`
+    pub name: String,
+    /// The domain's NS records end in one of these, e.g. "sedoparking.com"
+    #[serde(default)]
+    pub nameservers: Vec<String>,
+    /// The site redirects to one of these hosts or their subdomains
+    #[serde(default)]
+    pub redirect_hosts: Vec<String>,
+    /// The page body matches one of these patterns
+    #[serde(default)]
+    pub body: Vec<Pattern>,
+}
+
+/// MaxMind-compatible (`.mmdb`) databases, e.g. GeoLite2-Country and GeoLite2-ASN
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct GeoIpConfig {
+    /// Country or City database; empty stores no country
+    #[serde(default)]
+    pub country_db: String,
+    /// ASN database; empty stores no asn
+    #[serde(default)]
+    pub asn_db: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct StatsHistoryConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Time between snapshots
+    #[serde(default = "default_stats_history_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub interval: Duration,
+    /// Snapshots older than this are dropped
+    #[serde(default = "default_stats_history_retention", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub retention: Duration,
+}
+
+impl Default for StatsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: default_stats_history_interval(),
+            retention: default_stats_history_retention(),
+        }
+    }
+}
+
+/// OTLP export of the spans of checks, store writes and API requests
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub protocol: TelemetryProtocol,
+    /// Collector URL; empty uses the protocol's default on localhost
+    #[serde(default)]
+    pub endpoint: String,
+    /// Sent with every export, e.g. an API key of a hosted collector
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Share of traces kept (0..1); a caller's sampling decision wins
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+    #[serde(default = "default_telemetry_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub timeout: Duration,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            protocol: TelemetryProtocol::default(),
+            endpoint: String::new(),
+            headers: BTreeMap::new(),
+            service_name: default_service_name(),
+            sample_ratio: default_sample_ratio(),
+            timeout: default_telemetry_timeout(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// `endpoint`, or the collector default for `protocol`.
+    pub fn endpoint(&self, protocol: TelemetryProtocol) -> String {
+        match (self.endpoint.trim(), protocol) {
+            ("", TelemetryProtocol::Grpc) => "http://localhost:4317".to_string(),
+            ("", TelemetryProtocol::Http) => "http://localhost:4318/v1/traces".to_string(),
+            (ep, _) => ep.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TelemetryProtocol {
+    /// OTLP over gRPC (port 4317)
+    #[default]
+    Grpc,
+    /// OTLP protobuf over HTTP (port 4318)
+    Http,
+}
+
+fn default_service_name() -> String {
+    env!("CARGO_PKG_NAME").to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_telemetry_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_stats_history_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_stats_history_retention() -> Duration {
+    Duration::from_secs(7 * 24 * 3600)
+}
+
+// Checked against the main scan's counters every interval; applied live on reload
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct AlertsConfig {
+    /// Time between evaluations; each rule looks at the counters of the last one
+    #[serde(default = "default_alerts_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub interval: Duration,
+    /// Where the `webhook` action POSTs, with the retry and timeout of notifications.webhook
+    #[serde(default)]
+    pub webhook: Vec<WebhookEndpoint>,
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_alerts_interval(),
+            webhook: vec![],
+            rules: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct AlertRule {
+    /// Names the rule in logs, webhooks and /stats/
+    pub name: String,
+    pub metric: AlertMetric,
+    /// Failure causes counted by error_percent and errors_per_sec, e.g.
+    /// dns_timeout; empty counts all of them, dns_nxdomain included
+    #[serde(default)]
+    pub causes: Vec<String>,
+    /// Fires while the metric is over this...
+    #[serde(default)]
+    pub above: Option<f64>,
+    /// ...or under this; exactly one of the two is set
+    #[serde(default)]
+    pub below: Option<f64>,
+    /// How long the condition has to hold before the actions run; 0 runs them
+    /// at the first evaluation it holds in
+    #[serde(rename = "for", default, deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub hold: Duration,
+    /// Run once when the rule fires; log and webhook also report when it resolves
+    #[serde(default = "default_alert_actions")]
+    pub actions: Vec<AlertAction>,
+}
+
+impl AlertRule {
+    /// The threshold and whether the metric has to be over it.
+    pub fn threshold(&self) -> (f64, bool) {
+        match (self.above, self.below) {
+            (Some(t), _) => (t, true),
+            (_, Some(t)) => (t, false),
+            _ => (0.0, true),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// Failed check attempts in percent of all attempts, re-checks included
+    ErrorPercent,
+    ErrorsPerSec,
+    /// Checks finished per second
+    Speed,
+    FoundPerHour,
+}
+
+impl AlertMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertMetric::ErrorPercent => "error_percent",
+            AlertMetric::ErrorsPerSec => "errors_per_sec",
+            AlertMetric::Speed => "speed",
+            AlertMetric::FoundPerHour => "found_per_hour",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertAction {
+    /// A warning in the log
+    Log,
+    /// POST to alerts.webhook
+    Webhook,
+    /// Pause the main scan until POST /control/resume
+    Pause,
+}
+
+fn default_alerts_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_alert_actions() -> Vec<AlertAction> {
+    vec![AlertAction::Log]
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct VerifyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Time from the start of one round to the start of the next
+    #[serde(default = "default_verify_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub interval: Duration,
+    /// Re-checks in flight at once; they share limits.rate_per_second with the scans
+    #[serde(default = "default_verify_concurrency")]
+    pub concurrency: usize,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_verify_interval(),
+            concurrency: default_verify_concurrency(),
+        }
+    }
+}
+
+fn default_verify_interval() -> Duration {
+    Duration::from_secs(24 * 3600)
+}
+
+fn default_verify_concurrency() -> usize {
+    16
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct DedupConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Size of the `skip_checked` bloom filter
+    #[serde(default = "default_dedup_memory_mb")]
+    pub memory_mb: u32,
+    /// Also skip candidates already checked in the current pass (resume overlap,
+    /// TLD list reloads); stored domains are never appended twice either way.
+    /// A false positive skips a candidate that was never checked
+    #[serde(default)]
+    pub skip_checked: bool,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            memory_mb: default_dedup_memory_mb(),
+            skip_checked: false,
+        }
+    }
+}
+
+fn default_dedup_memory_mb() -> u32 {
+    64
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ClusterConfig {
+    #[serde(default)]
+    pub role: ClusterRole,
+    /// Coordinator base URL, e.g. http://10.0.0.1:8080 (worker role)
+    #[serde(default)]
+    pub coordinator_url: String,
+    /// Worker name reported to the coordinator; defaults to the hostname
+    #[serde(default)]
+    pub worker_id: String,
+    /// Number of label partitions handed out (coordinator role)
+    #[serde(default = "default_cluster_shards")]
+    pub shards: u32,
+    /// A lease not renewed by the worker within this time goes back to the pool
+    #[serde(default = "default_lease_ttl", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub lease_ttl: Duration,
+    /// Write-role key sent to a coordinator that has `auth` enabled (worker role)
+    #[serde(default)]
+    pub api_key: Secret,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            role: ClusterRole::default(),
+            coordinator_url: String::new(),
+            worker_id: String::new(),
+            shards: default_cluster_shards(),
+            lease_ttl: default_lease_ttl(),
+            api_key: Secret::default(),
+        }
+    }
+}
+
+fn default_cluster_shards() -> u32 {
+    64
+}
+
+fn default_lease_ttl() -> Duration {
+    Duration::from_secs(300)
+}
+
+// Found domains published by the store writer, as each batch is written
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct SinksConfig {
+    /// Keep writing to the storage backend too; off publishes only
+    #[serde(default = "default_true")]
+    pub store: bool,
+    #[serde(default)]
+    pub kafka: KafkaSinkConfig,
+    #[serde(default)]
+    pub nats: NatsSinkConfig,
+    /// Attempts after the first failed publish of a batch, with 1s, 2s, 4s... between
+    #[serde(default = "default_webhook_retry")]
+    pub retry: u32,
+    /// Batches waiting for each sink; more go to its spool file
+    #[serde(default = "default_sink_queue")]
+    pub queue: usize,
+    /// Limit on connecting and on publishing one batch
+    #[serde(default = "default_webhook_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub timeout: Duration,
+}
+
+impl Default for SinksConfig {
+    fn default() -> Self {
+        Self {
+            store: true,
+            kafka: KafkaSinkConfig::default(),
+            nats: NatsSinkConfig::default(),
+            retry: default_webhook_retry(),
+            queue: default_sink_queue(),
+            timeout: default_webhook_timeout(),
+        }
+    }
+}
+
+fn default_sink_queue() -> usize {
+    100
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct KafkaSinkConfig {
+    /// Bootstrap "host:port" addresses; empty turns the sink off
+    #[serde(default)]
+    pub brokers: Vec<String>,
+    /// Existing topic; records are keyed by domain, spread over its partitions
+    #[serde(default)]
+    pub topic: String,
+    #[serde(default = "default_kafka_client_id")]
+    pub client_id: String,
+    /// TLS to the brokers, verified against the system roots
+    #[serde(default)]
+    pub tls: bool,
+    /// SASL credentials; empty connects without authenticating
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub sasl_mechanism: KafkaSaslMechanism,
+}
+
+impl Default for KafkaSinkConfig {
+    fn default() -> Self {
+        Self {
+            brokers: vec![],
+            topic: String::new(),
+            client_id: default_kafka_client_id(),
+            tls: false,
+            username: String::new(),
+            password: String::new(),
+            sasl_mechanism: KafkaSaslMechanism::default(),
+        }
+    }
+}
+
+impl KafkaSinkConfig {
+    pub fn enabled(&self) -> bool {
+        !self.brokers.is_empty()
+    }
+}
+
+fn default_kafka_client_id() -> String {
+    env!("CARGO_PKG_NAME").to_string()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum KafkaSaslMechanism {
+    #[default]
+    Plain,
+    ScramSha256,
+    ScramSha512,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct NatsSinkConfig {
+    /// nats://[user:pass@]host[:4222] or tls://...; empty turns the sink off
+    #[serde(default)]
+    pub url: String,
+    /// "{tld}" is replaced with the domain's TLD
+    #[serde(default = "default_nats_subject")]
+    pub subject: String,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub token: String,
+}
+
+impl Default for NatsSinkConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            subject: default_nats_subject(),
+            user: String::new(),
+            password: String::new(),
+            token: String::new(),
+        }
+    }
+}
+
+impl NatsSinkConfig {
+    pub fn enabled(&self) -> bool {
+        !self.url.trim().is_empty()
+    }
+}
+
+fn default_nats_subject() -> String {
+    "domains.found.{tld}".to_string()
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub email: EmailConfig,
+}
+
+// Digest of the main scan by mail; applied live on reload
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct EmailConfig {
+    /// Recipients; empty turns the digest off
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub from: String,
+    #[serde(default)]
+    pub smtp_host: String,
+    /// 0 means 465 with security: tls, otherwise 587
+    #[serde(default)]
+    pub smtp_port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// AUTH PLAIN credentials; empty sends without logging in
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    /// Time between digests; 0 sends one only when the scan ends
+    #[serde(default = "default_email_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub interval: Duration,
+    /// Also send one when the main scan finishes or the process shuts down
+    #[serde(default = "default_true")]
+    pub on_completion: bool,
+    /// New finds listed in a digest, shortest names first
+    #[serde(default = "default_email_top")]
+    pub top: usize,
+    /// Limit on one delivery, connection to QUIT
+    #[serde(default = "default_webhook_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub timeout: Duration,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            to: vec![],
+            from: String::new(),
+            smtp_host: String::new(),
+            smtp_port: 0,
+            security: SmtpSecurity::default(),
+            username: String::new(),
+            password: String::new(),
+            interval: default_email_interval(),
+            on_completion: true,
+            top: default_email_top(),
+            timeout: default_webhook_timeout(),
+        }
+    }
+}
+
+impl EmailConfig {
+    pub fn enabled(&self) -> bool {
+        !self.to.is_empty()
+    }
+
+    pub fn port(&self) -> u16 {
+        match (self.smtp_port, self.security) {
+            (0, SmtpSecurity::Tls) => 465,
+            (0, _) => 587,
+            (p, _) => p,
+        }
+    }
+}
+
+/// How the SMTP connection is secured
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS (port 587)
+    #[default]
+    Starttls,
+    /// TLS from the start (port 465)
+    Tls,
+    /// No encryption, e.g. a local relay
+    None,
+}
+
+fn default_email_interval() -> Duration {
+    Duration::from_secs(24 * 3600)
+}
+
+fn default_email_top() -> usize {
+    20
+}
+
+// POST newly found domains to these URLs; applied live on reload
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// Send as soon as this many domains are waiting...
+    #[serde(default = "default_webhook_batch_size")]
+    pub batch_size: usize,
+    /// ...or once the oldest waiting one is this old
+    #[serde(default = "default_webhook_batch_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub batch_interval: Duration,
+    /// Attempts after the first on errors, 5xx and 429, with 1s, 2s, 4s... between
+    #[serde(default = "default_webhook_retry")]
+    pub retry: u32,
+    #[serde(default = "default_webhook_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub timeout: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: vec![],
+            batch_size: default_webhook_batch_size(),
+            batch_interval: default_webhook_batch_interval(),
+            retry: default_webhook_retry(),
+            timeout: default_webhook_timeout(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+fn default_webhook_batch_size() -> usize {
+    50
+}
+
+fn default_webhook_batch_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_webhook_retry() -> u32 {
+    3
+}
+
+fn default_webhook_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Listen address of the REST API, e.g. ":8080"; `--addr` overrides it
+    #[serde(default)]
+    pub public_addr: String,
+    /// Listen address of the control and admin routes and of every write,
+    /// e.g. "127.0.0.1:8081"; the public address then serves reads only.
+    /// Empty serves everything on the public address
+    #[serde(default)]
+    pub admin_addr: String,
+    /// Listen address of the gRPC API, e.g. ":50051"; empty disables it
+    #[serde(default)]
+    pub grpc_addr: String,
+    /// URL prefix every route is served under, e.g. "/domain-searcher"
+    /// behind a reverse proxy; empty serves from the root
+    #[serde(default)]
+    pub base_path: String,
+    #[serde(default)]
+    pub rate_limit: ApiRateLimitConfig,
+    /// Caps on the domains one list response returns; applied live on reload
+    #[serde(default)]
+    pub lists: ListLimitsConfig,
+    /// /stats/ payload layout when the request names none; applied live on reload
+    #[serde(default)]
+    pub stats_schema: StatsSchema,
+}
+
+impl ServerConfig {
+    /// `base_path` with a leading and no trailing slash; "" for the root
+    pub fn base_path(&self) -> String {
+        let p = self.base_path.trim().trim_matches('/');
+        if p.is_empty() {
+            String::new()
+        } else {
+            format!("/{p}")
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ListLimitsConfig {
+    /// Page size of `__all__` lists when the request has no `limit`; 0 for none
+    #[serde(default = "default_max_all")]
+    pub max_all: usize,
+    /// Largest page of any list, whatever `limit` asks for; 0 for none
+    #[serde(default)]
+    pub max_limit: usize,
+}
+
+impl Default for ListLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_all: default_max_all(),
+            max_limit: 0,
+        }
+    }
+}
+
+impl ListLimitsConfig {
+    /// Page size of a list request that asked for `limit` (`all`: an `__all__`
+    /// list), and whether it is smaller than what the client asked for.
+    pub fn page(&self, limit: Option<usize>, all: bool) -> (Option<usize>, bool) {
+        let mut page = limit.or((all && self.max_all > 0).then_some(self.max_all));
+        if self.max_limit > 0 {
+            page = Some(page.map_or(self.max_limit, |n| n.min(self.max_limit)));
+        }
+        (page, page != limit)
+    }
+}
+
+fn default_max_all() -> usize {
+    100_000
+}
+
+// Per-client request limits on the HTTP API; applied live on reload
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ApiRateLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Sustained requests per second of one client
+    #[serde(default = "default_api_rate")]
+    pub rate_per_second: f64,
+    /// Requests a client may send in a burst; 0 means rate_per_second
+    #[serde(default)]
+    pub burst: u32,
+    #[serde(default)]
+    pub by: ClientBy,
+    /// Take the client IP from the last X-Forwarded-For hop (behind a reverse proxy)
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for ApiRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate_per_second: default_api_rate(),
+            burst: 0,
+            by: ClientBy::default(),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+fn default_api_rate() -> f64 {
+    10.0
+}
+
+// Serve the API over HTTPS when cert and key are set (--tls-cert/--tls-key override)
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TlsConfig {
+    /// PEM certificate chain, e.g. fullchain.pem
+    #[serde(default)]
+    pub cert: String,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    #[serde(default)]
+    pub key: String,
+    /// How often the files are checked for changes; 0 disables reloading
+    #[serde(default = "default_tls_reload_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub reload_interval: Duration,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            cert: String::new(),
+            key: String::new(),
+            reload_interval: default_tls_reload_interval(),
+        }
+    }
+}
+
+fn default_tls_reload_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+// API keys checked by the auth middleware; applied live on reload
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub keys: Vec<ApiKey>,
+    /// Leave GET endpoints (stats, listings) open; writes still need a key
+    #[serde(default)]
+    pub public_read: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ApiKey {
+    #[serde(default)]
+    pub name: String,
+    pub key: Secret,
+    #[serde(default)]
+    pub role: AuthRole,
+    /// Requests per second for this key instead of server.rate_limit.rate_per_second; 0 exempts it
+    #[serde(default)]
+    pub rate_limit: Option<f64>,
+}
+
+// A string kept out of Debug output (API keys)
+#[derive(Clone, Default, Deserialize, utoipa::ToSchema)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+// printed configs show whether a secret is set, never the secret
+impl Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if self.0.is_empty() { "" } else { "<redacted>" })
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_empty() { "\"\"" } else { "<redacted>" })
+    }
+}
+
+// Delayed re-checks of candidates that failed transiently (DNS timeout, connection reset)
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RetryConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Re-checks after the first attempt before the domain counts as failed
+    #[serde(default = "default_retry_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first re-check, doubled for every further one
+    #[serde(default = "default_retry_base_delay", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub base_delay: Duration,
+    #[serde(default = "default_retry_max_delay", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub max_delay: Duration,
+    /// Queue bound; domains beyond it count as failed right away
+    #[serde(default = "default_retry_max_queued")]
+    pub max_queued: usize,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: default_retry_max_attempts(),
+            base_delay: default_retry_base_delay(),
+            max_delay: default_retry_max_delay(),
+            max_queued: default_retry_max_queued(),
+        }
+    }
+}
+
+fn default_retry_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_base_delay() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_retry_max_delay() -> Duration {
+    Duration::from_secs(120)
+}
+
+fn default_retry_max_queued() -> usize {
+    100_000
+}
+
+// External candidate lists posted to /candidates/
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct IngestConfig {
+    /// Posted candidates waiting to be checked; entries beyond it are rejected
+    #[serde(default = "default_ingest_max_queued")]
+    pub max_queued: usize,
+    /// Largest accepted request body
+    #[serde(default = "default_ingest_body_limit", deserialize_with = "de_size")]
+    #[schema(schema_with = size_schema)]
+    pub body_limit: usize,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_queued: default_ingest_max_queued(),
+            body_limit: default_ingest_body_limit(),
+        }
+    }
+}
+
+fn default_ingest_max_queued() -> usize {
+    1_000_000
+}
+
+fn default_ingest_body_limit() -> usize {
+    16 << 20
+}
+
+// Where the main scan's candidates come from
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct SourceConfig {
+    #[serde(default)]
+    pub kind: SourceKind,
+    #[serde(default)]
+    pub ct: CtConfig,
+    #[serde(default)]
+    pub zone: ZoneConfig,
+}
+
+// Certificate Transparency logs tailed by source kind "ct"
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct CtConfig {
+    /// RFC 6962 log base URLs, e.g. "https://ct.googleapis.com/logs/us1/argon2025h2"
+    #[serde(default)]
+    pub logs: Vec<String>,
+    #[serde(default = "default_ct_poll_interval", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub poll_interval: Duration,
+    /// Entries requested per get-entries call
+    #[serde(default = "default_ct_batch_size")]
+    pub batch_size: u64,
+    /// Per-log read positions; defaults to "<storage.dir>/ct_positions.json"
+    #[serde(default)]
+    pub state_file: String,
+}
+
+impl Default for CtConfig {
+    fn default() -> Self {
+        Self {
+            logs: vec![],
+            poll_interval: default_ct_poll_interval(),
+            batch_size: default_ct_batch_size(),
+            state_file: String::new(),
+        }
+    }
+}
+
+fn default_ct_poll_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_ct_batch_size() -> u64 {
+    256
+}
+
+// Zone files read by source kind "zone"
+#[derive(Clone, Debug, Default, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ZoneConfig {
+    /// Master-format zone files with absolute owner names, plain or .gz
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RdapConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// IANA bootstrap registry; defaults to https://data.iana.org/rdap/dns.json
+    #[serde(default)]
+    pub bootstrap_url: String,
+    #[serde(default = "default_rdap_timeout", deserialize_with = "de_duration", serialize_with = "ser_duration")]
+    #[schema(value_type = String)]
+    pub timeout: Duration,
+    #[serde(default = "default_rdap_max_in_flight")]
+    pub max_in_flight: i32,
+    /// Only query candidates without DNS answers (the ones likely to be free)
+    #[serde(default = "default_true")]
+    pub only_unresolved: bool,
+}
+
+impl Default for RdapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bootstrap_url: String::new(),
+            timeout: default_rdap_timeout(),
+            max_in_flight: default_rdap_max_in_flight(),
+            only_unresolved: true,
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct RunConfig {
-    #[serde(default)]
-    pub loop_: bool,
+fn default_rdap_timeout() -> Duration {
+    Duration::from_secs(5)
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct StorageConfig {
-    pub dir: String,
-    #[serde(default)]
-    pub resume: bool,
-    #[serde(default)]
-    pub state_file: String,
+fn default_rdap_max_in_flight() -> i32 {
+    10
 }
 
+fn default_true() -> bool {
+    true
+}
 
 // -------- Duration "3s" etc --------
+// written back in the largest unit that keeps it exact
+fn ser_duration<S: serde::Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    let ms = d.as_millis();
+    let s = match ms {
+        0 => "0s".to_string(),
+        _ if ms.is_multiple_of(3_600_000) => format!("{}h", ms / 3_600_000),
+        _ if ms.is_multiple_of(60_000) => format!("{}m", ms / 60_000),
+        _ if ms.is_multiple_of(1000) => format!("{}s", ms / 1000),
+        _ => format!("{ms}ms"),
+    };
+    serializer.serialize_str(&s)
+}
+
 fn de_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
@@ -97,7 +2414,117 @@ where
     deserializer.deserialize_any(DVisitor)
 }
 
-fn parse_duration(s: &str) -> Result<Duration, String> {
+// -------- sizes: 512, "512B", "32KB", "1MB" (1024-based) --------
+fn de_size<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct SVisitor;
+    impl<'de> Visitor<'de> for SVisitor {
+        type Value = usize;
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("size like 512, 32KB, 1MB")
+        }
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(v as usize)
+        }
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            parse_size(v).map_err(E::custom)
+        }
+    }
+    deserializer.deserialize_any(SVisitor)
+}
+
+fn de_count<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct CVisitor;
+    impl<'de> Visitor<'de> for CVisitor {
+        type Value = i64;
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("count like 5000, 100k, 1M")
+        }
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(v.min(i64::MAX as u64) as i64)
+        }
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            if v < 0 {
+                return Err(E::custom("count must be >= 0"));
+            }
+            Ok(v)
+        }
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            parse_count(v).map_err(E::custom)
+        }
+    }
+    deserializer.deserialize_any(CVisitor)
+}
+
+// a plain number or one with a unit suffix
+fn size_schema() -> Schema {
+    unit_schema("size like 512, 32KB, 1MB", "32KB")
+}
+
+fn count_schema() -> Schema {
+    unit_schema("count like 5000, 100k, 1M", "100k")
+}
+
+fn unit_schema(description: &str, example: &str) -> Schema {
+    OneOfBuilder::new()
+        .item(ObjectBuilder::new().schema_type(Type::Integer).minimum(Some(0)))
+        .item(ObjectBuilder::new().schema_type(Type::String).examples([example]))
+        .description(Some(description))
+        .into()
+}
+
+// decimal multiples, unlike sizes: 100k is 100000
+fn parse_count(s: &str) -> Result<i64, String> {
+    let st = s.trim().to_uppercase();
+    let digits = st.find(|c: char| !c.is_ascii_digit()).unwrap_or(st.len());
+    let (num, unit) = st.split_at(digits);
+    let n: i64 = num.parse().map_err(|e| format!("invalid count {s}: {e}"))?;
+    let mult = match unit.trim() {
+        "" => 1,
+        "K" => 1_000,
+        "M" => 1_000_000,
+        "G" | "B" => 1_000_000_000,
+        u => return Err(format!("invalid count {s}: unknown unit {u:?}")),
+    };
+    Ok(n.saturating_mul(mult))
+}
+
+fn parse_size(s: &str) -> Result<usize, String> {
+    let st = s.trim().to_uppercase();
+    let digits = st.find(|c: char| !c.is_ascii_digit()).unwrap_or(st.len());
+    let (num, unit) = st.split_at(digits);
+    let n: usize = num.parse().map_err(|e| format!("invalid size {s}: {e}"))?;
+    let mult = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        u => return Err(format!("invalid size {s}: unknown unit {u:?}")),
+    };
+    Ok(n.saturating_mul(mult))
+}
+
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
     let st = s.trim().to_lowercase();
     let unit = if st.ends_with("ms") {
         "ms"
@@ -112,7 +2539,7 @@ fn parse_duration(s: &str) -> Result<Duration, String> {
     };
     let num_str = if unit == "ms" {
         &st[..st.len() - 2]
-    } else if ["s", "m", "h"].contains(&unit) && st.len() >= 1 && st.ends_with(unit) {
+    } else if ["s", "m", "h"].contains(&unit) && !st.is_empty() && st.ends_with(unit) {
         &st[..st.len() - 1]
     } else {
         &st
@@ -131,12 +2558,135 @@ fn parse_duration(s: &str) -> Result<Duration, String> {
     Ok(dur)
 }
 
+// -------- live reload --------
+
+// Current configuration, swapped atomically on reload (POST /control/reload, SIGHUP)
+#[derive(Clone)]
+pub struct LiveConfig {
+    path: Arc<String>,
+    tx: Arc<watch::Sender<Arc<Config>>>,
+}
+
+impl LiveConfig {
+    pub fn new(path: &str, cfg: Config) -> Self {
+        let (tx, _rx) = watch::channel(Arc::new(cfg));
+        Self {
+            path: Arc::new(path.to_string()),
+            tx: Arc::new(tx),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.tx.borrow().clone()
+    }
+
+    /// A fixed snapshot that reloads of this config do not touch.
+    pub fn fork(&self, cfg: Config) -> Self {
+        Self::new(&self.path, cfg)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.tx.subscribe()
+    }
+
+    /// Publish a changed copy of the current config if it still validates;
+    /// the next reload replaces it with the file again.
+    pub fn update(&self, f: impl FnOnce(&mut Config)) -> anyhow::Result<Arc<Config>> {
+        let mut result = Ok(());
+        self.tx.send_if_modified(|cur| {
+            let mut cfg = (**cur).clone();
+            f(&mut cfg);
+            result = validate_config(&cfg);
+            if result.is_ok() {
+                *cur = Arc::new(cfg);
+            }
+            result.is_ok()
+        });
+        result.map(|()| self.current())
+    }
+
+    /// Re-read and validate the YAML, then publish it. On error the current
+    /// config stays in place. Returns sections whose changes need a restart.
+    pub async fn reload(&self) -> anyhow::Result<Vec<&'static str>> {
+        let new = load_config(&self.path).await?;
+        let old = self.current();
+        let restart = restart_required(&old, &new);
+        self.tx.send_replace(Arc::new(new));
+        info!("config reloaded from {}", self.path);
+        Ok(restart)
+    }
+}
+
+// sections that are only read at startup
+fn restart_required(old: &Config, new: &Config) -> Vec<&'static str> {
+    let mut out = vec![];
+    if format!("{:?}", old.storage) != format!("{:?}", new.storage) {
+        out.push("storage");
+    }
+    if format!("{:?}", old.dns) != format!("{:?}", new.dns) {
+        out.push("dns");
+    }
+    if format!("{:?}", old.rdap) != format!("{:?}", new.rdap) {
+        out.push("rdap");
+    }
+    if format!("{:?}", old.dedup) != format!("{:?}", new.dedup) {
+        out.push("dedup");
+    }
+    if format!("{:?}", old.cluster) != format!("{:?}", new.cluster) {
+        out.push("cluster");
+    }
+    if format!("{:?}", old.wildcard) != format!("{:?}", new.wildcard) {
+        out.push("wildcard");
+    }
+    // server.rate_limit is read on every request
+    let (os, ns) = (&old.server, &new.server);
+    if format!("{:?}", os.tls) != format!("{:?}", ns.tls)
+        || os.public_addr != ns.public_addr
+        || os.admin_addr != ns.admin_addr
+        || os.grpc_addr != ns.grpc_addr
+        || os.base_path != ns.base_path
+    {
+        out.push("server");
+    }
+    if old.verify.enabled != new.verify.enabled {
+        out.push("verify.enabled");
+    }
+    if old.ingest.body_limit != new.ingest.body_limit {
+        out.push("ingest.body_limit");
+    }
+    if old.http_check.tls.accept_invalid != new.http_check.tls.accept_invalid {
+        out.push("http_check.tls.accept_invalid");
+    }
+    if format!("{:?}", old.http_check.client) != format!("{:?}", new.http_check.client) {
+        out.push("http_check.client");
+    }
+    if old.mode != new.mode {
+        out.push("mode");
+    }
+    if old.source.kind != new.source.kind {
+        out.push("source.kind");
+    }
+    if format!("{:?}", old.telemetry) != format!("{:?}", new.telemetry) {
+        out.push("telemetry");
+    }
+    out
+}
+
 // -------- TLD loading --------
 
 pub async fn load_config(path: &str) -> anyhow::Result<Config> {
     info!("loading config from {}", path);
     let data = fs::read(path).with_context(|| format!("read config {path}"))?;
-    let mut cfg: Config = yaml::from_slice(&data)?;
+    let mut doc: yaml::Value = yaml::from_slice(&data)?;
+    let overridden = apply_env_overrides(&mut doc, std::env::vars())?;
+    if !overridden.is_empty() {
+        // names only: values may be secrets
+        info!("config overridden from environment: {}", overridden.join(", "));
+    }
+    for key in unknown_keys(&doc) {
+        warn!("config: {key}");
+    }
+    let mut cfg = parse_config(doc, &data)?;
     validate_config(&cfg)?;
     info!(
         "config validated: storage.dir={}, limits.concurrency={}, rps={}, len={}..{}, inline_tlds={}",
@@ -151,7 +2701,7 @@ pub async fn load_config(path: &str) -> anyhow::Result<Config> {
     if !cfg.generator.tlds_file.trim().is_empty() {
         let src = cfg.generator.tlds_file.trim();
         info!("loading TLDs from {}", src);
-        let tlds = if src.starts_with("http://") || src.starts_with("https://") {
+        let tlds = if is_url(src) {
             load_tlds_from_url(src).await?
         } else {
             load_tlds_from_file(src)?
@@ -166,19 +2716,110 @@ pub async fn load_config(path: &str) -> anyhow::Result<Config> {
         cfg.storage.state_file = Path::new(&cfg.storage.dir).join("state.json").to_string_lossy().to_string();
         info!("storage.state_file not set, computed default: {}", cfg.storage.state_file);
     }
+    if cfg.storage.journal_file.trim().is_empty() {
+        cfg.storage.journal_file = Path::new(&cfg.storage.dir).join("journal.log").to_string_lossy().to_string();
+    }
+    if cfg.storage.queue.dir.trim().is_empty() {
+        cfg.storage.queue.dir = Path::new(&cfg.storage.dir).join("queue").to_string_lossy().to_string();
+    }
+    // results under a parent zone are filed by the zone, profiles' zones included
+    let mut zones = cfg.generator.parent_zones.clone();
+    for p in cfg.profiles.values() {
+        zones.extend(p.resolve(&cfg).map(|c| c.generator.parent_zones).unwrap_or_default());
+    }
+    crate::store::set_parent_zones(&zones);
     Ok(cfg)
 }
 
-pub fn validate_config(cfg: &Config) -> anyhow::Result<()> {
-    if cfg.generator.tlds.is_empty() && cfg.generator.tlds_file.trim().is_empty() {
-        anyhow::bail!("generator.tlds must not be empty (or provide generator.tlds_file)");
+// serde alone names neither the key nor the line of a bad value
+fn parse_config(doc: yaml::Value, data: &[u8]) -> anyhow::Result<Config> {
+    let err = match serde_path_to_error::deserialize(doc) {
+        Ok(cfg) => return Ok(cfg),
+        Err(e) => e,
+    };
+    // positions are lost with the parsed document: ask the file again
+    let at = match yaml::from_slice::<Config>(data) {
+        Ok(_) => " (set from the environment)".to_string(),
+        Err(e) => e.location().map(|l| format!(" (line {}, column {})", l.line(), l.column())).unwrap_or_default(),
+    };
+    let key = match err.path().to_string() {
+        p if p == "." => String::new(),
+        p => format!("{p}: "),
+    };
+    anyhow::bail!("invalid config: {key}{}{at}", err.inner())
+}
+
+// prefix of the environment variables that override config fields
+const ENV_PREFIX: &str = "DS_";
+
+// DS_LIMITS__CONCURRENCY=500 sets limits.concurrency: `__` separates the
+// levels, names match the YAML keys regardless of case and a number picks a
+// list entry. Values are read as YAML, so `true`, `500` and `[com, net]` keep
+// their types; missing levels are created.
+fn apply_env_overrides(doc: &mut yaml::Value, vars: impl Iterator<Item = (String, String)>) -> anyhow::Result<Vec<String>> {
+    let mut applied = Vec::new();
+    let mut vars: Vec<(String, String)> = vars.filter(|(k, _)| k.starts_with(ENV_PREFIX) && k.len() > ENV_PREFIX.len()).collect();
+    // parents before children, so DS_HTTP_CHECK and DS_HTTP_CHECK__TIMEOUT combine
+    vars.sort();
+    for (name, raw) in vars {
+        let path: Vec<&str> = name[ENV_PREFIX.len()..].split("__").collect();
+        if path.iter().any(|p| p.is_empty()) {
+            anyhow::bail!("{name}: empty key between '__'");
+        }
+        let value = if raw.trim().is_empty() {
+            yaml::Value::String(String::new())
+        } else {
+            yaml::from_str(&raw).unwrap_or(yaml::Value::String(raw.clone()))
+        };
+        let mut node = &mut *doc;
+        for part in &path {
+            node = env_child(node, part).with_context(|| format!("{name}: cannot set {part:?}"))?;
+        }
+        *node = value;
+        applied.push(name);
     }
-    if cfg.generator.min_length < 1 || cfg.generator.max_length < cfg.generator.min_length {
-        anyhow::bail!(
-            "invalid lengths: {}..{}",
-            cfg.generator.min_length,
-            cfg.generator.max_length
-        );
+    Ok(applied)
+}
+
+fn env_child<'a>(node: &'a mut yaml::Value, part: &str) -> anyhow::Result<&'a mut yaml::Value> {
+    if node.is_null() {
+        *node = yaml::Value::Mapping(yaml::Mapping::new());
+    }
+    match node {
+        yaml::Value::Mapping(m) => {
+            let key = m
+                .keys()
+                .find(|k| k.as_str().is_some_and(|k| k.eq_ignore_ascii_case(part)))
+                .cloned()
+                .unwrap_or_else(|| yaml::Value::String(part.to_lowercase()));
+            Ok(m.entry(key).or_insert(yaml::Value::Null))
+        }
+        yaml::Value::Sequence(seq) => {
+            let len = seq.len();
+            let i: usize = part.parse().context("list index expected")?;
+            seq.get_mut(i).with_context(|| format!("index {i} out of range, the list has {len} entries"))
+        }
+        _ => anyhow::bail!("not a mapping"),
+    }
+}
+
+pub fn validate_config(cfg: &Config) -> anyhow::Result<()> {
+    validate_generator(&cfg.generator, "generator")?;
+    for (name, p) in &cfg.profiles {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            anyhow::bail!("profiles: name {name:?} may only contain letters, digits, '-' and '_'");
+        }
+        if p.generator.contains_key("tlds_file") {
+            anyhow::bail!("profiles.{name}.generator: tlds_file is not supported, list tlds instead");
+        }
+        if p.max_candidates < 0 {
+            anyhow::bail!("profiles.{name}.max_candidates must be >= 0");
+        }
+        if !p.namespace.is_empty() && !crate::namespace::valid_name(&p.namespace) {
+            anyhow::bail!("profiles.{name}.namespace: {:?} may only contain letters, digits, '-' and '_'", p.namespace);
+        }
+        let resolved = p.resolve(cfg).with_context(|| format!("profiles.{name}.generator"))?;
+        validate_generator(&resolved.generator, &format!("profiles.{name}.generator"))?;
     }
     if cfg.limits.concurrency <= 0 {
         anyhow::bail!("limits.concurrency must be > 0");
@@ -186,15 +2827,428 @@ pub fn validate_config(cfg: &Config) -> anyhow::Result<()> {
     if cfg.limits.rate_per_second <= 0 {
         anyhow::bail!("limits.rate_per_second must be > 0");
     }
-    if cfg.http_check.accept_status_min <= 0 || cfg.http_check.accept_status_max < cfg.http_check.accept_status_min {
+    if cfg.limits.burst < 0 {
+        anyhow::bail!("limits.burst must be >= 0");
+    }
+    for (tld, l) in &cfg.limits.per_tld {
+        if tld.trim().trim_start_matches('.').is_empty() {
+            anyhow::bail!("limits.per_tld: empty TLD key");
+        }
+        if l.concurrency < 0 || l.rate_per_second < 0 || l.burst < 0 {
+            anyhow::bail!("limits.per_tld.{tld}: values must be >= 0");
+        }
+    }
+    let hc = &cfg.http_check;
+    if hc.status_rules.is_empty() && (hc.accept_status_min <= 0 || hc.accept_status_max < hc.accept_status_min) {
         anyhow::bail!("invalid http_check accept status range");
     }
+    for (i, rule) in hc.status_rules.iter().enumerate() {
+        if rule.status.is_empty() {
+            anyhow::bail!("http_check.status_rules[{i}]: status must not be empty");
+        }
+        if rule.action == StatusAction::StoreWithTag && rule.tag.trim().is_empty() {
+            anyhow::bail!("http_check.status_rules[{i}]: store_with_tag needs a tag");
+        }
+    }
+    for (name, value) in &hc.headers {
+        reqwest::header::HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("http_check.headers: invalid name {name:?}"))?;
+        reqwest::header::HeaderValue::from_str(value).with_context(|| format!("http_check.headers.{name}: invalid value"))?;
+    }
+    reqwest::header::HeaderValue::from_str(&hc.user_agent).context("http_check.user_agent")?;
+    let method = hc.method.trim();
+    if !hc.body.is_empty() && (method.is_empty() || method.eq_ignore_ascii_case("GET") || method.eq_ignore_ascii_case("HEAD")) {
+        anyhow::bail!("http_check.body needs a method that sends one, e.g. POST");
+    }
+    if hc.strategy == CheckStrategy::HeadThenGet && !(method.is_empty() || method.eq_ignore_ascii_case("GET")) {
+        anyhow::bail!("http_check.strategy head_then_get sends HEAD and GET; leave http_check.method empty or GET");
+    }
+    if !hc.head_timeout.is_zero() && hc.head_timeout > hc.timeout {
+        anyhow::bail!("http_check.head_timeout must be <= http_check.timeout");
+    }
+    let content = &cfg.http_check.content;
+    if content.reads_body() && cfg.http_check.method.trim().eq_ignore_ascii_case("HEAD") {
+        anyhow::bail!("http_check.match needs a method that returns a body, not HEAD");
+    }
+    if hc.robots.enabled && hc.robots.max_entries == 0 {
+        anyhow::bail!("http_check.robots.max_entries must be > 0");
+    }
+    if content.min_body_size > cfg.http_check.body_limit {
+        anyhow::bail!("http_check.match.min_body_size must be <= http_check.body_limit");
+    }
+    if cfg.dns.attempts <= 0 {
+        anyhow::bail!("dns.attempts must be > 0");
+    }
+    if cfg.dns.record_types.is_empty() {
+        anyhow::bail!("dns.record_types must not be empty");
+    }
+    let hc = &cfg.http_check;
+    if !hc.http_only_tag.trim().is_empty() {
+        match hc.scheme_policy {
+            SchemePolicy::HttpsOnly => anyhow::bail!("http_check.http_only_tag: scheme_policy https_only never accepts plain HTTP"),
+            SchemePolicy::First if !hc.try_https_first => {
+                anyhow::bail!("http_check.http_only_tag needs try_https_first: true or scheme_policy: all to know HTTPS was not accepted")
+            }
+            _ => {}
+        }
+    }
+    if cfg.http_check.per_family && matches!(cfg.dns.address_family, AddressFamily::Ipv4 | AddressFamily::Ipv6) {
+        anyhow::bail!("http_check.per_family checks both families; it needs dns.address_family any or both");
+    }
+    for spec in cfg.dns.servers.iter().filter(|s| !s.trim().is_empty()) {
+        crate::dns::parse_server(spec.trim())?;
+    }
+    if cfg.mode == ScanMode::Availability && !cfg.rdap.enabled {
+        anyhow::bail!("mode: availability needs rdap.enabled: true");
+    }
+    if cfg.mode == ScanMode::Availability && !cfg.checks.is_empty() {
+        anyhow::bail!("checks applies to mode: live; availability runs its own SOA -> RDAP sequence");
+    }
+    for (i, c) in cfg.checks.iter().enumerate() {
+        if cfg.checks[..i].iter().any(|p| p.stage == c.stage) {
+            anyhow::bail!("checks: stage {} is listed twice", c.stage.as_str());
+        }
+        if c.stage == CheckKind::Rdap && !cfg.rdap.enabled {
+            anyhow::bail!("checks: the rdap stage needs rdap.enabled: true");
+        }
+    }
+    if !cfg.checks.is_empty() && cfg.checks.iter().all(|c| matches!(c.stage, CheckKind::Rdap | CheckKind::Parking | CheckKind::Geoip)) {
+        anyhow::bail!("checks: rdap, parking and geoip only classify, list at least one of dns, http, tls");
+    }
+    if let Some(expr) = &cfg.success {
+        if cfg.mode == ScanMode::Availability {
+            anyhow::bail!("success applies to mode: live; availability decides by RDAP");
+        }
+        let stages = cfg.check_stages();
+        for k in expr.stages() {
+            if matches!(k, CheckKind::Rdap | CheckKind::Geoip) {
+                anyhow::bail!("success: the {} stage only records, it never passes or fails", k.as_str());
+            }
+            if !stages.iter().any(|c| c.stage == k) {
+                anyhow::bail!("success: stage {} is not in checks", k.as_str());
+            }
+        }
+    }
+    if let Some(i) = cfg.checks.iter().position(|c| c.stage == CheckKind::Geoip) {
+        if !cfg.checks[..i].iter().any(|c| c.stage == CheckKind::Dns) {
+            anyhow::bail!("checks: the geoip stage looks up the addresses of the dns stage, list dns before it");
+        }
+        let g = &cfg.geoip;
+        if g.country_db.trim().is_empty() && g.asn_db.trim().is_empty() {
+            anyhow::bail!("checks: the geoip stage needs geoip.country_db or geoip.asn_db");
+        }
+        for (key, path) in [("country_db", &g.country_db), ("asn_db", &g.asn_db)] {
+            if !path.trim().is_empty() && !Path::new(path.trim()).is_file() {
+                anyhow::bail!("geoip.{key}: {} is not a file", path.trim());
+            }
+        }
+    }
+    for p in &cfg.parking.providers {
+        if p.name.trim().is_empty() {
+            anyhow::bail!("parking.providers: every provider needs a name");
+        }
+        if p.nameservers.is_empty() && p.redirect_hosts.is_empty() && p.body.is_empty() {
+            anyhow::bail!("parking.providers.{}: list at least one of nameservers, redirect_hosts, body", p.name);
+        }
+    }
+    if cfg.checks.iter().any(|c| c.stage == CheckKind::Parking) && !cfg.parking.builtin && cfg.parking.providers.is_empty() {
+        anyhow::bail!("checks: the parking stage needs parking.builtin or parking.providers");
+    }
+    if cfg.rdap.enabled && cfg.rdap.max_in_flight <= 0 {
+        anyhow::bail!("rdap.max_in_flight must be > 0");
+    }
+    if cfg.dedup.enabled && cfg.dedup.skip_checked && cfg.dedup.memory_mb == 0 {
+        anyhow::bail!("dedup.memory_mb must be > 0");
+    }
+    if cfg.cluster.role == ClusterRole::Worker && cfg.cluster.coordinator_url.trim().is_empty() {
+        anyhow::bail!("cluster.coordinator_url is required for role: worker");
+    }
+    match cfg.source.kind {
+        SourceKind::Generator => {}
+        // shards are ranges of generated labels
+        _ if cfg.cluster.role == ClusterRole::Worker => {
+            anyhow::bail!("cluster role worker needs source.kind: generator");
+        }
+        SourceKind::Ct if cfg.source.ct.logs.iter().all(|l| l.trim().is_empty()) => {
+            anyhow::bail!("source.ct.logs must not be empty for source.kind: ct");
+        }
+        SourceKind::Zone if cfg.source.zone.files.iter().all(|f| f.trim().is_empty()) => {
+            anyhow::bail!("source.zone.files must not be empty for source.kind: zone");
+        }
+        _ => {}
+    }
+    if cfg.retry.enabled && cfg.retry.max_delay < cfg.retry.base_delay {
+        anyhow::bail!("retry.max_delay must be >= retry.base_delay");
+    }
+    cfg.schedule.validate().context("schedule")?;
+    // shards are leased from the coordinator as workers come free
+    if !cfg.schedule.cron.trim().is_empty() && cfg.cluster.role != ClusterRole::Standalone {
+        anyhow::bail!("schedule is not supported in cluster mode");
+    }
+    if cfg.server.tls.cert.trim().is_empty() != cfg.server.tls.key.trim().is_empty() {
+        anyhow::bail!("server.tls needs both cert and key");
+    }
+    let batch = &cfg.dns.batch;
+    if batch.enabled {
+        if !(1..=1024).contains(&batch.size) {
+            anyhow::bail!("dns.batch.size must be 1-1024");
+        }
+        if !(1..=65535).contains(&batch.max_in_flight) {
+            anyhow::bail!("dns.batch.max_in_flight must be 1-65535");
+        }
+        // raw queries go out over plain UDP only
+        if let Some(s) = cfg.dns.servers.iter().find(|s| s.split_once("://").is_some_and(|(scheme, _)| !scheme.eq_ignore_ascii_case("udp"))) {
+            anyhow::bail!("dns.batch needs plain UDP servers, got {s:?}");
+        }
+    }
+    let consensus = &cfg.dns.consensus;
+    if consensus.enabled {
+        let voters = consensus.voters(&cfg.dns.servers);
+        if voters.len() < 2 {
+            anyhow::bail!("dns.consensus needs at least 2 resolvers (dns.consensus.resolvers or dns.servers)");
+        }
+        if consensus.quorum == 0 || consensus.quorum > voters.len() {
+            anyhow::bail!("dns.consensus.quorum must be 1-{}", voters.len());
+        }
+        for spec in &voters {
+            crate::dns::parse_server(spec)?;
+        }
+    }
+    let neg = &cfg.dns.negative_cache;
+    if neg.enabled {
+        if neg.ttl.as_secs() == 0 {
+            anyhow::bail!("dns.negative_cache.ttl must be at least 1s");
+        }
+        if neg.max_entries == 0 {
+            anyhow::bail!("dns.negative_cache.max_entries must be > 0");
+        }
+        if !(1..=1024).contains(&neg.shards) {
+            anyhow::bail!("dns.negative_cache.shards must be 1-1024");
+        }
+    }
+    let pos = &cfg.dns.positive_cache;
+    if pos.enabled {
+        if pos.max_ttl.as_secs() == 0 {
+            anyhow::bail!("dns.positive_cache.max_ttl must be at least 1s");
+        }
+        if pos.max_entries == 0 {
+            anyhow::bail!("dns.positive_cache.max_entries must be > 0");
+        }
+        if !(1..=1024).contains(&pos.shards) {
+            anyhow::bail!("dns.positive_cache.shards must be 1-1024");
+        }
+    }
+    let rl = &cfg.server.rate_limit;
+    if rl.enabled && !(rl.rate_per_second.is_finite() && rl.rate_per_second > 0.0) {
+        anyhow::bail!("server.rate_limit.rate_per_second must be > 0");
+    }
+    if let Some(k) = cfg.auth.keys.iter().find(|k| k.rate_limit.is_some_and(|r| !(r.is_finite() && r >= 0.0))) {
+        anyhow::bail!("auth.keys: rate_limit of key {:?} must be >= 0", k.name);
+    }
+    let base = cfg.server.base_path();
+    if base.contains("//") || !base.chars().all(|c| c.is_ascii_alphanumeric() || "/-_.~".contains(c)) {
+        anyhow::bail!("server.base_path {:?} may only contain letters, digits and '/', '-', '_', '.', '~'", cfg.server.base_path);
+    }
+    for (key, addr) in [("public_addr", &cfg.server.public_addr), ("admin_addr", &cfg.server.admin_addr)] {
+        let addr = addr.trim();
+        let full = if addr.starts_with(':') { format!("0.0.0.0{addr}") } else { addr.to_string() };
+        if !addr.is_empty() && full.parse::<std::net::SocketAddr>().is_err() {
+            anyhow::bail!("server.{key} {addr:?} is not a listen address like \":8080\" or \"127.0.0.1:8081\"");
+        }
+    }
+    if !cfg.server.admin_addr.trim().is_empty() && cfg.server.admin_addr.trim() == cfg.server.public_addr.trim() {
+        anyhow::bail!("server.admin_addr must differ from server.public_addr");
+    }
+    for ep in &cfg.notifications.webhook.endpoints {
+        if !(ep.url.starts_with("http://") || ep.url.starts_with("https://")) {
+            anyhow::bail!("notifications.webhook: endpoint url {:?} must be http(s)", ep.url);
+        }
+    }
+    let email = &cfg.notifications.email;
+    if email.enabled() {
+        if email.smtp_host.trim().is_empty() {
+            anyhow::bail!("notifications.email.smtp_host must be set when `to` is");
+        }
+        // these go into SMTP commands and headers as they are
+        let address_ok = |a: &str| a.contains('@') && !a.chars().any(|c| c.is_whitespace() || c.is_control() || "<>,".contains(c));
+        if let Some(a) = email.to.iter().chain([&email.from]).find(|a| !address_ok(a)) {
+            anyhow::bail!("notifications.email: {a:?} is not a mail address like scans@example.com");
+        }
+        if email.username.is_empty() && !email.password.is_empty() {
+            anyhow::bail!("notifications.email.password needs a username");
+        }
+    }
+    if cfg.auth.enabled && cfg.auth.keys.is_empty() {
+        anyhow::bail!("auth.keys must not be empty when auth is enabled");
+    }
+    if cfg.auth.keys.iter().any(|k| k.key.0.trim().len() < 16) {
+        anyhow::bail!("auth.keys: every key must be at least 16 characters");
+    }
+    if cfg.cluster.shards == 0 {
+        anyhow::bail!("cluster.shards must be > 0");
+    }
+    if cfg.storage.backend == StorageBackendKind::S3 && cfg.storage.s3.bucket.trim().is_empty() {
+        anyhow::bail!("storage.s3.bucket is required for backend: s3");
+    }
+    if cfg.storage.rotation.enabled() && cfg.storage.backend != StorageBackendKind::Text {
+        anyhow::bail!("storage.rotation applies to backend: text (s3 rolls shards with s3.shard_size/shard_interval)");
+    }
     if cfg.storage.dir.trim().is_empty() {
         anyhow::bail!("storage.dir must not be empty");
     }
+    if cfg.verify.interval.is_zero() {
+        anyhow::bail!("verify.interval must be > 0");
+    }
+    if cfg.verify.concurrency == 0 {
+        anyhow::bail!("verify.concurrency must be > 0");
+    }
+    let alerts = &cfg.alerts;
+    if alerts.interval < Duration::from_secs(1) {
+        anyhow::bail!("alerts.interval must be at least 1s");
+    }
+    for ep in &alerts.webhook {
+        if !(ep.url.starts_with("http://") || ep.url.starts_with("https://")) {
+            anyhow::bail!("alerts.webhook: endpoint url {:?} must be http(s)", ep.url);
+        }
+    }
+    let mut rule_names = std::collections::HashSet::new();
+    for rule in &alerts.rules {
+        let name = rule.name.as_str();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c)) {
+            anyhow::bail!("alerts.rules: name {name:?} must be letters, digits, '_', '-' or '.'");
+        }
+        if !rule_names.insert(name) {
+            anyhow::bail!("alerts.rules: {name:?} is defined twice");
+        }
+        match (rule.above, rule.below) {
+            (Some(t), None) | (None, Some(t)) if t.is_finite() => {}
+            _ => anyhow::bail!("alerts.rules.{name}: set exactly one of `above` and `below`, to a number"),
+        }
+        if !rule.causes.is_empty() && !matches!(rule.metric, AlertMetric::ErrorPercent | AlertMetric::ErrorsPerSec) {
+            anyhow::bail!("alerts.rules.{name}: causes apply to error_percent and errors_per_sec");
+        }
+        if let Some(c) = rule.causes.iter().find(|c| !FailureKind::ALL.iter().any(|k| k.as_str() == c.as_str())) {
+            anyhow::bail!("alerts.rules.{name}: unknown cause {c:?} (one of the /stats/ `errors` keys, e.g. dns_timeout)");
+        }
+        if rule.actions.is_empty() {
+            anyhow::bail!("alerts.rules.{name}: actions must not be empty");
+        }
+        if rule.actions.contains(&AlertAction::Webhook) && alerts.webhook.is_empty() {
+            anyhow::bail!("alerts.rules.{name}: the webhook action needs alerts.webhook endpoints");
+        }
+    }
+    let history = &cfg.stats_history;
+    if history.interval < Duration::from_secs(1) {
+        anyhow::bail!("stats_history.interval must be at least 1s");
+    }
+    if history.retention < history.interval {
+        anyhow::bail!("stats_history.retention must be at least stats_history.interval");
+    }
+    if cfg.storage.queue.capacity == 0 {
+        anyhow::bail!("storage.queue.capacity must be > 0");
+    }
+    if cfg.storage.queue.kind == QueueKind::Disk && cfg.storage.queue.segment_size < 4096 {
+        anyhow::bail!("storage.queue.segment_size must be at least 4KB");
+    }
+    let tel = &cfg.telemetry;
+    if !(0.0..=1.0).contains(&tel.sample_ratio) {
+        anyhow::bail!("telemetry.sample_ratio must be between 0 and 1");
+    }
+    if tel.enabled && !is_url(&tel.endpoint(tel.protocol)) {
+        anyhow::bail!("telemetry.endpoint must be an http:// or https:// URL");
+    }
     Ok(())
 }
 
+pub fn validate_generator(g: &GeneratorConfig, section: &str) -> anyhow::Result<()> {
+    if g.tlds.is_empty() && g.tlds_file.trim().is_empty() && g.parent_zones.is_empty() {
+        anyhow::bail!("{section}.tlds must not be empty (or provide {section}.tlds_file or {section}.parent_zones)");
+    }
+    for z in &g.parent_zones {
+        let zone = z.trim().trim_matches('.');
+        if !zone.contains('.') || crate::generator::to_ace(&zone.to_lowercase()).is_none() {
+            anyhow::bail!("{section}.parent_zones: {z:?} is not a domain like example.com (list TLDs under tlds)");
+        }
+    }
+    if g.min_length < 1 || g.max_length < g.min_length {
+        anyhow::bail!("{section}: invalid lengths: {}..{}", g.min_length, g.max_length);
+    }
+    if matches!(g.order, GenerationOrder::RoundRobin | GenerationOrder::Priority) && g.order_batch == 0 {
+        anyhow::bail!("{section}.order_batch must be > 0");
+    }
+    let sc = &g.scoring;
+    if !(0.0..=100.0).contains(&sc.min_score) {
+        anyhow::bail!("{section}.scoring.min_score must be between 0 and 100");
+    }
+    let w = &sc.weights;
+    let weights = [w.pronounceable, w.dictionary, w.length, w.digits, w.hyphens];
+    if weights.iter().any(|w| w.is_nan() || *w < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+        anyhow::bail!("{section}.scoring.weights must be >= 0 and not all 0");
+    }
+    for re in g.exclude_regex.iter().filter(|r| !r.trim().is_empty()) {
+        regex::Regex::new(re.trim()).with_context(|| format!("{section}.exclude_regex"))?;
+    }
+    if g.mode == GeneratorMode::Wordlist && g.wordlists.iter().all(|w| w.trim().is_empty()) {
+        anyhow::bail!("{section}.wordlists must not be empty in wordlist mode");
+    }
+    if g.mode == GeneratorMode::Mask && g.masks.iter().all(|m| m.trim().is_empty()) {
+        anyhow::bail!("{section}.masks must not be empty in mask mode");
+    }
+    if g.mode == GeneratorMode::Markov {
+        let mk = &g.markov;
+        if mk.corpus.iter().all(|c| c.trim().is_empty()) && mk.model.trim().is_empty() {
+            anyhow::bail!("{section}.markov needs a corpus or a model in markov mode");
+        }
+        if !(1..=8).contains(&mk.order) {
+            anyhow::bail!("{section}.markov.order must be between 1 and 8");
+        }
+        if mk.count == 0 {
+            anyhow::bail!("{section}.markov.count must be > 0");
+        }
+    }
+    if g.mode == GeneratorMode::Typos {
+        if g.typos.seeds.iter().all(|s| s.trim().is_empty()) {
+            anyhow::bail!("{section}.typos.seeds must not be empty in typos mode");
+        }
+        if g.typos.kinds.is_empty() {
+            anyhow::bail!("{section}.typos.kinds must not be empty in typos mode");
+        }
+    }
+    for s in g.typos.seeds.iter().filter(|s| !s.trim().is_empty()) {
+        if crate::generator::seed_label(s).is_none() {
+            anyhow::bail!("{section}.typos.seeds: {s:?} is not a label or domain like example.com");
+        }
+    }
+    for m in g.masks.iter().filter(|m| !m.trim().is_empty()) {
+        crate::generator::parse_mask(m.trim(), &g.alphabet).with_context(|| format!("{section}.masks"))?;
+    }
+    if let Some(c) = g.alphabet.chars().find(|c| !crate::generator::is_label_char(*c)) {
+        anyhow::bail!("{section}.alphabet: character {c:?} is not allowed in a label");
+    }
+    for a in g.prefixes.iter().chain(&g.suffixes) {
+        if !a.chars().all(crate::generator::is_label_char) {
+            anyhow::bail!("{section} prefix/suffix {a:?}: only lowercase letters, digits and '-' are allowed");
+        }
+    }
+    if g.strategy == GenerationStrategy::RandomSample {
+        if !(g.sample.rate > 0.0 && g.sample.rate <= 1.0) {
+            anyhow::bail!("{section}.sample.rate must be > 0 and <= 1");
+        }
+        // the estimates are per label length of the sampled keyspace
+        if !g.prefixes.is_empty() || !g.suffixes.is_empty() {
+            anyhow::bail!("{section}: prefixes and suffixes are not supported with strategy random_sample");
+        }
+        if g.mode == GeneratorMode::Bruteforce {
+            crate::generator::keyspace_size(g, g.max_length.max(0) as usize).with_context(|| format!("{section}.strategy"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a tlds_file (or other list source) is fetched over HTTP.
+pub fn is_url(src: &str) -> bool {
+    src.starts_with("http://") || src.starts_with("https://")
+}
+
 pub fn load_tlds_from_file(path: &str) -> anyhow::Result<Vec<String>> {
     let txt = fs::read_to_string(path)?;
     let mut uniq = std::collections::BTreeSet::<String>::new();
@@ -0,0 +1,120 @@
+use serde_json::{json, Map, Value};
+use serde_yaml as yaml;
+use utoipa::{PartialSchema, ToSchema};
+
+use super::{Config, GeneratorConfig};
+
+const COMPONENTS: &str = "#/components/schemas/";
+const DEFS: &str = "#/$defs/";
+
+/// JSON Schema (draft 2020-12) of the YAML config file, for editors and linters.
+pub fn json_schema() -> Value {
+    let mut defs = Vec::new();
+    Config::schemas(&mut defs);
+    let mut doc = match serde_json::to_value(Config::schema()) {
+        Ok(Value::Object(m)) => m,
+        _ => Map::new(),
+    };
+    doc.insert("$schema".into(), json!("https://json-schema.org/draft/2020-12/schema"));
+    doc.insert("title".into(), json!("rust_domain_searcher_api config"));
+    let defs: Map<String, Value> = defs
+        .into_iter()
+        .map(|(name, s)| (name, serde_json::to_value(s).unwrap_or(Value::Null)))
+        .collect();
+    doc.insert("$defs".into(), Value::Object(defs));
+    let mut doc = Value::Object(doc);
+    // utoipa writes OpenAPI component references
+    rewrite_refs(&mut doc);
+    doc
+}
+
+fn rewrite_refs(v: &mut Value) {
+    match v {
+        Value::Object(m) => {
+            if let Some(Value::String(r)) = m.get_mut("$ref") {
+                if let Some(name) = r.strip_prefix(COMPONENTS) {
+                    *r = format!("{DEFS}{name}");
+                }
+            }
+            m.values_mut().for_each(rewrite_refs);
+        }
+        Value::Array(a) => a.iter_mut().for_each(rewrite_refs),
+        _ => {}
+    }
+}
+
+/// Keys of the config document that no setting reads, which serde skips
+/// without a word: "generator.min_lenght: unknown key (did you mean min_length?)".
+pub fn unknown_keys(doc: &yaml::Value) -> Vec<String> {
+    let root = json_schema();
+    let mut out = Vec::new();
+    walk(&root, &root, doc, "", &mut out);
+    // profile generators are merged over `generator`, so they have its keys
+    let gen = json!({ "$ref": format!("{DEFS}{}", GeneratorConfig::name()) });
+    if let Some(profiles) = doc.get("profiles").and_then(yaml::Value::as_mapping) {
+        for (name, p) in profiles {
+            if let (Some(name), Some(g)) = (name.as_str(), p.get("generator")) {
+                walk(&root, &gen, g, &format!("profiles.{name}.generator"), &mut out);
+            }
+        }
+    }
+    out
+}
+
+fn walk(root: &Value, schema: &Value, doc: &yaml::Value, path: &str, out: &mut Vec<String>) {
+    let Some(schema) = pick(root, schema, doc) else { return };
+    match doc {
+        yaml::Value::Mapping(m) => {
+            let props = schema.get("properties").and_then(Value::as_object);
+            let extra = schema.get("additionalProperties").filter(|v| v.is_object());
+            for (k, v) in m {
+                let Some(k) = k.as_str() else { continue };
+                let at = if path.is_empty() { k.to_string() } else { format!("{path}.{k}") };
+                match (props.and_then(|p| p.get(k)), extra, props) {
+                    (Some(s), _, _) | (None, Some(s), _) => walk(root, s, v, &at, out),
+                    (None, None, Some(props)) => out.push(match closest(k, props.keys()) {
+                        Some(near) => format!("{at}: unknown key (did you mean {near}?)"),
+                        None => format!("{at}: unknown key"),
+                    }),
+                    // free-form mapping
+                    (None, None, None) => {}
+                }
+            }
+        }
+        yaml::Value::Sequence(seq) => {
+            if let Some(items) = schema.get("items") {
+                for (i, v) in seq.iter().enumerate() {
+                    walk(root, items, v, &format!("{path}[{i}]"), out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// The schema a mapping or list is read with, through references and the
+// alternatives of optional and string-or-object settings
+fn pick<'a>(root: &'a Value, schema: &'a Value, doc: &yaml::Value) -> Option<&'a Value> {
+    if let Some(name) = schema.get("$ref").and_then(Value::as_str).and_then(|r| r.strip_prefix(DEFS)) {
+        return pick(root, root.get("$defs")?.get(name)?, doc);
+    }
+    for key in ["oneOf", "anyOf", "allOf"] {
+        if let Some(alts) = schema.get(key).and_then(Value::as_array) {
+            return alts.iter().find_map(|alt| pick(root, alt, doc));
+        }
+    }
+    let fits = match doc {
+        yaml::Value::Mapping(_) => schema.get("properties").is_some() || schema.get("additionalProperties").is_some(),
+        yaml::Value::Sequence(_) => schema.get("items").is_some(),
+        _ => false,
+    };
+    fits.then_some(schema)
+}
+
+fn closest<'a>(key: &str, known: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    known
+        .map(|k| (strsim::jaro_winkler(key, k), k))
+        .filter(|(score, _)| *score > 0.85)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, k)| k.as_str())
+}
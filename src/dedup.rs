@@ -0,0 +1,220 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use tracing::{info, warn};
+
+use crate::config::DedupConfig;
+use crate::store::DomainStore;
+
+// bit probes per key; ~1% false positives at 10 bits per key
+const HASHES: u64 = 7;
+
+// Lock-free bloom filter; false positives are possible, false negatives are not
+struct Bloom {
+    words: Vec<AtomicU64>,
+    bits: u64,
+    // keys inserted since the last clear
+    len: AtomicU64,
+}
+
+impl Bloom {
+    fn with_bytes(bytes: usize) -> Self {
+        let words = (bytes / 8).max(1);
+        Self {
+            words: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            bits: words as u64 * 64,
+            len: AtomicU64::new(0),
+        }
+    }
+
+    // set the key's bits; true if at least one was unset (key not seen before)
+    fn insert(&self, key: &str) -> bool {
+        let h1 = fnv1a(key.as_bytes());
+        let h2 = splitmix(h1) | 1;
+        let mut fresh = false;
+        for i in 0..HASHES {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.bits;
+            let mask = 1u64 << (bit % 64);
+            let prev = self.words[(bit / 64) as usize].fetch_or(mask, Ordering::Relaxed);
+            fresh |= prev & mask == 0;
+        }
+        if fresh {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        fresh
+    }
+
+    // all of the key's bits set, without setting any
+    fn contains(&self, key: &str) -> bool {
+        let h1 = fnv1a(key.as_bytes());
+        let h2 = splitmix(h1) | 1;
+        (0..HASHES).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.bits;
+            self.words[(bit / 64) as usize].load(Ordering::Relaxed) & (1u64 << (bit % 64)) != 0
+        })
+    }
+
+    fn clear(&self) {
+        self.len.store(0, Ordering::Relaxed);
+        for w in &self.words {
+            w.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Past its capacity the false positives climb fast (over half of all keys
+    // at four times it), so a full filter starts over; it only stays useful
+    // while it holds about `capacity` keys
+    fn clear_if_full(&self) -> bool {
+        let cap = self.capacity();
+        if self.len.load(Ordering::Relaxed) < cap || self.len.swap(0, Ordering::Relaxed) < cap {
+            return false;
+        }
+        self.clear();
+        true
+    }
+
+    // rough number of keys before false positives exceed ~1%
+    fn capacity(&self) -> u64 {
+        self.bits / 10
+    }
+}
+
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in data {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+fn splitmix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+// Two sets: candidates checked in the current pass, in a bloom filter that may
+// skip one that was not, and records already in storage, in an exact set on
+// disk: a find must never be taken for a stored one
+pub struct Dedup {
+    checked: Option<Bloom>,
+    stored: Mutex<Connection>,
+}
+
+// keys per seeding transaction; the lock is released in between so stores
+// opened mid-scan (namespaces) do not hold up the workers
+const SEED_BATCH: usize = 50_000;
+
+impl Dedup {
+    pub fn from_config(cfg: &DedupConfig, dir: &Path) -> anyhow::Result<Option<Arc<Self>>> {
+        if !cfg.enabled {
+            return Ok(None);
+        }
+        let checked = cfg
+            .skip_checked
+            .then(|| Bloom::with_bytes(cfg.memory_mb as usize * 1024 * 1024));
+        // seeded from storage on every start, so nothing in it needs to survive a crash
+        let path = dir.join("dedup.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = OFF;
+             PRAGMA synchronous = OFF;
+             CREATE TABLE IF NOT EXISTS stored (key TEXT PRIMARY KEY) WITHOUT ROWID;
+             DELETE FROM stored;",
+        )?;
+        match &checked {
+            Some(b) => info!("dedup: stored set in {}, ~{} checked domains per pass", path.display(), b.capacity()),
+            None => info!("dedup: stored set in {}", path.display()),
+        }
+        Ok(Some(Arc::new(Self {
+            checked,
+            stored: Mutex::new(conn),
+        })))
+    }
+
+    /// Remember what is already stored so it is not appended again.
+    pub async fn seed(self: &Arc<Self>, store: &DomainStore, kind: &str) {
+        let (this, store, key_kind) = (self.clone(), store.clone(), kind.to_string());
+        let n = tokio::task::spawn_blocking(move || {
+            let mut n = 0usize;
+            let mut keys = Vec::with_capacity(SEED_BATCH);
+            store.scan_all(&mut |d| {
+                keys.push(store_key(&key_kind, d));
+                if keys.len() >= SEED_BATCH {
+                    this.insert_all(&mut keys);
+                }
+                n += 1;
+            });
+            this.insert_all(&mut keys);
+            n
+        })
+        .await
+        .unwrap_or_default();
+        info!("dedup: seeded {} {} domains from storage", n, kind);
+    }
+
+    fn insert_all(&self, keys: &mut Vec<String>) {
+        let mut conn = self.stored.lock();
+        let res = conn.transaction().and_then(|tx| {
+            {
+                let mut stmt = tx.prepare_cached("INSERT OR IGNORE INTO stored (key) VALUES (?1)")?;
+                for k in keys.iter() {
+                    stmt.execute([k])?;
+                }
+            }
+            tx.commit()
+        });
+        if let Err(e) = res {
+            warn!("dedup: seeding failed, these domains may be stored again: {e}");
+        }
+        keys.clear();
+    }
+
+    /// Whether `first_check` saw the candidate in this pass, without marking it.
+    pub fn checked_before(&self, domain: &str) -> bool {
+        self.checked.as_ref().is_some_and(|b| b.contains(domain))
+    }
+
+    /// True the first time a candidate is seen in this pass.
+    pub fn first_check(&self, domain: &str) -> bool {
+        let Some(b) = &self.checked else { return true };
+        if b.clear_if_full() {
+            info!("dedup: checked filter full at ~{} domains, starting over", b.capacity());
+        }
+        b.insert(domain)
+    }
+
+    /// True the first time a domain is stored under `kind` (found/available/registered,
+    /// found/<namespace>).
+    pub fn first_store(&self, kind: &str, domain: &str) -> bool {
+        let conn = self.stored.lock();
+        let res = conn
+            .prepare_cached("INSERT OR IGNORE INTO stored (key) VALUES (?1)")
+            .and_then(|mut stmt| stmt.execute([store_key(kind, domain)]));
+        match res {
+            Ok(n) => n == 1,
+            Err(e) => {
+                // storing a domain twice beats losing it
+                warn!("dedup: lookup of {} failed: {e}", domain);
+                true
+            }
+        }
+    }
+
+    // a new pass may re-check everything; stored records stay deduplicated
+    pub fn new_pass(&self) {
+        if let Some(b) = &self.checked {
+            b.clear();
+        }
+    }
+}
+
+fn store_key(kind: &str, domain: &str) -> String {
+    format!("{kind}:{domain}")
+}
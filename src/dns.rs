@@ -0,0 +1,563 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::lookup::Lookup;
+use hickory_resolver::lookup_ip::LookupIp;
+use hickory_resolver::proto::op::{Query, ResponseCode};
+use hickory_resolver::proto::rr::{Name, RecordType};
+use hickory_resolver::proto::serialize::binary::BinEncodable;
+use hickory_resolver::TokioAsyncResolver;
+use serde::{Deserialize, Serialize};
+use tracing::field::Empty;
+use tracing::{info, info_span, Instrument};
+
+use crate::bandwidth::{self, Channel};
+use crate::config::DnsConfig;
+
+mod batch;
+mod consensus;
+mod negative;
+mod positive;
+
+use batch::BatchClient;
+pub use consensus::{stats as consensus_stats, ResolverStats};
+use consensus::{Consensus, Verdict, Vote};
+pub use negative::NegativeCache;
+pub use positive::{stats as positive_cache_stats, PositiveCache, PositiveCacheStats};
+
+/// DNS record types that can make a domain a hit (`dns.record_types`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordKind {
+    A,
+    Aaaa,
+    Mx,
+    Ns,
+    Txt,
+}
+
+impl RecordKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RecordKind::A => "a",
+            RecordKind::Aaaa => "aaaa",
+            RecordKind::Mx => "mx",
+            RecordKind::Ns => "ns",
+            RecordKind::Txt => "txt",
+        }
+    }
+
+    /// A and AAAA come from the address lookup and can be checked over HTTP.
+    pub fn is_address(self) -> bool {
+        matches!(self, RecordKind::A | RecordKind::Aaaa)
+    }
+
+    fn record_type(self) -> RecordType {
+        match self {
+            RecordKind::A => RecordType::A,
+            RecordKind::Aaaa => RecordType::AAAA,
+            RecordKind::Mx => RecordType::MX,
+            RecordKind::Ns => RecordType::NS,
+            RecordKind::Txt => RecordType::TXT,
+        }
+    }
+}
+
+/// Whether any or all of `dns.record_types` must be present.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordMatch {
+    #[default]
+    Any,
+    All,
+}
+
+/// Addresses a domain needs to pass the dns stage (`dns.address_family`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    /// A or AAAA; AAAA is only looked up for names without A
+    #[default]
+    Any,
+    /// A records; HTTP connects over IPv4 only
+    Ipv4,
+    /// AAAA records; HTTP connects over IPv6 only
+    Ipv6,
+    /// A and AAAA records, both looked up for every name
+    Both,
+}
+
+impl AddressFamily {
+    /// Whether the addresses satisfy the policy
+    pub fn satisfied_by(self, addrs: &[IpAddr]) -> bool {
+        let (v4, v6) = (addrs.iter().any(IpAddr::is_ipv4), addrs.iter().any(IpAddr::is_ipv6));
+        match self {
+            AddressFamily::Any => v4 || v6,
+            AddressFamily::Ipv4 => v4,
+            AddressFamily::Ipv6 => v6,
+            AddressFamily::Both => v4 && v6,
+        }
+    }
+}
+
+// One or more resolvers; lookups are spread round-robin across them
+pub struct ResolverPool {
+    resolvers: Vec<TokioAsyncResolver>,
+    next: AtomicUsize,
+    record_types: Vec<RecordKind>,
+    record_match: RecordMatch,
+    family: AddressFamily,
+    strategy: LookupIpStrategy,
+    // dns.batch: A/AAAA straight to the servers
+    batch: Option<BatchClient>,
+    // dns.negative_cache, shared by the scans
+    negative: Option<Arc<NegativeCache>>,
+    // dns.positive_cache, shared by the scans and verify rounds
+    positive: Option<Arc<PositiveCache>>,
+    // dns.consensus: every lookup goes to all of its resolvers instead
+    consensus: Option<Consensus>,
+}
+
+impl ResolverPool {
+    pub fn from_config(cfg: &DnsConfig) -> anyhow::Result<Self> {
+        let mut opts = ResolverOpts::default();
+        opts.timeout = cfg.timeout;
+        opts.attempts = cfg.attempts.max(1) as usize;
+        let needs_both = cfg.record_match == RecordMatch::All && cfg.record_types.contains(&RecordKind::A) && cfg.record_types.contains(&RecordKind::Aaaa);
+        opts.ip_strategy = match cfg.address_family {
+            AddressFamily::Ipv4 => LookupIpStrategy::Ipv4Only,
+            AddressFamily::Ipv6 => LookupIpStrategy::Ipv6Only,
+            AddressFamily::Both => LookupIpStrategy::Ipv4AndIpv6,
+            // the default stops at A, which would hide AAAA from record_match: all
+            AddressFamily::Any if needs_both => LookupIpStrategy::Ipv4AndIpv6,
+            AddressFamily::Any => LookupIpStrategy::Ipv4thenIpv6,
+        };
+
+        let specs: Vec<&str> = cfg
+            .servers
+            .iter()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let strategy = opts.ip_strategy;
+        let consensus = if cfg.consensus.enabled {
+            Some(Consensus::new(&cfg.consensus, &cfg.servers, &opts)?)
+        } else {
+            None
+        };
+        let batch = if cfg.batch.enabled && consensus.is_none() {
+            let servers: Vec<SocketAddr> = if specs.is_empty() {
+                vec![([8, 8, 8, 8], 53).into(), ([8, 8, 4, 4], 53).into()]
+            } else {
+                let mut v = Vec::new();
+                for s in &specs {
+                    v.extend(parse_server(s)?.into_iter().filter(|ns| ns.protocol == Protocol::Udp).map(|ns| ns.socket_addr));
+                }
+                v
+            };
+            Some(BatchClient::new(&servers, &cfg.batch, cfg.timeout, cfg.attempts.max(1) as usize)?)
+        } else {
+            None
+        };
+        let resolvers = if specs.is_empty() {
+            info!("dns: using Google public resolvers");
+            vec![TokioAsyncResolver::tokio(ResolverConfig::google(), opts)]
+        } else {
+            let groups = specs
+                .iter()
+                .map(|s| parse_server(s))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            info!(
+                "dns: {} upstream servers, round_robin={}",
+                groups.len(),
+                cfg.round_robin
+            );
+            if cfg.round_robin {
+                groups
+                    .into_iter()
+                    .map(|ns| TokioAsyncResolver::tokio(resolver_config(ns), opts.clone()))
+                    .collect()
+            } else {
+                let all = groups.into_iter().flatten().collect();
+                vec![TokioAsyncResolver::tokio(resolver_config(all), opts)]
+            }
+        };
+        Ok(Self {
+            resolvers,
+            next: AtomicUsize::new(0),
+            record_types: cfg.record_types.clone(),
+            record_match: cfg.record_match,
+            family: cfg.address_family,
+            strategy,
+            batch,
+            negative: None,
+            positive: None,
+            consensus,
+        })
+    }
+
+    /// Answer names that were NXDOMAIN within the cache's ttl from `cache`.
+    pub fn with_negative_cache(mut self, cache: Option<Arc<NegativeCache>>) -> Self {
+        self.negative = cache;
+        self
+    }
+
+    /// Answer lookups that had records within their TTL from `cache`.
+    pub fn with_positive_cache(mut self, cache: Option<Arc<PositiveCache>>) -> Self {
+        self.positive = cache;
+        self
+    }
+
+    // "<domain> <question>": address lookups of pools with another
+    // address_family ask a different question
+    fn cached_answer(&self, domain: &str, question: &str) -> Option<Vec<IpAddr>> {
+        self.positive.as_ref()?.get(&format!("{domain} {question}"))
+    }
+
+    fn note_answer(&self, domain: &str, question: &str, ips: &[IpAddr], valid_until: Instant) {
+        if let Some(c) = &self.positive {
+            c.insert(format!("{domain} {question}"), ips.to_vec(), valid_until);
+        }
+    }
+
+    fn ip_question(&self) -> String {
+        format!("{:?}", self.strategy)
+    }
+
+    fn cached_nxdomain(&self, domain: &str) -> bool {
+        self.negative.as_ref().is_some_and(|c| c.contains(domain))
+    }
+
+    fn note_nxdomain<T>(&self, domain: &str, res: &Result<T, ResolveError>) {
+        if let (Some(c), Err(e)) = (&self.negative, res) {
+            if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { response_code, .. } if *response_code == ResponseCode::NXDomain) {
+                c.insert(domain);
+            }
+        }
+    }
+
+    /// Address families a domain needs (`dns.address_family`).
+    pub fn address_family(&self) -> AddressFamily {
+        self.family
+    }
+
+    /// Record types that make a domain a hit, and whether any or all must be present.
+    pub fn record_types(&self) -> (&[RecordKind], RecordMatch) {
+        (&self.record_types, self.record_match)
+    }
+
+    fn pick(&self) -> &TokioAsyncResolver {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.resolvers.len();
+        &self.resolvers[i]
+    }
+
+    pub async fn lookup_ip(&self, domain: &str) -> Result<Vec<IpAddr>, ResolveError> {
+        let span = info_span!("dns.lookup", domain, record = "address", cached = false, answers = Empty, error = Empty);
+        if self.cached_nxdomain(domain) {
+            span.record("cached", true);
+            return Err(nxdomain(domain, RecordType::A));
+        }
+        if let Some(ips) = self.cached_answer(domain, &self.ip_question()) {
+            span.record("cached", true);
+            span.record("answers", ips.len());
+            return Ok(ips);
+        }
+        let res = self.lookup_ip_uncached(domain).instrument(span.clone()).await;
+        match &res {
+            Ok((ips, valid_until)) => {
+                span.record("answers", ips.len());
+                self.note_answer(domain, &self.ip_question(), ips, *valid_until);
+            }
+            Err(e) => {
+                span.record("error", tracing::field::display(e));
+            }
+        };
+        self.note_nxdomain(domain, &res);
+        res.map(|(ips, _)| ips)
+    }
+
+    // the addresses and the end of their shortest TTL
+    async fn lookup_ip_uncached(&self, domain: &str) -> Result<(Vec<IpAddr>, Instant), ResolveError> {
+        if let Some(c) = &self.consensus {
+            let strategy = self.strategy;
+            let verdict = c
+                .vote(|r| async move {
+                    let res = r.lookup_ip(domain).await;
+                    count_ips(domain, strategy, &res);
+                    match res {
+                        Ok(l) => Vote::Yes((l.iter().collect::<Vec<_>>(), l.valid_until())),
+                        Err(e) => negative_vote(e),
+                    }
+                })
+                .await;
+            return match verdict {
+                Verdict::Yes(answers) => {
+                    let valid_until = answers.iter().map(|(_, t)| *t).min().unwrap_or_else(Instant::now);
+                    let mut ips: Vec<IpAddr> = answers.into_iter().flat_map(|(ips, _)| ips).collect();
+                    ips.sort();
+                    ips.dedup();
+                    Ok((ips, valid_until))
+                }
+                Verdict::No(e) => Err(e.unwrap_or_else(|| nxdomain(domain, RecordType::A))),
+                Verdict::Failed(e) => Err(e),
+            };
+        }
+        if let Some(b) = &self.batch {
+            // a truncated answer goes through the resolver, which retries over TCP
+            if let Some(answer) = b.lookup_ip(domain, self.strategy).await? {
+                return Ok(answer);
+            }
+        }
+        let res = self.pick().lookup_ip(domain).await;
+        count_ips(domain, self.strategy, &res);
+        let l = res?;
+        Ok((l.iter().collect(), l.valid_until()))
+    }
+
+    /// Whether `domain` has at least one record of this type; NXDOMAIN and
+    /// empty answers are `Ok(false)`.
+    pub async fn has_records(&self, domain: &str, kind: RecordKind) -> Result<bool, ResolveError> {
+        if self.cached_nxdomain(domain) {
+            return Ok(false);
+        }
+        if self.cached_answer(domain, kind.as_str()).is_some() {
+            return Ok(true);
+        }
+        let span = info_span!("dns.lookup", domain, record = kind.as_str(), answers = Empty, error = Empty);
+        if let Some(c) = &self.consensus {
+            let verdict = c
+                .vote(|r| async move {
+                    let res = r.lookup(domain, kind.record_type()).await;
+                    count_records(domain, 1, res.as_ref());
+                    match res {
+                        Ok(l) if l.iter().next().is_some() => Vote::Yes(l.valid_until()),
+                        Ok(_) => Vote::No(None),
+                        Err(e) => negative_vote(e),
+                    }
+                })
+                .instrument(span.clone())
+                .await;
+            return match verdict {
+                Verdict::Yes(answers) => {
+                    span.record("answers", answers.len());
+                    if let Some(valid_until) = answers.into_iter().min() {
+                        self.note_answer(domain, kind.as_str(), &[], valid_until);
+                    }
+                    Ok(true)
+                }
+                Verdict::No(e) => {
+                    if let Some(e) = e {
+                        self.note_nxdomain::<()>(domain, &Err(e));
+                    }
+                    Ok(false)
+                }
+                Verdict::Failed(e) => {
+                    span.record("error", tracing::field::display(&e));
+                    Err(e)
+                }
+            };
+        }
+        let res = self.pick().lookup(domain, kind.record_type()).instrument(span.clone()).await;
+        count_records(domain, 1, res.as_ref());
+        match &res {
+            Ok(l) => span.record("answers", l.iter().count()),
+            Err(e) => span.record("error", tracing::field::display(e)),
+        };
+        self.note_nxdomain(domain, &res);
+        match res {
+            Ok(l) => {
+                let found = l.iter().next().is_some();
+                if found {
+                    self.note_answer(domain, kind.as_str(), &[], l.valid_until());
+                }
+                Ok(found)
+            }
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } if *response_code != ResponseCode::ServFail => Ok(false),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Nameservers of `domain` from its NS records, without the trailing dot;
+    /// empty for NXDOMAIN and empty answers.
+    pub async fn ns_names(&self, domain: &str) -> Result<Vec<String>, ResolveError> {
+        let res = self.pick().lookup(domain, RecordType::NS).await;
+        count_records(domain, 1, res.as_ref());
+        match res {
+            Ok(l) => Ok(l
+                .iter()
+                .filter_map(|r| r.as_ns())
+                .map(|ns| ns.to_utf8().trim_end_matches('.').to_ascii_lowercase())
+                .collect()),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } if *response_code != ResponseCode::ServFail => Ok(Vec::new()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Whether `domain` exists in DNS, by its SOA: any answer or an empty one
+    /// (NODATA) is `Ok(true)`, NXDOMAIN is `Ok(false)`.
+    pub async fn name_exists(&self, domain: &str) -> Result<bool, ResolveError> {
+        if self.cached_nxdomain(domain) {
+            return Ok(false);
+        }
+        if self.cached_answer(domain, "soa").is_some() {
+            return Ok(true);
+        }
+        if let Some(c) = &self.consensus {
+            let verdict = c
+                .vote(|r| async move {
+                    let res = r.lookup(domain, RecordType::SOA).await;
+                    count_records(domain, 1, res.as_ref());
+                    match res {
+                        Ok(l) => Vote::Yes(Some(l.valid_until())),
+                        // an empty answer carries no TTL to keep it for
+                        Err(e) => match e.kind() {
+                            ResolveErrorKind::NoRecordsFound { response_code, .. } if *response_code == ResponseCode::NoError => Vote::Yes(None),
+                            _ => negative_vote(e),
+                        },
+                    }
+                })
+                .await;
+            return match verdict {
+                Verdict::Yes(answers) => {
+                    if let Some(valid_until) = answers.into_iter().collect::<Option<Vec<_>>>().and_then(|t| t.into_iter().min()) {
+                        self.note_answer(domain, "soa", &[], valid_until);
+                    }
+                    Ok(true)
+                }
+                Verdict::No(e) => {
+                    if let Some(e) = e {
+                        self.note_nxdomain::<()>(domain, &Err(e));
+                    }
+                    Ok(false)
+                }
+                Verdict::Failed(e) => Err(e),
+            };
+        }
+        let res = self.pick().lookup(domain, RecordType::SOA).await;
+        count_records(domain, 1, res.as_ref());
+        self.note_nxdomain(domain, &res);
+        match res {
+            Ok(l) => {
+                self.note_answer(domain, "soa", &[], l.valid_until());
+                Ok(true)
+            }
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => match *response_code {
+                    ResponseCode::NXDomain => Ok(false),
+                    ResponseCode::NoError => Ok(true),
+                    _ => Err(e),
+                },
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+// An address lookup asks for A and AAAA in turn or at once, as the strategy says
+fn count_ips(domain: &str, strategy: LookupIpStrategy, res: &Result<LookupIp, ResolveError>) {
+    let queries = match strategy {
+        LookupIpStrategy::Ipv4AndIpv6 => 2,
+        LookupIpStrategy::Ipv4thenIpv6 if !res.as_ref().is_ok_and(|l| l.iter().any(|ip| ip.is_ipv4())) => 2,
+        LookupIpStrategy::Ipv6thenIpv4 if !res.as_ref().is_ok_and(|l| l.iter().any(|ip| ip.is_ipv6())) => 2,
+        _ => 1,
+    };
+    count_records(domain, queries, res.as_ref().map(LookupIp::as_lookup));
+}
+
+// hickory does not tell what went over the wire: count the questions and the
+// answers' records, names uncompressed. Negative answers are about a question
+// long, failed lookups count what was sent
+fn count_records(domain: &str, queries: u64, res: Result<&Lookup, &ResolveError>) {
+    let query = (12 + domain.trim_end_matches('.').len() + 2 + 4) as u64 * queries;
+    let received = match res {
+        Ok(l) => query + l.records().iter().map(|r| r.to_bytes().map_or(0, |b| b.len() as u64)).sum::<u64>(),
+        Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => query,
+        Err(_) => 0,
+    };
+    bandwidth::record(Channel::Dns, query, received);
+}
+
+// a consensus vote from a failed lookup: NXDOMAIN and empty answers say the
+// records are missing, anything else is no answer at all
+fn negative_vote<T>(e: ResolveError) -> Vote<T> {
+    match e.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } if matches!(*response_code, ResponseCode::NXDomain | ResponseCode::NoError) => Vote::No(Some(e)),
+        _ => Vote::Failed(e),
+    }
+}
+
+// the error a lookup of a name that does not exist returns
+fn nxdomain(domain: &str, rtype: RecordType) -> ResolveError {
+    let name = Name::from_ascii(domain).unwrap_or_default();
+    ResolveErrorKind::NoRecordsFound {
+        query: Box::new(Query::query(name, rtype)),
+        soa: None,
+        negative_ttl: None,
+        response_code: ResponseCode::NXDomain,
+        trusted: true,
+    }
+    .into()
+}
+
+fn resolver_config(servers: Vec<NameServerConfig>) -> ResolverConfig {
+    let mut rc = ResolverConfig::new();
+    for ns in servers {
+        rc.add_name_server(ns);
+    }
+    rc
+}
+
+/// Parse an upstream spec:
+///   "8.8.8.8", "8.8.8.8:53", "udp://..." - plain DNS (UDP with TCP fallback)
+///   "tcp://1.1.1.1"                       - TCP only
+///   "tls://1.1.1.1:853#cloudflare-dns.com" - DNS-over-TLS
+///   "https://1.1.1.1#cloudflare-dns.com"   - DNS-over-HTTPS (/dns-query)
+/// The "#name" suffix is the TLS server name and is required for tls/https.
+pub fn parse_server(spec: &str) -> anyhow::Result<Vec<NameServerConfig>> {
+    let (scheme, rest) = match spec.split_once("://") {
+        Some((s, r)) => (s.to_lowercase(), r),
+        None => ("udp".to_string(), spec),
+    };
+    let (addr, tls_name) = match rest.split_once('#') {
+        Some((a, n)) => (a, Some(n.trim().to_string())),
+        None => (rest, None),
+    };
+    // ignore any path, DoH always uses /dns-query
+    let addr = addr.split('/').next().unwrap_or_default().trim();
+    let (protos, default_port): (&[Protocol], u16) = match scheme.as_str() {
+        "udp" => (&[Protocol::Udp, Protocol::Tcp], 53),
+        "tcp" => (&[Protocol::Tcp], 53),
+        "tls" => (&[Protocol::Tls], 853),
+        "https" => (&[Protocol::Https], 443),
+        other => anyhow::bail!("dns server {spec}: unsupported scheme {other}"),
+    };
+    let socket_addr = if let Ok(sa) = addr.parse::<SocketAddr>() {
+        sa
+    } else if let Ok(ip) = addr.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        SocketAddr::new(ip, default_port)
+    } else {
+        anyhow::bail!("dns server {spec}: expected an IP address with optional port");
+    };
+    let needs_name = matches!(scheme.as_str(), "tls" | "https");
+    if needs_name && tls_name.as_deref().is_none_or(str::is_empty) {
+        anyhow::bail!("dns server {spec}: {scheme} requires a TLS name, e.g. {scheme}://{addr}#dns.example");
+    }
+    Ok(protos
+        .iter()
+        .map(|p| {
+            let mut ns = NameServerConfig::new(socket_addr, *p);
+            if needs_name {
+                ns.tls_dns_name = tls_name.clone();
+            }
+            ns
+        })
+        .collect())
+}
@@ -0,0 +1,253 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::config::LookupIpStrategy;
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_resolver::proto::rr::{Name, RData, RecordType};
+use parking_lot::Mutex;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::AbortHandle;
+use tracing::{debug, info};
+
+use crate::bandwidth::{self, Channel};
+use crate::config::DnsBatchConfig;
+
+// EDNS payload size that avoids IP fragmentation
+const EDNS_PAYLOAD: u16 = 1232;
+
+// queries waiting for their answer on one socket, by message id
+type Waiting = Mutex<HashMap<u16, (Query, oneshot::Sender<Message>)>>;
+
+struct Upstream {
+    addr: SocketAddr,
+    socket: Arc<UdpSocket>,
+    waiting: Arc<Waiting>,
+}
+
+/// A/AAAA lookups sent straight to the upstream servers over UDP, with many
+/// queries in flight on one socket per server (`dns.batch`).
+pub struct BatchClient {
+    upstreams: Vec<Arc<Upstream>>,
+    next: AtomicUsize,
+    // encoded queries for the sender task, by upstream index
+    out: mpsc::Sender<(usize, Vec<u8>)>,
+    slots: Semaphore,
+    rng: SystemRandom,
+    timeout: Duration,
+    attempts: usize,
+    tasks: Vec<AbortHandle>,
+}
+
+impl BatchClient {
+    pub fn new(servers: &[SocketAddr], cfg: &DnsBatchConfig, timeout: Duration, attempts: usize) -> anyhow::Result<Self> {
+        let mut upstreams = Vec::new();
+        let mut tasks = Vec::new();
+        for &addr in servers {
+            let local: SocketAddr = if addr.is_ipv4() {
+                (Ipv4Addr::UNSPECIFIED, 0).into()
+            } else {
+                (Ipv6Addr::UNSPECIFIED, 0).into()
+            };
+            let socket = std::net::UdpSocket::bind(local)?;
+            socket.set_nonblocking(true)?;
+            let up = Arc::new(Upstream {
+                addr,
+                socket: Arc::new(UdpSocket::from_std(socket)?),
+                waiting: Arc::new(Mutex::new(HashMap::new())),
+            });
+            tasks.push(tokio::spawn(receive(up.clone())).abort_handle());
+            upstreams.push(up);
+        }
+        let (out, rx) = mpsc::channel(cfg.max_in_flight.max(1));
+        tasks.push(tokio::spawn(send(rx, upstreams.clone(), cfg.size.max(1))).abort_handle());
+        info!(
+            "dns: batch resolution over {} servers, batch size {}, {} queries in flight",
+            upstreams.len(),
+            cfg.size,
+            cfg.max_in_flight
+        );
+        Ok(Self {
+            upstreams,
+            next: AtomicUsize::new(0),
+            out,
+            slots: Semaphore::new(cfg.max_in_flight.max(1)),
+            rng: SystemRandom::new(),
+            timeout,
+            attempts: attempts.max(1),
+            tasks,
+        })
+    }
+
+    /// Addresses of `domain` the way `strategy` looks them up, with the end
+    /// of their shortest TTL; Ok(None) when an answer came back truncated and
+    /// needs the resolver's TCP fallback.
+    pub async fn lookup_ip(&self, domain: &str, strategy: LookupIpStrategy) -> Result<Option<(Vec<IpAddr>, Instant)>, ResolveError> {
+        let mut name = Name::from_ascii(domain)?;
+        name.set_fqdn(true);
+        match strategy {
+            LookupIpStrategy::Ipv4Only => self.addresses(&name, RecordType::A).await,
+            LookupIpStrategy::Ipv6Only => self.addresses(&name, RecordType::AAAA).await,
+            LookupIpStrategy::Ipv4AndIpv6 => {
+                let (v4, v6) = tokio::join!(self.addresses(&name, RecordType::A), self.addresses(&name, RecordType::AAAA));
+                match (v4, v6) {
+                    (Ok(None), _) | (_, Ok(None)) => Ok(None),
+                    (Ok(Some((mut a, ta))), Ok(Some((b, tb)))) => {
+                        a.extend(b);
+                        Ok(Some((a, ta.min(tb))))
+                    }
+                    (Ok(Some(a)), Err(_)) | (Err(_), Ok(Some(a))) => Ok(Some(a)),
+                    (Err(e), Err(_)) => Err(e),
+                }
+            }
+            // A first, AAAA only for names that exist without A records
+            _ => match self.addresses(&name, RecordType::A).await {
+                Err(e) if no_data(&e) => self.addresses(&name, RecordType::AAAA).await,
+                other => other,
+            },
+        }
+    }
+
+    async fn addresses(&self, name: &Name, rtype: RecordType) -> Result<Option<(Vec<IpAddr>, Instant)>, ResolveError> {
+        let query = Query::query(name.clone(), rtype);
+        let msg = self.query(&query).await?;
+        if msg.truncated() {
+            return Ok(None);
+        }
+        let records: Vec<_> = msg.answers().iter().filter(|r| r.record_type() == rtype).collect();
+        let ips: Vec<IpAddr> = records
+            .iter()
+            .filter_map(|r| match r.data() {
+                Some(RData::A(a)) => Some(IpAddr::V4(a.0)),
+                Some(RData::AAAA(a)) => Some(IpAddr::V6(a.0)),
+                _ => None,
+            })
+            .collect();
+        if msg.response_code() == ResponseCode::NoError && !ips.is_empty() {
+            let ttl = records.iter().map(|r| r.ttl()).min().unwrap_or(0);
+            return Ok(Some((ips, Instant::now() + Duration::from_secs(ttl.into()))));
+        }
+        Err(ResolveErrorKind::NoRecordsFound {
+            query: Box::new(query),
+            soa: None,
+            negative_ttl: None,
+            response_code: msg.response_code(),
+            trusted: true,
+        }
+        .into())
+    }
+
+    // send `query` to the next server, again to the one after on timeout
+    async fn query(&self, query: &Query) -> Result<Message, ResolveError> {
+        let _slot = self.slots.acquire().await.map_err(|_| ResolveErrorKind::Message("dns batch client closed"))?;
+        for _ in 0..self.attempts {
+            let i = self.next.fetch_add(1, Ordering::Relaxed) % self.upstreams.len();
+            let up = &self.upstreams[i];
+            let (id, answer) = self.register(up, query);
+            let packet = encode(id, query)?;
+            if self.out.send((i, packet)).await.is_err() {
+                up.waiting.lock().remove(&id);
+                return Err(ResolveErrorKind::Message("dns batch client closed").into());
+            }
+            match tokio::time::timeout(self.timeout, answer).await {
+                Ok(Ok(msg)) => return Ok(msg),
+                _ => {
+                    up.waiting.lock().remove(&id);
+                }
+            }
+        }
+        Err(ResolveErrorKind::Timeout.into())
+    }
+
+    // a random message id not in use on this socket
+    fn register(&self, up: &Upstream, query: &Query) -> (u16, oneshot::Receiver<Message>) {
+        let (tx, rx) = oneshot::channel();
+        let mut waiting = up.waiting.lock();
+        loop {
+            let mut b = [0u8; 2];
+            let _ = self.rng.fill(&mut b);
+            let id = u16::from_ne_bytes(b);
+            if let Entry::Vacant(e) = waiting.entry(id) {
+                e.insert((query.clone(), tx));
+                return (id, rx);
+            }
+        }
+    }
+}
+
+impl Drop for BatchClient {
+    fn drop(&mut self) {
+        for t in &self.tasks {
+            t.abort();
+        }
+    }
+}
+
+// an empty answer for a name that exists
+fn no_data(e: &ResolveError) -> bool {
+    matches!(e.kind(), ResolveErrorKind::NoRecordsFound { response_code, .. } if *response_code == ResponseCode::NoError)
+}
+
+fn encode(id: u16, query: &Query) -> Result<Vec<u8>, ResolveError> {
+    let mut msg = Message::new();
+    msg.set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(query.clone());
+    let mut edns = Edns::new();
+    edns.set_max_payload(EDNS_PAYLOAD);
+    msg.set_edns(edns);
+    Ok(msg.to_vec()?)
+}
+
+// Write queued queries out, up to `size` per wakeup
+async fn send(mut rx: mpsc::Receiver<(usize, Vec<u8>)>, upstreams: Vec<Arc<Upstream>>, size: usize) {
+    let mut batch = Vec::with_capacity(size);
+    while rx.recv_many(&mut batch, size).await > 0 {
+        for (i, packet) in batch.drain(..) {
+            let up = &upstreams[i];
+            match up.socket.send_to(&packet, up.addr).await {
+                Ok(n) => bandwidth::record(Channel::Dns, n as u64, 0),
+                Err(e) => debug!("dns batch: send to {} failed: {}", up.addr, e),
+            }
+        }
+    }
+}
+
+// Hand answers to the queries waiting for them; anything from another
+// address, with an unknown id or for another question is dropped
+async fn receive(up: Arc<Upstream>) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (n, from) = match up.socket.recv_from(&mut buf).await {
+            Ok(r) => r,
+            Err(e) => {
+                debug!("dns batch: receive from {} failed: {}", up.addr, e);
+                continue;
+            }
+        };
+        if from != up.addr {
+            continue;
+        }
+        bandwidth::record(Channel::Dns, 0, n as u64);
+        let Ok(msg) = Message::from_vec(&buf[..n]) else {
+            continue;
+        };
+        if msg.message_type() != MessageType::Response {
+            continue;
+        }
+        let mut waiting = up.waiting.lock();
+        if waiting.get(&msg.id()).is_some_and(|(q, _)| msg.queries().first() == Some(q)) {
+            if let Some((_, tx)) = waiting.remove(&msg.id()) {
+                let _ = tx.send(msg);
+            }
+        }
+    }
+}
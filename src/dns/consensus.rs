@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+use hickory_resolver::config::ResolverOpts;
+use hickory_resolver::error::ResolveError;
+use hickory_resolver::TokioAsyncResolver;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use super::{parse_server, resolver_config};
+use crate::checker::dns_failure;
+use crate::config::DnsConsensusConfig;
+
+// Counted per resolver spec since start: scans and the health check build
+// their own pools, so the counters cannot live with one of them
+static COUNTERS: Lazy<Mutex<BTreeMap<String, Arc<Counters>>>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct Counters {
+    queries: AtomicU64,
+    answered: AtomicU64,
+    negative: AtomicU64,
+    outvoted: AtomicU64,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+/// Answers of one `dns.consensus` resolver since start, in /stats/
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct ResolverStats {
+    /// Lookups asked of this resolver
+    pub queries: u64,
+    /// Lookups it answered with records
+    pub answered: u64,
+    /// Lookups it answered with NXDOMAIN or an empty answer
+    pub negative: u64,
+    /// Answers the quorum decided against: records for a name the others
+    /// call missing (hijacking, resolver wildcards) or the other way round
+    pub outvoted: u64,
+    /// Failed lookups by cause: dns_servfail, dns_refused, dns_timeout, dns_other
+    pub errors: BTreeMap<String, u64>,
+}
+
+/// Per-resolver counters of `dns.consensus`; empty when it was never on.
+pub fn stats() -> BTreeMap<String, ResolverStats> {
+    COUNTERS
+        .lock()
+        .iter()
+        .map(|(name, c)| {
+            let st = ResolverStats {
+                queries: c.queries.load(Ordering::Relaxed),
+                answered: c.answered.load(Ordering::Relaxed),
+                negative: c.negative.load(Ordering::Relaxed),
+                outvoted: c.outvoted.load(Ordering::Relaxed),
+                errors: c.errors.lock().iter().map(|(k, n)| (k.to_string(), *n)).collect(),
+            };
+            (name.clone(), st)
+        })
+        .collect()
+}
+
+/// One resolver's answer to a lookup.
+pub enum Vote<T> {
+    /// Records
+    Yes(T),
+    /// NXDOMAIN or an empty answer, with the error that said so if any
+    No(Option<ResolveError>),
+    /// No usable answer: timeout, SERVFAIL, REFUSED, ...
+    Failed(ResolveError),
+}
+
+/// What the quorum decided.
+pub enum Verdict<T> {
+    /// The answers of the resolvers that had records
+    Yes(Vec<T>),
+    No(Option<ResolveError>),
+    /// Too many resolvers failed to decide either way; worth a re-check
+    Failed(ResolveError),
+}
+
+struct Voter {
+    name: String,
+    resolver: TokioAsyncResolver,
+    counters: Arc<Counters>,
+}
+
+/// The voting resolvers of `dns.consensus`.
+pub struct Consensus {
+    voters: Vec<Voter>,
+    quorum: usize,
+}
+
+impl Consensus {
+    pub fn new(cfg: &DnsConsensusConfig, servers: &[String], opts: &ResolverOpts) -> anyhow::Result<Self> {
+        let mut voters = Vec::new();
+        for spec in cfg.voters(servers) {
+            let counters = COUNTERS.lock().entry(spec.to_string()).or_default().clone();
+            voters.push(Voter {
+                name: spec.to_string(),
+                resolver: TokioAsyncResolver::tokio(resolver_config(parse_server(spec)?), opts.clone()),
+                counters,
+            });
+        }
+        info!(
+            "dns: consensus of {} resolvers ({}), quorum {}",
+            voters.len(),
+            voters.iter().map(|v| v.name.as_str()).collect::<Vec<_>>().join(", "),
+            cfg.quorum
+        );
+        Ok(Self { voters, quorum: cfg.quorum })
+    }
+
+    /// Ask every resolver at once. Records win with `quorum` answers that
+    /// have them; otherwise the name is missing unless the failed resolvers
+    /// could still have made up the quorum, which leaves it undecided.
+    pub async fn vote<T, F, Fut>(&self, query: F) -> Verdict<T>
+    where
+        F: Fn(TokioAsyncResolver) -> Fut,
+        Fut: Future<Output = Vote<T>>,
+    {
+        let votes = join_all(self.voters.iter().map(|v| query(v.resolver.clone()))).await;
+        let yes = votes.iter().filter(|v| matches!(v, Vote::Yes(_))).count();
+        let failed = votes.iter().filter(|v| matches!(v, Vote::Failed(_))).count();
+        let decided_yes = yes >= self.quorum;
+        let decided_no = !decided_yes && yes + failed < self.quorum;
+        let (mut answers, mut negative, mut error) = (Vec::new(), None, None);
+        for (voter, vote) in self.voters.iter().zip(votes) {
+            let c = &voter.counters;
+            c.queries.fetch_add(1, Ordering::Relaxed);
+            match vote {
+                Vote::Yes(t) => {
+                    c.answered.fetch_add(1, Ordering::Relaxed);
+                    if decided_no {
+                        c.outvoted.fetch_add(1, Ordering::Relaxed);
+                    }
+                    answers.push(t);
+                }
+                Vote::No(e) => {
+                    c.negative.fetch_add(1, Ordering::Relaxed);
+                    if decided_yes {
+                        c.outvoted.fetch_add(1, Ordering::Relaxed);
+                    }
+                    negative = negative.or(e);
+                }
+                Vote::Failed(e) => {
+                    *c.errors.lock().entry(dns_failure(&e).as_str()).or_default() += 1;
+                    error = error.or(Some(e));
+                }
+            }
+        }
+        match (decided_yes, decided_no, error) {
+            (true, _, _) => Verdict::Yes(answers),
+            (_, true, _) => Verdict::No(negative),
+            (_, _, Some(e)) => Verdict::Failed(e),
+            // unreachable: without failures one of the two is decided
+            (_, _, None) => Verdict::No(negative),
+        }
+    }
+}
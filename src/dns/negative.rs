@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hashlink::LruCache;
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use crate::config::NegativeCacheConfig;
+use crate::dedup::fnv1a;
+
+/// Names that were NXDOMAIN, kept for `dns.negative_cache.ttl` so loop passes
+/// and retries do not ask again; the least recently used go first once full.
+pub struct NegativeCache {
+    shards: Vec<Mutex<LruCache<String, u64>>>,
+    ttl: Duration,
+    // saved here on shutdown and loaded at startup, with `persist`
+    path: Option<PathBuf>,
+    hits: AtomicU64,
+}
+
+impl NegativeCache {
+    pub fn from_config(cfg: &NegativeCacheConfig, storage_dir: &str) -> Option<Arc<Self>> {
+        if !cfg.enabled {
+            return None;
+        }
+        let shards = cfg.shards.max(1);
+        let per_shard = cfg.max_entries.div_ceil(shards).max(1);
+        let cache = Self {
+            shards: (0..shards).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+            ttl: cfg.ttl,
+            path: cfg.persist.then(|| Self::file_path(storage_dir)),
+            hits: AtomicU64::new(0),
+        };
+        if let Some(path) = &cache.path {
+            // saved as negative_cache.txt before
+            let legacy = Path::new(storage_dir).join("negative_cache.txt");
+            if !path.exists() && legacy.exists() {
+                if let Err(e) = std::fs::rename(&legacy, path) {
+                    warn!("dns: cannot move {} to {}: {e}", legacy.display(), path.display());
+                }
+            }
+            match cache.load(path) {
+                Ok(0) => {}
+                Ok(n) => info!("dns: loaded {} negative cache entries from {}", n, path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("dns: cannot read negative cache {}: {e}", path.display()),
+            }
+        }
+        info!("dns: negative cache of {} entries, ttl {:?}", cfg.max_entries, cfg.ttl);
+        Some(Arc::new(cache))
+    }
+
+    /// Where `persist` keeps the entries, for --reset. Not a `.txt`: the text
+    /// backend takes every `.txt` in storage.dir for a TLD's list.
+    pub fn file_path(storage_dir: &str) -> PathBuf {
+        Path::new(storage_dir).join("negative_cache.lst")
+    }
+
+    fn shard(&self, domain: &str) -> &Mutex<LruCache<String, u64>> {
+        &self.shards[(fnv1a(domain.as_bytes()) % self.shards.len() as u64) as usize]
+    }
+
+    /// Whether `domain` was NXDOMAIN within the ttl.
+    pub fn contains(&self, domain: &str) -> bool {
+        let mut shard = self.shard(domain).lock();
+        match shard.get(domain) {
+            Some(&expires) if expires > now_unix() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Some(_) => {
+                shard.remove(domain);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&self, domain: &str) {
+        let expires = now_unix() + self.ttl.as_secs();
+        self.shard(domain).lock().insert(domain.to_string(), expires);
+    }
+
+    // "<domain> <expires_unix>" per line; expired entries are skipped
+    fn load(&self, path: &Path) -> std::io::Result<usize> {
+        let now = now_unix();
+        let mut n = 0;
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let Some((domain, expires)) = line.split_once(' ') else {
+                continue;
+            };
+            match expires.parse::<u64>() {
+                Ok(expires) if expires > now => {
+                    self.shard(domain).lock().insert(domain.to_string(), expires);
+                    n += 1;
+                }
+                _ => {}
+            }
+        }
+        Ok(n)
+    }
+
+    /// Write the live entries to the store directory, with `persist`.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp = path.with_extension("lst.tmp");
+        let res = (|| -> std::io::Result<usize> {
+            let now = now_unix();
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            let mut n = 0;
+            for shard in &self.shards {
+                // oldest first, so loading keeps the recency order
+                let entries: Vec<(String, u64)> = shard.lock().iter().filter(|(_, e)| **e > now).map(|(d, e)| (d.clone(), *e)).collect();
+                for (domain, expires) in entries {
+                    writeln!(w, "{domain} {expires}")?;
+                    n += 1;
+                }
+            }
+            w.into_inner()?.sync_all()?;
+            std::fs::rename(&tmp, path)?;
+            Ok(n)
+        })();
+        match res {
+            Ok(n) => info!(
+                "dns: saved {} negative cache entries to {} ({} lookups answered from it this run)",
+                n,
+                path.display(),
+                self.hits.load(Ordering::Relaxed)
+            ),
+            Err(e) => warn!("dns: cannot save negative cache to {}: {e}", path.display()),
+        }
+    }
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
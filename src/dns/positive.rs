@@ -0,0 +1,95 @@
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hashlink::LruCache;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::config::PositiveCacheConfig;
+use crate::dedup::fnv1a;
+
+// Counted since start like the consensus counters; /stats/ has no handle on
+// the cache the scans share
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Lookups of `dns.positive_cache` since start, in /stats/
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct PositiveCacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that went to the resolvers; 0 while the cache is off
+    pub misses: u64,
+}
+
+pub fn stats() -> PositiveCacheStats {
+    PositiveCacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+    }
+}
+
+struct Entry {
+    // addresses of an address lookup; empty for "has records" answers
+    ips: Vec<IpAddr>,
+    expires: Instant,
+}
+
+/// Answers with records, kept for as long as their TTL says (at most
+/// `dns.positive_cache.max_ttl`) so loop passes, re-checks and verify rounds
+/// do not ask again; the least recently used go first once full.
+pub struct PositiveCache {
+    shards: Vec<Mutex<LruCache<String, Entry>>>,
+    max_ttl: Duration,
+}
+
+impl PositiveCache {
+    pub fn from_config(cfg: &PositiveCacheConfig) -> Option<Arc<Self>> {
+        if !cfg.enabled {
+            return None;
+        }
+        let shards = cfg.shards.max(1);
+        let per_shard = cfg.max_entries.div_ceil(shards).max(1);
+        info!("dns: positive cache of {} entries, ttl up to {:?}", cfg.max_entries, cfg.max_ttl);
+        Some(Arc::new(Self {
+            shards: (0..shards).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+            max_ttl: cfg.max_ttl,
+        }))
+    }
+
+    fn shard(&self, key: &str) -> &Mutex<LruCache<String, Entry>> {
+        &self.shards[(fnv1a(key.as_bytes()) % self.shards.len() as u64) as usize]
+    }
+
+    /// The cached answer under `key` while its TTL lasts.
+    pub fn get(&self, key: &str) -> Option<Vec<IpAddr>> {
+        let mut shard = self.shard(key).lock();
+        let hit = match shard.get(key) {
+            Some(e) if e.expires > Instant::now() => Some(e.ips.clone()),
+            Some(_) => {
+                shard.remove(key);
+                None
+            }
+            None => None,
+        };
+        match hit {
+            Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+            None => MISSES.fetch_add(1, Ordering::Relaxed),
+        };
+        hit
+    }
+
+    /// Keep an answer until `valid_until`, the end of its shortest TTL; an
+    /// answer with a TTL of 0 is not kept.
+    pub fn insert(&self, key: String, ips: Vec<IpAddr>, valid_until: Instant) {
+        let expires = valid_until.min(Instant::now() + self.max_ttl);
+        if expires <= Instant::now() {
+            return;
+        }
+        self.shard(&key).lock().insert(key, Entry { ips, expires });
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::ops::Deref;
+
+use serde::Serialize;
+
+use crate::generator::{is_valid_label, to_ace};
+
+/// A domain name in the one form the pipeline works with: lowercase, IDN
+/// labels in ACE ("xn--") form, no surrounding blanks or trailing dot.
+/// Names are normalized once where they come in (generator, sources, posted
+/// candidates, the API and the check command), so "Example.COM." and
+/// "example.com" are one candidate for dedup, the queue and the store.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Domain(String);
+
+impl Domain {
+    /// Normalize a fully qualified name; None when it is not a valid domain.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().trim_end_matches('.').to_lowercase();
+        let ace = to_ace(&name)?;
+        (ace.contains('.') && ace.len() <= 253 && ace.split('.').all(is_valid_label)).then_some(Self(ace))
+    }
+
+    /// A name this process normalized before and wrote down, e.g. in the disk queue.
+    pub fn from_normalized(name: String) -> Self {
+        Self(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Unicode form, the same as `as_str` unless it is an IDN.
+    pub fn unicode(&self) -> String {
+        idna::domain_to_unicode(&self.0).0
+    }
+}
+
+impl Deref for Domain {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Domain {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Domain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Domain> for String {
+    fn from(d: Domain) -> Self {
+        d.0
+    }
+}
+
+/// "com", ".COM" and "рф" name the same TLD (or parent zone): lowercase ACE
+/// form without the leading dot, as the store and the counters key it; None
+/// when empty.
+pub fn normalize_tld(tld: &str) -> Option<String> {
+    let t = tld.trim().trim_start_matches('.').to_lowercase();
+    if t.is_empty() {
+        return None;
+    }
+    Some(idna::domain_to_ascii(&t).unwrap_or(t))
+}
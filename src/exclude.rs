@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+use std::fs;
+
+use regex::RegexSet;
+use tracing::info;
+
+use crate::config::GeneratorConfig;
+
+/// Generated candidates to leave out: labels and full domains listed in
+/// `generator.exclude_file`, and labels matching `generator.exclude_regex`.
+#[derive(Default)]
+pub struct Exclusions {
+    labels: HashSet<String>,
+    domains: HashSet<String>,
+    patterns: Option<RegexSet>,
+}
+
+impl Exclusions {
+    /// Read the exclude file (path or URL) and compile the patterns; entries
+    /// with a dot are full domains, the others labels.
+    pub async fn load(gen: &GeneratorConfig) -> anyhow::Result<Self> {
+        let mut ex = Self::default();
+        let patterns: Vec<&str> = gen.exclude_regex.iter().map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+        if !patterns.is_empty() {
+            ex.patterns = Some(RegexSet::new(patterns)?);
+        }
+        let src = gen.exclude_file.trim();
+        if src.is_empty() {
+            return Ok(ex);
+        }
+        let body = if src.starts_with("http://") || src.starts_with("https://") {
+            reqwest::get(src).await?.error_for_status()?.text().await?
+        } else {
+            fs::read_to_string(src)?
+        };
+        for line in body.lines() {
+            let e = line.trim().trim_end_matches('.').to_lowercase();
+            if e.is_empty() || e.starts_with('#') {
+                continue;
+            }
+            // generated names are compared in Unicode form
+            let e = idna::domain_to_unicode(&e).0;
+            if e.contains('.') {
+                ex.domains.insert(e);
+            } else {
+                ex.labels.insert(e);
+            }
+        }
+        info!("exclude list {}: {} labels, {} domains", src, ex.labels.len(), ex.domains.len());
+        Ok(ex)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.domains.is_empty() && self.patterns.is_none()
+    }
+
+    /// The label is listed or matches one of the patterns.
+    pub fn skips_label(&self, label: &str) -> bool {
+        self.labels.contains(label) || self.patterns.as_ref().is_some_and(|p| p.is_match(label))
+    }
+
+    /// The full domain (Unicode form, without trailing dot) is listed.
+    pub fn skips_domain(&self, domain: &str) -> bool {
+        self.domains.contains(domain)
+    }
+}
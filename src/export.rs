@@ -0,0 +1,282 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tracing::info;
+
+use crate::config::Config;
+use crate::rdap::AvailabilityStores;
+use crate::store::{DomainStore, FoundDomain};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One domain per line
+    Txt,
+    /// Header row plus one row per domain with its metadata
+    Csv,
+    /// One JSON object per line with its metadata
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportKind {
+    Found,
+    Available,
+    Registered,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    #[arg(long, value_enum, default_value = "jsonl")]
+    pub format: ExportFormat,
+    /// Output file, "-" for stdout; a ".gz" name implies --gzip
+    #[arg(long, short, default_value = "-")]
+    pub output: String,
+    /// Gzip-compress the output
+    #[arg(long)]
+    pub gzip: bool,
+    /// Which stored set to export (available/registered come from the RDAP stage)
+    #[arg(long, value_enum, default_value = "found")]
+    pub kind: ExportKind,
+    /// Only this TLD; repeat for several
+    #[arg(long = "tld")]
+    pub tlds: Vec<String>,
+    /// Found at or after: unix seconds or YYYY-MM-DD (UTC)
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Found at or before: unix seconds or YYYY-MM-DD (UTC, whole day)
+    #[arg(long)]
+    pub until: Option<String>,
+    /// HTTP status: 200, 2xx or a range like 200-399
+    #[arg(long)]
+    pub status: Option<String>,
+}
+
+struct Filter {
+    since: u64,
+    until: u64,
+    status: Option<(u16, u16)>,
+}
+
+impl Filter {
+    fn from_args(args: &ExportArgs) -> anyhow::Result<Self> {
+        Ok(Self {
+            since: args
+                .since
+                .as_deref()
+                .map(|s| parse_time(s, false).context("--since"))
+                .transpose()?
+                .unwrap_or(0),
+            until: args
+                .until
+                .as_deref()
+                .map(|s| parse_time(s, true).context("--until"))
+                .transpose()?
+                .unwrap_or(u64::MAX),
+            status: args.status.as_deref().map(|s| parse_status(s).context("--status")).transpose()?,
+        })
+    }
+
+    // only names are needed when nothing filters on metadata
+    fn needs_metadata(&self) -> bool {
+        self.since > 0 || self.until < u64::MAX || self.status.is_some()
+    }
+
+    fn accepts(&self, rec: &FoundDomain) -> bool {
+        (self.since..=self.until).contains(&rec.found_at_unix)
+            && self.status.is_none_or(|(lo, hi)| (lo..=hi).contains(&rec.http_status))
+    }
+}
+
+/// `export` subcommand: write stored domains to a file or stdout.
+pub fn run_export(cfg: &Config, args: &ExportArgs) -> anyhow::Result<()> {
+    let filter = Filter::from_args(args)?;
+    let store = match args.kind {
+        ExportKind::Found => DomainStore::new(&cfg.storage)?,
+        ExportKind::Available => AvailabilityStores::open(&cfg.storage)?.available,
+        ExportKind::Registered => AvailabilityStores::open(&cfg.storage)?.registered,
+    };
+    let gzip = args.gzip || args.output.ends_with(".gz");
+    let out: Box<dyn Write> = if args.output == "-" {
+        Box::new(BufWriter::new(std::io::stdout().lock()))
+    } else {
+        let f = File::create(&args.output).with_context(|| format!("create {}", args.output))?;
+        Box::new(BufWriter::new(f))
+    };
+
+    let written = if gzip {
+        let mut enc = GzEncoder::new(out, Compression::default());
+        let n = write_records(&store, args, &filter, &mut enc)?;
+        enc.finish()?.flush()?;
+        n
+    } else {
+        let mut out = out;
+        let n = write_records(&store, args, &filter, &mut out)?;
+        out.flush()?;
+        n
+    };
+    info!(
+        "exported {} domains ({:?}, {:?}{}) to {}",
+        written,
+        args.kind,
+        args.format,
+        if gzip { ", gzip" } else { "" },
+        args.output
+    );
+    Ok(())
+}
+
+fn write_records(store: &DomainStore, args: &ExportArgs, filter: &Filter, out: &mut dyn Write) -> anyhow::Result<u64> {
+    let tlds: Vec<Option<&str>> = if args.tlds.is_empty() {
+        vec![None]
+    } else {
+        args.tlds.iter().map(|t| Some(t.as_str())).collect()
+    };
+    let mut written = 0u64;
+    let mut err: Option<anyhow::Error> = None;
+
+    // plain names can come from the name lists, which also cover domains stored without metadata
+    if args.format == ExportFormat::Txt && !filter.needs_metadata() {
+        for tld in tlds {
+            store.scan(tld, &mut |d| match writeln!(out, "{d}") {
+                Ok(()) => {
+                    written += 1;
+                    true
+                }
+                Err(e) => {
+                    err = Some(e.into());
+                    false
+                }
+            });
+        }
+        return err.map_or(Ok(written), Err);
+    }
+
+    let mut sink = match args.format {
+        ExportFormat::Txt => Sink::Names(out),
+        ExportFormat::Jsonl => Sink::Json(out),
+        ExportFormat::Csv => {
+            let mut w = csv::Writer::from_writer(out);
+            w.write_record(["domain", "tld", "ips", "http_status", "scheme", "final_url", "server", "response_ms", "found_at_unix", "title", "unicode", "record_types", "tag", "cert_issuer", "cert_not_after_unix", "cert_valid", "scan_id", "parked_by", "http_families", "country", "asn", "as_org", "schemes", "robots"])?;
+            Sink::Csv(Box::new(w))
+        }
+    };
+    for tld in tlds {
+        store.scan_detailed(tld, &mut |rec| {
+            if !filter.accepts(&rec) {
+                return true;
+            }
+            match sink.write(&rec) {
+                Ok(()) => {
+                    written += 1;
+                    true
+                }
+                Err(e) => {
+                    err = Some(e);
+                    false
+                }
+            }
+        });
+    }
+    if let Sink::Csv(w) = &mut sink {
+        w.flush()?;
+    }
+    err.map_or(Ok(written), Err)
+}
+
+enum Sink<'a> {
+    Names(&'a mut dyn Write),
+    Json(&'a mut dyn Write),
+    Csv(Box<csv::Writer<&'a mut dyn Write>>),
+}
+
+impl Sink<'_> {
+    fn write(&mut self, rec: &FoundDomain) -> anyhow::Result<()> {
+        match self {
+            Sink::Names(out) => writeln!(out, "{}", rec.domain)?,
+            Sink::Json(out) => {
+                serde_json::to_writer(&mut *out, rec)?;
+                writeln!(out)?;
+            }
+            Sink::Csv(w) => w.write_record([
+                rec.domain.as_str(),
+                rec.tld.as_str(),
+                &rec.ips.join(" "),
+                &rec.http_status.to_string(),
+                rec.scheme.as_str(),
+                rec.final_url.as_str(),
+                rec.server.as_str(),
+                &rec.response_ms.to_string(),
+                &rec.found_at_unix.to_string(),
+                rec.title.as_str(),
+                rec.unicode.as_str(),
+                &rec.record_types.join(" "),
+                rec.tag.as_str(),
+                rec.cert.as_ref().map_or("", |c| c.issuer.as_str()),
+                &rec.cert.as_ref().map_or(String::new(), |c| c.not_after_unix.to_string()),
+                &rec.cert.as_ref().map_or(String::new(), |c| c.valid.to_string()),
+                &rec.scan_id.to_string(),
+                rec.parked_by.as_str(),
+                &rec.http_families.join(" "),
+                rec.country.as_str(),
+                &Some(rec.asn).filter(|a| *a > 0).map_or(String::new(), |a| a.to_string()),
+                rec.as_org.as_str(),
+                &rec.schemes.join(" "),
+                rec.robots.as_str(),
+            ])?,
+        }
+        Ok(())
+    }
+}
+
+// unix seconds or YYYY-MM-DD; `end_of_day` makes a date inclusive as an upper bound
+fn parse_time(s: &str, end_of_day: bool) -> anyhow::Result<u64> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<u64>() {
+        return Ok(n);
+    }
+    let parts: Vec<&str> = s.split('-').collect();
+    let [y, m, d] = parts.as_slice() else {
+        anyhow::bail!("invalid time {s:?}: expected unix seconds or YYYY-MM-DD");
+    };
+    let num = |v: &str| v.parse::<i64>().with_context(|| format!("invalid date {s:?}"));
+    let (y, m, d) = (num(y)?, num(m)?, num(d)?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) || y < 1970 {
+        anyhow::bail!("invalid date {s:?}");
+    }
+    // days since 1970-01-01 (proleptic Gregorian)
+    let (y, m) = if m <= 2 { (y - 1, m + 9) } else { (y, m - 3) };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let start = days as u64 * 86_400;
+    Ok(if end_of_day { start + 86_399 } else { start })
+}
+
+// "200", "2xx" or "200-399"
+fn parse_status(s: &str) -> anyhow::Result<(u16, u16)> {
+    let s = s.trim().to_lowercase();
+    let num = |v: &str| {
+        v.trim()
+            .parse::<u16>()
+            .with_context(|| format!("invalid status {s:?}: expected 200, 2xx or 200-399"))
+    };
+    let range = if let Some(class) = s.strip_suffix("xx") {
+        let c = num(class)?;
+        (c * 100, c * 100 + 99)
+    } else if let Some((lo, hi)) = s.split_once('-') {
+        (num(lo)?, num(hi)?)
+    } else {
+        let n = num(&s)?;
+        (n, n)
+    };
+    if range.0 > range.1 {
+        anyhow::bail!("invalid status range {s:?}");
+    }
+    Ok(range)
+}
@@ -0,0 +1,1240 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::ops::RangeInclusive;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::GeneratorConfig;
+
+mod markov;
+
+pub const DEFAULT_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz0123456789-";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratorMode {
+    #[default]
+    Bruteforce,
+    Wordlist,
+    Mask,
+    /// Typo variants of `typos.seeds`
+    Typos,
+    /// Likeliest labels of an n-gram model trained on `markov.corpus`
+    Markov,
+}
+
+/// Which labels of the keyspace are generated (`generator.strategy`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationStrategy {
+    /// Every label, in order
+    #[default]
+    Exhaustive,
+    /// `sample.rate` of the labels of each length, picked and shuffled by
+    /// `sample.seed`; shortest length first
+    RandomSample,
+}
+
+/// How generated labels are combined with the TLDs (`generator.order`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationOrder {
+    /// Each label with every TLD before the next label
+    #[default]
+    Label,
+    /// Every label with the first TLD, then every label with the next one
+    TldMajor,
+    /// Labels of one length with each TLD in turn, shortest length first
+    LengthMajor,
+    /// `order_batch` labels with each TLD in turn, then the next batch
+    RoundRobin,
+    /// `order_batch` labels at a time, best `generator.scoring` score first,
+    /// each with every TLD
+    Priority,
+}
+
+impl GenerationOrder {
+    /// Whether `label`, the `n`th of the current block (0-based), starts a new
+    /// block; `block_len` is the label length of the block, once known.
+    pub fn ends_block(self, batch: usize, n: usize, block_len: Option<usize>, label: &str) -> bool {
+        match self {
+            GenerationOrder::Label => n >= 1,
+            GenerationOrder::TldMajor => false,
+            GenerationOrder::LengthMajor => block_len.is_some_and(|l| l != label.chars().count()),
+            GenerationOrder::RoundRobin | GenerationOrder::Priority => n >= batch.max(1),
+        }
+    }
+}
+
+// A strategy producing candidate labels (without TLD), in a stable order
+pub trait CandidateGenerator: Send {
+    fn next_label(&mut self) -> Option<String>;
+
+    /// Position the generator so the next label is `label` (used by resume).
+    /// Returns false when the label is not part of this generator's output.
+    fn seek(&mut self, _label: &str) -> bool {
+        false
+    }
+
+    /// Current position as an index vector that `restore` returns to without
+    /// replaying the keyspace; empty when the generator cannot report one.
+    fn checkpoint(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// Return to a position taken with `checkpoint`. Returns false when it does
+    /// not fit this generator (e.g. the settings changed since).
+    fn restore(&mut self, _index: &[usize]) -> bool {
+        false
+    }
+
+    /// Produce only part `index` of `count` of the labels (cluster shards),
+    /// walking just that range of the keyspace. Returns false when the
+    /// generator cannot split itself; the caller then filters its labels.
+    fn shard(&mut self, _index: u32, _count: u32) -> bool {
+        false
+    }
+}
+
+// Part `index` of `count` of an odometer over positions with these radixes,
+// as a range of values of its leading positions, so each length splits
+// evenly: the first index vector in it and the first past it (None: the end)
+fn shard_range(radix: &[usize], index: u32, count: u32) -> (Vec<usize>, Option<Vec<usize>>) {
+    let count = count.max(1) as u128;
+    // just enough leading positions to tell the parts apart
+    let (mut lead, mut values) = (0, 1u128);
+    while lead < radix.len() && values < count {
+        values *= radix[lead] as u128;
+        lead += 1;
+    }
+    let at = |mut v: u128| {
+        let mut idx = vec![0; radix.len()];
+        for i in (0..lead).rev() {
+            idx[i] = (v % radix[i] as u128) as usize;
+            v /= radix[i] as u128;
+        }
+        idx
+    };
+    let (lo, hi) = (values * index as u128 / count, values * (index as u128 + 1) / count);
+    (at(lo), (hi < values).then(|| at(hi)))
+}
+
+pub async fn build_generator(gen: &GeneratorConfig) -> anyhow::Result<Box<dyn CandidateGenerator>> {
+    if gen.strategy == GenerationStrategy::RandomSample {
+        return Ok(Box::new(Sample::build(gen).await?));
+    }
+    let base: Box<dyn CandidateGenerator> = match gen.mode {
+        GeneratorMode::Bruteforce => Box::new(Odometer::new(gen)),
+        GeneratorMode::Wordlist => {
+            let words = load_wordlists(gen).await?;
+            info!("wordlist generator: {} labels", words.len());
+            let list = Wordlist::new(words);
+            Box::new(if gen.order == GenerationOrder::LengthMajor { list.by_length() } else { list })
+        }
+        GeneratorMode::Mask => {
+            let masks = gen
+                .masks
+                .iter()
+                .map(|m| m.trim())
+                .filter(|m| !m.is_empty())
+                .map(|m| parse_mask(m, &gen.alphabet))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            info!("mask generator: {} masks", masks.len());
+            Box::new(MaskGen::new(masks))
+        }
+        GeneratorMode::Typos => {
+            let labels = typo_labels(gen);
+            info!("typo generator: {} labels from {} seeds", labels.len(), gen.typos.seeds.len());
+            let list = Wordlist::new(labels);
+            Box::new(if gen.order == GenerationOrder::LengthMajor { list.by_length() } else { list })
+        }
+        GeneratorMode::Markov => {
+            let model = markov::load_model(gen).await?;
+            info!("markov generator: up to {} labels, likeliest first", gen.markov.count);
+            // ranked labels are not grouped by length; shortest first needs the whole list
+            if gen.order == GenerationOrder::LengthMajor {
+                Box::new(Wordlist::new(markov::labels(&model, gen)).by_length())
+            } else {
+                Box::new(markov::Markov::new(&model, gen))
+            }
+        }
+    };
+    if gen.prefixes.is_empty() && gen.suffixes.is_empty() {
+        Ok(base)
+    } else {
+        Ok(Box::new(Affixed::new(base, &gen.prefixes, &gen.suffixes)))
+    }
+}
+
+/// Lengths (in characters) of the labels `gen` can produce, affixes
+/// included; resume keeps a cursor per length and TLD.
+pub fn label_lengths(gen: &GeneratorConfig) -> RangeInclusive<usize> {
+    let (lo, hi) = match gen.mode {
+        GeneratorMode::Mask => {
+            let lens: Vec<usize> = gen
+                .masks
+                .iter()
+                .filter_map(|m| parse_mask(m.trim(), &gen.alphabet).ok())
+                .map(|m| m.len())
+                .collect();
+            (lens.iter().copied().min().unwrap_or(1), lens.iter().copied().max().unwrap_or(0))
+        }
+        // omissions are one shorter, "m" -> "rn" one longer
+        GeneratorMode::Typos => {
+            let lens: Vec<usize> = gen.typos.seeds.iter().filter_map(|s| seed_label(s)).map(|s| s.chars().count()).collect();
+            let lo = lens.iter().copied().min().unwrap_or(2);
+            (lo.saturating_sub(1).max(1), lens.iter().copied().max().map_or(0, |n| n + 1))
+        }
+        _ => (gen.min_length.max(1) as usize, gen.max_length.max(0) as usize),
+    };
+    let affix = |v: &[String]| {
+        let lens = || v.iter().map(|a| a.chars().count());
+        (lens().min().unwrap_or(0), lens().max().unwrap_or(0))
+    };
+    let (pre, suf) = (affix(&gen.prefixes), affix(&gen.suffixes));
+    (lo + pre.0 + suf.0)..=(hi + pre.1 + suf.1).min(63)
+}
+
+// -------- brute-force odometer --------
+
+pub struct Odometer {
+    alpha: Vec<char>,
+    len: usize,
+    max_len: usize,
+    idx: Vec<usize>,
+    done: bool,
+    // cluster shard (index, count) and where its labels of `len` end
+    shard: Option<(u32, u32)>,
+    end: Option<Vec<usize>>,
+    allow_hyphen: bool,
+    forbid_leading_hyphen: bool,
+    forbid_trailing_hyphen: bool,
+    forbid_double_hyphen: bool,
+}
+
+impl Odometer {
+    pub fn new(gen: &GeneratorConfig) -> Self {
+        let alpha = if gen.alphabet.is_empty() {
+            DEFAULT_ALPHABET.to_string()
+        } else {
+            gen.alphabet.clone()
+        };
+        let len = gen.min_length.max(1) as usize;
+        let max_len = gen.max_length.max(0) as usize;
+        let alpha: Vec<char> = alpha.chars().collect();
+        Self {
+            done: alpha.is_empty() || len > max_len,
+            alpha,
+            len,
+            max_len,
+            idx: vec![0; len],
+            shard: None,
+            end: None,
+            allow_hyphen: gen.allow_hyphen,
+            forbid_leading_hyphen: gen.forbid_leading_hyphen,
+            forbid_trailing_hyphen: gen.forbid_trailing_hyphen,
+            forbid_double_hyphen: gen.forbid_double_hyphen,
+        }
+    }
+
+    // build label at the current position; Err(i) if hyphen rules reject
+    // its character at position i
+    fn current(&self) -> Result<String, usize> {
+        let ln = self.len;
+        let mut prev_hyphen = false;
+        let mut label = String::with_capacity(ln);
+        for i in 0..ln {
+            let r = self.alpha[self.idx[i]];
+            if r == '-' {
+                if !self.allow_hyphen
+                    || (self.forbid_leading_hyphen && i == 0)
+                    || (self.forbid_trailing_hyphen && i == ln - 1)
+                    || (self.forbid_double_hyphen && prev_hyphen)
+                {
+                    return Err(i);
+                }
+                prev_hyphen = true;
+            } else {
+                prev_hyphen = false;
+            }
+            label.push(r);
+        }
+        Ok(label)
+    }
+
+    // labels of `len` characters the hyphen rules let through
+    fn valid_count(&self, len: usize) -> u64 {
+        let dashes = self.alpha.iter().filter(|c| **c == '-').count() as u64;
+        let other = self.alpha.len() as u64 - dashes;
+        let dashes = if self.allow_hyphen { dashes } else { 0 };
+        // ending in another character / in a hyphen
+        let (mut a, mut b) = (other, if self.forbid_leading_hyphen { 0 } else { dashes });
+        for _ in 1..len {
+            (a, b) = ((a + b) * other, if self.forbid_double_hyphen { a } else { a + b } * dashes);
+        }
+        if self.forbid_trailing_hyphen {
+            b = 0;
+        }
+        a + b
+    }
+
+    // increment odometer, moving to the next length when exhausted
+    fn advance(&mut self) {
+        let mut carry = 1usize;
+        let mut i = self.len as isize - 1;
+        while i >= 0 && carry > 0 {
+            let ii = i as usize;
+            self.idx[ii] += carry;
+            if self.idx[ii] >= self.alpha.len() {
+                self.idx[ii] = 0;
+                carry = 1;
+            } else {
+                carry = 0;
+            }
+            i -= 1;
+        }
+        if carry > 0 || self.end.as_ref().is_some_and(|e| self.idx >= *e) {
+            self.next_length();
+        }
+    }
+
+    fn next_length(&mut self) {
+        self.len += 1;
+        if self.len > self.max_len {
+            self.done = true;
+        } else {
+            self.idx = vec![0; self.len];
+            self.settle();
+        }
+    }
+
+    // with a shard: into its range of the current length, or on to the next
+    // length once past it
+    fn settle(&mut self) {
+        let Some((index, count)) = self.shard.filter(|_| !self.done) else {
+            return;
+        };
+        let (start, end) = shard_range(&vec![self.alpha.len(); self.len], index, count);
+        if self.idx < start {
+            self.idx = start;
+        }
+        if end.as_ref().is_some_and(|e| self.idx >= *e) {
+            return self.next_length();
+        }
+        self.end = end;
+    }
+}
+
+impl CandidateGenerator for Odometer {
+    fn next_label(&mut self) -> Option<String> {
+        while !self.done {
+            match self.current() {
+                Ok(label) => {
+                    self.advance();
+                    return Some(label);
+                }
+                Err(i) => {
+                    // every label with this prefix is rejected too: skip them
+                    let last = self.alpha.len() - 1;
+                    self.idx[i + 1..].fill(last);
+                    self.advance();
+                }
+            }
+        }
+        None
+    }
+
+    fn seek(&mut self, label: &str) -> bool {
+        let idx: Option<Vec<usize>> = label
+            .chars()
+            .map(|c| self.alpha.iter().position(|a| *a == c))
+            .collect();
+        match idx {
+            Some(idx) if !idx.is_empty() && idx.len() <= self.max_len => {
+                self.len = idx.len();
+                self.idx = idx;
+                self.done = false;
+                self.settle();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // the index vector; its length is the label length
+    fn checkpoint(&self) -> Vec<usize> {
+        if self.done {
+            return Vec::new();
+        }
+        self.idx.clone()
+    }
+
+    fn restore(&mut self, index: &[usize]) -> bool {
+        if index.is_empty() || index.len() > self.max_len || index.iter().any(|i| *i >= self.alpha.len()) {
+            return false;
+        }
+        self.len = index.len();
+        self.idx = index.to_vec();
+        self.done = false;
+        self.settle();
+        true
+    }
+
+    fn shard(&mut self, index: u32, count: u32) -> bool {
+        self.shard = Some((index, count));
+        self.settle();
+        true
+    }
+}
+
+/// Number of `len`-character strings over the bruteforce alphabet; an error
+/// when it does not fit 64 bits, as `strategy: random_sample` numbers them.
+pub fn keyspace_size(gen: &GeneratorConfig, len: usize) -> anyhow::Result<u64> {
+    let alpha = if gen.alphabet.is_empty() { DEFAULT_ALPHABET } else { &gen.alphabet };
+    let n = alpha.chars().count() as u64;
+    u32::try_from(len)
+        .ok()
+        .and_then(|l| n.checked_pow(l))
+        .ok_or_else(|| anyhow::anyhow!("the {n}^{len} labels of length {len} are too many to sample, lower max_length"))
+}
+
+// -------- wordlist --------
+
+pub struct Wordlist {
+    // sorted, deduplicated
+    words: Vec<String>,
+    pos: usize,
+    // where a shard's range ends
+    end: usize,
+    // sorted by length first
+    by_length: bool,
+}
+
+impl Wordlist {
+    pub fn new(words: Vec<String>) -> Self {
+        Self {
+            end: words.len(),
+            words,
+            pos: 0,
+            by_length: false,
+        }
+    }
+
+    /// Shortest words first, alphabetical within a length.
+    pub fn by_length(mut self) -> Self {
+        self.words.sort_by_cached_key(|w| (w.chars().count(), w.clone()));
+        self.by_length = true;
+        self
+    }
+}
+
+impl CandidateGenerator for Wordlist {
+    fn next_label(&mut self) -> Option<String> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let w = self.words.get(self.pos)?.clone();
+        self.pos += 1;
+        Some(w)
+    }
+
+    fn seek(&mut self, label: &str) -> bool {
+        let found = if self.by_length {
+            let key = (label.chars().count(), label);
+            self.words.binary_search_by(|w| (w.chars().count(), w.as_str()).cmp(&key))
+        } else {
+            self.words.binary_search_by(|w| w.as_str().cmp(label))
+        };
+        match found {
+            Ok(i) => {
+                self.pos = i;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn checkpoint(&self) -> Vec<usize> {
+        vec![self.pos]
+    }
+
+    fn restore(&mut self, index: &[usize]) -> bool {
+        match index {
+            [pos] if *pos <= self.words.len() => {
+                self.pos = *pos;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn shard(&mut self, index: u32, count: u32) -> bool {
+        let (n, count) = (self.words.len() as u64, count.max(1) as u64);
+        self.pos = (n * index as u64 / count) as usize;
+        self.end = (n * (index as u64 + 1) / count) as usize;
+        true
+    }
+}
+
+// -------- masks --------
+
+/// Parse a mask into per-position character sets:
+///   ?l a-z, ?d 0-9, ?a a-z0-9, ?x the configured alphabet, ?? a literal '?';
+///   anything else is a literal character.
+pub fn parse_mask(mask: &str, alphabet: &str) -> anyhow::Result<Vec<Vec<char>>> {
+    let letters: Vec<char> = ('a'..='z').collect();
+    let digits: Vec<char> = ('0'..='9').collect();
+    let custom: Vec<char> = if alphabet.is_empty() { DEFAULT_ALPHABET } else { alphabet }.chars().collect();
+    let mut out = vec![];
+    let mut chars = mask.chars();
+    while let Some(c) = chars.next() {
+        let set = match c {
+            '?' => match chars.next() {
+                Some('l') => letters.clone(),
+                Some('d') => digits.clone(),
+                Some('a') => letters.iter().chain(&digits).copied().collect(),
+                Some('x') => custom.clone(),
+                Some('?') => vec!['?'],
+                other => anyhow::bail!("mask {mask:?}: unknown placeholder ?{}", other.map(String::from).unwrap_or_default()),
+            },
+            c if is_label_char(c) => vec![c],
+            c => anyhow::bail!("mask {mask:?}: character {c:?} is not allowed in a label"),
+        };
+        out.push(set);
+    }
+    if out.is_empty() || out.len() > 63 {
+        anyhow::bail!("mask {mask:?}: must expand to 1..63 characters");
+    }
+    Ok(out)
+}
+
+// Expands masks one after another, each as an odometer over its positions
+pub struct MaskGen {
+    masks: Vec<Vec<Vec<char>>>,
+    mask_i: usize,
+    idx: Vec<usize>,
+    // cluster shard (index, count) and where its labels of the mask end
+    shard: Option<(u32, u32)>,
+    end: Option<Vec<usize>>,
+}
+
+impl MaskGen {
+    pub fn new(masks: Vec<Vec<Vec<char>>>) -> Self {
+        let idx = vec![0; masks.first().map_or(0, Vec::len)];
+        Self { masks, mask_i: 0, idx, shard: None, end: None }
+    }
+
+    fn advance(&mut self) {
+        for i in (0..self.idx.len()).rev() {
+            self.idx[i] += 1;
+            if self.idx[i] < self.masks[self.mask_i][i].len() {
+                if self.end.as_ref().is_some_and(|e| self.idx >= *e) {
+                    self.next_mask();
+                }
+                return;
+            }
+            self.idx[i] = 0;
+        }
+        // wrapped around: next mask
+        self.next_mask();
+    }
+
+    fn next_mask(&mut self) {
+        self.mask_i += 1;
+        self.idx = vec![0; self.masks.get(self.mask_i).map_or(0, Vec::len)];
+        self.settle();
+    }
+
+    // with a shard: into its range of the current mask, or on to the next
+    // mask once past it
+    fn settle(&mut self) {
+        let (Some((index, count)), Some(mask)) = (self.shard, self.masks.get(self.mask_i)) else {
+            return;
+        };
+        let radix: Vec<usize> = mask.iter().map(Vec::len).collect();
+        let (start, end) = shard_range(&radix, index, count);
+        if self.idx < start {
+            self.idx = start;
+        }
+        if end.as_ref().is_some_and(|e| self.idx >= *e) {
+            return self.next_mask();
+        }
+        self.end = end;
+    }
+}
+
+impl CandidateGenerator for MaskGen {
+    fn next_label(&mut self) -> Option<String> {
+        while self.mask_i < self.masks.len() {
+            let mask = &self.masks[self.mask_i];
+            let label: String = self.idx.iter().enumerate().map(|(i, j)| mask[i][*j]).collect();
+            self.advance();
+            if is_valid_label(&label) {
+                return Some(label);
+            }
+        }
+        None
+    }
+
+    fn seek(&mut self, label: &str) -> bool {
+        let chars: Vec<char> = label.chars().collect();
+        for (mi, mask) in self.masks.iter().enumerate() {
+            if mask.len() != chars.len() {
+                continue;
+            }
+            let idx: Option<Vec<usize>> = mask
+                .iter()
+                .zip(&chars)
+                .map(|(set, c)| set.iter().position(|s| s == c))
+                .collect();
+            if let Some(idx) = idx {
+                self.mask_i = mi;
+                self.idx = idx;
+                self.settle();
+                return true;
+            }
+        }
+        false
+    }
+
+    // mask number, then the index vector within it
+    fn checkpoint(&self) -> Vec<usize> {
+        if self.mask_i >= self.masks.len() {
+            return Vec::new();
+        }
+        std::iter::once(self.mask_i).chain(self.idx.iter().copied()).collect()
+    }
+
+    fn restore(&mut self, index: &[usize]) -> bool {
+        let Some((&mi, idx)) = index.split_first() else {
+            return false;
+        };
+        let Some(mask) = self.masks.get(mi) else {
+            return false;
+        };
+        if mask.len() != idx.len() || mask.iter().zip(idx).any(|(set, i)| *i >= set.len()) {
+            return false;
+        }
+        self.mask_i = mi;
+        self.idx = idx.to_vec();
+        self.settle();
+        true
+    }
+
+    fn shard(&mut self, index: u32, count: u32) -> bool {
+        self.shard = Some((index, count));
+        self.settle();
+        true
+    }
+}
+
+// -------- typos --------
+
+/// Kinds of variants `mode: typos` derives from a seed label
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TypoKind {
+    /// One character left out: "exmple"
+    Omission,
+    /// Two neighbouring characters swapped: "exapmle"
+    Transposition,
+    /// One character replaced by a QWERTY neighbour: "ezample"
+    AdjacentKey,
+    /// Look-alike characters: "examp1e", "rn" for "m", Cyrillic "е" for "e"
+    Homoglyph,
+    /// One bit of one character flipped: "ewample"
+    Bitsquat,
+}
+
+impl TypoKind {
+    pub const ALL: [TypoKind; 5] = [
+        TypoKind::Omission,
+        TypoKind::Transposition,
+        TypoKind::AdjacentKey,
+        TypoKind::Homoglyph,
+        TypoKind::Bitsquat,
+    ];
+}
+
+const KEYBOARD: [&str; 4] = ["1234567890-", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+// single characters and the look-alikes they are swapped for
+const HOMOGLYPHS: &[(char, &str)] = &[
+    ('a', "а"),
+    ('b', "6"),
+    ('c', "с"),
+    ('e', "е3"),
+    ('g', "q9"),
+    ('i', "1lі"),
+    ('j', "ј"),
+    ('l', "1i"),
+    ('o', "0о"),
+    ('p', "р"),
+    ('q', "g"),
+    ('s', "5ѕ"),
+    ('u', "v"),
+    ('v', "u"),
+    ('x', "х"),
+    ('y', "у"),
+    ('z', "2"),
+    ('0', "o"),
+    ('1', "li"),
+];
+
+// look-alike character sequences, both ways
+const HOMOGLYPH_SEQS: &[(&str, &str)] = &[("m", "rn"), ("m", "nn"), ("w", "vv"), ("d", "cl")];
+
+/// The label a seed stands for: "example" for "Example.com".
+pub fn seed_label(seed: &str) -> Option<String> {
+    let label = seed.trim().trim_start_matches("www.").to_lowercase();
+    let label = label.split('.').next().unwrap_or_default().to_string();
+    is_valid_label(&label).then_some(label)
+}
+
+/// Variants of every seed in `generator.typos`, sorted and without the seeds
+/// themselves.
+pub fn typo_labels(gen: &GeneratorConfig) -> Vec<String> {
+    let seeds: BTreeSet<String> = gen.typos.seeds.iter().filter_map(|s| seed_label(s)).collect();
+    let mut out = BTreeSet::new();
+    for seed in &seeds {
+        out.extend(typo_variants(seed, &gen.typos.kinds));
+    }
+    out.retain(|l| is_valid_label(l) && !seeds.contains(l));
+    out.into_iter().collect()
+}
+
+fn typo_variants(seed: &str, kinds: &[TypoKind]) -> BTreeSet<String> {
+    let chars: Vec<char> = seed.chars().collect();
+    let with = |i: usize, len: usize, repl: &str| -> String {
+        chars[..i].iter().collect::<String>() + repl + &chars[i + len..].iter().collect::<String>()
+    };
+    let mut out = BTreeSet::new();
+    for kind in kinds {
+        match kind {
+            TypoKind::Omission => {
+                for i in 0..chars.len() {
+                    out.insert(with(i, 1, ""));
+                }
+            }
+            TypoKind::Transposition => {
+                for i in 1..chars.len() {
+                    if chars[i - 1] != chars[i] {
+                        out.insert(with(i - 1, 2, &format!("{}{}", chars[i], chars[i - 1])));
+                    }
+                }
+            }
+            TypoKind::AdjacentKey => {
+                for (i, c) in chars.iter().enumerate() {
+                    for n in keyboard_neighbours(*c) {
+                        out.insert(with(i, 1, &n.to_string()));
+                    }
+                }
+            }
+            TypoKind::Homoglyph => {
+                for (i, c) in chars.iter().enumerate() {
+                    for (_, alikes) in HOMOGLYPHS.iter().filter(|(h, _)| h == c) {
+                        for a in alikes.chars() {
+                            out.insert(with(i, 1, &a.to_string()));
+                        }
+                    }
+                }
+                for (a, b) in HOMOGLYPH_SEQS.iter().flat_map(|&(a, b)| [(a, b), (b, a)]) {
+                    for (i, _) in seed.match_indices(a) {
+                        let i = seed[..i].chars().count();
+                        out.insert(with(i, a.chars().count(), b));
+                    }
+                }
+            }
+            TypoKind::Bitsquat => {
+                for (i, c) in chars.iter().enumerate().filter(|(_, c)| c.is_ascii()) {
+                    for bit in 0..7 {
+                        let flipped = (*c as u8 ^ (1 << bit)) as char;
+                        if flipped.is_ascii_lowercase() || flipped.is_ascii_digit() || flipped == '-' {
+                            out.insert(with(i, 1, &flipped.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+// keys around `c`: left and right, and the two touching it in the rows above and below
+fn keyboard_neighbours(c: char) -> Vec<char> {
+    let Some((row, col)) = KEYBOARD.iter().enumerate().find_map(|(r, keys)| keys.find(c).map(|i| (r, i))) else {
+        return Vec::new();
+    };
+    let key = |r: usize, i: isize| -> Option<char> { KEYBOARD.get(r)?.chars().nth(usize::try_from(i).ok()?) };
+    let col = col as isize;
+    let mut out: Vec<char> = [key(row, col - 1), key(row, col + 1)].into_iter().flatten().collect();
+    if row > 0 {
+        out.extend([key(row - 1, col), key(row - 1, col + 1)].into_iter().flatten());
+    }
+    out.extend([key(row + 1, col - 1), key(row + 1, col)].into_iter().flatten());
+    out
+}
+
+// -------- prefixes / suffixes --------
+
+// Every label of the inner generator combined with each prefix and suffix
+pub struct Affixed {
+    inner: Box<dyn CandidateGenerator>,
+    prefixes: Vec<String>,
+    suffixes: Vec<String>,
+    core: Option<String>,
+    // inner position the current core label was taken from
+    core_at: Vec<usize>,
+    // position in prefixes x suffixes for the current core label
+    combo: usize,
+}
+
+impl Affixed {
+    pub fn new(inner: Box<dyn CandidateGenerator>, prefixes: &[String], suffixes: &[String]) -> Self {
+        let or_empty = |v: &[String]| if v.is_empty() { vec![String::new()] } else { v.to_vec() };
+        Self {
+            inner,
+            prefixes: or_empty(prefixes),
+            suffixes: or_empty(suffixes),
+            core: None,
+            core_at: Vec::new(),
+            combo: 0,
+        }
+    }
+}
+
+impl CandidateGenerator for Affixed {
+    fn next_label(&mut self) -> Option<String> {
+        let combos = self.prefixes.len() * self.suffixes.len();
+        loop {
+            if self.core.is_none() || self.combo >= combos {
+                self.core_at = self.inner.checkpoint();
+                self.core = Some(self.inner.next_label()?);
+                self.combo = 0;
+            }
+            let core = self.core.as_deref().unwrap_or_default();
+            let p = &self.prefixes[self.combo / self.suffixes.len()];
+            let s = &self.suffixes[self.combo % self.suffixes.len()];
+            self.combo += 1;
+            let label = format!("{p}{core}{s}");
+            if is_valid_label(&label) {
+                return Some(label);
+            }
+        }
+    }
+
+    fn seek(&mut self, label: &str) -> bool {
+        for (pi, p) in self.prefixes.iter().enumerate() {
+            for (si, s) in self.suffixes.iter().enumerate() {
+                let Some(core) = label.strip_prefix(p.as_str()).and_then(|l| l.strip_suffix(s.as_str())) else {
+                    continue;
+                };
+                if !core.is_empty() && self.inner.seek(core) {
+                    // consume the core label so the next call continues with this combination
+                    self.core_at = self.inner.checkpoint();
+                    self.core = self.inner.next_label();
+                    self.combo = pi * self.suffixes.len() + si;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // [combination, 1, core position...] mid-label, [0, 0, inner position...] before the first
+    fn checkpoint(&self) -> Vec<usize> {
+        let (head, inner) = match self.core {
+            Some(_) => ([self.combo, 1], self.core_at.clone()),
+            None => ([0, 0], self.inner.checkpoint()),
+        };
+        if inner.is_empty() {
+            return Vec::new();
+        }
+        head.into_iter().chain(inner).collect()
+    }
+
+    fn restore(&mut self, index: &[usize]) -> bool {
+        let [combo, has_core, inner @ ..] = index else {
+            return false;
+        };
+        if *combo > self.prefixes.len() * self.suffixes.len() || !self.inner.restore(inner) {
+            return false;
+        }
+        self.core = None;
+        self.combo = *combo;
+        if *has_core == 1 {
+            self.core_at = inner.to_vec();
+            self.core = self.inner.next_label();
+            return self.core.is_some();
+        }
+        true
+    }
+
+    // by core label, each with all its affixes
+    fn shard(&mut self, index: u32, count: u32) -> bool {
+        self.inner.shard(index, count)
+    }
+}
+
+// -------- random sample --------
+
+// Label lengths and how many valid labels of each the sample was drawn from,
+// for the live estimates of /stats/sample/
+static SAMPLED: Lazy<Mutex<Option<SampleSpace>>> = Lazy::new(Default::default);
+
+/// The keyspace of the last `strategy: random_sample` generator built.
+#[derive(Clone, Debug, Default)]
+pub struct SampleSpace {
+    pub rate: f64,
+    pub seed: u64,
+    /// Valid labels per length in characters
+    pub keyspace: BTreeMap<usize, u64>,
+}
+
+/// None unless a generator with `strategy: random_sample` was built.
+pub fn sample_space() -> Option<SampleSpace> {
+    SAMPLED.lock().clone()
+}
+
+// The labels of one length (one mask, for masks), numbered 0..size
+enum Group {
+    Strings(usize),
+    Mask(Vec<Vec<char>>),
+    Words(Vec<String>),
+}
+
+struct Part {
+    group: Group,
+    // label length in characters
+    len: usize,
+    // valid labels among the size numbers
+    valid: u64,
+    perm: Permutation,
+    // numbers taken: the first `take` of the permutation
+    take: u64,
+}
+
+// Takes `sample.rate` of the labels of every length in an order shuffled by
+// `sample.seed`. Each length is numbered and walked through a seeded
+// permutation, so a position is (length, step) and a label can be found
+// again without replaying the sample.
+pub struct Sample {
+    // hyphen rules and alphabet for Group::Strings
+    strings: Odometer,
+    parts: Vec<Part>,
+    part: usize,
+    step: u64,
+    // cluster shard (index, count): a range of the steps of every part
+    shard: Option<(u32, u32)>,
+}
+
+impl Sample {
+    async fn build(gen: &GeneratorConfig) -> anyhow::Result<Self> {
+        let strings = Odometer::new(gen);
+        let mut groups: Vec<(Group, usize, u64, u64)> = Vec::new();
+        match gen.mode {
+            GeneratorMode::Bruteforce => {
+                for len in gen.min_length.max(1) as usize..=gen.max_length.max(0) as usize {
+                    let size = keyspace_size(gen, len)?;
+                    groups.push((Group::Strings(len), len, size, strings.valid_count(len)));
+                }
+            }
+            GeneratorMode::Mask => {
+                for m in gen.masks.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+                    let mask = parse_mask(m, &gen.alphabet)?;
+                    let size = mask.iter().try_fold(1u64, |n, set| n.checked_mul(set.len() as u64));
+                    let Some(size) = size else {
+                        anyhow::bail!("mask {m:?}: too many labels to sample");
+                    };
+                    let last = mask.len() - 1;
+                    // is_valid_label: label characters, no hyphen at either end
+                    let valid = mask
+                        .iter()
+                        .enumerate()
+                        .map(|(i, set)| set.iter().filter(|c| is_label_char(**c) && !(**c == '-' && (i == 0 || i == last))).count() as u64)
+                        .product();
+                    groups.push((Group::Mask(mask), last + 1, size, valid));
+                }
+            }
+            GeneratorMode::Wordlist | GeneratorMode::Typos | GeneratorMode::Markov => {
+                let words = match gen.mode {
+                    GeneratorMode::Wordlist => load_wordlists(gen).await?,
+                    // the sample shuffles them anyway; found again by binary search
+                    GeneratorMode::Markov => {
+                        let mut labels = markov::labels(&markov::load_model(gen).await?, gen);
+                        labels.sort();
+                        labels
+                    }
+                    _ => typo_labels(gen),
+                };
+                let mut by_len: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+                for w in words {
+                    by_len.entry(w.chars().count()).or_default().push(w);
+                }
+                for (len, words) in by_len {
+                    let size = words.len() as u64;
+                    groups.push((Group::Words(words), len, size, size));
+                }
+            }
+        }
+        let mut keyspace = BTreeMap::new();
+        let parts: Vec<Part> = groups
+            .into_iter()
+            .enumerate()
+            .filter(|(_, g)| g.2 > 0)
+            .map(|(i, (group, len, size, valid))| {
+                *keyspace.entry(len).or_default() += valid;
+                let take = ((gen.sample.rate * size as f64).ceil() as u64).clamp(1, size);
+                Part { group, len, valid, perm: Permutation::new(size, gen.sample.seed, i as u64), take }
+            })
+            .collect();
+        let planned: f64 = parts.iter().map(|p| p.valid as f64 * gen.sample.rate).sum();
+        info!(
+            "random sample: {:.0} of {} labels (rate {}, seed {})",
+            planned,
+            keyspace.values().sum::<u64>(),
+            gen.sample.rate,
+            gen.sample.seed
+        );
+        *SAMPLED.lock() = Some(SampleSpace { rate: gen.sample.rate, seed: gen.sample.seed, keyspace });
+        Ok(Self { strings, parts, part: 0, step: 0, shard: None })
+    }
+
+    // the steps of `part` to take: all of them, or the shard's range
+    fn steps(&self, part: usize) -> (u64, u64) {
+        let take = self.parts[part].take as u128;
+        match self.shard {
+            Some((index, count)) => {
+                let count = count.max(1) as u128;
+                ((take * index as u128 / count) as u64, (take * (index as u128 + 1) / count) as u64)
+            }
+            None => (0, take as u64),
+        }
+    }
+
+    // the label numbered `n` in `part`; None when it is not a valid label
+    fn label(&mut self, part: usize, n: u64) -> Option<String> {
+        let p = &self.parts[part];
+        match &p.group {
+            Group::Strings(len) => {
+                let radix = self.strings.alpha.len() as u64;
+                let mut idx = vec![0; *len];
+                let mut rest = n;
+                for i in idx.iter_mut().rev() {
+                    *i = (rest % radix) as usize;
+                    rest /= radix;
+                }
+                self.strings.len = *len;
+                self.strings.idx = idx;
+                self.strings.current().ok()
+            }
+            Group::Mask(mask) => {
+                let mut rest = n;
+                let mut label: Vec<char> = Vec::with_capacity(mask.len());
+                for set in mask.iter().rev() {
+                    label.push(set[(rest % set.len() as u64) as usize]);
+                    rest /= set.len() as u64;
+                }
+                let label: String = label.into_iter().rev().collect();
+                is_valid_label(&label).then_some(label)
+            }
+            Group::Words(words) => words.get(n as usize).cloned(),
+        }
+    }
+
+    // the number of `label` in `part`, if it has one
+    fn number(&self, part: usize, label: &str) -> Option<u64> {
+        let p = &self.parts[part];
+        let chars: Vec<char> = label.chars().collect();
+        if chars.len() != p.len {
+            return None;
+        }
+        match &p.group {
+            Group::Strings(_) => {
+                let radix = self.strings.alpha.len() as u64;
+                chars.iter().try_fold(0u64, |n, c| Some(n * radix + self.strings.alpha.iter().position(|a| a == c)? as u64))
+            }
+            Group::Mask(mask) => mask
+                .iter()
+                .zip(&chars)
+                .try_fold(0u64, |n, (set, c)| Some(n * set.len() as u64 + set.iter().position(|s| s == c)? as u64)),
+            Group::Words(words) => words.binary_search_by(|w| w.as_str().cmp(label)).ok().map(|i| i as u64),
+        }
+    }
+}
+
+impl CandidateGenerator for Sample {
+    fn next_label(&mut self) -> Option<String> {
+        while self.part < self.parts.len() {
+            let (first, end) = self.steps(self.part);
+            self.step = self.step.max(first);
+            if self.step >= end {
+                self.part += 1;
+                self.step = 0;
+                continue;
+            }
+            let n = self.parts[self.part].perm.forward(self.step);
+            self.step += 1;
+            if let Some(label) = self.label(self.part, n) {
+                return Some(label);
+            }
+        }
+        None
+    }
+
+    fn seek(&mut self, label: &str) -> bool {
+        for part in 0..self.parts.len() {
+            let Some(n) = self.number(part, label) else {
+                continue;
+            };
+            let step = self.parts[part].perm.backward(n);
+            if step < self.parts[part].take {
+                self.part = part;
+                self.step = step;
+                return true;
+            }
+        }
+        false
+    }
+
+    // part, then the step within it
+    fn checkpoint(&self) -> Vec<usize> {
+        if self.part >= self.parts.len() {
+            return Vec::new();
+        }
+        vec![self.part, self.step as usize]
+    }
+
+    fn restore(&mut self, index: &[usize]) -> bool {
+        match index {
+            [part, step] if self.parts.get(*part).is_some_and(|p| *step as u64 <= p.take) => {
+                self.part = *part;
+                self.step = *step as u64;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn shard(&mut self, index: u32, count: u32) -> bool {
+        self.shard = Some((index, count));
+        true
+    }
+}
+
+// A seeded shuffle of 0..size: a Feistel network over the smallest even
+// power of two above size, walking the cycle until it lands back in range
+struct Permutation {
+    size: u64,
+    half: u32,
+    keys: [u64; 4],
+}
+
+impl Permutation {
+    fn new(size: u64, seed: u64, salt: u64) -> Self {
+        let bits = 64 - size.saturating_sub(1).leading_zeros();
+        let mut k = mix(seed ^ mix(salt));
+        let keys = std::array::from_fn(|_| {
+            k = mix(k);
+            k
+        });
+        Self { size, half: bits.div_ceil(2).max(1), keys }
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.half)
+    }
+
+    fn forward(&self, mut x: u64) -> u64 {
+        let m = self.mask();
+        loop {
+            let (mut l, mut r) = (x >> self.half, x & m);
+            for k in self.keys {
+                (l, r) = (r, l ^ (mix(r ^ k) & m));
+            }
+            x = (l << self.half) | r;
+            if x < self.size {
+                return x;
+            }
+        }
+    }
+
+    fn backward(&self, mut x: u64) -> u64 {
+        let m = self.mask();
+        loop {
+            let (mut l, mut r) = (x >> self.half, x & m);
+            for k in self.keys.iter().rev() {
+                (l, r) = (r ^ (mix(l ^ k) & m), l);
+            }
+            x = (l << self.half) | r;
+            if x < self.size {
+                return x;
+            }
+        }
+    }
+}
+
+// splitmix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Read all configured wordlists (files or URLs) into a sorted, deduplicated label set
+async fn load_wordlists(gen: &GeneratorConfig) -> anyhow::Result<Vec<String>> {
+    let mut uniq = BTreeSet::<String>::new();
+    for src in &gen.wordlists {
+        let src = src.trim();
+        if src.is_empty() {
+            continue;
+        }
+        info!("loading wordlist from {}", src);
+        let body = if src.starts_with("http://") || src.starts_with("https://") {
+            reqwest::get(src).await?.error_for_status()?.text().await?
+        } else {
+            fs::read_to_string(src)?
+        };
+        let before = uniq.len();
+        for line in body.lines() {
+            let w = line.trim().to_lowercase();
+            if w.is_empty() || w.starts_with('#') {
+                continue;
+            }
+            let n = w.chars().count() as i32;
+            if n < gen.min_length || n > gen.max_length || !is_valid_label(&w) {
+                continue;
+            }
+            uniq.insert(w);
+        }
+        info!("wordlist {}: {} new labels", src, uniq.len() - before);
+    }
+    Ok(uniq.into_iter().collect())
+}
+
+/// Lowercase letters (any script), digits and '-'; non-ASCII labels are
+/// punycode-encoded before lookup, which enforces the final length limit.
+pub fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.chars().count() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(is_label_char)
+}
+
+pub fn is_label_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || (!c.is_ascii() && c.is_alphanumeric() && !c.is_uppercase())
+}
+
+/// ACE ("xn--") form of a generated domain, used for DNS, HTTP and storage;
+/// None when the Unicode name is not a valid IDN.
+pub fn to_ace(domain: &str) -> Option<String> {
+    if domain.is_ascii() {
+        return Some(domain.to_string());
+    }
+    idna::domain_to_ascii_strict(domain).ok()
+}
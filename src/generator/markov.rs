@@ -0,0 +1,261 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::{is_valid_label, seed_label, CandidateGenerator};
+use crate::config::GeneratorConfig;
+
+// Pads the context at the start of a label and ends it; neither is a label character
+const START: char = '^';
+const END: char = '$';
+
+// Open prefixes kept by the search; past this the worse half is dropped,
+// which only costs labels far down the ranking
+const FRONTIER: usize = 1 << 20;
+
+/// Character n-gram counts, as trained from a corpus and saved to `markov.model`.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Model {
+    order: usize,
+    // context (the `order` characters before, START-padded) -> next character
+    // (END closes the label) -> times seen
+    counts: BTreeMap<String, BTreeMap<char, u64>>,
+}
+
+impl Model {
+    fn train<'a>(order: usize, labels: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut model = Self {
+            order,
+            ..Default::default()
+        };
+        for label in labels {
+            let chars: Vec<char> = std::iter::repeat_n(START, order).chain(label.chars()).chain([END]).collect();
+            for w in chars.windows(order + 1) {
+                let ctx: String = w[..order].iter().collect();
+                *model.counts.entry(ctx).or_default().entry(w[order]).or_default() += 1;
+            }
+        }
+        model
+    }
+
+    // next characters of each context with their log probability, likeliest first
+    fn transitions(&self) -> HashMap<String, Vec<(char, f64)>> {
+        self.counts
+            .iter()
+            .map(|(ctx, next)| {
+                let total: u64 = next.values().sum();
+                let mut t: Vec<(char, f64)> = next.iter().map(|(c, n)| (*c, (*n as f64 / total as f64).ln())).collect();
+                t.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                (ctx.clone(), t)
+            })
+            .collect()
+    }
+}
+
+/// The model of `generator.markov`: read from `model` when that file exists,
+/// else trained on `corpus` and written to `model` when set.
+pub async fn load_model(gen: &GeneratorConfig) -> anyhow::Result<Model> {
+    let cfg = &gen.markov;
+    let path = cfg.model.trim();
+    if !path.is_empty() && Path::new(path).exists() {
+        let model: Model = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("markov model {path}: {e}"))?;
+        info!("markov: model of order {} loaded from {} ({} contexts)", model.order, path, model.counts.len());
+        return Ok(model);
+    }
+    let mut labels = Vec::new();
+    for src in cfg.corpus.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        info!("loading markov corpus from {}", src);
+        let body = if src.starts_with("http://") || src.starts_with("https://") {
+            reqwest::get(src).await?.error_for_status()?.text().await?
+        } else {
+            fs::read_to_string(src)?
+        };
+        let before = labels.len();
+        labels.extend(
+            body.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .filter_map(seed_label),
+        );
+        info!("markov corpus {}: {} labels", src, labels.len() - before);
+    }
+    labels.sort();
+    labels.dedup();
+    if labels.is_empty() {
+        anyhow::bail!("markov: the corpus has no valid labels");
+    }
+    let model = Model::train(cfg.order, labels.iter().map(String::as_str));
+    info!("markov: trained an order {} model on {} labels ({} contexts)", cfg.order, labels.len(), model.counts.len());
+    if !path.is_empty() {
+        fs::write(path, serde_json::to_vec(&model)?)?;
+        info!("markov: model saved to {}", path);
+    }
+    Ok(model)
+}
+
+// A label being built, or a finished one (`done`), with its log probability
+struct Node {
+    logp: f64,
+    label: String,
+    done: bool,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+// likeliest first; ties go to the label first in alphabetical order, so
+// the output is the same on every run and resume can replay it
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.logp.total_cmp(&other.logp).then_with(|| other.label.cmp(&self.label))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Best-first search over the model: every step multiplies by a probability,
+// so finished labels come off the heap in order of likelihood
+pub struct Markov {
+    order: usize,
+    transitions: HashMap<String, Vec<(char, f64)>>,
+    min_len: usize,
+    max_len: usize,
+    count: usize,
+    heap: BinaryHeap<Node>,
+    emitted: usize,
+}
+
+impl Markov {
+    pub fn new(model: &Model, gen: &GeneratorConfig) -> Self {
+        let mut m = Self {
+            order: model.order,
+            transitions: model.transitions(),
+            min_len: gen.min_length.max(1) as usize,
+            max_len: gen.max_length.clamp(0, 63) as usize,
+            count: gen.markov.count,
+            heap: BinaryHeap::new(),
+            emitted: 0,
+        };
+        m.rewind();
+        m
+    }
+
+    fn rewind(&mut self) {
+        self.heap.clear();
+        self.heap.push(Node {
+            logp: 0.0,
+            label: String::new(),
+            done: false,
+        });
+        self.emitted = 0;
+    }
+
+    fn expand(&mut self, node: Node) {
+        let len = node.label.chars().count();
+        let pad = self.order.saturating_sub(len);
+        let ctx: String = std::iter::repeat_n(START, pad)
+            .chain(node.label.chars().skip(len.saturating_sub(self.order)))
+            .collect();
+        let Some(next) = self.transitions.get(&ctx) else { return };
+        for &(c, lp) in next {
+            if c == END {
+                if len >= self.min_len {
+                    self.heap.push(Node {
+                        logp: node.logp + lp,
+                        label: node.label.clone(),
+                        done: true,
+                    });
+                }
+            } else if len < self.max_len {
+                let mut label = node.label.clone();
+                label.push(c);
+                self.heap.push(Node {
+                    logp: node.logp + lp,
+                    label,
+                    done: false,
+                });
+            }
+        }
+        if self.heap.len() > FRONTIER {
+            let mut nodes = std::mem::take(&mut self.heap).into_sorted_vec();
+            nodes.drain(..nodes.len() - FRONTIER / 2);
+            self.heap = nodes.into();
+        }
+    }
+
+    // the next finished label with its probability
+    fn next_node(&mut self) -> Option<Node> {
+        while self.emitted < self.count {
+            let node = self.heap.pop()?;
+            if !node.done {
+                self.expand(node);
+                continue;
+            }
+            if is_valid_label(&node.label) {
+                self.emitted += 1;
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+impl CandidateGenerator for Markov {
+    fn next_label(&mut self) -> Option<String> {
+        self.next_node().map(|n| n.label)
+    }
+
+    // replays the ranking up to the label: the search has no cheaper
+    // position to return to
+    fn seek(&mut self, label: &str) -> bool {
+        self.rewind();
+        while let Some(node) = self.next_node() {
+            if node.label == label {
+                // still the likeliest, so it comes off the heap next
+                self.heap.push(node);
+                self.emitted -= 1;
+                return true;
+            }
+        }
+        self.rewind();
+        false
+    }
+
+    fn checkpoint(&self) -> Vec<usize> {
+        vec![self.emitted]
+    }
+
+    fn restore(&mut self, index: &[usize]) -> bool {
+        let [n] = index else { return false };
+        if *n > self.count {
+            return false;
+        }
+        self.rewind();
+        while self.emitted < *n {
+            if self.next_label().is_none() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The first `markov.count` labels of the model, likeliest first.
+pub fn labels(model: &Model, gen: &GeneratorConfig) -> Vec<String> {
+    let mut m = Markov::new(model, gen);
+    std::iter::from_fn(|| m.next_label()).collect()
+}
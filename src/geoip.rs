@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tracing::info;
+
+use crate::config::GeoIpConfig;
+
+type Db = Arc<Reader<Vec<u8>>>;
+
+// Open databases by path and modification time: every scan builds its own
+// pipeline, and a database replaced on disk is read again by the next one
+static OPEN: Lazy<Mutex<HashMap<String, (SystemTime, Db)>>> = Lazy::new(Default::default);
+
+/// Country and network of an address, from the `geoip` databases
+#[derive(Clone, Debug, Default)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    /// Name of the organization behind `asn`
+    pub as_org: Option<String>,
+}
+
+/// MaxMind-compatible databases (`.mmdb`) of the `geoip` check stage: a
+/// Country or City database for the country, an ASN database for the network.
+pub struct GeoIp {
+    country: Option<Db>,
+    asn: Option<Db>,
+}
+
+impl GeoIp {
+    pub fn open(cfg: &GeoIpConfig) -> anyhow::Result<Self> {
+        let open = |path: &str| -> anyhow::Result<Option<Db>> {
+            let path = path.trim();
+            if path.is_empty() {
+                return Ok(None);
+            }
+            open_cached(path).map(Some)
+        };
+        Ok(Self {
+            country: open(&cfg.country_db)?,
+            asn: open(&cfg.asn_db)?,
+        })
+    }
+
+    /// What the databases know about the first of `ips` they have data for;
+    /// country and network may come from different addresses.
+    pub fn lookup(&self, ips: &[IpAddr]) -> GeoInfo {
+        let mut info = GeoInfo::default();
+        if let Some(db) = &self.country {
+            info.country = ips.iter().find_map(|ip| {
+                let c: geoip2::Country = db.lookup(*ip).ok()?;
+                // the country the network is registered in when the location is unknown
+                let code = c.country.and_then(|c| c.iso_code).or(c.registered_country.and_then(|c| c.iso_code))?;
+                Some(code.to_string())
+            });
+        }
+        if let Some(db) = &self.asn {
+            if let Some(a) = ips.iter().find_map(|ip| db.lookup::<geoip2::Asn>(*ip).ok().filter(|a| a.autonomous_system_number.is_some())) {
+                info.asn = a.autonomous_system_number;
+                info.as_org = a.autonomous_system_organization.map(str::to_string);
+            }
+        }
+        info
+    }
+}
+
+fn open_cached(path: &str) -> anyhow::Result<Db> {
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| anyhow::anyhow!("geoip: {path}: {e}"))?;
+    let mut open = OPEN.lock();
+    if let Some((at, db)) = open.get(path) {
+        if *at == modified {
+            return Ok(db.clone());
+        }
+    }
+    let db = Arc::new(Reader::open_readfile(Path::new(path)).map_err(|e: MaxMindDBError| anyhow::anyhow!("geoip: {path}: {e}"))?);
+    info!("geoip: opened {} ({}, built {})", path, db.metadata.database_type, db.metadata.build_epoch);
+    open.insert(path.to_string(), (modified, db.clone()));
+    Ok(db)
+}
@@ -0,0 +1,269 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::Stream;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+use tracing::{debug, info, warn};
+
+use crate::auth::{self, AuthRole, Denied};
+use crate::config::LiveConfig;
+use crate::domain::normalize_tld;
+use crate::latency::Stage;
+use crate::notify::Notifier;
+use crate::progress::Progress;
+use crate::scans::ScanManager;
+use crate::service::{ScanControl, ScanState};
+use crate::store::{self, DomainStore, ListQuery};
+
+mod pb {
+    tonic::include_proto!("domain_searcher.v1");
+}
+
+use pb::control_scan_request::Action;
+use pb::domain_searcher_server::{DomainSearcher, DomainSearcherServer};
+
+// ListDomains page size when the request leaves limit at 0, and the most it may ask for
+const DEFAULT_LIMIT: usize = 10_000;
+const MAX_LIMIT: usize = 100_000;
+
+// The gRPC API: same progress, storage and controls as the REST handlers
+pub struct GrpcApi {
+    pub live: LiveConfig,
+    pub prog: Progress,
+    pub store: DomainStore,
+    pub control: ScanControl,
+    pub notifier: Notifier,
+    pub scans: Arc<ScanManager>,
+}
+
+impl GrpcApi {
+    /// Serve on `addr` until the task is dropped.
+    pub async fn serve(self, addr: SocketAddr) -> anyhow::Result<()> {
+        info!("grpc listening on {}", addr);
+        tonic::transport::Server::builder()
+            .add_service(DomainSearcherServer::new(self))
+            .serve(addr)
+            .await?;
+        Ok(())
+    }
+
+    // same keys and roles as the REST API
+    fn authorize(&self, md: &MetadataMap, need: AuthRole) -> Result<(), Denied> {
+        auth::authorize(&self.live.current().auth, presented_key(md), need).map(|_| ())
+    }
+}
+
+impl From<Denied> for Status {
+    fn from(d: Denied) -> Self {
+        match d {
+            Denied::ReadOnly => Status::permission_denied(d.message()),
+            _ => Status::unauthenticated(d.message()),
+        }
+    }
+}
+
+// "authorization: Bearer <key>" or "x-api-key: <key>"
+fn presented_key(md: &MetadataMap) -> Option<&str> {
+    if let Some(v) = md.get("authorization").and_then(|v| v.to_str().ok()) {
+        if let Some(k) = v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")) {
+            return Some(k.trim());
+        }
+    }
+    md.get("x-api-key").and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+impl From<store::FoundDomain> for pb::FoundDomain {
+    fn from(r: store::FoundDomain) -> Self {
+        Self {
+            domain: r.domain,
+            tld: r.tld,
+            ips: r.ips,
+            http_status: r.http_status as u32,
+            scheme: r.scheme,
+            final_url: r.final_url,
+            server: r.server,
+            response_ms: r.response_ms,
+            found_at_unix: r.found_at_unix,
+            title: r.title,
+            unicode: r.unicode,
+            record_types: r.record_types,
+            tag: r.tag,
+            cert: r.cert.map(|c| pb::CertInfo {
+                subject: c.subject,
+                issuer: c.issuer,
+                sans: c.sans,
+                not_before_unix: c.not_before_unix,
+                not_after_unix: c.not_after_unix,
+                self_signed: c.self_signed,
+                valid: c.valid,
+                error: c.error,
+            }),
+            scan_id: r.scan_id,
+            parked_by: r.parked_by,
+            http_families: r.http_families,
+            country: r.country,
+            asn: r.asn,
+            as_org: r.as_org,
+            schemes: r.schemes,
+            robots: r.robots,
+        }
+    }
+}
+
+type FoundStream = Pin<Box<dyn Stream<Item = Result<pb::FoundDomain, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl DomainSearcher for GrpcApi {
+    async fn get_stats(&self, req: Request<pb::GetStatsRequest>) -> Result<Response<pb::Stats>, Status> {
+        self.authorize(req.metadata(), AuthRole::Read)?;
+        let (enq, chk, fnd, elapsed) = self.prog.snapshot();
+        let (available, registered) = self.prog.availability();
+        let (retry_pending, retried, failed) = self.prog.retries();
+        let est = self.prog.estimate();
+        let (queued, queue_full) = self.prog.queue();
+        Ok(Response::new(pb::Stats {
+            state: self.control.state().as_str().to_string(),
+            elapsed_secs: elapsed.as_secs(),
+            eta_secs: if est.remaining >= 0 { est.eta.as_secs() as i64 } else { -1 },
+            generated: enq,
+            checked: chk,
+            found: fnd,
+            available,
+            registered,
+            duplicates_skipped: self.prog.duplicates(),
+            wildcard_skipped: self.prog.wildcard(),
+            retry_pending,
+            retried,
+            failed_permanent: failed,
+            total_planned: self.prog.total_planned(),
+            remaining: est.remaining,
+            percent: est.percent,
+            speed_per_sec: est.speed_per_sec,
+            efficiency_percent: est.efficiency_percent,
+            domains_memory_bytes: self.store.approx_bytes(),
+            errors: self.prog.errors().into_iter().map(|(k, n)| (k.to_string(), n)).collect(),
+            queue_depth: [("candidates", queued), ("store", self.store.writer_status().0 as i64)]
+                .into_iter()
+                .map(|(k, n)| (k.to_string(), n))
+                .collect(),
+            backpressure_events: [("candidates", queue_full), ("store", self.store.backpressure_events() as i64)]
+                .into_iter()
+                .map(|(k, n)| (k.to_string(), n))
+                .collect(),
+            latency: Stage::ALL
+                .iter()
+                .map(|&st| {
+                    let l = self.prog.latency().stage(st).summary();
+                    let msg = pb::Latency {
+                        count: l.count,
+                        unanswered: l.unanswered,
+                        availability_percent: l.availability_percent,
+                        mean_ms: l.mean_ms,
+                        p50_ms: l.p50_ms,
+                        p95_ms: l.p95_ms,
+                        p99_ms: l.p99_ms,
+                        max_ms: l.max_ms,
+                    };
+                    (st.as_str().to_string(), msg)
+                })
+                .collect(),
+        }))
+    }
+
+    type StreamFoundDomainsStream = FoundStream;
+
+    async fn stream_found_domains(
+        &self,
+        req: Request<pb::StreamFoundDomainsRequest>,
+    ) -> Result<Response<FoundStream>, Status> {
+        self.authorize(req.metadata(), AuthRole::Read)?;
+        let tld = normalize_tld(&req.get_ref().tld);
+        debug!("grpc: found-domain stream opened, tld={:?}", tld);
+        let rx = self.notifier.subscribe();
+        let stream = futures_util::stream::unfold((rx, tld), |(mut rx, tld)| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(rec) if tld.as_ref().is_none_or(|t| *t == rec.tld) => {
+                        return Some((Ok(rec.into()), (rx, tld)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(n)) => warn!("grpc: found-domain stream fell behind, {} domains skipped", n),
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn list_domains(&self, req: Request<pb::ListDomainsRequest>) -> Result<Response<pb::ListDomainsResponse>, Status> {
+        self.authorize(req.metadata(), AuthRole::Read)?;
+        let r = req.into_inner();
+        let limit = match r.limit as usize {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        };
+        let lq = ListQuery {
+            offset: r.offset as usize,
+            limit: Some(limit),
+            prefix: r.prefix,
+            contains: r.contains,
+            pattern: None,
+            ..Default::default()
+        };
+        let tld = normalize_tld(&r.tld);
+        let store = self.store.clone();
+        let resp = tokio::task::spawn_blocking(move || {
+            let t = tld.as_deref();
+            let mut resp = pb::ListDomainsResponse {
+                total: store.count(t, &lq) as u64,
+                ..Default::default()
+            };
+            if r.detailed {
+                store.scan_window_detailed(t, &lq, &mut |rec| {
+                    resp.records.push(rec.into());
+                    true
+                });
+            } else {
+                store.scan_window(t, &lq, &mut |d| {
+                    resp.domains.push(d.to_string());
+                    true
+                });
+            }
+            resp
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(resp))
+    }
+
+    async fn control_scan(&self, req: Request<pb::ControlScanRequest>) -> Result<Response<pb::ControlScanResponse>, Status> {
+        self.authorize(req.metadata(), AuthRole::Write)?;
+        let r = req.into_inner();
+        let action = Action::try_from(r.action).unwrap_or(Action::Unspecified);
+        let control = match r.scan_id {
+            0 => self.control.clone(),
+            id => self
+                .scans
+                .control(id)
+                .ok_or_else(|| Status::not_found(format!("no scan with id {id}")))?,
+        };
+        info!("grpc: control requested: {:?} scan={}", action, r.scan_id);
+        let before = control.state();
+        let after = match action {
+            Action::Pause => control.pause(),
+            Action::Resume => control.resume(),
+            Action::Stop => control.stop(),
+            Action::Unspecified => return Err(Status::invalid_argument("action must be PAUSE, RESUME or STOP")),
+        };
+        // pause/resume on a stopped scan cannot take effect
+        if before == ScanState::Stopped && action != Action::Stop {
+            return Err(Status::failed_precondition("scan is stopped"));
+        }
+        Ok(Response::new(pb::ControlScanResponse {
+            state: after.as_str().to_string(),
+        }))
+    }
+}
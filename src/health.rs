@@ -0,0 +1,205 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::time;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::dns::ResolverPool;
+use crate::service::ScanControl;
+use crate::store::DomainStore;
+
+// how often the resolver is probed
+const RESOLVER_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+// the store writer wakes every 2s, so an older flush means it is stuck or failing
+const WRITER_STALL_SECS: u64 = 30;
+// liveness gives a slow storage backend much longer before the pod is restarted
+const WRITER_DEAD_SECS: u64 = 300;
+
+// Pipeline liveness reported by /healthz and /readyz; the service updates it,
+// the handlers only read it
+#[derive(Clone, Default)]
+pub struct Health {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    // dedup seeding and resume are done, candidates flow
+    started: AtomicBool,
+    generating: AtomicBool,
+    busy: AtomicUsize,
+    workers: AtomicUsize,
+    resolver: Mutex<ResolverProbe>,
+}
+
+#[derive(Clone, Default)]
+struct ResolverProbe {
+    enabled: bool,
+    checked_unix: u64,
+    error: Option<String>,
+}
+
+/// Pipeline diagnostics of GET /healthz and /readyz
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct HealthReport {
+    /// "ok", or "unavailable" with the reasons in `problems`
+    pub status: &'static str,
+    pub problems: Vec<String>,
+    /// The service finished startup (dedup seeding, resume) and entered its main loop
+    pub started: bool,
+    /// running, paused or stopped
+    pub scan_state: &'static str,
+    /// A generator (or ct/zone source) pass is producing candidates
+    pub generator_running: bool,
+    /// Checks in flight
+    pub workers_busy: usize,
+    /// limits.concurrency
+    pub workers_total: usize,
+    pub worker_utilization_percent: f64,
+    /// Records waiting for the store writer
+    pub store_backlog: usize,
+    /// Last time the store writer flushed (or found nothing to flush), unix seconds
+    pub last_flush_unix: u64,
+    pub last_flush_age_secs: u64,
+    /// The last probe of the configured DNS servers got an answer
+    pub resolver_reachable: bool,
+    /// 0 until the first probe; no probes on a cluster coordinator
+    pub resolver_checked_unix: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolver_error: Option<String>,
+}
+
+/// Marks a check in flight until dropped.
+pub struct Busy(Arc<Inner>);
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        self.0.busy.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Marks a generator pass as running until dropped.
+pub struct Generating(Arc<Inner>);
+
+impl Drop for Generating {
+    fn drop(&mut self) {
+        self.0.generating.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Health {
+    pub fn set_started(&self) {
+        self.inner.started.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_workers(&self, n: usize) {
+        self.inner.workers.store(n, Ordering::Relaxed);
+    }
+
+    pub fn busy(&self) -> Busy {
+        self.inner.busy.fetch_add(1, Ordering::Relaxed);
+        Busy(self.inner.clone())
+    }
+
+    pub fn generating(&self) -> Generating {
+        self.inner.generating.store(true, Ordering::Relaxed);
+        Generating(self.inner.clone())
+    }
+
+    /// Probe the configured DNS servers in the background (an SOA query for the root zone).
+    pub fn spawn_resolver_probe(&self, cfg: &Config) {
+        let resolver = match ResolverPool::from_config(&cfg.dns) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("health: no resolver probe: {e}");
+                return;
+            }
+        };
+        self.inner.resolver.lock().enabled = true;
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let mut ticker = time::interval(RESOLVER_PROBE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                // NXDOMAIN or NODATA still prove the server answers
+                let error = resolver.name_exists(".").await.err().map(|e| e.to_string());
+                if let Some(e) = &error {
+                    warn!("health: resolver probe failed: {}", e);
+                }
+                let mut r = inner.resolver.lock();
+                r.checked_unix = now_unix();
+                r.error = error;
+            }
+        });
+    }
+
+    /// Liveness: only a store writer stuck for minutes fails it.
+    pub fn liveness(&self, store: &DomainStore, control: &ScanControl) -> HealthReport {
+        let mut report = self.report(store, control);
+        if report.last_flush_age_secs > WRITER_DEAD_SECS {
+            report.problems.push(format!("store writer has not flushed for {}s", report.last_flush_age_secs));
+        }
+        report.finish()
+    }
+
+    /// Readiness: started, the resolver answers and the store writer keeps up.
+    pub fn readiness(&self, store: &DomainStore, control: &ScanControl) -> HealthReport {
+        let mut report = self.report(store, control);
+        if !report.started {
+            report.problems.push("service is starting".to_string());
+        }
+        if !report.resolver_reachable {
+            let why = report.resolver_error.as_deref().unwrap_or("not probed yet");
+            report.problems.push(format!("resolver unreachable: {why}"));
+        }
+        if report.last_flush_age_secs > WRITER_STALL_SECS {
+            report.problems.push(format!("store writer has not flushed for {}s", report.last_flush_age_secs));
+        }
+        report.finish()
+    }
+
+    fn report(&self, store: &DomainStore, control: &ScanControl) -> HealthReport {
+        let busy = self.inner.busy.load(Ordering::Relaxed);
+        let total = self.inner.workers.load(Ordering::Relaxed);
+        let (backlog, last_flush) = store.writer_status();
+        let resolver = self.inner.resolver.lock().clone();
+        HealthReport {
+            status: "ok",
+            problems: Vec::new(),
+            started: self.inner.started.load(Ordering::Relaxed),
+            scan_state: control.state().as_str(),
+            generator_running: self.inner.generating.load(Ordering::Relaxed),
+            workers_busy: busy,
+            workers_total: total,
+            worker_utilization_percent: if total == 0 { 0.0 } else { (busy as f64 * 1000.0 / total as f64).round() / 10.0 },
+            store_backlog: backlog,
+            last_flush_unix: last_flush,
+            last_flush_age_secs: now_unix().saturating_sub(last_flush),
+            resolver_reachable: !resolver.enabled || (resolver.checked_unix > 0 && resolver.error.is_none()),
+            resolver_checked_unix: resolver.checked_unix,
+            resolver_error: resolver.error,
+        }
+    }
+}
+
+impl HealthReport {
+    fn finish(mut self) -> Self {
+        if !self.problems.is_empty() {
+            self.status = "unavailable";
+        }
+        self
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn now_unix() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,240 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::config::LiveConfig;
+use crate::progress::Progress;
+
+/// Counters of the main scan over one `stats_history.interval`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StatsPoint {
+    /// End of the interval, unix seconds
+    pub unix: u64,
+    /// Length of the interval in seconds
+    pub seconds: f64,
+    /// Candidates checked in the interval
+    pub checked: i64,
+    /// Domains found in the interval
+    pub found: i64,
+    /// Failed check attempts in the interval, re-checks included
+    pub errors: i64,
+    /// The same by cause; causes without failures are left out
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors_by_cause: BTreeMap<String, i64>,
+    pub speed_per_sec: f64,
+    pub found_per_hour: f64,
+    pub errors_per_sec: f64,
+    /// Totals since the scan started (restored on resume)
+    pub checked_total: i64,
+    pub found_total: i64,
+}
+
+impl StatsPoint {
+    fn rates(mut self) -> Self {
+        if self.seconds > 0.0 {
+            self.speed_per_sec = self.checked as f64 / self.seconds;
+            self.found_per_hour = self.found as f64 * 3600.0 / self.seconds;
+            self.errors_per_sec = self.errors as f64 / self.seconds;
+        }
+        self
+    }
+
+    // the next interval added to this one
+    fn merge(&mut self, p: &StatsPoint) {
+        self.unix = p.unix;
+        self.seconds += p.seconds;
+        self.checked += p.checked;
+        self.found += p.found;
+        self.errors += p.errors;
+        for (k, n) in &p.errors_by_cause {
+            *self.errors_by_cause.entry(k.clone()).or_default() += n;
+        }
+        self.checked_total = p.checked_total;
+        self.found_total = p.found_total;
+    }
+}
+
+// counters at the start of the current interval
+struct Mark {
+    at: Instant,
+    checked: i64,
+    found: i64,
+    errors: BTreeMap<&'static str, i64>,
+}
+
+impl Mark {
+    fn of(prog: &Progress) -> Self {
+        let (_, checked, found, _) = prog.snapshot();
+        Self {
+            at: Instant::now(),
+            checked,
+            found,
+            errors: prog.errors().into_iter().collect(),
+        }
+    }
+}
+
+/// Time series of the main scan's speed, finds and errors (`stats_history`),
+/// appended to `<storage.dir>/stats_history.jsonl` so it survives restarts.
+#[derive(Clone)]
+pub struct StatsHistory {
+    config: LiveConfig,
+    prog: Progress,
+    path: PathBuf,
+    points: Arc<Mutex<VecDeque<StatsPoint>>>,
+}
+
+impl StatsHistory {
+    /// Where the snapshots are kept, for --reset.
+    pub fn file_path(storage_dir: &str) -> PathBuf {
+        Path::new(storage_dir).join("stats_history.jsonl")
+    }
+
+    /// Load the snapshots kept by earlier runs, within the retention.
+    pub fn open(config: LiveConfig, prog: Progress) -> Self {
+        let cfg = config.current();
+        let path = Self::file_path(&cfg.storage.dir);
+        let since = now_unix().saturating_sub(cfg.stats_history.retention.as_secs());
+        let mut points = VecDeque::new();
+        if let Ok(f) = File::open(&path) {
+            for line in BufReader::new(f).lines().map_while(Result::ok) {
+                // a torn last line after a crash is skipped
+                match serde_json::from_str::<StatsPoint>(&line) {
+                    Ok(p) if p.unix >= since => points.push_back(p),
+                    _ => {}
+                }
+            }
+            info!("stats history: loaded {} snapshots from {}", points.len(), path.display());
+        }
+        Self {
+            config,
+            prog,
+            path,
+            points: Arc::new(Mutex::new(points)),
+        }
+    }
+
+    /// Take a snapshot every `stats_history.interval` until the process exits.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            // the first interval is only a baseline: resume restores the
+            // counters during it, which would read as a burst of checks
+            let mut mark: Option<Mark> = None;
+            // lines in the file, to know when dropped snapshots are worth rewriting it
+            let mut lines = self.points.lock().len();
+            loop {
+                tokio::time::sleep(self.config.current().stats_history.interval).await;
+                let cfg = self.config.current();
+                let next = Mark::of(&self.prog);
+                let point = mark.as_ref().map(|m| point_between(m, &next));
+                mark = Some(next);
+                let Some(point) = point.filter(|_| cfg.stats_history.enabled) else {
+                    continue;
+                };
+                let since = point.unix.saturating_sub(cfg.stats_history.retention.as_secs());
+                let kept = {
+                    let mut points = self.points.lock();
+                    points.push_back(point.clone());
+                    while points.front().is_some_and(|p| p.unix < since) {
+                        points.pop_front();
+                    }
+                    points.len()
+                };
+                lines += 1;
+                let res = if lines > kept * 2 {
+                    lines = kept;
+                    self.rewrite()
+                } else {
+                    self.append(&point)
+                };
+                if let Err(e) = res {
+                    warn!("stats history: cannot write {}: {e}", self.path.display());
+                }
+            }
+        });
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.config.current().stats_history.interval
+    }
+
+    /// Snapshots of the last `range`, oldest first; with `step`, consecutive
+    /// snapshots are added up into one per step.
+    pub fn query(&self, range: Duration, step: Option<Duration>) -> Vec<StatsPoint> {
+        let since = now_unix().saturating_sub(range.as_secs());
+        let points = self.points.lock();
+        let recent = points.iter().filter(|p| p.unix >= since);
+        let Some(step) = step.map(|s| s.as_secs().max(1)) else {
+            return recent.cloned().collect();
+        };
+        let mut out: Vec<StatsPoint> = Vec::new();
+        let mut bucket: Option<(u64, StatsPoint)> = None;
+        for p in recent {
+            let b = p.unix.div_ceil(step);
+            match &mut bucket {
+                Some((n, acc)) if *n == b => acc.merge(p),
+                _ => {
+                    if let Some((_, acc)) = bucket.take() {
+                        out.push(acc.rates());
+                    }
+                    bucket = Some((b, p.clone()));
+                }
+            }
+        }
+        out.extend(bucket.map(|(_, acc)| acc.rates()));
+        out
+    }
+
+    fn append(&self, p: &StatsPoint) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut f = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{}", serde_json::to_string(p)?)
+    }
+
+    // drop expired snapshots from the file
+    fn rewrite(&self) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("jsonl.tmp");
+        let mut out = String::new();
+        for p in self.points.lock().iter() {
+            out.push_str(&serde_json::to_string(p)?);
+            out.push('\n');
+        }
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+fn point_between(a: &Mark, b: &Mark) -> StatsPoint {
+    let errors_by_cause: BTreeMap<String, i64> = b
+        .errors
+        .iter()
+        .map(|(k, n)| (k.to_string(), (n - a.errors.get(k).copied().unwrap_or(0)).max(0)))
+        .filter(|(_, n)| *n > 0)
+        .collect();
+    StatsPoint {
+        unix: now_unix(),
+        seconds: b.at.duration_since(a.at).as_secs_f64(),
+        checked: (b.checked - a.checked).max(0),
+        found: (b.found - a.found).max(0),
+        errors: errors_by_cause.values().sum(),
+        errors_by_cause,
+        checked_total: b.checked,
+        found_total: b.found,
+        ..Default::default()
+    }
+    .rates()
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,123 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use reqwest::ClientBuilder;
+use serde::Serialize;
+use tower::{Layer, Service};
+use utoipa::ToSchema;
+
+use crate::config::Config;
+
+// Counted over all check clients since start; scans build their own clients,
+// so the counters cannot live with one of them
+static OPENED: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+static HEAD_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Connection use of the HTTP checks since start, in /stats/
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct PoolStats {
+    /// Check requests sent, redirects included
+    pub requests: u64,
+    /// New connections set up (TCP, plus TLS for HTTPS)
+    pub connections_opened: u64,
+    /// Connection attempts that failed: refused, unreachable, TLS errors, timeouts
+    pub connect_failures: u64,
+    /// Requests that went over a pooled connection
+    pub reused: u64,
+    /// Share of requests that went over a pooled connection
+    pub reuse_percent: f64,
+    /// HEAD checks retried as GET (http_check.strategy head_then_get)
+    pub head_fallbacks: u64,
+}
+
+pub fn stats() -> PoolStats {
+    let requests = REQUESTS.load(Ordering::Relaxed);
+    let opened = OPENED.load(Ordering::Relaxed);
+    let failed = FAILED.load(Ordering::Relaxed);
+    // HTTP/2 carries several requests on one connection, a failed attempt none
+    let reused = requests.saturating_sub(opened + failed);
+    PoolStats {
+        requests,
+        connections_opened: opened,
+        connect_failures: failed,
+        reused,
+        reuse_percent: if requests > 0 { reused as f64 * 100.0 / requests as f64 } else { 0.0 },
+        head_fallbacks: HEAD_FALLBACKS.load(Ordering::Relaxed),
+    }
+}
+
+/// A check request is about to be sent.
+pub fn count_request() {
+    REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A HEAD check was rejected or timed out and is sent again as GET.
+pub fn count_head_fallback() {
+    HEAD_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Apply `http_check.client` and the total timeout to a client.
+pub fn tune(builder: ClientBuilder, cfg: &Config) -> ClientBuilder {
+    let c = &cfg.http_check.client;
+    let idle = if c.pool_max_idle_per_host > 0 { c.pool_max_idle_per_host } else { cfg.limits.concurrency.max(1) as usize };
+    let mut b = builder
+        .pool_max_idle_per_host(idle)
+        .pool_idle_timeout(nonzero(c.pool_idle_timeout))
+        .tcp_keepalive(nonzero(c.tcp_keepalive))
+        .timeout(cfg.http_check.timeout);
+    if let Some(t) = nonzero(c.connect_timeout) {
+        b = b.connect_timeout(t);
+    }
+    if !c.http2 {
+        b = b.http1_only();
+    }
+    b
+}
+
+fn nonzero(d: Duration) -> Option<Duration> {
+    (!d.is_zero()).then_some(d)
+}
+
+/// Connector layer of the check clients counting the connections they open.
+#[derive(Clone)]
+pub struct CountConnections;
+
+impl<S> Layer<S> for CountConnections {
+    type Service = Counted<S>;
+
+    fn layer(&self, inner: S) -> Counted<S> {
+        Counted(inner)
+    }
+}
+
+#[derive(Clone)]
+pub struct Counted<S>(S);
+
+impl<S, R> Service<R> for Counted<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let connect = self.0.call(req);
+        Box::pin(async move {
+            let res = connect.await;
+            let counter = if res.is_ok() { &OPENED } else { &FAILED };
+            counter.fetch_add(1, Ordering::Relaxed);
+            res
+        })
+    }
+}
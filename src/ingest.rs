@@ -0,0 +1,100 @@
+use std::collections::{HashSet, VecDeque};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::domain::Domain;
+
+// Candidates posted to /candidates/, checked ahead of the generator queue
+#[derive(Default)]
+pub struct CandidateQueue {
+    queue: Mutex<VecDeque<Domain>>,
+    notify: Notify,
+}
+
+impl CandidateQueue {
+    pub fn len(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().is_empty()
+    }
+
+    /// Append while the queue holds fewer than `max_queued`; returns how many fit.
+    pub fn push(&self, domains: Vec<Domain>, max_queued: usize) -> usize {
+        let mut q = self.queue.lock();
+        let room = max_queued.saturating_sub(q.len());
+        let n = domains.len().min(room);
+        q.extend(domains.into_iter().take(n));
+        drop(q);
+        if n > 0 {
+            self.notify.notify_one();
+        }
+        n
+    }
+
+    /// Wait for the next candidate.
+    pub async fn next(&self) -> Domain {
+        loop {
+            if let Some(d) = self.queue.lock().pop_front() {
+                return d;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Drop everything still waiting; returns how many were dropped.
+    pub fn clear(&self) -> usize {
+        std::mem::take(&mut *self.queue.lock()).len()
+    }
+}
+
+/// Result of POST /candidates/
+#[derive(Debug, Default, Serialize, utoipa::ToSchema)]
+pub struct IngestResp {
+    /// Queued for checking
+    pub accepted: usize,
+    /// Already checked or stored (dedup), or repeated in the request
+    pub duplicates: usize,
+    /// Not a valid domain name
+    pub invalid: usize,
+    /// Valid but dropped because the queue is full (ingest.max_queued)
+    pub rejected: usize,
+    /// Waiting in the queue after this request
+    pub queued: usize,
+}
+
+/// Entries of a request body: a JSON array of names, or one name per line.
+/// Lines may be zone-file records; only the owner name is used.
+pub fn parse_body(body: &str, json: bool) -> anyhow::Result<Vec<String>> {
+    if json {
+        return Ok(serde_json::from_str(body)?);
+    }
+    Ok(body.lines().filter_map(owner_name).map(str::to_string).collect())
+}
+
+/// First field of a name list or zone-file line; None for blank lines,
+/// comments and directives ($ORIGIN, $TTL).
+pub fn owner_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with(['#', ';', '$']) {
+        return None;
+    }
+    line.split_whitespace().next()
+}
+
+/// Normalize entries, dropping invalid ones and repeats within the request.
+pub fn prepare(entries: Vec<String>, resp: &mut IngestResp) -> Vec<Domain> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::with_capacity(entries.len());
+    for e in entries {
+        match Domain::parse(&e) {
+            Some(d) if seen.insert(d.clone()) => out.push(d),
+            Some(_) => resp.duplicates += 1,
+            None => resp.invalid += 1,
+        }
+    }
+    out
+}
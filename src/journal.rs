@@ -0,0 +1,140 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+// Write-ahead log of checked candidates for exact resume.
+//
+// Candidates are tracked in generation order. The resume position is the
+// watermark: the last candidate before which everything has been checked.
+// Candidates finished out of order past it are appended to the journal, so
+// after a crash the generator restarts at the watermark and skips those.
+pub struct Journal {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    file: File,
+    next_seq: u64,
+    // generated but not yet below the watermark, in generation order; true once checked
+    pending: BTreeMap<u64, (String, bool)>,
+    seq_of: HashMap<String, u64>,
+    watermark: String,
+    // checked past the watermark before the restart, not generated again yet
+    resumed: HashSet<String>,
+    // the file has entries a compaction would drop
+    dirty: bool,
+}
+
+impl Journal {
+    /// Open (or create) the journal at `path`; entries left by the previous run
+    /// are skipped by `sent` until the next pass.
+    pub fn open(path: &Path, watermark: String) -> anyhow::Result<Self> {
+        let mut resumed = HashSet::new();
+        if let Ok(f) = File::open(path) {
+            for line in BufReader::new(f).lines() {
+                let line = line?;
+                let d = line.trim();
+                if !d.is_empty() {
+                    resumed.insert(d.to_string());
+                }
+            }
+        }
+        if !resumed.is_empty() {
+            info!("journal: {} candidates already checked past '{}'", resumed.len(), watermark);
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            inner: Mutex::new(Inner {
+                file,
+                next_seq: 0,
+                pending: BTreeMap::new(),
+                seq_of: HashMap::new(),
+                watermark,
+                resumed,
+                dirty: false,
+            }),
+        })
+    }
+
+    /// Register a generated candidate before it is queued; false when it was
+    /// already checked before the restart and should be skipped.
+    pub fn sent(&self, domain: &str) -> bool {
+        let mut g = self.inner.lock();
+        if g.resumed.remove(domain) {
+            return false;
+        }
+        let seq = g.next_seq;
+        g.next_seq += 1;
+        g.pending.insert(seq, (domain.to_string(), false));
+        // generated again before the first check finished; this one covers both
+        if let Some(old) = g.seq_of.insert(domain.to_string(), seq) {
+            if let Some(e) = g.pending.get_mut(&old) {
+                e.1 = true;
+            }
+        }
+        true
+    }
+
+    /// Record a finished check and advance the watermark past everything done.
+    pub fn done(&self, domain: &str) {
+        let mut g = self.inner.lock();
+        let Some(seq) = g.seq_of.remove(domain) else { return };
+        if let Some(e) = g.pending.get_mut(&seq) {
+            e.1 = true;
+        }
+        while let Some(e) = g.pending.first_entry() {
+            if !e.get().1 {
+                break;
+            }
+            g.watermark = e.remove().0;
+        }
+        // only out-of-order completions need a journal line
+        if g.pending.first_key_value().is_some_and(|(first, _)| *first < seq) {
+            if let Err(e) = writeln!(g.file, "{domain}") {
+                warn!("journal: append failed: {e}");
+            }
+            g.dirty = true;
+        }
+    }
+
+    /// Everything generated up to and including this candidate has been checked.
+    pub fn watermark(&self) -> String {
+        self.inner.lock().watermark.clone()
+    }
+
+    /// Forget candidates left from the previous run; a new pass checks them again.
+    pub fn new_pass(&self) {
+        let mut g = self.inner.lock();
+        if !g.resumed.is_empty() {
+            g.resumed.clear();
+            g.dirty = true;
+        }
+    }
+
+    /// Rewrite the journal with only the entries past the watermark. Call after
+    /// the watermark has been saved, so that a crash in between loses nothing.
+    pub fn compact(&self) -> anyhow::Result<()> {
+        let mut g = self.inner.lock();
+        if !g.dirty {
+            return Ok(());
+        }
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            for d in g.resumed.iter().chain(g.pending.values().filter(|e| e.1).map(|e| &e.0)) {
+                writeln!(w, "{d}")?;
+            }
+            w.into_inner()?.sync_data()?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        g.file = OpenOptions::new().append(true).open(&self.path)?;
+        g.dirty = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Upper bounds (ms) of the histogram buckets; slower answers go to +Inf
+pub const BOUNDS_MS: [u64; 24] = [
+    1, 2, 3, 5, 7, 10, 15, 20, 30, 50, 75, 100, 150, 200, 300, 500, 750, 1000, 1500, 2000, 3000, 5000, 10000, 30000,
+];
+
+/// Check stage a latency is recorded for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Address lookup of the candidate
+    Dns,
+    /// One HTTP request, up to the response headers
+    Http,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 2] = [Stage::Dns, Stage::Http];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Dns => "dns",
+            Stage::Http => "http",
+        }
+    }
+}
+
+/// One timed DNS lookup or HTTP request
+#[derive(Clone, Copy, Debug)]
+pub struct Timing {
+    pub stage: Stage,
+    pub elapsed: Duration,
+    /// The server answered (NXDOMAIN and rejected statuses count); false for
+    /// timeouts, refused connections and the like
+    pub answered: bool,
+}
+
+/// Latency percentiles of one stage in /stats/
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct LatencySummary {
+    /// Answered lookups or requests
+    pub count: u64,
+    /// Lookups or requests without an answer
+    pub unanswered: u64,
+    /// Share of answered ones
+    pub availability_percent: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+// Answer times of one stage in fixed buckets
+#[derive(Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; BOUNDS_MS.len() + 1],
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+    unanswered: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, t: Timing) {
+        if !t.answered {
+            self.unanswered.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let us = t.elapsed.as_micros() as u64;
+        let i = BOUNDS_MS.partition_point(|b| b * 1000 < us);
+        self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    /// Answers per bucket (the last one is +Inf), their total time in
+    /// microseconds, and the unanswered count.
+    pub fn snapshot(&self) -> (Vec<u64>, u64, u64) {
+        (
+            self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+            self.sum_us.load(Ordering::Relaxed),
+            self.unanswered.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let (buckets, sum_us, unanswered) = self.snapshot();
+        let count: u64 = buckets.iter().sum();
+        let max_ms = self.max_us.load(Ordering::Relaxed) as f64 / 1000.0;
+        let total = count + unanswered;
+        LatencySummary {
+            count,
+            unanswered,
+            availability_percent: if total > 0 { count as f64 * 100.0 / total as f64 } else { 0.0 },
+            mean_ms: if count > 0 { sum_us as f64 / 1000.0 / count as f64 } else { 0.0 },
+            p50_ms: quantile(&buckets, count, max_ms, 0.50),
+            p95_ms: quantile(&buckets, count, max_ms, 0.95),
+            p99_ms: quantile(&buckets, count, max_ms, 0.99),
+            max_ms,
+        }
+    }
+}
+
+// Interpolated within the bucket the quantile falls in, capped at the slowest answer
+fn quantile(buckets: &[u64], count: u64, max_ms: f64, q: f64) -> f64 {
+    if count == 0 {
+        return 0.0;
+    }
+    let target = q * count as f64;
+    let mut seen = 0u64;
+    for (i, &n) in buckets.iter().enumerate() {
+        if n > 0 && (seen + n) as f64 >= target {
+            let lower = if i == 0 { 0.0 } else { BOUNDS_MS[i - 1] as f64 };
+            let upper = BOUNDS_MS.get(i).map_or(max_ms, |b| *b as f64).min(max_ms).max(lower);
+            let frac = ((target - seen as f64) / n as f64).clamp(0.0, 1.0);
+            return lower + frac * (upper - lower);
+        }
+        seen += n;
+    }
+    max_ms
+}
+
+/// Histograms of every stage, indexed by Stage
+#[derive(Default)]
+pub struct Latencies {
+    stages: [Histogram; Stage::ALL.len()],
+}
+
+impl Latencies {
+    pub fn observe(&self, t: Timing) {
+        self.stages[t.stage as usize].observe(t);
+    }
+
+    pub fn stage(&self, stage: Stage) -> &Histogram {
+        &self.stages[stage as usize]
+    }
+}
+
+/// Part of the worker time the stage breakdown of /stats/ accounts for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for a token of the global rate limit
+    RateLimit,
+    Dns,
+    Http,
+    /// Handing found domains to the store writer
+    Store,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::RateLimit, Phase::Dns, Phase::Http, Phase::Store];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::RateLimit => "rate_limit",
+            Phase::Dns => "dns",
+            Phase::Http => "http",
+            Phase::Store => "store",
+        }
+    }
+}
+
+/// Time spent in one stage since start, in /stats/
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct StageSummary {
+    /// Lookups, requests, waits or store hand-offs; checks started for
+    /// generation_wait and other
+    pub count: u64,
+    pub total_secs: f64,
+    pub avg_ms: f64,
+    /// Share of the worker slots' time (limits.concurrency x elapsed) spent here
+    pub utilization_percent: f64,
+}
+
+#[derive(Default)]
+struct Capacity {
+    // worker-slot microseconds up to `since`
+    slot_us: f64,
+    since: Option<Instant>,
+    workers: usize,
+}
+
+/// Busy time per Phase and of whole checks, against the worker slots
+/// available, so the breakdown shows which stage holds the workers.
+pub struct StageTimes {
+    epoch: Instant,
+    busy_us: [AtomicU64; Phase::ALL.len()],
+    count: [AtomicU64; Phase::ALL.len()],
+    // finished checks, and the checks running with the sum of their start offsets from `epoch`
+    check_us: AtomicU64,
+    checks: AtomicU64,
+    running: AtomicU64,
+    running_since_us: AtomicU64,
+    capacity: Mutex<Capacity>,
+}
+
+impl Default for StageTimes {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            busy_us: Default::default(),
+            count: Default::default(),
+            check_us: AtomicU64::new(0),
+            checks: AtomicU64::new(0),
+            running: AtomicU64::new(0),
+            running_since_us: AtomicU64::new(0),
+            capacity: Mutex::new(Capacity::default()),
+        }
+    }
+}
+
+/// Counts a check as running until dropped.
+pub struct CheckTimer {
+    times: Arc<StageTimes>,
+    offset_us: u64,
+}
+
+impl Drop for CheckTimer {
+    fn drop(&mut self) {
+        let t = &self.times;
+        t.running.fetch_sub(1, Ordering::Relaxed);
+        t.running_since_us.fetch_sub(self.offset_us, Ordering::Relaxed);
+        t.check_us.fetch_add(t.now_us().saturating_sub(self.offset_us), Ordering::Relaxed);
+        t.checks.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl StageTimes {
+    fn now_us(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    pub fn add(&self, phase: Phase, elapsed: Duration) {
+        self.busy_us[phase as usize].fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.count[phase as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Time a check, from taking a worker slot to giving it back.
+    pub fn start_check(self: &Arc<Self>) -> CheckTimer {
+        let offset_us = self.now_us();
+        self.running.fetch_add(1, Ordering::Relaxed);
+        self.running_since_us.fetch_add(offset_us, Ordering::Relaxed);
+        CheckTimer { times: self.clone(), offset_us }
+    }
+
+    /// Worker slots from now on (limits.concurrency, also after a reload).
+    pub fn set_workers(&self, workers: usize) {
+        let mut c = self.capacity.lock();
+        let now = Instant::now();
+        if let Some(since) = c.since {
+            c.slot_us += now.duration_since(since).as_micros() as f64 * c.workers as f64;
+        }
+        c.since = Some(now);
+        c.workers = workers;
+    }
+
+    /// Every Phase, plus generation_wait (worker slots without a check:
+    /// no candidate queued, or paused) and other (the rest of the checks:
+    /// RDAP, TLS, wildcard probes, ...).
+    pub fn summary(&self) -> BTreeMap<String, StageSummary> {
+        let capacity_us = {
+            let c = self.capacity.lock();
+            c.slot_us + c.since.map_or(0.0, |s| s.elapsed().as_micros() as f64 * c.workers as f64)
+        };
+        // running checks count up to now; their lookups once they end
+        let running = self.running.load(Ordering::Relaxed);
+        let running_us = running as f64 * self.now_us() as f64 - self.running_since_us.load(Ordering::Relaxed) as f64;
+        let checks = self.checks.load(Ordering::Relaxed) + running;
+        let check_us = self.check_us.load(Ordering::Relaxed) as f64 + running_us.max(0.0);
+        let entry = |count: u64, us: f64| StageSummary {
+            count,
+            total_secs: us / 1e6,
+            avg_ms: if count > 0 { us / 1000.0 / count as f64 } else { 0.0 },
+            utilization_percent: if capacity_us > 0.0 { (us * 100.0 / capacity_us).min(100.0) } else { 0.0 },
+        };
+        let mut out = BTreeMap::new();
+        let mut staged_us = 0.0;
+        for p in Phase::ALL {
+            let us = self.busy_us[p as usize].load(Ordering::Relaxed) as f64;
+            staged_us += us;
+            out.insert(p.as_str().to_string(), entry(self.count[p as usize].load(Ordering::Relaxed), us));
+        }
+        // lookups can overlap within a check, so the stages may add up to more than it
+        out.insert("other".to_string(), entry(checks, (check_us - staged_us).max(0.0)));
+        out.insert("generation_wait".to_string(), entry(checks, (capacity_us - check_us).max(0.0)));
+        out
+    }
+}
@@ -1,28 +1,86 @@
+mod alerts;
+mod analytics;
+mod api_limit;
+mod auth;
+mod bandwidth;
+mod budget;
+mod certs;
+mod checker;
+mod checkpoint;
+mod cluster;
 mod config;
+mod dedup;
+mod dns;
+mod domain;
+mod error;
+mod exclude;
+mod export;
+mod generator;
+mod geoip;
+mod grpc;
+mod health;
+mod history;
+mod http_pool;
+mod ingest;
+mod journal;
+mod latency;
+mod metrics;
+mod namespace;
+mod notify;
+mod openapi;
+mod parking;
 mod progress;
+mod queue;
+mod rdap;
+mod retry;
+mod scans;
+mod schedule;
+mod score;
 mod service;
+mod sinks;
+mod sources;
+mod stats;
 mod store;
+mod streaming;
+mod systemd;
+mod telemetry;
+mod tlds;
+mod tls;
+mod tui;
+mod verify;
+mod wildcard;
 
 use std::net::SocketAddr;
 use std::time::Duration;
 use std::{fs, sync::Arc};
 
 use axum::{
-    body::Body,
-    extract::Path as AxPath,
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::get,
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        ws::{rejection::WebSocketUpgradeRejection, Message, WebSocketUpgrade},
+        DefaultBodyLimit, OriginalUri, Path as AxPath, Query,
+    },
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, patch, post},
     Json, Router,
 };
-use clap::Parser;
-use config::Config;
+use clap::{Parser, Subcommand};
+use futures_util::{FutureExt, SinkExt};
+use config::{Config, ListLimitsConfig, LiveConfig};
+use domain::Domain;
+use error::ApiError;
 use progress::Progress;
+use rdap::AvailabilityStores;
 use reqwest::Client;
-use service::{run_service, ShutdownSignal};
-use store::DomainStore;
-use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
+use service::{run_service, RateLimiter, ScanControl, ScanState, ServiceContext, ShutdownSignal, TldLimits};
+use store::{DomainMatcher, DomainStore, ListQuery};
+use tracing::{error, info, warn};
+use tracing_subscriber::filter::{filter_fn, FilterExt};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 /// Rust port of go_domain_searcher_api
 #[derive(Parser, Debug)]
@@ -32,39 +90,140 @@ struct Args {
     #[arg(long = "config", default_value = "../domain_search.config.yaml")]
     config: String,
 
-    /// Listen address, e.g. :8080 or 0.0.0.0:8080
-    #[arg(long = "addr", default_value = ":8080")]
-    addr: String,
+    /// Listen address, e.g. :8080 or 0.0.0.0:8080 (overrides server.public_addr; default :8080)
+    #[arg(long = "addr")]
+    addr: Option<String>,
+
+    /// gRPC listen address, e.g. :50051 (overrides server.grpc_addr)
+    #[arg(long = "grpc-addr")]
+    grpc_addr: Option<String>,
+
+    /// PEM certificate chain; serves the API over HTTPS (overrides server.tls.cert)
+    #[arg(long = "tls-cert", requires = "tls_key")]
+    tls_cert: Option<String>,
+
+    /// PEM private key for --tls-cert (overrides server.tls.key)
+    #[arg(long = "tls-key", requires = "tls_cert")]
+    tls_key: Option<String>,
+
+    /// Run under systemd: report readiness and watchdog pings over NOTIFY_SOCKET
+    /// and serve the API on a socket passed by socket activation, if any
+    #[arg(long = "systemd", default_value_t = false)]
+    systemd: bool,
+
+    /// Show a live terminal dashboard (progress, speed, recent finds, errors)
+    /// instead of plain log output
+    #[arg(long = "tui", default_value_t = false)]
+    tui: bool,
 
     /// Reset storage: delete all stored domains (*.txt) and state file, then exit
     #[arg(long = "reset", default_value_t = false)]
     reset: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the DNS + HTTP (+ RDAP) checks against given domains and print one JSON verdict per line
+    Check {
+        /// Domains to check; read from stdin (one per line) when empty or "-"
+        domains: Vec<String>,
+    },
+    /// Write stored domains to a CSV, JSONL or text file (optionally gzip-compressed)
+    Export(export::ExportArgs),
+    /// Print the first candidates a scan would queue, one per line, without checking them
+    Preview {
+        /// Number of candidates
+        #[arg(long = "count", default_value_t = 100)]
+        count: usize,
+        /// Start after this domain instead of the saved resume point
+        #[arg(long = "from")]
+        from: Option<String>,
+    },
+    /// Sort the stored per-TLD lists and drop repeated names (storage.backend: text);
+    /// while the service runs, use POST /control/compact instead
+    Compact {
+        /// Only this TLD (or parent zone)
+        #[arg(long = "tld")]
+        tld: Option<String>,
+    },
+    /// Load and validate the config, then print it as the scanner sees it
+    /// (defaults filled in, TLD file or URL expanded, secrets redacted)
+    Validate {
+        /// Print a JSON Schema of the config file instead; no config is read
+        #[arg(long = "schema", default_value_t = false)]
+        schema: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // logging
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env().add_directive("info".parse().unwrap()))
-        .init();
-
     let args = Args::parse();
+    if let Some(Command::Validate { schema: true }) = args.command {
+        println!("{}", serde_json::to_string_pretty(&config::json_schema())?);
+        return Ok(());
+    }
 
-    // config
+    // the dashboard shows the log in a pane of its own
+    let tui_logs = (args.tui && args.command.is_none()).then(tui::LogBuffer::default);
+    // config, logged by a plain logger until it says whether spans are exported
+    let early = tracing::subscriber::set_default(tracing_subscriber::registry().with(log_layer(&args, tui_logs.as_ref())));
     let cfg: Config = config::load_config(&args.config).await?;
+    drop(early);
+    let telemetry = match args.command {
+        None => telemetry::Telemetry::new(&cfg.telemetry)?,
+        Some(_) => None,
+    };
+    tracing_subscriber::registry()
+        .with(log_layer(&args, tui_logs.as_ref()))
+        .with(telemetry::layer(telemetry.as_ref()))
+        .init();
+    if telemetry.is_some() {
+        let t = &cfg.telemetry;
+        info!("telemetry: exporting spans to {} over {:?}, sampling {}", t.endpoint(t.protocol), t.protocol, t.sample_ratio);
+    }
     info!(
         "configuration loaded: storage.dir={}, tlds_count={}",
         &cfg.storage.dir,
         cfg.generator.tlds.len()
     );
+    match args.command {
+        Some(Command::Check { domains }) => return run_check(&cfg, domains).await,
+        Some(Command::Export(ex)) => return export::run_export(&cfg, &ex),
+        Some(Command::Compact { tld }) => return run_compact(&cfg, tld).await,
+        Some(Command::Preview { count, from }) => return run_preview(&args.config, cfg, count, from).await,
+        Some(Command::Validate { .. }) => {
+            print!("{}", serde_yaml::to_string(&cfg)?);
+            return Ok(());
+        }
+        None => {}
+    }
     fs::create_dir_all(&cfg.storage.dir)?;
 
     // storage
-    let store = DomainStore::new(&cfg.storage.dir)?;
+    let sinks = sinks::Sinks::new(&cfg.sinks, std::path::Path::new(&cfg.storage.dir))?;
+    let store = DomainStore::with_sinks(&cfg.storage, sinks.clone())?;
+    // RDAP classifications live in their own subdirectories
+    let avail_stores = if cfg.rdap.enabled {
+        Some(AvailabilityStores::open(&cfg.storage)?)
+    } else {
+        None
+    };
 
     // reset path
     if args.reset {
         store.reset(&cfg.storage.state_file)?;
+        let _ = std::fs::remove_file(&cfg.storage.journal_file);
+        queue::DiskQueue::reset(std::path::Path::new(&cfg.storage.queue.dir))?;
+        let _ = std::fs::remove_file(verify::Verifier::state_path(&cfg.storage.dir));
+        let _ = std::fs::remove_file(dns::NegativeCache::file_path(&cfg.storage.dir));
+        let _ = std::fs::remove_file(history::StatsHistory::file_path(&cfg.storage.dir));
+        if let Some(st) = &avail_stores {
+            st.available.reset("")?;
+            st.registered.reset("")?;
+        }
         info!(
             "reset completed: removed domain files in {} and state {}",
             &cfg.storage.dir, &cfg.storage.state_file
@@ -72,12 +231,7 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    // http client (conservative defaults)
-    let client = Client::builder()
-        .pool_max_idle_per_host(cfg.limits.concurrency.max(1) as usize)
-        .tcp_keepalive(Some(Duration::from_secs(30)))
-        .timeout(cfg.http_check.timeout)
-        .build()?;
+    let client = build_client(&cfg)?;
 
     // progress
     let total_planned = (cfg.limits.max_candidates as i64).max(0);
@@ -87,21 +241,150 @@ async fn main() -> anyhow::Result<()> {
     // background service
     let shutdown = ShutdownSignal::new();
     let shutdown_clone = shutdown.clone();
-    let svc_cfg = cfg.clone();
-    let svc_store = store.clone();
-    let svc_client = client.clone();
+    let live_cfg = LiveConfig::new(&args.config, cfg.clone());
+    let limiter = Arc::new(RateLimiter::new(
+        cfg.limits.rate_per_second.max(1) as u64,
+        cfg.limits.burst.max(0) as u64,
+    ));
+    let tld_limits = Arc::new(TldLimits::new(&cfg.limits.per_tld));
+    let health = health::Health::default();
+    let control = ScanControl::new();
+    let dedup = dedup::Dedup::from_config(&cfg.dedup, std::path::Path::new(&cfg.storage.dir))?;
+    let notifier = notify::Notifier::new(live_cfg.clone(), client.clone());
+    let grpc_notifier = notifier.clone();
+    let ws_notifier = notifier.clone();
+    let tui_finds = tui_logs.as_ref().map(|_| notifier.subscribe());
+    let digest = notify::EmailDigest::spawn(live_cfg.clone(), prog.clone(), notifier.subscribe());
+    let candidates = Arc::new(ingest::CandidateQueue::default());
+    let scheduler = schedule::Scheduler::new(&cfg.schedule);
+    scheduler.follow(&live_cfg);
+    tlds::spawn_refresh(live_cfg.clone());
+    let coordinator = (cfg.cluster.role == cluster::ClusterRole::Coordinator)
+        .then(|| cluster::Coordinator::new(&cfg, store.clone(), dedup.clone(), notifier.clone()));
+    let positive_cache = dns::PositiveCache::from_config(&cfg.dns.positive_cache);
+    let ctx = ServiceContext {
+        config: live_cfg.clone(),
+        store: store.clone(),
+        prog,
+        client: client.clone(),
+        limiter: limiter.clone(),
+        tld_limits,
+        health: health.clone(),
+        control: control.clone(),
+        avail_stores: avail_stores.clone(),
+        dedup: dedup.clone(),
+        negative_cache: dns::NegativeCache::from_config(&cfg.dns.negative_cache, &cfg.storage.dir),
+        positive_cache: positive_cache.clone(),
+        notifier,
+        candidates: candidates.clone(),
+        schedule: scheduler.clone(),
+    };
+    // periodic re-checks of the found domains
+    let verifier = cfg
+        .verify
+        .enabled
+        .then(|| verify::Verifier::new(live_cfg.clone(), store.clone(), client.clone(), limiter.clone(), positive_cache.clone()));
+    if let Some(v) = &verifier {
+        v.clone().spawn();
+    }
+    // speed, finds and errors over time for /stats/history
+    let history = history::StatsHistory::open(live_cfg.clone(), (*prog_arc).clone());
+    history.clone().spawn();
+    // alerts.rules on the main scan's rates
+    alerts::spawn(live_cfg.clone(), (*prog_arc).clone(), control.clone(), client.clone());
+    // profile scans with a namespace write to a store of their own
+    let namespaces = namespace::Namespaces::new(&cfg.storage, dedup.clone());
+    // profile scans share everything but progress and control with the main scan
+    let scans = scans::ScanManager::new(ctx.clone(), namespaces.clone());
     // run service as a future (avoid Send requirement of tokio::spawn)
-    let svc_fut = run_service(svc_cfg, svc_store, prog, svc_client, shutdown_clone);
+    let mut svc_fut = std::pin::pin!(run_service(ctx, shutdown_clone));
+
+    // reload config on SIGHUP
+    #[cfg(unix)]
+    {
+        let live = live_cfg.clone();
+        let mut hup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hup.recv().await.is_some() {
+                info!("SIGHUP received, reloading config");
+                match live.reload().await {
+                    Ok(restart) if !restart.is_empty() => {
+                        info!("config reloaded; changes to {:?} need a restart", restart)
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("config reload failed, keeping current config: {e:#}"),
+                }
+            }
+        });
+    }
 
+    let base_path = cfg.server.base_path();
     // http routes
-    let tlds = Arc::new(cfg.generator.tlds.clone());
     let app = Router::new()
         .route(
             "/stats/",
             get({
                 let p = prog_arc.clone();
                 let st = store.clone();
-                move || stats_handler(p.clone(), st.clone())
+                let c = control.clone();
+                let live = live_cfg.clone();
+                move |q: Result<Query<StatsQuery>, QueryRejection>| stats_handler(q, p.clone(), st.clone(), c.clone(), live.clone())
+            }),
+        )
+        .route(
+            "/ws/stats",
+            get({
+                let (p, st, c, n) = (prog_arc.clone(), store.clone(), control.clone(), ws_notifier.clone());
+                let live = live_cfg.clone();
+                move |q: Result<Query<StatsQuery>, QueryRejection>, ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>| {
+                    ws_stats_handler(q, ws, p.clone(), st.clone(), c.clone(), n.clone(), live.clone())
+                }
+            }),
+        )
+        .route(
+            "/metrics",
+            get({
+                let p = prog_arc.clone();
+                let st = store.clone();
+                let c = control.clone();
+                move || metrics_handler(p.clone(), st.clone(), c.clone())
+            }),
+        )
+        .route(
+            "/stats/history",
+            get({
+                let h = history.clone();
+                move |q: Result<Query<HistoryQuery>, QueryRejection>| stats_history_handler(q, h.clone())
+            }),
+        )
+        .route(
+            "/stats/tlds/",
+            get({
+                let p = prog_arc.clone();
+                let live = live_cfg.clone();
+                move || tld_stats_handler(p.clone(), live.clone())
+            }),
+        )
+        .route(
+            "/stats/tlds",
+            get({
+                let p = prog_arc.clone();
+                let live = live_cfg.clone();
+                move || tld_stats_handler(p.clone(), live.clone())
+            }),
+        )
+        .route(
+            "/stats/sample/",
+            get({
+                let p = prog_arc.clone();
+                move || sample_stats_handler(p.clone())
+            }),
+        )
+        .route(
+            "/stats/sample",
+            get({
+                let p = prog_arc.clone();
+                move || sample_stats_handler(p.clone())
             }),
         )
         // also accept without trailing slash for compatibility
@@ -110,102 +393,756 @@ async fn main() -> anyhow::Result<()> {
             get({
                 let p = prog_arc.clone();
                 let st = store.clone();
-                move || stats_handler(p.clone(), st.clone())
+                let c = control.clone();
+                let live = live_cfg.clone();
+                move |q: Result<Query<StatsQuery>, QueryRejection>| stats_handler(q, p.clone(), st.clone(), c.clone(), live.clone())
             }),
         )
         .route(
             "/domain/*path",
             get({
                 let st = store.clone();
-                move |path: AxPath<String>| domain_handler(path, st.clone())
+                let ns = namespaces.clone();
+                let live = live_cfg.clone();
+                move |path: AxPath<String>, q: Result<Query<DomainQuery>, QueryRejection>, OriginalUri(uri): OriginalUri, headers: HeaderMap| {
+                    // <namespace>/<file> reads a namespace, <file> the main store
+                    let (st, ns, lists) = (st.clone(), ns.clone(), live.current().server.lists.clone());
+                    async move {
+                        match path.0.split_once('/') {
+                            Some((name, file)) => {
+                                namespace_domain_handler(AxPath((name.to_string(), file.to_string())), q, uri, headers, ns, lists).await
+                            }
+                            None => domain_handler(path, q, uri, headers, st, lists).await,
+                        }
+                    }
+                }
+            })
+            .delete({
+                let st = store.clone();
+                move |path: AxPath<String>| domain_delete_handler(path, st.clone())
+            })
+            .post({
+                let st = store.clone();
+                move |path: AxPath<String>, q: Result<Query<PurgeQuery>, QueryRejection>| domain_purge_handler(path, q, st.clone())
+            }),
+        )
+        .route(
+            "/search/",
+            get({
+                let st = store.clone();
+                let live = live_cfg.clone();
+                move |q: Result<Query<SearchQuery>, QueryRejection>, OriginalUri(uri): OriginalUri| {
+                    search_handler(q, uri, st.clone(), live.current().server.lists.clone())
+                }
+            }),
+        )
+        .route(
+            "/found/",
+            get({
+                let st = store.clone();
+                move |q: Result<Query<FoundQuery>, QueryRejection>| found_handler(q, st.clone())
+            }),
+        )
+        .route(
+            "/analytics/",
+            get({
+                let st = store.clone();
+                move |q: Result<Query<AnalyticsQuery>, QueryRejection>| analytics_handler(q, st.clone())
+            }),
+        )
+        .route(
+            "/ips/",
+            get({
+                let st = store.clone();
+                move |q: Result<Query<IpsQuery>, QueryRejection>| ips_top_handler(q, st.clone())
+            }),
+        )
+        .route(
+            "/ips/:ip",
+            get({
+                let st = store.clone();
+                move |path: AxPath<String>, q: Result<Query<IpQuery>, QueryRejection>| ip_domains_handler(path, q, st.clone())
             }),
         )
         .route(
             "/tlds/",
             get({
-                let tlds = tlds.clone();
-                move || tlds_handler(tlds.clone())
+                let live = live_cfg.clone();
+                move || tlds_handler(live.clone())
             }),
         )
         .route(
             "/tlds",
             get({
-                let tlds = tlds.clone();
-                move || tlds_handler(tlds.clone())
+                let live = live_cfg.clone();
+                move || tlds_handler(live.clone())
+            }),
+        )
+        .route(
+            "/control/pause",
+            post({
+                let c = control.clone();
+                move || pause_handler(c.clone())
+            }),
+        )
+        .route(
+            "/control/resume",
+            post({
+                let c = control.clone();
+                move || resume_handler(c.clone())
+            }),
+        )
+        .route(
+            "/control/stop",
+            post({
+                let c = control.clone();
+                move || stop_handler(c.clone())
+            }),
+        )
+        .route(
+            "/control/reload",
+            post({
+                let live = live_cfg.clone();
+                move || reload_handler(live.clone())
+            }),
+        )
+        .route(
+            "/control/compact",
+            post({
+                let (st, avail, backend) = (store.clone(), avail_stores.clone(), cfg.storage.backend);
+                move |q: Result<Query<CompactQuery>, QueryRejection>| compact_handler(q, st.clone(), avail.clone(), backend)
+            }),
+        )
+        .route(
+            "/control/limits",
+            patch({
+                let live = live_cfg.clone();
+                move |req: Result<Json<LimitsReq>, JsonRejection>| limits_handler(live.clone(), req)
+            }),
+        )
+        .route(
+            "/schedule/",
+            get({
+                let sch = scheduler.clone();
+                move || schedule_get_handler(sch.clone())
+            })
+            .put({
+                let sch = scheduler.clone();
+                let live = live_cfg.clone();
+                move |req: Result<Json<config::ScheduleConfig>, JsonRejection>| schedule_put_handler(sch.clone(), live.clone(), req)
+            }),
+        )
+        .route(
+            "/preview/candidates",
+            get({
+                let live = live_cfg.clone();
+                move |q: Result<Query<PreviewQuery>, QueryRejection>| preview_handler(q, live.clone())
+            }),
+        )
+        .route(
+            "/candidates/",
+            post({
+                let q = candidates.clone();
+                let live = live_cfg.clone();
+                let d = dedup.clone();
+                let p = prog_arc.clone();
+                let c = control.clone();
+                move |headers: HeaderMap, body: String| candidates_handler(q.clone(), live.clone(), d.clone(), p.clone(), c.clone(), headers, body)
+            })
+            .layer(DefaultBodyLimit::max(cfg.ingest.body_limit)),
+        )
+        .route(
+            "/scans/",
+            get({
+                let sc = scans.clone();
+                move || scans_list_handler(sc.clone())
+            })
+            .post({
+                let sc = scans.clone();
+                move |req: Result<Json<scans::StartScanReq>, JsonRejection>| scan_start_handler(sc.clone(), req)
+            }),
+        )
+        .route(
+            "/scans",
+            get({
+                let sc = scans.clone();
+                move || scans_list_handler(sc.clone())
+            })
+            .post({
+                let sc = scans.clone();
+                move |req: Result<Json<scans::StartScanReq>, JsonRejection>| scan_start_handler(sc.clone(), req)
+            }),
+        )
+        .route(
+            "/scans/profiles",
+            get({
+                let sc = scans.clone();
+                move || scan_profiles_handler(sc.clone())
+            }),
+        )
+        .route(
+            "/scans/:id",
+            get({
+                let sc = scans.clone();
+                move |id: AxPath<String>| scan_get_handler(sc.clone(), id)
+            }),
+        )
+        .route(
+            "/scans/:id/stats",
+            get({
+                let sc = scans.clone();
+                let live = live_cfg.clone();
+                move |id: AxPath<String>, q: Result<Query<StatsQuery>, QueryRejection>| {
+                    scan_stats_handler(sc.clone(), id, q, live.clone())
+                }
+            }),
+        )
+        .route(
+            "/scans/:id/domain/:file",
+            get({
+                let sc = scans.clone();
+                let live = live_cfg.clone();
+                move |path: AxPath<(String, String)>, q: Result<Query<DomainQuery>, QueryRejection>, OriginalUri(uri): OriginalUri, headers: HeaderMap| {
+                    scan_domain_handler(sc.clone(), path, q, uri, headers, live.current().server.lists.clone())
+                }
+            }),
+        )
+        .route(
+            "/scans/:id/pause",
+            post({
+                let sc = scans.clone();
+                move |id: AxPath<String>| scan_pause_handler(sc.clone(), id)
+            }),
+        )
+        .route(
+            "/scans/:id/resume",
+            post({
+                let sc = scans.clone();
+                move |id: AxPath<String>| scan_resume_handler(sc.clone(), id)
+            }),
+        )
+        .route(
+            "/scans/:id/stop",
+            post({
+                let sc = scans.clone();
+                move |id: AxPath<String>| scan_stop_handler(sc.clone(), id)
+            }),
+        )
+        .route(
+            "/openapi.json",
+            get({
+                let base = base_path.clone();
+                move || async move { Json(openapi::spec(&base)) }
+            }),
+        )
+        .route("/docs", get(|| async { axum::response::Html(openapi::DOCS_HTML) }))
+        .fallback(|| async { ApiError::not_found("no such endpoint") });
+
+    let app = if let Some(st) = &avail_stores {
+        app.route(
+            "/available/*path",
+            get({
+                let st = st.available.clone();
+                let live = live_cfg.clone();
+                move |path: AxPath<String>, q: Result<Query<DomainQuery>, QueryRejection>, OriginalUri(uri): OriginalUri, headers: HeaderMap| {
+                    domain_handler(path, q, uri, headers, st.clone(), live.current().server.lists.clone())
+                }
+            }),
+        )
+        .route(
+            "/registered/*path",
+            get({
+                let st = st.registered.clone();
+                let live = live_cfg.clone();
+                move |path: AxPath<String>, q: Result<Query<DomainQuery>, QueryRejection>, OriginalUri(uri): OriginalUri, headers: HeaderMap| {
+                    domain_handler(path, q, uri, headers, st.clone(), live.current().server.lists.clone())
+                }
+            }),
+        )
+    } else {
+        app
+    };
+
+    // shard leases and result collection for cluster workers
+    let app = if let Some(co) = &coordinator {
+        app.route(
+            "/cluster/",
+            get({
+                let co = co.clone();
+                move || cluster_status_handler(co.clone())
+            }),
+        )
+        .route(
+            "/cluster/lease",
+            post({
+                let co = co.clone();
+                move |req: Result<Json<cluster::LeaseReq>, JsonRejection>| cluster_lease_handler(co.clone(), req)
+            }),
+        )
+        .route(
+            "/cluster/complete",
+            post({
+                let co = co.clone();
+                move |req: Result<Json<cluster::CompleteReq>, JsonRejection>| cluster_complete_handler(co.clone(), req)
+            }),
+        )
+        .route(
+            "/cluster/report",
+            post({
+                let co = co.clone();
+                move |req: Result<Json<cluster::ReportReq>, JsonRejection>| cluster_report_handler(co.clone(), req)
+            }),
+        )
+    } else {
+        app
+    };
+    let app = if let Some(v) = &verifier {
+        app.route(
+            "/verify/",
+            get({
+                let v = v.clone();
+                move || verify_status_handler(v.clone())
+            }),
+        )
+        .route(
+            "/verify/stale",
+            get({
+                let v = v.clone();
+                move || verify_stale_handler(v.clone())
+            }),
+        )
+    } else {
+        app
+    };
+    // a span per request when telemetry is on; matched routes only, so the span carries the route
+    let app = app.route_layer(axum::middleware::from_fn(telemetry::trace_request));
+    // API keys, checked before any handler
+    let app = app.layer(axum::middleware::from_fn({
+        let live = live_cfg.clone();
+        move |req, next| auth::require_key(live.clone(), req, next)
+    }));
+    // per-client request limits, ahead of the key check so key guessing is throttled too
+    let app = app.layer(axum::middleware::from_fn({
+        let live = live_cfg.clone();
+        let limiter = api_limit::ApiLimiter::default();
+        move |req, next| api_limit::limit(live.clone(), limiter.clone(), req, next)
+    }));
+    // probes are added after the auth layer: Kubernetes sends no API key.
+    // So is the dashboard page, which holds no data and sends the key itself
+    let app = app
+        .route("/", get(dashboard_handler))
+        .route(
+            "/healthz",
+            get({
+                let (h, st, c) = (health.clone(), store.clone(), control.clone());
+                move || healthz_handler(h.clone(), st.clone(), c.clone())
+            }),
+        )
+        .route(
+            "/readyz",
+            get({
+                let (h, st, c) = (health.clone(), store.clone(), control.clone());
+                move || readyz_handler(h.clone(), st.clone(), c.clone())
             }),
         );
+    // with server.admin_addr the public listener serves reads only
+    let admin_addr = cfg.server.admin_addr.trim().to_string();
+    let public_app = if admin_addr.is_empty() {
+        None
+    } else {
+        Some(mount(app.clone().layer(axum::middleware::from_fn(auth::public_only)), &base_path))
+    };
+    if !base_path.is_empty() {
+        info!("api mounted under {}", base_path);
+    }
+    let app = mount(app, &base_path);
+
+    let public_addr = args.addr.clone().unwrap_or_else(|| match cfg.server.public_addr.trim() {
+        "" => ":8080".to_string(),
+        a => a.to_string(),
+    });
+    let tls_files = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::TlsFiles {
+            cert: cert.into(),
+            key: key.into(),
+        }),
+        _ if !cfg.server.tls.cert.trim().is_empty() => Some(tls::TlsFiles {
+            cert: cfg.server.tls.cert.trim().into(),
+            key: cfg.server.tls.key.trim().into(),
+        }),
+        _ => None,
+    };
+    // socket activation hands over a bound listener; --addr is ignored then
+    let sd = args.systemd.then(systemd::Notifier::from_env);
+    let listener = match sd {
+        Some(_) => systemd::take_listener()?,
+        None => None,
+    };
+    let listener = match listener {
+        Some(l) => {
+            info!("api socket passed by systemd: {}", l.local_addr()?);
+            l
+        }
+        None => bind(listen_addr(public_addr.trim())?)?,
+    };
+    let admin_listener = match public_app {
+        Some(_) => Some(bind(listen_addr(&admin_addr)?)?),
+        None => None,
+    };
+    let addr = listener.local_addr()?;
+    let rustls = match tls_files {
+        Some(files) => {
+            let rustls = files.load().await?;
+            if !cfg.server.tls.reload_interval.is_zero() {
+                files.watch(rustls.clone(), cfg.server.tls.reload_interval);
+            }
+            Some(rustls)
+        }
+        None => None,
+    };
+    let scheme = if rustls.is_some() { "https" } else { "http" };
+    // the first listener to fail ends the process, as a lone one did
+    let server = match (admin_listener, public_app) {
+        (Some(admin), Some(public_app)) => {
+            info!("public api (reads only) listening on {}://{}", scheme, addr);
+            info!("admin api listening on {}://{}", scheme, admin.local_addr()?);
+            let servers = [serve(listener, public_app, rustls.clone())?, serve(admin, app, rustls)?];
+            futures_util::future::select_all(servers).map(|(res, _, _)| res).boxed()
+        }
+        _ => {
+            info!("api listening on {}://{}", scheme, addr);
+            serve(listener, app, rustls)?
+        }
+    };
 
-    // bind addr (support :8080)
-    let addr_str = if args.addr.starts_with(':') {
-        format!("0.0.0.0{}", args.addr)
+    // gRPC API next to the REST one; it ends with the process
+    let grpc_addr = args.grpc_addr.clone().unwrap_or_else(|| cfg.server.grpc_addr.clone());
+    let grpc_task = if grpc_addr.trim().is_empty() {
+        None
     } else {
-        args.addr.clone()
+        let addr = listen_addr(grpc_addr.trim())?;
+        let api = grpc::GrpcApi {
+            live: live_cfg.clone(),
+            prog: (*prog_arc).clone(),
+            store: store.clone(),
+            control: control.clone(),
+            notifier: grpc_notifier,
+            scans: scans.clone(),
+        };
+        Some(tokio::spawn(async move {
+            if let Err(e) = api.serve(addr).await {
+                error!("grpc server error: {e:#}");
+            }
+        }))
+    };
+
+    // the API accepts connections from here on
+    if let Some(sd) = &sd {
+        sd.ready(&format!("api listening on {addr}"));
+        sd.spawn_watchdog(health.clone(), store.clone(), control.clone());
+    }
+
+    let mut dashboard = match (tui_logs, tui_finds) {
+        (Some(logs), Some(finds)) => Some(tui::Dashboard::spawn((*prog_arc).clone(), control.clone(), finds, logs)),
+        _ => None,
+    };
+    let tui_quit = async {
+        match &mut dashboard {
+            Some(d) => d.quit().await,
+            None => std::future::pending().await,
+        }
     };
-    let addr: SocketAddr = addr_str.parse().unwrap_or_else(|_| "0.0.0.0:8080".parse().unwrap());
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    info!("api listening on {}", addr);
 
-    // graceful shutdown when ctrl-c
-    let server = axum::serve(listener, app);
-    tokio::select! {
+    // graceful shutdown on ctrl-c / SIGTERM (or quitting the dashboard): the service drains before we exit
+    let reason = tokio::select! {
         res = server => {
             if let Err(e) = res {
                 error!("server error: {e}");
             }
+            if let Some(sd) = &sd {
+                sd.stopping();
+            }
+            shutdown.trigger();
+            svc_fut.await;
+            "stopped"
         }
-        _ = svc_fut => {
+        _ = &mut svc_fut => {
             info!("service finished");
+            "finished"
         }
-        _ = tokio::signal::ctrl_c() => {
+        _ = shutdown_requested() => {
             info!("signal received, shutting down...");
+            if let Some(sd) = &sd {
+                sd.stopping();
+            }
+            shutdown.trigger();
+            svc_fut.await;
+            "stopped"
+        }
+        _ = tui_quit => {
+            info!("dashboard closed, shutting down...");
+            if let Some(sd) = &sd {
+                sd.stopping();
+            }
+            shutdown.trigger();
+            svc_fut.await;
+            "stopped"
         }
+    };
+    if let Some(d) = dashboard {
+        d.close().await;
+    }
+    digest.finish(reason).await;
+    // profile scans end with the process
+    scans.shutdown().await;
+    sinks.close().await;
+    if let Some(t) = grpc_task {
+        t.abort();
+    }
+    // the provider's flush blocks until the exporter is done
+    if let Some(t) = telemetry {
+        let _ = tokio::task::spawn_blocking(move || t.shutdown()).await;
     }
-
-    // trigger background shutdown and give tasks time to finish
-    shutdown.trigger();
-    tokio::time::sleep(Duration::from_secs(1)).await;
 
     Ok(())
 }
 
-// ------------------------- HTTP Handlers -------------------------
+// Everything under server.base_path, for a reverse proxy that forwards the prefix
+fn mount(app: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        return app;
+    }
+    // nest matches "/prefix" but not "/prefix/", where the dashboard lives
+    Router::new()
+        .route(&format!("{base_path}/"), get(dashboard_handler))
+        .nest(base_path, app)
+        .fallback(|| async { ApiError::not_found("not under server.base_path") })
+}
 
-#[derive(serde::Serialize)]
-struct StatsResp {
-    elapsed: String,
-    eta: String,
-    found: i64,
-    remaining: i64,
-    speed_per_sec: f64,
-    efficiency_percent: f64,
-    percent: f64,
-    generated: i64,
-    checked: i64,
-    total_planned: i64,
-    domains_memory_bytes: u64,
-    domains_memory_human: String,
+fn bind(addr: SocketAddr) -> anyhow::Result<std::net::TcpListener> {
+    let l = std::net::TcpListener::bind(addr)?;
+    l.set_nonblocking(true)?;
+    Ok(l)
 }
 
-fn human_bytes(n: u64) -> String {
-    const UNIT: u64 = 1024;
-    if n < UNIT {
-        return format!("{n}B");
+fn serve(
+    listener: std::net::TcpListener,
+    app: Router,
+    rustls: Option<axum_server::tls_rustls::RustlsConfig>,
+) -> anyhow::Result<futures_util::future::BoxFuture<'static, std::io::Result<()>>> {
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    Ok(match rustls {
+        Some(rustls) => axum_server::from_tcp_rustls(listener, rustls).serve(service).boxed(),
+        None => {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            async move { axum::serve(listener, service).await }.boxed()
+        }
+    })
+}
+
+// ":50051" listens on all interfaces
+fn listen_addr(s: &str) -> anyhow::Result<SocketAddr> {
+    let full = if s.starts_with(':') { format!("0.0.0.0{s}") } else { s.to_string() };
+    full.parse().map_err(|e| anyhow::anyhow!("invalid listen address {s:?}: {e}"))
+}
+
+async fn shutdown_requested() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
     }
-    let mut div = UNIT;
-    let mut exp = 0;
-    let mut m = n / UNIT;
-    while m >= UNIT {
-        div *= UNIT;
-        exp += 1;
-        m /= UNIT;
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
     }
-    let suffixes = ['K', 'M', 'G', 'T', 'P', 'E'];
-    format!("{:.1}{}iB", (n as f64) / (div as f64), suffixes[exp])
 }
 
-fn fmt_duration(d: Duration) -> String {
-    let secs = (d.as_secs_f64() + 0.5) as i64;
-    let mut h = secs / 3600;
+// ------------------------- HTTP Handlers -------------------------
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HistoryQuery {
+    /// How far back, e.g. 30m, 24h (default 24h)
+    range: Option<String>,
+    /// Add up snapshots into one per step, e.g. 15m; default every snapshot
+    step: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct StatsHistoryResp {
+    /// Seconds between snapshots (stats_history.interval)
+    interval_secs: u64,
+    /// Oldest first
+    points: Vec<history::StatsPoint>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct TldStats {
+    tld: String,
+    generated: i64,
+    checked: i64,
+    found: i64,
+    efficiency_percent: f64,
+}
+
+/// Live domains of the keyspace estimated from a `strategy: random_sample` scan
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct SampleStats {
+    rate: f64,
+    seed: u64,
+    estimates: Vec<SampleEstimate>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct SampleEstimate {
+    tld: String,
+    /// Label length in characters
+    length: usize,
+    /// Valid labels of this length
+    keyspace: u64,
+    /// Sampled candidates checked since startup
+    checked: i64,
+    found: i64,
+    /// found / checked
+    live_ratio: f64,
+    /// live_ratio x keyspace
+    estimated_live: f64,
+    /// Half-width of the 95% confidence interval of estimated_live
+    margin: f64,
+}
+
+// Log lines; subcommands keep stdout for their output. Spans only go to
+// telemetry, so the lines read the same with it on
+fn log_layer<S>(args: &Args, tui_logs: Option<&tui::LogBuffer>) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let filter = EnvFilter::from_default_env()
+        .add_directive("info".parse().unwrap())
+        .and(filter_fn(|m| m.is_event()));
+    let layer = tracing_subscriber::fmt::layer();
+    if args.command.is_some() {
+        layer.with_writer(std::io::stderr).with_filter(filter).boxed()
+    } else if let Some(logs) = tui_logs {
+        layer.with_ansi(false).with_writer(logs.clone()).with_filter(filter).boxed()
+    } else {
+        layer.with_filter(filter).boxed()
+    }
+}
+
+// http client for RDAP, webhooks and sources, tuned by http_check.client
+fn build_client(cfg: &Config) -> anyhow::Result<Client> {
+    Ok(http_pool::tune(Client::builder(), cfg).build()?)
+}
+
+// `compact` subcommand: the stores are opened here, so the service must not
+// be writing to them
+async fn run_compact(cfg: &Config, tld: Option<String>) -> anyhow::Result<()> {
+    let tld = tld.map(|t| tld_param(Some(&t)).ok_or_else(|| anyhow::anyhow!("invalid tld {t:?}"))).transpose()?;
+    let store = DomainStore::new(&cfg.storage)?;
+    let avail = cfg.rdap.enabled.then(|| AvailabilityStores::open(&cfg.storage)).transpose()?;
+    let done = compact_stores(&store, avail.as_ref(), tld.as_deref()).await?;
+    println!("{}", serde_json::to_string_pretty(&done)?);
+    Ok(())
+}
+
+// `preview` subcommand: the next candidates of the generator on stdout, nothing is checked
+async fn run_preview(path: &str, cfg: Config, count: usize, from: Option<String>) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let from = from
+        .map(|f| Domain::parse(&f).ok_or_else(|| anyhow::anyhow!("invalid domain {f:?}")))
+        .transpose()?;
+    let preview = service::preview_candidates(&LiveConfig::new(path, cfg), count, from).await?;
+    info!("preview: {} candidates after '{}'", preview.candidates.len(), preview.from);
+    let mut out = std::io::stdout().lock();
+    for d in &preview.candidates {
+        writeln!(out, "{d}")?;
+    }
+    Ok(())
+}
+
+// `check` subcommand: same pipeline as the scanner, nothing is stored
+async fn run_check(cfg: &Config, mut domains: Vec<String>) -> anyhow::Result<()> {
+    use futures_util::StreamExt;
+    use std::io::{BufRead, Write};
+
+    if domains.is_empty() || domains.iter().any(|d| d == "-") {
+        domains.retain(|d| d != "-");
+        for line in std::io::stdin().lock().lines() {
+            domains.push(line?);
+        }
+    }
+    let domains: Vec<Domain> = domains
+        .iter()
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty() && !d.starts_with('#'))
+        // Unicode names are checked by their ACE form
+        .filter_map(|d| {
+            let domain = Domain::parse(d);
+            if domain.is_none() {
+                warn!("check: skipping {d:?}: not a valid domain");
+            }
+            domain
+        })
+        .collect();
+    if domains.is_empty() {
+        anyhow::bail!("no domains given");
+    }
+
+    let client = build_client(cfg)?;
+    let resolver = dns::ResolverPool::from_config(&cfg.dns)?;
+    let rdap = rdap::RdapClient::maybe_bootstrap(&client, &cfg.rdap).await;
+    let probe = checker::Probe::new(
+        cfg,
+        checker::check_clients(cfg)?,
+        resolver,
+        rdap,
+        wildcard::WildcardFilter::from_config(&cfg.wildcard),
+    );
+
+    let mut verdicts = futures_util::stream::iter(domains.iter())
+        .map(|d| probe.evaluate(d, &cfg.http_check))
+        .buffered(cfg.limits.concurrency.max(1) as usize);
+    let mut out = std::io::stdout().lock();
+    while let Some(v) = verdicts.next().await {
+        serde_json::to_writer(&mut out, &v)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn human_bytes(n: u64) -> String {
+    const UNIT: u64 = 1024;
+    if n < UNIT {
+        return format!("{n}B");
+    }
+    let mut div = UNIT;
+    let mut exp = 0;
+    let mut m = n / UNIT;
+    while m >= UNIT {
+        div *= UNIT;
+        exp += 1;
+        m /= UNIT;
+    }
+    let suffixes = ['K', 'M', 'G', 'T', 'P', 'E'];
+    format!("{:.1}{}iB", (n as f64) / (div as f64), suffixes[exp])
+}
+
+fn fmt_duration(d: Duration) -> String {
+    let secs = (d.as_secs_f64() + 0.5) as i64;
+    let mut h = secs / 3600;
     let m = (secs % 3600) / 60;
     let s = secs % 60;
     let days = h / 24;
@@ -219,110 +1156,1021 @@ fn fmt_duration(d: Duration) -> String {
     format!("{:02}:{:02}", m, s)
 }
 
-async fn stats_handler(prog: Arc<Progress>, store: DomainStore) -> impl IntoResponse {
+/// Speed, finds and errors of the main scan over time
+#[utoipa::path(
+    get,
+    path = "/stats/history",
+    tag = "stats",
+    params(HistoryQuery),
+    responses(
+        (status = 200, body = StatsHistoryResp),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn stats_history_handler(
+    q: Result<Query<HistoryQuery>, QueryRejection>,
+    history: history::StatsHistory,
+) -> Result<Json<StatsHistoryResp>, ApiError> {
+    let Query(q) = q?;
+    let duration = |name: &str, v: &str| match config::parse_duration(v) {
+        Ok(d) if !d.is_zero() => Ok(d),
+        Ok(_) => Err(ApiError::bad_request(format!("{name} must be > 0"))),
+        Err(e) => Err(ApiError::bad_request(format!("{name}: {e}"))),
+    };
+    let range = duration("range", q.range.as_deref().unwrap_or("24h"))?;
+    let step = q.step.as_deref().map(|s| duration("step", s)).transpose()?;
+    Ok(Json(StatsHistoryResp {
+        interval_secs: history.interval().as_secs(),
+        points: history.query(range, step),
+    }))
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StatsQuery {
+    /// Payload layout, v1 (flat, Go-compatible) or v2 (grouped, with
+    /// schema_version); default server.stats_schema
+    schema: Option<stats::StatsSchema>,
+}
+
+impl StatsQuery {
+    fn schema(&self, live: &LiveConfig) -> stats::StatsSchema {
+        self.schema.unwrap_or(live.current().server.stats_schema)
+    }
+}
+
+/// Runtime and progress metrics
+#[utoipa::path(get, path = "/stats/", tag = "stats", params(StatsQuery),
+    responses((status = 200, body = stats::Stats), (status = 400, body = error::ErrorBody)))]
+async fn stats_handler(
+    q: Result<Query<StatsQuery>, QueryRejection>,
+    prog: Arc<Progress>,
+    store: DomainStore,
+    control: ScanControl,
+    live: LiveConfig,
+) -> Result<Json<stats::Stats>, ApiError> {
+    let Query(q) = q?;
     info!("stats requested");
-    let (enq, chk, fnd, elapsed) = prog.snapshot();
-    let elapsed_sec = elapsed.as_secs_f64();
-    let speed = if elapsed_sec > 0.0 {
-        (chk as f64) / elapsed_sec
-    } else {
-        0.0
-    };
-    let total_planned = prog.total_planned();
-    let mut remaining: i64 = -1;
-    let mut eta = Duration::from_secs(0);
-    let percent: f64;
-    if total_planned > 0 {
-        if chk >= total_planned {
-            remaining = 0;
-            eta = Duration::from_secs(0);
-            percent = 100.0;
-        } else {
-            remaining = total_planned - chk;
-            eta = if speed > 0.0 {
-                Duration::from_secs_f64((remaining as f64) / speed)
-            } else {
-                Duration::from_secs(0)
+    Ok(Json(stats::snapshot(&prog, &store, &control, q.schema(&live))))
+}
+
+// One message of /ws/stats
+#[derive(serde::Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+enum LiveEvent {
+    Stats(stats::Stats),
+    Found(store::FoundDomain),
+    /// Found domains this client missed because it read too slowly
+    Lagged(u64),
+}
+
+/// Live stats: the /stats/ payload every second plus every newly stored domain
+#[utoipa::path(get, path = "/ws/stats", tag = "stats",
+    responses(
+        (status = 101, description = "WebSocket of JSON text messages: {\"type\":\"stats\",\"data\":<Stats>} every second (in the schema of the `schema` query parameter), {\"type\":\"found\",\"data\":<FoundDomain>} per stored domain, {\"type\":\"lagged\",\"data\":<skipped>} when found events were dropped"),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn ws_stats_handler(
+    q: Result<Query<StatsQuery>, QueryRejection>,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+    prog: Arc<Progress>,
+    store: DomainStore,
+    control: ScanControl,
+    notifier: notify::Notifier,
+    live: LiveConfig,
+) -> Result<Response, ApiError> {
+    let Query(q) = q?;
+    let schema = q.schema(&live);
+    let ws = ws.map_err(|e| ApiError::bad_request(e.body_text()))?;
+    // client messages are ignored, larger ones end the connection
+    Ok(ws.max_message_size(64 * 1024).on_upgrade(move |mut sock| async move {
+        info!("ws: stats client connected");
+        let mut found = notifier.subscribe();
+        let mut tick = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            let event = tokio::select! {
+                _ = tick.tick() => LiveEvent::Stats(stats::snapshot(&prog, &store, &control, schema)),
+                rec = found.recv() => match rec {
+                    Ok(rec) => LiveEvent::Found(rec),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => LiveEvent::Lagged(n),
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                },
+                // reading also answers pings
+                msg = sock.recv() => match msg {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
             };
-            percent = (100.0 * (chk as f64) / (total_planned as f64)).min(100.0);
+            let Ok(text) = serde_json::to_string(&event) else { continue };
+            if sock.send(Message::Text(text)).await.is_err() {
+                break;
+            }
         }
-    } else {
-        percent = 0.0;
+        // sends our close frame, or the reply to the client's
+        let _ = SinkExt::close(&mut sock).await;
+        info!("ws: stats client disconnected");
+    }))
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DomainQuery {
+    /// Number of matching entries to skip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    /// Maximum number of entries to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    /// Only domains starting with this string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    /// Only domains containing this substring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contains: Option<String>,
+    /// "detailed" returns stored metadata objects (.json only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    /// true: only parked domains, false: only the others (parking check)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parked: Option<bool>,
+    /// Only domains hosted in these countries, comma-separated ISO codes (geoip check)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<String>,
+    /// Only domains in this autonomous system (geoip check)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asn: Option<u32>,
+}
+
+impl DomainQuery {
+    fn is_detailed(&self) -> bool {
+        self.format.as_deref() == Some("detailed")
     }
-    let eff = if chk > 0 {
-        (fnd as f64) / (chk as f64) * 100.0
-    } else {
-        0.0
+}
+
+// RFC 8288 Link header with next/prev pages of the same query; `query`
+// renders the query string for a page offset
+fn page_links(uri: &Uri, offset: usize, limit: Option<usize>, total: usize, query: impl Fn(usize) -> String) -> String {
+    let Some(limit) = limit else {
+        return String::new();
     };
-    let dom_bytes = store.approx_bytes();
-    let resp = StatsResp {
-        elapsed: fmt_duration(elapsed),
-        eta: if remaining >= 0 {
-            fmt_duration(eta)
+    let link = |off: usize, rel: &str| format!("<{}?{}>; rel=\"{}\"", uri.path(), query(off), rel);
+    let mut out = vec![];
+    if offset.saturating_add(limit) < total {
+        out.push(link(offset + limit, "next"));
+    }
+    if offset > 0 {
+        out.push(link(offset.saturating_sub(limit), "prev"));
+    }
+    out.join(", ")
+}
+
+// X-Truncated and X-Truncated-Limit when a server.lists cap, not the
+// client's own limit, left matching domains out of the page
+fn truncation_headers(resp: axum::http::response::Builder, capped: bool, offset: usize, limit: Option<usize>, total: usize) -> axum::http::response::Builder {
+    match limit {
+        Some(n) if capped && offset.saturating_add(n) < total => resp.header("X-Truncated", "true").header("X-Truncated-Limit", n.to_string()),
+        _ => resp,
+    }
+}
+
+// Matching domains as a JSON array of names, or of stored records when `detailed`,
+// streamed one element at a time
+fn json_list_body(store: DomainStore, tld: Option<String>, lq: ListQuery, detailed: bool) -> axum::body::Body {
+    streaming::blocking_body(move |w| {
+        let t = tld.as_deref();
+        let mut first = true;
+        if !w.write(b"[") {
+            return;
+        }
+        let mut sep = |w: &mut streaming::ChunkWriter| std::mem::take(&mut first) || w.write(b",");
+        if detailed {
+            store.scan_window_detailed(t, &lq, &mut |rec| sep(w) && w.write_json(&rec));
         } else {
-            "-".to_string()
-        },
-        found: fnd,
-        remaining,
-        speed_per_sec: speed,
-        efficiency_percent: eff,
-        percent,
-        generated: enq,
-        checked: chk,
-        total_planned,
-        domains_memory_bytes: dom_bytes,
-        domains_memory_human: human_bytes(dom_bytes),
-    };
-    (StatusCode::OK, Json(resp))
-}
-
-async fn domain_handler(AxPath(path): AxPath<String>, store: DomainStore) -> Response {
+            store.scan_window(t, &lq, &mut |d| sep(w) && w.write_json(&d));
+        }
+        w.write(b"]");
+    })
+}
+
+/// Stored domains of one TLD (or `__all__`) in a scan namespace
+#[utoipa::path(
+    get,
+    path = "/domain/{namespace}/{file}",
+    tag = "domains",
+    params(
+        ("namespace" = String, Path, description = "`namespace` of the profile or POST /scans/ request"),
+        ("file" = String, Path, description = "`<tld>.txt`, `<tld>.json`, `__all__.txt` or `__all__.json`"),
+        DomainQuery,
+    ),
+    responses(
+        (status = 200, description = "Domain names, or FoundDomain objects with format=detailed", body = [String],
+         headers(("X-Total-Count" = usize, description = "Matching domains"), ("Link" = String, description = "next/prev pages"),
+                 ("X-Truncated" = bool, description = "A server.lists cap left matching domains out"),
+                 ("X-Truncated-Limit" = usize, description = "Page size the cap applied"),
+                 ("ETag" = String, description = "Not sent for s3 storage"), ("Last-Modified" = String))),
+        (status = 304, description = "Unchanged since the request's If-None-Match or If-Modified-Since"),
+        (status = 400, body = error::ErrorBody),
+        (status = 404, description = "No such namespace or file", body = error::ErrorBody),
+    )
+)]
+async fn namespace_domain_handler(
+    AxPath((name, file)): AxPath<(String, String)>,
+    q: Result<Query<DomainQuery>, QueryRejection>,
+    uri: Uri,
+    headers: HeaderMap,
+    namespaces: namespace::Namespaces,
+    lists: ListLimitsConfig,
+) -> Result<Response, ApiError> {
+    let store = namespaces
+        .get(&name)
+        .map_err(|e| ApiError::internal(format!("namespace {name:?}: {e:#}")))?
+        .ok_or_else(|| ApiError::not_found(format!("no namespace {name:?}")))?;
+    domain_handler(AxPath(file), q, uri, headers, store, lists).await
+}
+
+// domains named in a delete or purge answer
+const DELETE_LISTED: usize = 1000;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct DeleteResp {
+    /// Domains removed, or matching with dry_run
+    count: usize,
+    dry_run: bool,
+    /// The first 1000 of them, ACE form
+    domains: Vec<String>,
+}
+
+/// Remove one domain from the main store, e.g. a false positive
+#[utoipa::path(
+    delete,
+    path = "/domain/{tld}/{name}",
+    tag = "domains",
+    params(("tld" = String, Path, description = "TLD (or parent zone) the domain is stored under"),
+           ("name" = String, Path, description = "The domain, or its label without the TLD")),
+    responses(
+        (status = 200, body = DeleteResp),
+        (status = 400, body = error::ErrorBody),
+        (status = 404, description = "Not stored", body = error::ErrorBody),
+    )
+)]
+async fn domain_delete_handler(AxPath(path): AxPath<String>, store: DomainStore) -> Result<Json<DeleteResp>, ApiError> {
+    let Some((tld, name)) = path.split_once('/').filter(|(_, n)| !n.is_empty() && !n.contains('/')) else {
+        return Err(ApiError::not_found(format!("expected <tld>/<name>, got {path:?}")));
+    };
+    let tld = tld_param(Some(tld)).ok_or_else(|| ApiError::bad_request("tld is required"))?;
+    let name = name.trim().trim_end_matches('.');
+    let name = if name.contains('.') { name.to_string() } else { format!("{name}.{tld}") };
+    let name = Domain::parse(&name).ok_or_else(|| ApiError::bad_request(format!("invalid domain {name:?}")))?;
+    if store::extract_tld(&name) != Some(tld.as_str()) {
+        return Err(ApiError::bad_request(format!("{name} is not stored under {tld}")));
+    }
+    let target = name.clone();
+    let removed = store
+        .delete(&tld, Arc::new(move |d: &str| d == target.as_str()))
+        .await
+        .map_err(|e| ApiError::internal(format!("delete failed: {e:#}")))?;
+    if removed.is_empty() {
+        return Err(ApiError::not_found(format!("{name} is not stored")));
+    }
+    info!("domain deleted: {}", name);
+    Ok(Json(DeleteResp {
+        count: removed.len(),
+        dry_run: false,
+        domains: removed,
+    }))
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PurgeQuery {
+    /// Case-insensitive regular expression over the ACE form of the domains
+    #[serde(default)]
+    pattern: String,
+    /// Only list what would be removed
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Remove every domain of one TLD in the main store matching a pattern
+#[utoipa::path(
+    post,
+    path = "/domain/{tld}/purge",
+    tag = "domains",
+    params(("tld" = String, Path, description = "TLD (or parent zone) to purge"), PurgeQuery),
+    responses(
+        (status = 200, body = DeleteResp),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn domain_purge_handler(
+    AxPath(path): AxPath<String>,
+    q: Result<Query<PurgeQuery>, QueryRejection>,
+    store: DomainStore,
+) -> Result<Json<DeleteResp>, ApiError> {
+    let Query(q) = q?;
+    let Some(tld) = path.strip_suffix("/purge").filter(|t| !t.contains('/')) else {
+        return Err(ApiError::not_found(format!("expected <tld>/purge, got {path:?}")));
+    };
+    let tld = tld_param(Some(tld)).ok_or_else(|| ApiError::bad_request("tld is required"))?;
+    if q.pattern.trim().is_empty() {
+        return Err(ApiError::bad_request("pattern is required"));
+    }
+    let re = regex::RegexBuilder::new(q.pattern.trim())
+        .case_insensitive(true)
+        .size_limit(1 << 20)
+        .build()
+        .map_err(|e| ApiError::bad_request(format!("invalid pattern: {e}")))?;
+    let matches: DomainMatcher = Arc::new(move |d: &str| re.is_match(d));
+    let mut domains = if q.dry_run {
+        store.flush().await;
+        let (store, tld) = (store.clone(), tld.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut hits = std::collections::BTreeSet::new();
+            store.scan(Some(&tld), &mut |d| {
+                if matches(d) {
+                    hits.insert(d.to_string());
+                }
+                true
+            });
+            hits.into_iter().collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+    } else {
+        store
+            .delete(&tld, matches)
+            .await
+            .map_err(|e| ApiError::internal(format!("purge failed: {e:#}")))?
+    };
+    info!("domains purged: tld={} pattern={:?} dry_run={} count={}", tld, q.pattern, q.dry_run, domains.len());
+    let count = domains.len();
+    domains.truncate(DELETE_LISTED);
+    Ok(Json(DeleteResp {
+        count,
+        dry_run: q.dry_run,
+        domains,
+    }))
+}
+
+/// Stored domains of one TLD (or `__all__`) as text or JSON
+#[utoipa::path(
+    get,
+    path = "/domain/{file}",
+    tag = "domains",
+    params(("file" = String, Path, description = "`<tld>.txt`, `<tld>.json`, `__all__.txt` or `__all__.json`"), DomainQuery),
+    responses(
+        (status = 200, description = "Domain names, or FoundDomain objects with format=detailed", body = [String],
+         headers(("X-Total-Count" = usize, description = "Matching domains"), ("Link" = String, description = "next/prev pages"),
+                 ("X-Truncated" = bool, description = "A server.lists cap left matching domains out"),
+                 ("X-Truncated-Limit" = usize, description = "Page size the cap applied"),
+                 ("ETag" = String, description = "Not sent for s3 storage"), ("Last-Modified" = String),
+                 ("Accept-Ranges" = String, description = "`bytes` for an unfiltered .txt list from text storage, else `none`"))),
+        (status = 206, description = "The requested byte range of an unfiltered .txt list",
+         headers(("Content-Range" = String, description = "e.g. `bytes 100-199/5000`"))),
+        (status = 304, description = "Unchanged since the request's If-None-Match or If-Modified-Since"),
+        (status = 400, body = error::ErrorBody),
+        (status = 404, body = error::ErrorBody),
+        (status = 416, description = "The range starts past the end of the list"),
+    )
+)]
+async fn domain_handler(
+    AxPath(path): AxPath<String>,
+    q: Result<Query<DomainQuery>, QueryRejection>,
+    uri: Uri,
+    headers: HeaderMap,
+    store: DomainStore,
+    lists: ListLimitsConfig,
+) -> Result<Response, ApiError> {
+    let Query(q) = q?;
     // Expect path like ru.txt or ru.json or __all__.txt or __all__.json
+    let bad_path = || ApiError::not_found(format!("expected <tld>.txt or <tld>.json, got {path:?}"));
     if path.is_empty() || path.contains('/') {
-        return StatusCode::NOT_FOUND.into_response();
+        return Err(bad_path());
     }
-    let dot = path.rfind('.');
-    let Some(dot) = dot else {
-        return StatusCode::NOT_FOUND.into_response();
+    let Some(dot) = path.rfind('.') else {
+        return Err(bad_path());
     };
     if dot == 0 || dot == path.len() - 1 {
-        return StatusCode::NOT_FOUND.into_response();
+        return Err(bad_path());
     }
-    let tld = path[..dot].to_lowercase();
+    // "рф" and "xn--p1ai" name the same file
+    let tld = domain::normalize_tld(&path[..dot]).unwrap_or_default();
     let ext = path[dot + 1..].to_lowercase();
     info!("domain requested: path={}, tld={}, ext={}", path, tld, ext);
+    if ext != "txt" && ext != "json" {
+        return Err(bad_path());
+    }
+
+    let all = tld == "__all__";
+    // server.lists caps __all__ unless the client pages explicitly, and every page
+    let (limit, capped) = lists.page(q.limit, all);
+    let lq = ListQuery {
+        offset: q.offset.unwrap_or(0),
+        limit,
+        prefix: q.prefix.clone().unwrap_or_default(),
+        contains: q.contains.clone().unwrap_or_default(),
+        pattern: None,
+        parked: q.parked,
+        countries: q
+            .country
+            .iter()
+            .flat_map(|c| c.split(','))
+            .map(|c| c.trim().to_uppercase())
+            .filter(|c| !c.is_empty())
+            .collect(),
+        asn: q.asn,
+    };
+    let tld_filter = if all { None } else { Some(tld) };
+    let detailed = q.is_detailed();
+    if detailed && ext != "json" {
+        return Err(ApiError::bad_request("format=detailed requires .json"));
+    }
+
+    // a client that has this list already gets a 304 before anything is read
+    let version = {
+        let (store, tld) = (store.clone(), tld_filter.clone());
+        tokio::task::spawn_blocking(move || store.version(tld.as_deref()))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?
+    };
+    let validators = version.map(|v| (list_etag(v, &uri), http_date(v.modified_unix)));
+    if let (Some(v), Some((etag, modified))) = (version, &validators) {
+        if not_modified(&headers, etag, v.modified_unix) {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header(header::ETAG, etag)
+                .header(header::LAST_MODIFIED, modified)
+                .header(header::CACHE_CONTROL, "no-cache")
+                .body(axum::body::Body::empty())
+                .unwrap());
+        }
+    }
+
+    // counting is a separate pass so the body itself never has to be held in memory
+    let total = {
+        let (store, tld, lq) = (store.clone(), tld_filter.clone(), lq.clone());
+        tokio::task::spawn_blocking(move || store.count(tld.as_deref(), &lq))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?
+    };
+    let links = page_links(&uri, lq.offset, limit, total, |off| {
+        serde_urlencoded::to_string(DomainQuery {
+            offset: Some(off),
+            limit,
+            ..q.clone()
+        })
+        .unwrap_or_default()
+    });
+
+    let offset = lq.offset;
+    let content_type = if ext == "txt" {
+        "text/plain; charset=utf-8"
+    } else {
+        "application/json; charset=utf-8"
+    };
+    // a whole plain list is the backend's files as they are, so a download
+    // can be resumed from a byte offset
+    let files = if ext == "txt" && lq.is_unfiltered() {
+        let (store, tld) = (store.clone(), tld_filter.clone());
+        tokio::task::spawn_blocking(move || store.text_files(tld.as_deref()))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?
+    } else {
+        None
+    };
+    let mut resp = Response::builder();
+    let body = match files {
+        Some(files) => {
+            let len: u64 = files.iter().map(|(_, n)| n).sum();
+            let etag = validators.as_ref().map(|(etag, _)| etag.as_str());
+            let (start, end) = match byte_range(&headers, len, etag, version.map(|v| v.modified_unix)) {
+                ByteRange::Full => {
+                    resp = resp.status(StatusCode::OK);
+                    (0, len.saturating_sub(1))
+                }
+                ByteRange::Part(start, end) => {
+                    resp = resp
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"));
+                    (start, end)
+                }
+                ByteRange::Unsatisfiable => {
+                    return Ok(Response::builder()
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+                        .header(header::ACCEPT_RANGES, "bytes")
+                        .body(axum::body::Body::empty())
+                        .unwrap());
+                }
+            };
+            let size = if len == 0 { 0 } else { end - start + 1 };
+            resp = resp.header(header::ACCEPT_RANGES, "bytes").header(header::CONTENT_LENGTH, size);
+            streaming::file_body(files, start, end)
+        }
+        None if ext == "txt" => {
+            resp = resp.status(StatusCode::OK).header(header::ACCEPT_RANGES, "none");
+            streaming::blocking_body(move |w| {
+                store.scan_window(tld_filter.as_deref(), &lq, &mut |d| w.write(d.as_bytes()) && w.write(b"\n"));
+            })
+        }
+        None => {
+            resp = resp.status(StatusCode::OK).header(header::ACCEPT_RANGES, "none");
+            json_list_body(store, tld_filter, lq, detailed)
+        }
+    };
+
+    resp = resp
+        .header("Content-Type", content_type)
+        .header("X-Total-Count", total.to_string())
+        .header(header::CACHE_CONTROL, "no-cache");
+    resp = truncation_headers(resp, capped, offset, limit, total);
+    if let Some((etag, modified)) = validators {
+        resp = resp.header(header::ETAG, etag).header(header::LAST_MODIFIED, modified);
+    }
+    if !links.is_empty() {
+        resp = resp.header("Link", links);
+    }
+    Ok(resp.body(body).unwrap())
+}
+
+// ETag of a list response: the store's version and the request URI, which
+// holds everything else that shapes the body (file, filters, page, format)
+fn list_etag(v: store::Version, uri: &Uri) -> String {
+    format!("\"{:016x}\"", dedup::fnv1a(format!("{:x}|{}", v.tag, uri).as_bytes()))
+}
+
+// whether the client's copy is current: If-None-Match when sent, else If-Modified-Since.
+// Last-Modified has whole seconds, so data changed this second counts as modified
+fn not_modified(headers: &HeaderMap, etag: &str, modified_unix: u64) -> bool {
+    if let Some(tags) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        return tags.split(',').map(|t| t.trim().trim_start_matches("W/")).any(|t| t == etag || t == "*");
+    }
+    let now = chrono::Utc::now().timestamp();
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| chrono::DateTime::parse_from_rfc2822(s).ok())
+        .is_some_and(|since| since.timestamp() >= modified_unix as i64 && (modified_unix as i64) < now)
+}
+
+enum ByteRange {
+    Full,
+    // first and last byte, inclusive
+    Part(u64, u64),
+    Unsatisfiable,
+}
+
+// The one byte range of a `Range: bytes=...` request over `len` bytes. Several
+// ranges, other units, a malformed header and an If-Range that no longer
+// matches get the whole body, as RFC 9110 allows
+fn byte_range(headers: &HeaderMap, len: u64, etag: Option<&str>, modified_unix: Option<u64>) -> ByteRange {
+    let Some(spec) = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+    else {
+        return ByteRange::Full;
+    };
+    if let Some(cond) = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        let cond = cond.trim();
+        // a weak tag never matches here
+        let current = if cond.starts_with('"') {
+            etag.is_some_and(|e| e == cond)
+        } else {
+            modified_unix.is_some_and(|m| http_date(m) == cond)
+        };
+        if !current {
+            return ByteRange::Full;
+        }
+    }
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        // the last n bytes
+        match last.parse::<u64>() {
+            Ok(n) if n > 0 && len > 0 => Some((len.saturating_sub(n), len - 1)),
+            Ok(_) => None,
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        let Ok(a) = first.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        let b = if last.is_empty() { Ok(u64::MAX) } else { last.parse::<u64>() };
+        match b {
+            Ok(b) if a <= b => (a < len).then(|| (a, b.min(len - 1))),
+            _ => return ByteRange::Full,
+        }
+    };
+    match range {
+        Some((a, b)) => ByteRange::Part(a, b),
+        None => ByteRange::Unsatisfiable,
+    }
+}
+
+// RFC 7231 date, e.g. "Sun, 06 Nov 1994 08:49:37 GMT"
+fn http_date(unix: u64) -> String {
+    chrono::DateTime::from_timestamp(unix as i64, 0)
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+// Page size of /search/ when the client does not pass a limit
+const SEARCH_DEFAULT_LIMIT: usize = 1000;
 
-    let list = if tld == "__all__" {
-        store.list_all()
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchQuery {
+    /// Substring to look for, or a regular expression with regex=true (matched against the ACE form)
+    #[serde(default)]
+    q: String,
+    /// Treat q as a case-insensitive regular expression
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    regex: bool,
+    /// Only domains of this TLD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tld: Option<String>,
+    /// Number of matching entries to skip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    /// Maximum number of entries to return (default 1000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    /// "detailed" returns stored metadata objects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+}
+
+/// Stored domains matching a substring or regular expression
+#[utoipa::path(
+    get,
+    path = "/search/",
+    tag = "domains",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Domain names, or FoundDomain objects with format=detailed", body = [String],
+         headers(("X-Total-Count" = usize, description = "Matching domains"), ("Link" = String, description = "next/prev pages"),
+                 ("X-Truncated" = bool, description = "server.lists.max_limit left matching domains out"),
+                 ("X-Truncated-Limit" = usize, description = "Page size the cap applied"))),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn search_handler(
+    q: Result<Query<SearchQuery>, QueryRejection>,
+    uri: Uri,
+    store: DomainStore,
+    lists: ListLimitsConfig,
+) -> Result<Response, ApiError> {
+    let Query(q) = q?;
+    // as typed for a regex: lowercasing would turn \D, \W, \S into \d, \w, \s
+    let query = q.q.trim();
+    if query.is_empty() {
+        return Err(ApiError::bad_request("q is required"));
+    }
+    info!("search requested: q={:?} regex={} tld={:?}", query, q.regex, q.tld);
+    let pattern = if q.regex {
+        let re = regex::RegexBuilder::new(query)
+            .case_insensitive(true)
+            .size_limit(1 << 20)
+            .build()
+            .map_err(|e| ApiError::bad_request(format!("invalid regex: {e}")))?;
+        Some(re)
     } else {
-        store.list(&tld)
+        None
     };
+    let asked = q.limit.unwrap_or(SEARCH_DEFAULT_LIMIT);
+    let (page, capped) = lists.page(Some(asked), false);
+    let limit = page.unwrap_or(asked);
+    let lq = ListQuery {
+        offset: q.offset.unwrap_or(0),
+        limit: Some(limit),
+        prefix: String::new(),
+        contains: if q.regex { String::new() } else { query.to_lowercase() },
+        pattern,
+        ..Default::default()
+    };
+    let tld = tld_param(q.tld.as_deref());
+
+    let total = {
+        let (store, tld, lq) = (store.clone(), tld.clone(), lq.clone());
+        tokio::task::spawn_blocking(move || store.count(tld.as_deref(), &lq))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?
+    };
+    let links = page_links(&uri, lq.offset, Some(limit), total, |off| {
+        serde_urlencoded::to_string(SearchQuery {
+            offset: Some(off),
+            limit: Some(limit),
+            ..q.clone()
+        })
+        .unwrap_or_default()
+    });
+    let detailed = q.format.as_deref() == Some("detailed");
+    let mut resp = Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json; charset=utf-8")
+        .header("X-Total-Count", total.to_string());
+    resp = truncation_headers(resp, capped, lq.offset, Some(limit), total);
+    if !links.is_empty() {
+        resp = resp.header("Link", links);
+    }
+    Ok(resp.body(json_list_body(store, tld, lq, detailed)).unwrap())
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FoundQuery {
+    /// Range start, unix seconds (inclusive)
+    #[serde(default)]
+    from: u64,
+    /// Range end, unix seconds (inclusive)
+    to: Option<u64>,
+    tld: Option<String>,
+}
+
+// The web dashboard; it uses relative links, so under a base path "/prefix" needs to become "/prefix/"
+async fn dashboard_handler(OriginalUri(uri): OriginalUri) -> Response {
+    if !uri.path().ends_with('/') {
+        return Redirect::permanent(&format!("{}/", uri.path())).into_response();
+    }
+    axum::response::Html(include_str!("dashboard.html")).into_response()
+}
 
-    match ext.as_str() {
-        "txt" => {
-            let body = list.join("\n") + "\n";
-            Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "text/plain; charset=utf-8")
-                .body(Body::from(body))
-                .unwrap()
+// Liveness probe: 503 only when the store writer has been stuck for minutes
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, body = health::HealthReport), (status = 503, body = health::HealthReport))
+)]
+async fn healthz_handler(health: health::Health, store: DomainStore, control: ScanControl) -> Response {
+    health_response(health.liveness(&store, &control))
+}
+
+// Readiness probe: 503 while starting, when the resolver does not answer or the store writer lags
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses((status = 200, body = health::HealthReport), (status = 503, body = health::HealthReport))
+)]
+async fn readyz_handler(health: health::Health, store: DomainStore, control: ScanControl) -> Response {
+    health_response(health.readiness(&store, &control))
+}
+
+fn health_response(report: health::HealthReport) -> Response {
+    let status = if report.is_ok() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(report)).into_response()
+}
+
+// "рф" and "xn--p1ai" name the same TLD
+fn tld_param(tld: Option<&str>) -> Option<String> {
+    tld.and_then(domain::normalize_tld)
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AnalyticsQuery {
+    /// Only domains of this TLD
+    tld: Option<String>,
+    /// Characters per prefix in top_prefixes, 1-5 (default 2)
+    prefix_len: Option<usize>,
+    /// Number of top_prefixes entries (default 20, at most 1000)
+    top: Option<usize>,
+}
+
+/// Distribution of stored domains by length, TLD, character class and prefix
+#[utoipa::path(
+    get,
+    path = "/analytics/",
+    tag = "domains",
+    params(AnalyticsQuery),
+    responses(
+        (status = 200, body = analytics::Analytics),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn analytics_handler(q: Result<Query<AnalyticsQuery>, QueryRejection>, store: DomainStore) -> Result<Json<analytics::Analytics>, ApiError> {
+    let Query(q) = q?;
+    let prefix_len = q.prefix_len.unwrap_or(2);
+    if !(1..=5).contains(&prefix_len) {
+        return Err(ApiError::bad_request("prefix_len must be 1-5"));
+    }
+    let top = q.top.unwrap_or(20).min(1000);
+    let tld = tld_param(q.tld.as_deref());
+    info!("analytics requested: tld={:?} prefix_len={} top={}", tld, prefix_len, top);
+    tokio::task::spawn_blocking(move || analytics::summarize(&store, tld.as_deref(), prefix_len, top))
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IpsQuery {
+    /// Number of addresses listed (default 20, at most 1000)
+    top: Option<usize>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IpQuery {
+    /// Domains to skip
+    #[serde(default)]
+    offset: usize,
+    /// Most domains returned (default all)
+    limit: Option<usize>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct IpsTop {
+    /// Distinct addresses the stored domains resolved to
+    addresses: usize,
+    /// Addresses shared by the most domains, most first
+    top: Vec<IpCount>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct IpCount {
+    ip: String,
+    domains: usize,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct IpDomains {
+    ip: String,
+    /// Domains with this address, before offset and limit
+    count: usize,
+    /// By name, ACE form
+    domains: Vec<String>,
+}
+
+/// Addresses shared by the most stored domains: parking farms, shared hosting
+#[utoipa::path(
+    get,
+    path = "/ips/",
+    tag = "domains",
+    params(IpsQuery),
+    responses(
+        (status = 200, body = IpsTop),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn ips_top_handler(q: Result<Query<IpsQuery>, QueryRejection>, store: DomainStore) -> Result<Json<IpsTop>, ApiError> {
+    let Query(q) = q?;
+    let top = q.top.unwrap_or(20).min(1000);
+    let index = store.ip_index().await.map_err(|e| ApiError::internal(e.to_string()))?;
+    let (top, addresses) = index.top(top);
+    Ok(Json(IpsTop {
+        addresses,
+        top: top.into_iter().map(|(ip, domains)| IpCount { ip: ip.to_string(), domains }).collect(),
+    }))
+}
+
+/// Stored domains that resolved to an address (A or AAAA)
+#[utoipa::path(
+    get,
+    path = "/ips/{ip}",
+    tag = "domains",
+    params(("ip" = String, Path, description = "IPv4 or IPv6 address"), IpQuery),
+    responses(
+        (status = 200, body = IpDomains),
+        (status = 400, body = error::ErrorBody),
+    )
+)]
+async fn ip_domains_handler(
+    AxPath(ip): AxPath<String>,
+    q: Result<Query<IpQuery>, QueryRejection>,
+    store: DomainStore,
+) -> Result<Json<IpDomains>, ApiError> {
+    let Query(q) = q?;
+    let addr: std::net::IpAddr = ip
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| ApiError::bad_request(format!("{ip:?} is not an IP address")))?;
+    let index = store.ip_index().await.map_err(|e| ApiError::internal(e.to_string()))?;
+    let all = index.domains(&addr);
+    let count = all.len();
+    let domains = all.into_iter().skip(q.offset).take(q.limit.unwrap_or(usize::MAX)).collect();
+    Ok(Json(IpDomains { ip: addr.to_string(), count, domains }))
+}
+
+// Domains discovered within [from, to] (unix seconds); sqlite backend only
+#[utoipa::path(
+    get,
+    path = "/found/",
+    tag = "domains",
+    params(FoundQuery),
+    responses(
+        (status = 200, body = [store::FoundDomain]),
+        (status = 501, description = "Text or S3 storage backend", body = error::ErrorBody),
+    )
+)]
+async fn found_handler(q: Result<Query<FoundQuery>, QueryRejection>, store: DomainStore) -> Result<Response, ApiError> {
+    let Query(q) = q?;
+    info!("found requested: from={} to={:?} tld={:?}", q.from, q.to, q.tld);
+    let list = tokio::task::spawn_blocking(move || store.list_range(q.from, q.to.unwrap_or(u64::MAX), q.tld.as_deref()))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    match list {
+        Ok(list) => Ok((StatusCode::OK, Json(list)).into_response()),
+        Err(e) => Err(ApiError::new(StatusCode::NOT_IMPLEMENTED, "not_implemented", e.to_string())),
+    }
+}
+
+// Prometheus scrape target
+#[utoipa::path(get, path = "/metrics", tag = "stats",
+    responses((status = 200, content_type = "text/plain", body = String,
+        description = "Prometheus text format: the /stats/ counters and DNS/HTTP latency histograms")))]
+async fn metrics_handler(prog: Arc<Progress>, store: DomainStore, control: ScanControl) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        metrics::render(&prog, &store, &control),
+    )
+}
+
+// Per-TLD counters since startup; configured TLDs not generated yet are listed with zeros
+#[utoipa::path(get, path = "/stats/tlds/", tag = "stats", responses((status = 200, body = [TldStats])))]
+async fn tld_stats_handler(prog: Arc<Progress>, live: LiveConfig) -> Json<Vec<TldStats>> {
+    let mut counts: std::collections::BTreeMap<String, (i64, i64, i64)> = prog
+        .tld_snapshot()
+        .into_iter()
+        .map(|(tld, enq, chk, fnd)| (tld, (enq, chk, fnd)))
+        .collect();
+    for t in live.current().generator.tlds.iter() {
+        // ".co.uk" is counted under "uk", like the storage files
+        let t = t.trim().to_lowercase();
+        if let Some(tld) = t.rsplit('.').next().filter(|s| !s.is_empty()) {
+            // counters are keyed by the ACE form
+            let tld = idna::domain_to_ascii(tld).unwrap_or_else(|_| tld.to_string());
+            counts.entry(tld).or_default();
         }
-        "json" => match serde_json::to_vec(&list) {
-            Ok(b) => Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "application/json; charset=utf-8")
-                .body(Body::from(b))
-                .unwrap(),
-            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-        },
-        _ => StatusCode::NOT_FOUND.into_response(),
     }
+    for z in live.current().generator.parent_zones.iter() {
+        let z = z.trim().trim_matches('.').to_lowercase();
+        counts.entry(idna::domain_to_ascii(&z).unwrap_or(z)).or_default();
+    }
+    let out = counts
+        .into_iter()
+        .map(|(tld, (generated, checked, found))| TldStats {
+            tld,
+            generated,
+            checked,
+            found,
+            efficiency_percent: if checked > 0 {
+                (found as f64) / (checked as f64) * 100.0
+            } else {
+                0.0
+            },
+        })
+        .collect();
+    Json(out)
+}
+
+// Per-TLD and length estimates of a random sample scan; 404 with strategy exhaustive
+#[utoipa::path(
+    get,
+    path = "/stats/sample/",
+    tag = "stats",
+    responses(
+        (status = 200, body = SampleStats),
+        (status = 404, description = "No random_sample generator since startup", body = error::ErrorBody),
+    )
+)]
+async fn sample_stats_handler(prog: Arc<Progress>) -> Result<Json<SampleStats>, ApiError> {
+    let space = generator::sample_space().ok_or_else(|| ApiError::not_found("no generator.strategy random_sample scan since startup"))?;
+    let estimates = prog
+        .length_snapshot()
+        .into_iter()
+        .filter(|(_, _, checked, _)| *checked > 0)
+        .filter_map(|(tld, length, checked, found)| {
+            let keyspace = *space.keyspace.get(&length)?;
+            let (n, size) = (checked as f64, keyspace as f64);
+            let p = found as f64 / n;
+            // normal approximation, with the finite population correction
+            let fpc = if keyspace > 1 { ((size - n) / (size - 1.0)).max(0.0) } else { 0.0 };
+            let margin = 1.96 * size * (p * (1.0 - p) / n * fpc).sqrt();
+            Some(SampleEstimate { tld, length, keyspace, checked, found, live_ratio: p, estimated_live: p * size, margin })
+        })
+        .collect();
+    Ok(Json(SampleStats { rate: space.rate, seed: space.seed, estimates }))
 }
 
-async fn tlds_handler(cfg_tlds: Arc<Vec<String>>) -> impl IntoResponse {
+/// Configured TLDs without the leading dot
+#[utoipa::path(get, path = "/tlds/", tag = "config", responses((status = 200, body = [String])))]
+async fn tlds_handler(live: LiveConfig) -> impl IntoResponse {
     info!("tlds requested");
+    let cfg = live.current();
     let mut uniq = std::collections::BTreeSet::new();
-    for t in cfg_tlds.iter() {
+    for t in cfg.generator.tlds.iter() {
         let mut s = t.trim().to_lowercase();
         if s.starts_with('.') {
             s = s[1..].to_string();
@@ -334,3 +2182,496 @@ async fn tlds_handler(cfg_tlds: Arc<Vec<String>>) -> impl IntoResponse {
     let out: Vec<String> = uniq.into_iter().collect();
     (StatusCode::OK, Json(out))
 }
+
+#[derive(Clone, Copy, Debug)]
+enum ControlAction {
+    Pause,
+    Resume,
+    Stop,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct ControlResp {
+    state: ScanState,
+}
+
+/// Pause the scan, holding the generator position and queued candidates
+#[utoipa::path(post, path = "/control/pause", tag = "control",
+    responses((status = 200, body = ControlResp), (status = 409, description = "Scan is stopped", body = error::ErrorBody)))]
+async fn pause_handler(control: ScanControl) -> Result<Json<ControlResp>, ApiError> {
+    control_handler(control, ControlAction::Pause).await
+}
+
+/// Resume a paused scan
+#[utoipa::path(post, path = "/control/resume", tag = "control",
+    responses((status = 200, body = ControlResp), (status = 409, description = "Scan is stopped", body = error::ErrorBody)))]
+async fn resume_handler(control: ScanControl) -> Result<Json<ControlResp>, ApiError> {
+    control_handler(control, ControlAction::Resume).await
+}
+
+/// Stop the scan (terminal); resume state is saved and the process exits
+#[utoipa::path(post, path = "/control/stop", tag = "control", responses((status = 200, body = ControlResp)))]
+async fn stop_handler(control: ScanControl) -> Result<Json<ControlResp>, ApiError> {
+    control_handler(control, ControlAction::Stop).await
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CompactQuery {
+    /// Only this TLD (or parent zone); all when absent
+    tld: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct CompactResp {
+    found: store::Compacted,
+    /// The RDAP stores, with rdap.enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    available: Option<store::Compacted>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registered: Option<store::Compacted>,
+}
+
+async fn compact_stores(store: &DomainStore, avail: Option<&AvailabilityStores>, tld: Option<&str>) -> anyhow::Result<CompactResp> {
+    let found = store.compact(tld).await?;
+    let (available, registered) = match avail {
+        Some(st) => (Some(st.available.compact(tld).await?), Some(st.registered.compact(tld).await?)),
+        None => (None, None),
+    };
+    info!(
+        "storage compacted: {} tlds, {} files rewritten, {} repeated names dropped, {} kept",
+        found.tlds, found.files_rewritten, found.duplicates_removed, found.domains
+    );
+    Ok(CompactResp {
+        found,
+        available,
+        registered,
+    })
+}
+
+/// Sort the stored per-TLD lists and drop repeated names (storage.backend:
+/// text); the store writer holds new finds until it is done
+#[utoipa::path(post, path = "/control/compact", tag = "control", params(CompactQuery),
+    responses((status = 200, body = CompactResp), (status = 400, description = "Not a text backend", body = error::ErrorBody)))]
+async fn compact_handler(
+    q: Result<Query<CompactQuery>, QueryRejection>,
+    store: DomainStore,
+    avail: Option<AvailabilityStores>,
+    backend: store::StorageBackendKind,
+) -> Result<Json<CompactResp>, ApiError> {
+    let Query(q) = q?;
+    if backend != store::StorageBackendKind::Text {
+        return Err(ApiError::bad_request("compaction requires storage.backend: text"));
+    }
+    let tld = match q.tld.as_deref() {
+        Some(t) => Some(tld_param(Some(t)).ok_or_else(|| ApiError::bad_request("tld must not be empty"))?),
+        None => None,
+    };
+    info!("storage compaction requested: tld={:?}", tld);
+    compact_stores(&store, avail.as_ref(), tld.as_deref())
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal(format!("compaction failed: {e:#}")))
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PreviewQuery {
+    /// Number of candidates (default 100, at most 10000)
+    count: Option<usize>,
+    /// Start after this domain instead of the saved resume point
+    from: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct PreviewResp {
+    /// The pass starts after this domain; empty from the beginning
+    from: String,
+    count: usize,
+    /// The source has fewer candidates than asked for
+    exhausted: bool,
+    /// ACE form, in the order they would be queued
+    candidates: Vec<String>,
+}
+
+/// The first candidates a scan with the current config would queue, from the
+/// saved resume point; nothing is checked or stored
+#[utoipa::path(
+    get,
+    path = "/preview/candidates",
+    tag = "control",
+    params(PreviewQuery),
+    responses(
+        (status = 200, body = PreviewResp),
+        (status = 400, body = error::ErrorBody),
+        (status = 422, description = "The candidate source cannot run with this config", body = error::ErrorBody),
+    )
+)]
+async fn preview_handler(q: Result<Query<PreviewQuery>, QueryRejection>, live: LiveConfig) -> Result<Json<PreviewResp>, ApiError> {
+    let Query(q) = q?;
+    let count = q.count.unwrap_or(100).min(10_000);
+    info!("candidate preview requested: count={} from={:?}", count, q.from);
+    let from = match q.from.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+        Some(f) => Some(Domain::parse(f).ok_or_else(|| ApiError::bad_request(format!("invalid domain {f:?}")))?),
+        None => None,
+    };
+    let preview = service::preview_candidates(&live, count, from)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_config", format!("{e:#}")))?;
+    Ok(Json(PreviewResp {
+        from: preview.from,
+        count: preview.candidates.len(),
+        exhausted: preview.exhausted,
+        candidates: preview.candidates,
+    }))
+}
+
+/// Queue external candidates for checking ahead of the generator: a JSON array
+/// of names, or one name per line (zone-file records use their owner name)
+#[utoipa::path(post, path = "/candidates/", tag = "control",
+    request_body(content = String, content_type = "text/plain", description = "One domain per line, or a JSON array with Content-Type: application/json"),
+    responses((status = 202, body = ingest::IngestResp), (status = 400, body = error::ErrorBody), (status = 409, description = "Scan is stopped", body = error::ErrorBody)))]
+async fn candidates_handler(
+    queue: Arc<ingest::CandidateQueue>,
+    live: LiveConfig,
+    dedup: Option<Arc<dedup::Dedup>>,
+    prog: Arc<Progress>,
+    control: ScanControl,
+    headers: HeaderMap,
+    body: String,
+) -> Result<(StatusCode, Json<ingest::IngestResp>), ApiError> {
+    if control.state() == ScanState::Stopped {
+        return Err(ApiError::conflict("scan is stopped"));
+    }
+    let json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("json"));
+    let entries = ingest::parse_body(&body, json).map_err(|e| ApiError::bad_request(format!("invalid JSON list: {e}")))?;
+    let mut resp = ingest::IngestResp::default();
+    let max_queued = live.current().ingest.max_queued;
+    let mut fresh = Vec::new();
+    for d in ingest::prepare(entries, &mut resp) {
+        // same duplicate rule as generated candidates
+        if dedup.as_ref().is_some_and(|dd| dd.checked_before(&d)) {
+            resp.duplicates += 1;
+            prog.inc_duplicate();
+        } else {
+            fresh.push(d);
+        }
+    }
+    // only what the queue took is marked seen and counted, so a rejected
+    // name can be posted again
+    resp.accepted = queue.push(fresh.clone(), max_queued);
+    resp.rejected += fresh.len() - resp.accepted;
+    for d in &fresh[..resp.accepted] {
+        if let Some(dd) = &dedup {
+            dd.first_check(d);
+        }
+        prog.inc_enqueued(d);
+    }
+    resp.queued = queue.len();
+    info!(
+        "candidates: {} accepted, {} duplicates, {} invalid, {} rejected",
+        resp.accepted, resp.duplicates, resp.invalid, resp.rejected
+    );
+    Ok((StatusCode::ACCEPTED, Json(resp)))
+}
+
+async fn control_handler(control: ScanControl, action: ControlAction) -> Result<Json<ControlResp>, ApiError> {
+    info!("control requested: {:?}", action);
+    let before = control.state();
+    let after = match action {
+        ControlAction::Pause => control.pause(),
+        ControlAction::Resume => control.resume(),
+        ControlAction::Stop => control.stop(),
+    };
+    // pause/resume on a stopped scan cannot take effect
+    if before == ScanState::Stopped && !matches!(action, ControlAction::Stop) {
+        return Err(ApiError::conflict("scan is stopped"));
+    }
+    Ok(Json(ControlResp { state: after }))
+}
+
+/// Scans started from profiles, running and recently finished
+#[utoipa::path(get, path = "/scans/", tag = "scans", responses((status = 200, body = [scans::ScanInfo])))]
+async fn scans_list_handler(scans: Arc<scans::ScanManager>) -> Json<Vec<scans::ScanInfo>> {
+    Json(scans.list())
+}
+
+/// Start a scan from a named profile, or with generator settings of its own
+#[utoipa::path(post, path = "/scans/", tag = "scans", request_body = scans::StartScanReq,
+    responses(
+        (status = 201, body = scans::ScanInfo),
+        (status = 400, description = "Invalid settings or namespace", body = error::ErrorBody),
+        (status = 404, description = "No such profile", body = error::ErrorBody),
+        (status = 409, description = "The profile is already being scanned", body = error::ErrorBody),
+    ))]
+async fn scan_start_handler(
+    scans: Arc<scans::ScanManager>,
+    req: Result<Json<scans::StartScanReq>, JsonRejection>,
+) -> Result<(StatusCode, Json<scans::ScanInfo>), ApiError> {
+    let Json(req) = req?;
+    info!("scan requested: profile={:?}", req.profile);
+    let info = scans.start(&req).await?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+/// Profile names from the config
+#[utoipa::path(get, path = "/scans/profiles", tag = "scans", responses((status = 200, body = [String])))]
+async fn scan_profiles_handler(scans: Arc<scans::ScanManager>) -> Json<Vec<String>> {
+    Json(scans.profiles())
+}
+
+fn scan_id(id: &str) -> Result<u64, ApiError> {
+    id.parse().map_err(|_| ApiError::not_found(format!("no scan {id:?}")))
+}
+
+/// Progress of one scan
+#[utoipa::path(get, path = "/scans/{id}", tag = "scans", params(("id" = u64, Path)),
+    responses((status = 200, body = scans::ScanInfo), (status = 404, body = error::ErrorBody)))]
+async fn scan_get_handler(scans: Arc<scans::ScanManager>, AxPath(id): AxPath<String>) -> Result<Json<scans::ScanInfo>, ApiError> {
+    scans
+        .get(scan_id(&id)?)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("no scan {id:?}")))
+}
+
+/// Runtime and progress metrics of one scan, as in /stats/
+#[utoipa::path(get, path = "/scans/{id}/stats", tag = "scans", params(("id" = u64, Path), StatsQuery),
+    responses((status = 200, body = stats::Stats), (status = 400, body = error::ErrorBody), (status = 404, body = error::ErrorBody)))]
+async fn scan_stats_handler(
+    scans: Arc<scans::ScanManager>,
+    AxPath(id): AxPath<String>,
+    q: Result<Query<StatsQuery>, QueryRejection>,
+    live: LiveConfig,
+) -> Result<Json<stats::Stats>, ApiError> {
+    let Query(q) = q?;
+    let (prog, store, control) = scans
+        .parts(scan_id(&id)?)
+        .ok_or_else(|| ApiError::not_found(format!("no scan {id:?}")))?;
+    Ok(Json(stats::snapshot(&prog, &store, &control, q.schema(&live))))
+}
+
+/// Domains stored by one scan (its namespace, or the main store), as in /domain/{file}
+#[utoipa::path(
+    get,
+    path = "/scans/{id}/domain/{file}",
+    tag = "scans",
+    params(
+        ("id" = u64, Path),
+        ("file" = String, Path, description = "`<tld>.txt`, `<tld>.json`, `__all__.txt` or `__all__.json`"),
+        DomainQuery,
+    ),
+    responses(
+        (status = 200, description = "Domain names, or FoundDomain objects with format=detailed", body = [String],
+         headers(("X-Total-Count" = usize, description = "Matching domains"), ("Link" = String, description = "next/prev pages"),
+                 ("X-Truncated" = bool, description = "A server.lists cap left matching domains out"),
+                 ("X-Truncated-Limit" = usize, description = "Page size the cap applied"),
+                 ("ETag" = String, description = "Not sent for s3 storage"), ("Last-Modified" = String))),
+        (status = 304, description = "Unchanged since the request's If-None-Match or If-Modified-Since"),
+        (status = 400, body = error::ErrorBody),
+        (status = 404, description = "No such scan or file", body = error::ErrorBody),
+    )
+)]
+async fn scan_domain_handler(
+    scans: Arc<scans::ScanManager>,
+    AxPath((id, file)): AxPath<(String, String)>,
+    q: Result<Query<DomainQuery>, QueryRejection>,
+    uri: Uri,
+    headers: HeaderMap,
+    lists: ListLimitsConfig,
+) -> Result<Response, ApiError> {
+    let (_, store, _) = scans
+        .parts(scan_id(&id)?)
+        .ok_or_else(|| ApiError::not_found(format!("no scan {id:?}")))?;
+    domain_handler(AxPath(file), q, uri, headers, store, lists).await
+}
+
+/// Pause one scan
+#[utoipa::path(post, path = "/scans/{id}/pause", tag = "scans", params(("id" = u64, Path)),
+    responses((status = 200, body = ControlResp), (status = 404, body = error::ErrorBody), (status = 409, description = "Scan is stopped", body = error::ErrorBody)))]
+async fn scan_pause_handler(scans: Arc<scans::ScanManager>, AxPath(id): AxPath<String>) -> Result<Json<ControlResp>, ApiError> {
+    scan_control_handler(scans, &id, ControlAction::Pause).await
+}
+
+/// Resume one paused scan
+#[utoipa::path(post, path = "/scans/{id}/resume", tag = "scans", params(("id" = u64, Path)),
+    responses((status = 200, body = ControlResp), (status = 404, body = error::ErrorBody), (status = 409, description = "Scan is stopped", body = error::ErrorBody)))]
+async fn scan_resume_handler(scans: Arc<scans::ScanManager>, AxPath(id): AxPath<String>) -> Result<Json<ControlResp>, ApiError> {
+    scan_control_handler(scans, &id, ControlAction::Resume).await
+}
+
+/// Stop one scan; the main scan keeps running
+#[utoipa::path(post, path = "/scans/{id}/stop", tag = "scans", params(("id" = u64, Path)),
+    responses((status = 200, body = ControlResp), (status = 404, body = error::ErrorBody)))]
+async fn scan_stop_handler(scans: Arc<scans::ScanManager>, AxPath(id): AxPath<String>) -> Result<Json<ControlResp>, ApiError> {
+    scan_control_handler(scans, &id, ControlAction::Stop).await
+}
+
+async fn scan_control_handler(
+    scans: Arc<scans::ScanManager>,
+    id: &str,
+    action: ControlAction,
+) -> Result<Json<ControlResp>, ApiError> {
+    let control = scans
+        .control(scan_id(id)?)
+        .ok_or_else(|| ApiError::not_found(format!("no scan {id:?}")))?;
+    control_handler(control, action).await
+}
+
+/// Cron schedule of the main scan, upcoming start times and the current and last run
+#[utoipa::path(get, path = "/schedule/", tag = "control", responses((status = 200, body = schedule::ScheduleStatus)))]
+async fn schedule_get_handler(sch: schedule::Scheduler) -> Json<schedule::ScheduleStatus> {
+    Json(sch.status())
+}
+
+/// Replace the schedule until the next config reload that changes it; an empty cron disables it
+#[utoipa::path(put, path = "/schedule/", tag = "control", request_body = config::ScheduleConfig,
+    responses(
+        (status = 200, body = schedule::ScheduleStatus),
+        (status = 400, description = "Invalid cron expression or limits, or cluster mode", body = error::ErrorBody),
+    ))]
+async fn schedule_put_handler(
+    sch: schedule::Scheduler,
+    live: LiveConfig,
+    req: Result<Json<config::ScheduleConfig>, JsonRejection>,
+) -> Result<Json<schedule::ScheduleStatus>, ApiError> {
+    let Json(req) = req?;
+    info!("schedule update requested: cron={:?}", req.cron);
+    sch.set(req, live.current().cluster.role)
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    Ok(Json(sch.status()))
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct ReloadResp {
+    reloaded: bool,
+    restart_required: Vec<&'static str>,
+}
+
+/// Re-read and validate the config file, applying what can change live
+#[utoipa::path(post, path = "/control/reload", tag = "control",
+    responses((status = 200, body = ReloadResp), (status = 422, description = "Invalid config, current one kept", body = error::ErrorBody)))]
+async fn reload_handler(live: LiveConfig) -> Response {
+    info!("config reload requested");
+    match live.reload().await {
+        Ok(restart) => (
+            StatusCode::OK,
+            Json(ReloadResp {
+                reloaded: true,
+                restart_required: restart,
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("config reload failed, keeping current config: {e:#}");
+            ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_config", format!("{e:#}")).into_response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+struct LimitsReq {
+    /// Checks in flight at once
+    concurrency: Option<i32>,
+    /// Checks started per second
+    rate_per_second: Option<i32>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct LimitsResp {
+    concurrency: i32,
+    rate_per_second: i32,
+    burst: i32,
+}
+
+/// Change the main scan's concurrency and rate right away; omitted fields keep
+/// their value, and the next config reload restores the file's
+#[utoipa::path(patch, path = "/control/limits", tag = "control", request_body = LimitsReq,
+    responses((status = 200, body = LimitsResp), (status = 400, description = "Invalid limits", body = error::ErrorBody)))]
+async fn limits_handler(live: LiveConfig, req: Result<Json<LimitsReq>, JsonRejection>) -> Result<Json<LimitsResp>, ApiError> {
+    let Json(req) = req?;
+    info!("limits update requested: concurrency={:?} rate_per_second={:?}", req.concurrency, req.rate_per_second);
+    let cfg = live
+        .update(|c| {
+            if let Some(n) = req.concurrency {
+                c.limits.concurrency = n;
+            }
+            if let Some(r) = req.rate_per_second {
+                c.limits.rate_per_second = r;
+            }
+        })
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    Ok(Json(LimitsResp {
+        concurrency: cfg.limits.concurrency,
+        rate_per_second: cfg.limits.rate_per_second,
+        burst: cfg.limits.burst,
+    }))
+}
+
+/// Progress of the found-domain re-checks (verify.enabled only)
+#[utoipa::path(get, path = "/verify/", tag = "verify", responses((status = 200, body = verify::VerifyStatus)))]
+async fn verify_status_handler(v: verify::Verifier) -> impl IntoResponse {
+    Json(v.status())
+}
+
+/// Found domains that stopped passing the checks, by name (verify.enabled only)
+#[utoipa::path(get, path = "/verify/stale", tag = "verify", responses((status = 200, body = Vec<verify::StaleDomain>)))]
+async fn verify_stale_handler(v: verify::Verifier) -> impl IntoResponse {
+    Json(v.stale())
+}
+
+/// Shard progress (coordinator only)
+#[utoipa::path(get, path = "/cluster/", tag = "cluster", responses((status = 200, body = cluster::ClusterStatus)))]
+async fn cluster_status_handler(co: Arc<cluster::Coordinator>) -> impl IntoResponse {
+    Json(co.status())
+}
+
+/// Lease the next free shard (coordinator only)
+#[utoipa::path(post, path = "/cluster/lease", tag = "cluster", request_body = cluster::LeaseReq,
+    responses(
+        (status = 200, body = cluster::Lease),
+        (status = 204, description = "No shards left"),
+        (status = 409, description = "Generator config differs", body = error::ErrorBody),
+    ))]
+async fn cluster_lease_handler(
+    co: Arc<cluster::Coordinator>,
+    req: Result<Json<cluster::LeaseReq>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(req) = req?;
+    match co.lease(&req) {
+        Ok(Some(lease)) => Ok(Json(lease).into_response()),
+        Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(cluster::LeaseError::FingerprintMismatch) => {
+            error!("cluster: rejected lease for {}: generator config differs", req.worker);
+            Err(ApiError::conflict("generator config differs from the coordinator"))
+        }
+    }
+}
+
+/// Mark a leased shard as done (coordinator only)
+#[utoipa::path(post, path = "/cluster/complete", tag = "cluster", request_body = cluster::CompleteReq,
+    responses((status = 204), (status = 409, description = "Shard is leased to another worker", body = error::ErrorBody)))]
+async fn cluster_complete_handler(
+    co: Arc<cluster::Coordinator>,
+    req: Result<Json<cluster::CompleteReq>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Json(req) = req?;
+    if co.complete(&req) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::conflict(format!("shard {} is leased to another worker", req.shard)))
+    }
+}
+
+/// Found domains from a worker; also renews its lease (coordinator only)
+#[utoipa::path(post, path = "/cluster/report", tag = "cluster", request_body = cluster::ReportReq, responses((status = 204)))]
+async fn cluster_report_handler(
+    co: Arc<cluster::Coordinator>,
+    req: Result<Json<cluster::ReportReq>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Json(req) = req?;
+    co.report(req).await;
+    Ok(StatusCode::NO_CONTENT)
+}
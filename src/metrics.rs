@@ -0,0 +1,153 @@
+use std::fmt::Write;
+
+use crate::alerts::{self, AlertState};
+use crate::bandwidth;
+use crate::dns;
+use crate::http_pool;
+use crate::latency::{Stage, BOUNDS_MS};
+use crate::progress::Progress;
+use crate::service::{ScanControl, ScanState};
+use crate::store::DomainStore;
+
+const PREFIX: &str = "domain_searcher";
+
+/// GET /metrics in the Prometheus text format: the /stats/ counters plus the
+/// DNS and HTTP latency histograms.
+pub fn render(prog: &Progress, store: &DomainStore, control: &ScanControl) -> String {
+    let mut out = String::new();
+    let (enq, chk, fnd, elapsed) = prog.snapshot();
+    let (avail, regd) = prog.availability();
+    let (retry_pending, retried, failed) = prog.retries();
+    let (queued, queue_full) = prog.queue();
+
+    family(&mut out, "state", "gauge", "1 for the current scan state");
+    for st in [ScanState::Running, ScanState::Paused, ScanState::Stopped] {
+        let on = (control.state() == st) as u8;
+        let _ = writeln!(out, "{PREFIX}_state{{state=\"{}\"}} {on}", st.as_str());
+    }
+    for (name, help, v) in [
+        ("generated_total", "Candidates queued for checking", enq),
+        ("checked_total", "Candidates checked", chk),
+        ("found_total", "Domains found", fnd),
+        ("available_total", "Names RDAP reported as available", avail),
+        ("registered_total", "Names RDAP reported as registered", regd),
+        ("duplicates_skipped_total", "Candidates and records skipped by dedup", prog.duplicates()),
+        ("wildcard_skipped_total", "Candidates resolving only to wildcard addresses", prog.wildcard()),
+        ("retried_total", "Re-checks scheduled", retried),
+        ("failed_permanent_total", "Candidates given up after transient failures", failed),
+    ] {
+        family(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{PREFIX}_{name} {v}");
+    }
+    family(&mut out, "retry_pending", "gauge", "Candidates waiting in the retry queue");
+    let _ = writeln!(out, "{PREFIX}_retry_pending {retry_pending}");
+    family(&mut out, "total_planned", "gauge", "limits.max_candidates of the pass");
+    let _ = writeln!(out, "{PREFIX}_total_planned {}", prog.total_planned());
+    family(&mut out, "uptime_seconds", "gauge", "Seconds since start");
+    let _ = writeln!(out, "{PREFIX}_uptime_seconds {}", elapsed.as_secs());
+
+    family(&mut out, "errors_total", "counter", "Failed check attempts by cause");
+    for (kind, n) in prog.errors() {
+        let _ = writeln!(out, "{PREFIX}_errors_total{{kind=\"{kind}\"}} {n}");
+    }
+    family(&mut out, "queue_depth", "gauge", "Items waiting between pipeline stages");
+    let _ = writeln!(out, "{PREFIX}_queue_depth{{queue=\"candidates\"}} {queued}");
+    let _ = writeln!(out, "{PREFIX}_queue_depth{{queue=\"store\"}} {}", store.writer_status().0);
+    family(&mut out, "backpressure_events_total", "counter", "Times a producer waited on a full queue");
+    let _ = writeln!(out, "{PREFIX}_backpressure_events_total{{queue=\"candidates\"}} {queue_full}");
+    let _ = writeln!(out, "{PREFIX}_backpressure_events_total{{queue=\"store\"}} {}", store.backpressure_events());
+    family(&mut out, "store_pending_bytes", "gauge", "Approximate size of found domains not yet written (storage.buffer)");
+    let _ = writeln!(out, "{PREFIX}_store_pending_bytes {}", store.pending_bytes());
+    family(&mut out, "storage_bytes", "gauge", "Approximate size of the stored results");
+    let _ = writeln!(out, "{PREFIX}_storage_bytes {}", store.approx_bytes());
+
+    let pool = http_pool::stats();
+    for (name, help, v) in [
+        ("http_requests_total", "HTTP check requests sent", pool.requests),
+        ("http_connections_opened_total", "Connections opened by the HTTP checks", pool.connections_opened),
+        ("http_connect_failures_total", "Failed connection attempts of the HTTP checks", pool.connect_failures),
+        ("http_connections_reused_total", "HTTP check requests sent over a pooled connection", pool.reused),
+        ("http_head_fallbacks_total", "HEAD checks retried as GET", pool.head_fallbacks),
+    ] {
+        family(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{PREFIX}_{name} {v}");
+    }
+
+    let bw = bandwidth::stats();
+    family(&mut out, "network_bytes_total", "counter", "Estimated bytes the checks sent and received, by channel");
+    for (channel, t) in &bw.channels {
+        for (direction, n) in [("sent", t.sent), ("received", t.received)] {
+            let _ = writeln!(out, "{PREFIX}_network_bytes_total{{channel=\"{channel}\",direction=\"{direction}\"}} {n}");
+        }
+    }
+    family(&mut out, "bandwidth_capped", "gauge", "1 while limits.bandwidth.per_hour holds the checks");
+    let _ = writeln!(out, "{PREFIX}_bandwidth_capped {}", u8::from(bw.capped));
+
+    let rules = alerts::stats();
+    if !rules.is_empty() {
+        family(&mut out, "alert_firing", "gauge", "1 while a rule of alerts.rules is firing");
+        for (name, st) in &rules {
+            let _ = writeln!(out, "{PREFIX}_alert_firing{{rule=\"{name}\"}} {}", u8::from(st.state == AlertState::Firing));
+        }
+    }
+
+    let cache = dns::positive_cache_stats();
+    family(&mut out, "dns_cache_lookups_total", "counter", "Lookups answered by dns.positive_cache (hit) or sent to the resolvers (miss)");
+    for (result, n) in [("hit", cache.hits), ("miss", cache.misses)] {
+        let _ = writeln!(out, "{PREFIX}_dns_cache_lookups_total{{result=\"{result}\"}} {n}");
+    }
+
+    let resolvers = dns::consensus_stats();
+    if !resolvers.is_empty() {
+        family(&mut out, "dns_resolver_answers_total", "counter", "Lookups of each dns.consensus resolver by answer");
+        for (name, r) in &resolvers {
+            for (answer, n) in [("records", r.answered), ("negative", r.negative), ("error", r.errors.values().sum())] {
+                let _ = writeln!(out, "{PREFIX}_dns_resolver_answers_total{{resolver=\"{name}\",answer=\"{answer}\"}} {n}");
+            }
+        }
+        family(&mut out, "dns_resolver_outvoted_total", "counter", "Answers of a dns.consensus resolver the quorum decided against");
+        for (name, r) in &resolvers {
+            let _ = writeln!(out, "{PREFIX}_dns_resolver_outvoted_total{{resolver=\"{name}\"}} {}", r.outvoted);
+        }
+        family(&mut out, "dns_resolver_errors_total", "counter", "Failed lookups of a dns.consensus resolver by cause");
+        for (name, r) in &resolvers {
+            for (kind, n) in &r.errors {
+                let _ = writeln!(out, "{PREFIX}_dns_resolver_errors_total{{resolver=\"{name}\",kind=\"{kind}\"}} {n}");
+            }
+        }
+    }
+
+    family(&mut out, "latency_seconds", "histogram", "Answer times of DNS lookups and HTTP requests");
+    for st in Stage::ALL {
+        let (buckets, sum_us, _) = prog.latency().stage(st).snapshot();
+        let stage = st.as_str();
+        let mut cumulative = 0;
+        for (i, n) in buckets.iter().enumerate() {
+            cumulative += n;
+            let le = BOUNDS_MS.get(i).map_or_else(|| "+Inf".to_string(), |ms| (*ms as f64 / 1000.0).to_string());
+            let _ = writeln!(out, "{PREFIX}_latency_seconds_bucket{{stage=\"{stage}\",le=\"{le}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{PREFIX}_latency_seconds_sum{{stage=\"{stage}\"}} {}", sum_us as f64 / 1e6);
+        let _ = writeln!(out, "{PREFIX}_latency_seconds_count{{stage=\"{stage}\"}} {cumulative}");
+    }
+    family(&mut out, "unanswered_total", "counter", "DNS lookups and HTTP requests that got no answer");
+    for st in Stage::ALL {
+        let (_, _, unanswered) = prog.latency().stage(st).snapshot();
+        let _ = writeln!(out, "{PREFIX}_unanswered_total{{stage=\"{}\"}} {unanswered}", st.as_str());
+    }
+    let stages = prog.stages();
+    family(&mut out, "stage_seconds", "gauge", "Worker time spent per stage since start, generation_wait included");
+    for (stage, s) in &stages {
+        let _ = writeln!(out, "{PREFIX}_stage_seconds{{stage=\"{stage}\"}} {}", s.total_secs);
+    }
+    family(&mut out, "stage_utilization_ratio", "gauge", "Share of the worker slots' time spent per stage since start");
+    for (stage, s) in &stages {
+        let _ = writeln!(out, "{PREFIX}_stage_utilization_ratio{{stage=\"{stage}\"}} {}", s.utilization_percent / 100.0);
+    }
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {PREFIX}_{name} {help}");
+    let _ = writeln!(out, "# TYPE {PREFIX}_{name} {kind}");
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tracing::info;
+
+use crate::config::StorageConfig;
+use crate::dedup::Dedup;
+use crate::store::{DomainStore, S3Backend};
+
+/// Letters, digits, '-' and '_', like profile names; it becomes a directory.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Storage settings of namespace `name`: `<dir>/namespaces/<name>`, or the
+/// same prefix under the S3 one.
+pub fn storage_config(storage: &StorageConfig, name: &str) -> StorageConfig {
+    let sub = format!("namespaces/{name}");
+    let mut sc = storage.clone();
+    sc.dir = Path::new(&storage.dir).join(&sub).to_string_lossy().to_string();
+    sc.sqlite_path = String::new();
+    sc.s3 = S3Backend::sub_config(&storage.s3, &sub);
+    sc
+}
+
+/// Dedup key space of a namespace's found domains
+pub fn dedup_kind(name: &str) -> String {
+    format!("found/{name}")
+}
+
+/// Result stores of scans that write to a namespace of their own, opened on
+/// first use and shared by every scan and reader of that namespace
+#[derive(Clone)]
+pub struct Namespaces {
+    storage: StorageConfig,
+    dedup: Option<Arc<Dedup>>,
+    open: Arc<Mutex<BTreeMap<String, DomainStore>>>,
+}
+
+impl Namespaces {
+    pub fn new(storage: &StorageConfig, dedup: Option<Arc<Dedup>>) -> Self {
+        Self {
+            storage: storage.clone(),
+            dedup,
+            open: Arc::default(),
+        }
+    }
+
+    /// The store of `name` for a scan to write to, created if needed.
+    pub async fn open(&self, name: &str) -> anyhow::Result<DomainStore> {
+        if !valid_name(name) {
+            anyhow::bail!("namespace {name:?} may only contain letters, digits, '-' and '_'");
+        }
+        let store = {
+            let mut open = self.open.lock();
+            if let Some(st) = open.get(name) {
+                return Ok(st.clone());
+            }
+            let st = DomainStore::new(&storage_config(&self.storage, name))?;
+            open.insert(name.to_string(), st.clone());
+            st
+        };
+        info!("namespace '{}' opened", name);
+        if let Some(d) = &self.dedup {
+            d.seed(&store, &dedup_kind(name)).await;
+        }
+        Ok(store)
+    }
+
+    /// The store of an existing namespace, for reading; None when no scan
+    /// ever wrote to it.
+    pub fn get(&self, name: &str) -> anyhow::Result<Option<DomainStore>> {
+        if !valid_name(name) {
+            return Ok(None);
+        }
+        let mut open = self.open.lock();
+        if let Some(st) = open.get(name) {
+            return Ok(Some(st.clone()));
+        }
+        let sc = storage_config(&self.storage, name);
+        if !Path::new(&sc.dir).is_dir() {
+            return Ok(None);
+        }
+        let st = DomainStore::new(&sc)?;
+        open.insert(name.to_string(), st.clone());
+        Ok(Some(st))
+    }
+}
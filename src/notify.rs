@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time;
+use tracing::{debug, warn};
+
+use crate::config::{LiveConfig, WebhookConfig};
+use crate::store::FoundDomain;
+
+mod email;
+
+pub use email::EmailDigest;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// {"event": "domains_found", "count": N, "domains": [...]}
+    #[default]
+    Json,
+    /// {"text": "..."}
+    Slack,
+    /// {"content": "..."}
+    Discord,
+    /// {"text": "..."}, chat_id goes in the URL query
+    Telegram,
+}
+
+enum NotifyMsg {
+    Found(Box<FoundDomain>),
+    Flush(oneshot::Sender<()>),
+}
+
+// finds buffered per live subscriber before it starts missing some
+const FEED_CAPACITY: usize = 1024;
+
+// Batches newly stored domains and POSTs them to the configured webhooks;
+// also fans them out to live subscribers (gRPC streams)
+#[derive(Clone)]
+pub struct Notifier {
+    config: LiveConfig,
+    tx: mpsc::Sender<NotifyMsg>,
+    feed: broadcast::Sender<FoundDomain>,
+}
+
+impl Notifier {
+    pub fn new(config: LiveConfig, client: Client) -> Self {
+        let (tx, mut rx) = mpsc::channel::<NotifyMsg>(10000);
+        let live = config.clone();
+        tokio::spawn(async move {
+            let mut batch: Vec<FoundDomain> = Vec::new();
+            // when the oldest domain in the batch has waited batch_interval
+            let mut deadline = time::Instant::now();
+            loop {
+                let cfg = live.current();
+                let hook = &cfg.notifications.webhook;
+                let msg = if batch.is_empty() {
+                    rx.recv().await
+                } else {
+                    match time::timeout_at(deadline, rx.recv()).await {
+                        Ok(msg) => msg,
+                        Err(_) => {
+                            send_batch(&client, hook, std::mem::take(&mut batch)).await;
+                            continue;
+                        }
+                    }
+                };
+                match msg {
+                    Some(NotifyMsg::Found(rec)) => {
+                        if batch.is_empty() {
+                            deadline = time::Instant::now() + hook.batch_interval;
+                        }
+                        batch.push(*rec);
+                        if batch.len() >= hook.batch_size.max(1) {
+                            send_batch(&client, hook, std::mem::take(&mut batch)).await;
+                        }
+                    }
+                    Some(NotifyMsg::Flush(ack)) => {
+                        send_batch(&client, hook, std::mem::take(&mut batch)).await;
+                        let _ = ack.send(());
+                    }
+                    None => {
+                        send_batch(&client, hook, batch).await;
+                        break;
+                    }
+                }
+            }
+        });
+        let (feed, _) = broadcast::channel(FEED_CAPACITY);
+        Self { config, tx, feed }
+    }
+
+    /// Fresh finds from now on; a subscriber that falls behind skips ahead.
+    pub fn subscribe(&self) -> broadcast::Receiver<FoundDomain> {
+        self.feed.subscribe()
+    }
+
+    /// Queue a fresh find; dropped (with a warning) when the queue is full.
+    pub fn found(&self, rec: &FoundDomain) {
+        if self.feed.receiver_count() > 0 {
+            let _ = self.feed.send(rec.clone());
+        }
+        if self.config.current().notifications.webhook.endpoints.is_empty() {
+            return;
+        }
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.tx.try_send(NotifyMsg::Found(Box::new(rec.clone()))) {
+            warn!("webhook: queue full, dropping notification for {}", rec.domain);
+        }
+    }
+
+    /// Send whatever is batched now (shutdown).
+    pub async fn flush(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.tx.send(NotifyMsg::Flush(ack)).await.is_ok() {
+            let _ = done.await;
+        }
+    }
+}
+
+async fn send_batch(client: &Client, hook: &WebhookConfig, batch: Vec<FoundDomain>) {
+    if batch.is_empty() {
+        return;
+    }
+    for ep in &hook.endpoints {
+        let body = payload(ep.format, &batch);
+        if post(client, &ep.url, &body, hook).await {
+            debug!("webhook: sent {} domains to {}", batch.len(), host_of(&ep.url));
+        } else {
+            warn!("webhook: giving up on {} domains for {}", batch.len(), host_of(&ep.url));
+        }
+    }
+}
+
+/// POST `body` to a webhook, again after errors, 5xx and 429 up to
+/// `hook.retry` times; false when it never got through.
+pub async fn post(client: &Client, url: &str, body: &serde_json::Value, hook: &WebhookConfig) -> bool {
+    let target = host_of(url);
+    let mut attempt = 0;
+    loop {
+        let res = client.post(url).timeout(hook.timeout).json(body).send().await;
+        let retryable = match res {
+            Ok(resp) if resp.status().is_success() => return true,
+            Ok(resp) => {
+                let status = resp.status();
+                warn!("webhook: {} answered {}", target, status);
+                status.is_server_error() || status.as_u16() == 429
+            }
+            Err(e) => {
+                warn!("webhook: POST to {} failed: {}", target, e.without_url());
+                true
+            }
+        };
+        if !retryable || attempt >= hook.retry {
+            return false;
+        }
+        time::sleep(Duration::from_secs(1 << attempt.min(6))).await;
+        attempt += 1;
+    }
+}
+
+// webhook URLs often embed tokens (Slack, Telegram); only the host goes to the logs
+pub fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "<invalid url>".to_string())
+}
+
+fn payload(format: WebhookFormat, batch: &[FoundDomain]) -> serde_json::Value {
+    match format {
+        WebhookFormat::Json => json!({
+            "event": "domains_found",
+            "count": batch.len(),
+            "domains": batch,
+        }),
+        WebhookFormat::Slack | WebhookFormat::Telegram => json!({ "text": summary(batch, 3500) }),
+        // Discord rejects messages over 2000 characters
+        WebhookFormat::Discord => json!({ "content": summary(batch, 1900) }),
+    }
+}
+
+// human-readable list for chat webhooks, cut to fit the message size limit
+fn summary(batch: &[FoundDomain], max_chars: usize) -> String {
+    let mut out = format!("Found {} domain{}:", batch.len(), if batch.len() == 1 { "" } else { "s" });
+    for (i, rec) in batch.iter().enumerate() {
+        let line = format!("\n• {} ({} {})", rec.domain, rec.http_status, rec.scheme);
+        if out.len() + line.len() > max_chars {
+            out.push_str(&format!("\n…and {} more", batch.len() - i));
+            break;
+        }
+        out.push_str(&line);
+    }
+    out
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use base64::Engine;
+use parking_lot::Mutex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::certs;
+use crate::config::{EmailConfig, LiveConfig, SmtpSecurity};
+use crate::progress::Progress;
+use crate::store::FoundDomain;
+
+// how often a waiting digest looks at the config again
+const RECHECK_CONFIG: Duration = Duration::from_secs(60);
+// finds kept per period before only the shortest are kept
+const MAX_KEPT: usize = 10_000;
+
+// What happened since the last digest
+struct Period {
+    started_unix: u64,
+    // counters of the main scan when it began
+    generated: i64,
+    checked: i64,
+    found: i64,
+    errors: BTreeMap<&'static str, i64>,
+    finds: Vec<FoundDomain>,
+}
+
+/// Periodic summary of the main scan by mail (`notifications.email`): counts,
+/// efficiency, the best new finds and errors since the previous one.
+#[derive(Clone)]
+pub struct EmailDigest {
+    config: LiveConfig,
+    prog: Progress,
+    period: Arc<Mutex<Period>>,
+}
+
+impl EmailDigest {
+    /// Collect fresh finds from `found` and send a digest every `interval`.
+    pub fn spawn(config: LiveConfig, prog: Progress, mut found: broadcast::Receiver<FoundDomain>) -> Self {
+        let digest = Self {
+            period: Arc::new(Mutex::new(Self::period_from(&prog))),
+            config,
+            prog,
+        };
+        let d = digest.clone();
+        tokio::spawn(async move {
+            loop {
+                match found.recv().await {
+                    Ok(rec) => d.keep(rec),
+                    // the count comes from the counters, only the list misses some
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        let d = digest.clone();
+        tokio::spawn(async move {
+            loop {
+                let email = d.config.current().notifications.email.clone();
+                let due = d.period.lock().started_unix + email.interval.as_secs();
+                let now = now_unix();
+                if !email.enabled() || email.interval.is_zero() || now < due {
+                    time::sleep(Duration::from_secs(due.saturating_sub(now)).clamp(Duration::from_secs(1), RECHECK_CONFIG)).await;
+                    continue;
+                }
+                d.send("digest").await;
+            }
+        });
+        digest
+    }
+
+    /// Last digest when the main scan ends, with `on_completion`.
+    pub async fn finish(&self, reason: &str) {
+        if self.config.current().notifications.email.on_completion {
+            self.send(reason).await;
+        }
+    }
+
+    fn period_from(prog: &Progress) -> Period {
+        let (generated, checked, found, _) = prog.snapshot();
+        Period {
+            started_unix: now_unix(),
+            generated,
+            checked,
+            found,
+            errors: prog.errors().into_iter().collect(),
+            finds: Vec::new(),
+        }
+    }
+
+    fn keep(&self, rec: FoundDomain) {
+        let email = self.config.current().notifications.email.clone();
+        // profile scans have their own counters
+        if !email.enabled() || rec.scan_id != 0 {
+            return;
+        }
+        let mut p = self.period.lock();
+        p.finds.push(rec);
+        if p.finds.len() >= MAX_KEPT {
+            sort_best(&mut p.finds);
+            p.finds.truncate(email.top);
+        }
+    }
+
+    // the period ends whether or not the mail goes out, so a dead server
+    // does not make the next digest cover everything since the start
+    async fn send(&self, reason: &str) {
+        let email = self.config.current().notifications.email.clone();
+        if !email.enabled() {
+            return;
+        }
+        let (period, now) = {
+            let mut p = self.period.lock();
+            (std::mem::replace(&mut *p, Self::period_from(&self.prog)), now_unix())
+        };
+        let (subject, body) = self.compose(&email, period, now, reason);
+        match time::timeout(email.timeout, deliver(&email, &subject, &body)).await {
+            Ok(Ok(())) => info!("email: {} mail sent to {} recipients", reason, email.to.len()),
+            Ok(Err(e)) => warn!("email: cannot send the {} mail via {}: {e:#}", reason, email.smtp_host),
+            Err(_) => warn!("email: sending the {} mail via {} timed out", reason, email.smtp_host),
+        }
+    }
+
+    fn compose(&self, email: &EmailConfig, mut p: Period, now: u64, reason: &str) -> (String, String) {
+        let (generated, checked, found, elapsed) = self.prog.snapshot();
+        let est = self.prog.estimate();
+        let new = found - p.found;
+        let period_checked = checked - p.checked;
+        let subject = format!("Domain scan {reason}: {new} new domain{}", if new == 1 { "" } else { "s" });
+
+        let mut b = String::new();
+        b.push_str(&format!("{} ({} to {} UTC)\n\n", capitalize(reason), utc(p.started_unix), utc(now)));
+        b.push_str("Since the last digest:\n");
+        b.push_str(&format!("  generated  {}\n", generated - p.generated));
+        b.push_str(&format!("  checked    {}\n", period_checked));
+        b.push_str(&format!("  found      {}\n", new));
+        if period_checked > 0 {
+            b.push_str(&format!("  efficiency {:.3}%\n", new as f64 * 100.0 / period_checked as f64));
+        }
+        b.push_str(&format!(
+            "\nSince start ({} ago):\n  generated  {}\n  checked    {}\n  found      {}\n  efficiency {:.3}%\n  speed      {:.1}/s\n",
+            human_duration(elapsed),
+            generated,
+            checked,
+            found,
+            est.efficiency_percent,
+            est.speed_per_sec
+        ));
+        if est.remaining > 0 {
+            b.push_str(&format!("  remaining  {} ({:.1}% done, eta {})\n", est.remaining, est.percent, human_duration(est.eta)));
+        }
+
+        sort_best(&mut p.finds);
+        if !p.finds.is_empty() {
+            b.push_str("\nNew finds, shortest first:\n");
+            for rec in p.finds.iter().take(email.top) {
+                let mut line = format!("  {}", rec.domain);
+                if !rec.unicode.is_empty() {
+                    line.push_str(&format!(" ({})", rec.unicode));
+                }
+                if rec.http_status > 0 {
+                    line.push_str(&format!("  {} {}", rec.http_status, rec.scheme));
+                }
+                if !rec.title.is_empty() {
+                    line.push_str(&format!("  \"{}\"", rec.title));
+                }
+                b.push_str(&line);
+                b.push('\n');
+            }
+            let listed = p.finds.len().min(email.top) as i64;
+            if new > listed {
+                b.push_str(&format!("  ...and {} more\n", new - listed));
+            }
+        }
+
+        let errors: Vec<(&str, i64)> = self
+            .prog
+            .errors()
+            .into_iter()
+            .map(|(k, n)| (k, n - p.errors.get(k).copied().unwrap_or(0)))
+            .filter(|(_, n)| *n > 0)
+            .collect();
+        if !errors.is_empty() {
+            b.push_str("\nFailed checks since the last digest:\n");
+            for (kind, n) in errors {
+                b.push_str(&format!("  {kind:<14} {n}\n"));
+            }
+        }
+        (subject, b)
+    }
+}
+
+// short names first, the ones people look for
+fn sort_best(finds: &mut [FoundDomain]) {
+    finds.sort_by(|a, b| a.domain.len().cmp(&b.domain.len()).then_with(|| a.domain.cmp(&b.domain)));
+}
+
+// Plain SMTP: EHLO, STARTTLS when asked for, AUTH PLAIN, one message to all recipients
+async fn deliver(email: &EmailConfig, subject: &str, body: &str) -> anyhow::Result<()> {
+    let host = email.smtp_host.trim();
+    let tcp = TcpStream::connect((host, email.port()))
+        .await
+        .with_context(|| format!("connect to {host}:{}", email.port()))?;
+    match email.security {
+        SmtpSecurity::None => {
+            let mut s = Smtp::new(tcp);
+            s.reply(2).await?;
+            s.send_mail(email, subject, body).await
+        }
+        SmtpSecurity::Tls => {
+            let mut s = Smtp::new(tls(tcp, host).await?);
+            s.reply(2).await?;
+            s.send_mail(email, subject, body).await
+        }
+        SmtpSecurity::Starttls => {
+            let mut s = Smtp::new(tcp);
+            s.reply(2).await?;
+            s.command(&format!("EHLO {}", helo_name(email)), 2).await?;
+            s.command("STARTTLS", 2).await?;
+            let tcp = s.io.into_inner();
+            Smtp::new(tls(tcp, host).await?).send_mail(email, subject, body).await
+        }
+    }
+}
+
+async fn tls(tcp: TcpStream, host: &str) -> anyhow::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(certs::plain_client_tls()?));
+    let name = rustls::pki_types::ServerName::try_from(host.to_string()).context("smtp_host")?;
+    connector.connect(name, tcp).await.context("TLS handshake")
+}
+
+// the domain of the sender, which servers expect to be a real name
+fn helo_name(email: &EmailConfig) -> &str {
+    email.from.rsplit_once('@').map_or("localhost", |(_, d)| d)
+}
+
+struct Smtp<S> {
+    io: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Smtp<S> {
+    fn new(stream: S) -> Self {
+        Self { io: BufReader::new(stream) }
+    }
+
+    // read a (multi-line) reply and check its class: 2 for 2xx, 3 for 3xx
+    async fn reply(&mut self, class: u8) -> anyhow::Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.io.read_line(&mut line).await? == 0 {
+                anyhow::bail!("server closed the connection");
+            }
+            let line = line.trim_end();
+            if line.as_bytes().first() != Some(&(b'0' + class)) {
+                anyhow::bail!("server answered {line:?}");
+            }
+            // "250-..." continues, "250 ..." ends the reply
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn command(&mut self, cmd: &str, class: u8) -> anyhow::Result<()> {
+        self.io.write_all(format!("{cmd}\r\n").as_bytes()).await?;
+        self.io.flush().await?;
+        let verb = cmd.split(' ').next().unwrap_or(cmd);
+        self.reply(class).await.with_context(|| verb.to_string())
+    }
+
+    async fn send_mail(&mut self, email: &EmailConfig, subject: &str, body: &str) -> anyhow::Result<()> {
+        self.command(&format!("EHLO {}", helo_name(email)), 2).await?;
+        if !email.username.is_empty() {
+            let token = base64::engine::general_purpose::STANDARD.encode(format!("\0{}\0{}", email.username, email.password));
+            self.command(&format!("AUTH PLAIN {token}"), 2).await?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", email.from), 2).await?;
+        for to in &email.to {
+            self.command(&format!("RCPT TO:<{to}>"), 2).await?;
+        }
+        self.command("DATA", 3).await?;
+        let mut msg = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}.digest@{}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            email.from,
+            email.to.join(", "),
+            subject,
+            chrono::Utc::now().to_rfc2822(),
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0),
+            helo_name(email),
+        );
+        for line in body.lines() {
+            // a lone "." would end the message
+            if line.starts_with('.') {
+                msg.push('.');
+            }
+            msg.push_str(line);
+            msg.push_str("\r\n");
+        }
+        msg.push_str(".\r\n");
+        self.io.write_all(msg.as_bytes()).await?;
+        self.io.flush().await?;
+        self.reply(2).await.context("message")?;
+        // the message is accepted; a failed QUIT does not matter
+        let _ = self.command("QUIT", 2).await;
+        Ok(())
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut c = s.chars();
+    c.next().map(|f| f.to_uppercase().chain(c).collect()).unwrap_or_default()
+}
+
+fn utc(unix: u64) -> String {
+    chrono::DateTime::from_timestamp(unix as i64, 0).map(|t| t.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default()
+}
+
+fn human_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    match s {
+        0..=59 => format!("{s}s"),
+        60..=3599 => format!("{}m", s / 60),
+        3600..=86399 => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+        _ => format!("{}d{:02}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
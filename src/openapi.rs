@@ -0,0 +1,80 @@
+use utoipa::OpenApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "rust_domain_searcher_api", description = "Domain candidate generator and reachability scanner"),
+    paths(
+        crate::stats_handler,
+        crate::stats_history_handler,
+        crate::tld_stats_handler,
+        crate::sample_stats_handler,
+        crate::metrics_handler,
+        crate::ws_stats_handler,
+        crate::domain_handler,
+        crate::namespace_domain_handler,
+        crate::domain_delete_handler,
+        crate::domain_purge_handler,
+        crate::found_handler,
+        crate::search_handler,
+        crate::analytics_handler,
+        crate::ips_top_handler,
+        crate::ip_domains_handler,
+        crate::healthz_handler,
+        crate::readyz_handler,
+        crate::tlds_handler,
+        crate::pause_handler,
+        crate::resume_handler,
+        crate::stop_handler,
+        crate::reload_handler,
+        crate::limits_handler,
+        crate::compact_handler,
+        crate::schedule_get_handler,
+        crate::schedule_put_handler,
+        crate::preview_handler,
+        crate::candidates_handler,
+        crate::scans_list_handler,
+        crate::scan_start_handler,
+        crate::scan_profiles_handler,
+        crate::scan_get_handler,
+        crate::scan_stats_handler,
+        crate::scan_domain_handler,
+        crate::scan_pause_handler,
+        crate::scan_resume_handler,
+        crate::scan_stop_handler,
+        crate::verify_status_handler,
+        crate::verify_stale_handler,
+        crate::cluster_status_handler,
+        crate::cluster_lease_handler,
+        crate::cluster_complete_handler,
+        crate::cluster_report_handler,
+    ),
+    components(schemas(crate::error::ErrorBody, crate::store::FoundDomain))
+)]
+struct ApiDoc;
+
+pub fn spec(base_path: &str) -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    // so "Try it out" in the docs goes through the prefix
+    if !base_path.is_empty() {
+        doc.servers = Some(vec![utoipa::openapi::Server::new(base_path)]);
+    }
+    doc
+}
+
+// Swagger UI from the CDN, pointed at /openapi.json
+pub const DOCS_HTML: &str = r##"<!doctype html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>rust_domain_searcher_api - API docs</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
@@ -0,0 +1,159 @@
+use regex::bytes::Regex;
+use tracing::info;
+
+use crate::config::ParkingConfig;
+
+// A built-in provider, see `Provider`
+struct Builtin {
+    name: &'static str,
+    nameservers: &'static [&'static str],
+    redirect_hosts: &'static [&'static str],
+    body: &'static [&'static str],
+}
+
+const BUILTIN: &[Builtin] = &[
+    Builtin {
+        name: "sedo",
+        nameservers: &["sedoparking.com"],
+        redirect_hosts: &["sedo.com"],
+        body: &[r"(?i)sedoparking\.com"],
+    },
+    Builtin {
+        name: "bodis",
+        nameservers: &["bodis.com"],
+        redirect_hosts: &[],
+        body: &[r"(?i)\bbodis\.com"],
+    },
+    Builtin {
+        name: "parkingcrew",
+        nameservers: &["parkingcrew.net"],
+        redirect_hosts: &[],
+        body: &[r"(?i)parkingcrew\.net"],
+    },
+    Builtin {
+        name: "above",
+        nameservers: &["above.com"],
+        redirect_hosts: &[],
+        body: &[r"(?i)\babove\.com/marketplace"],
+    },
+    Builtin {
+        name: "dan",
+        nameservers: &["dan.com"],
+        redirect_hosts: &["dan.com"],
+        body: &[r"(?i)\bdan\.com/buy-domain"],
+    },
+    Builtin {
+        name: "afternic",
+        nameservers: &["afternic.com"],
+        redirect_hosts: &["afternic.com"],
+        body: &[],
+    },
+    Builtin {
+        name: "hugedomains",
+        nameservers: &["hugedomains.com"],
+        redirect_hosts: &["hugedomains.com"],
+        body: &[r"(?i)hugedomains\.com"],
+    },
+    Builtin {
+        name: "godaddy",
+        nameservers: &[],
+        redirect_hosts: &[],
+        body: &[r"(?i)wsimg\.com/parking-lander"],
+    },
+    Builtin {
+        name: "namecheap",
+        nameservers: &[],
+        redirect_hosts: &[],
+        body: &[r"(?i)parkingpage\.namecheap\.com"],
+    },
+    Builtin {
+        name: "undeveloped",
+        nameservers: &["undeveloped.com"],
+        redirect_hosts: &["undeveloped.com"],
+        body: &[],
+    },
+    Builtin {
+        name: "domainmarket",
+        nameservers: &[],
+        redirect_hosts: &["domainmarket.com"],
+        body: &[],
+    },
+];
+
+// Fingerprints of one parking provider, lower-case
+struct Provider {
+    name: String,
+    nameservers: Vec<String>,
+    redirect_hosts: Vec<String>,
+    body: Vec<Regex>,
+}
+
+/// Parking provider fingerprints for the `parking` check stage: the
+/// built-in list (unless `parking.builtin` is off) and `parking.providers`.
+pub struct Fingerprints {
+    providers: Vec<Provider>,
+}
+
+impl Fingerprints {
+    pub fn new(cfg: &ParkingConfig) -> Self {
+        let lower = |v: &[&str]| v.iter().map(|s| s.to_ascii_lowercase()).collect::<Vec<_>>();
+        let mut providers = Vec::new();
+        if cfg.builtin {
+            for b in BUILTIN {
+                providers.push(Provider {
+                    name: b.name.to_string(),
+                    nameservers: lower(b.nameservers),
+                    redirect_hosts: lower(b.redirect_hosts),
+                    // built-in patterns are known to compile
+                    body: b.body.iter().filter_map(|p| Regex::new(p).ok()).collect(),
+                });
+            }
+        }
+        for p in &cfg.providers {
+            let norm = |v: &[String]| v.iter().map(|s| s.trim().trim_matches('.').to_ascii_lowercase()).filter(|s| !s.is_empty()).collect();
+            providers.push(Provider {
+                name: p.name.clone(),
+                nameservers: norm(&p.nameservers),
+                redirect_hosts: norm(&p.redirect_hosts),
+                body: p.body.iter().map(|b| b.0.clone()).collect(),
+            });
+        }
+        info!("parking: {} provider fingerprints", providers.len());
+        Self { providers }
+    }
+
+    /// Some provider lists nameservers, so an NS lookup can tell.
+    pub fn uses_nameservers(&self) -> bool {
+        self.providers.iter().any(|p| !p.nameservers.is_empty())
+    }
+
+    /// Some provider lists body patterns, so the page is worth reading.
+    pub fn uses_body(&self) -> bool {
+        self.providers.iter().any(|p| !p.body.is_empty())
+    }
+
+    /// Provider whose nameservers serve the domain
+    pub fn by_nameservers(&self, names: &[String]) -> Option<&str> {
+        self.find(|p| names.iter().any(|n| p.nameservers.iter().any(|s| under(n, s))))
+    }
+
+    /// Provider the domain redirects to
+    pub fn by_host(&self, host: &str) -> Option<&str> {
+        self.find(|p| p.redirect_hosts.iter().any(|s| under(host, s)))
+    }
+
+    /// Provider whose page this is
+    pub fn by_body(&self, body: &[u8]) -> Option<&str> {
+        self.find(|p| p.body.iter().any(|re| re.is_match(body)))
+    }
+
+    fn find(&self, hit: impl Fn(&Provider) -> bool) -> Option<&str> {
+        self.providers.iter().find(|p| hit(p)).map(|p| p.name.as_str())
+    }
+}
+
+// `name` is `suffix` or a subdomain of it
+fn under(name: &str, suffix: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    name == suffix || name.strip_suffix(suffix).is_some_and(|sub| sub.ends_with('.'))
+}
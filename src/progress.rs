@@ -1,14 +1,53 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use parking_lot::{Mutex, RwLock};
+
+use crate::checker::FailureKind;
+use crate::latency::{CheckTimer, Latencies, Phase, Stage, StageSummary, StageTimes, Timing};
+
+pub struct Estimate {
+    pub speed_per_sec: f64,
+    pub remaining: i64,
+    pub eta: Duration,
+    pub percent: f64,
+    pub efficiency_percent: f64,
+}
+
+// Counters for one TLD, keyed like the storage files (last label, no dot)
+#[derive(Default)]
+struct TldCounters {
+    enqueued: AtomicI64,
+    checked: AtomicI64,
+    found: AtomicI64,
+    // [checked, found] by length of the first label in characters
+    lengths: Mutex<BTreeMap<usize, [i64; 2]>>,
+}
+
 #[derive(Clone)]
 pub struct Progress {
     start: Instant,
     enqueued: Arc<AtomicI64>,
     checked: Arc<AtomicI64>,
     found: Arc<AtomicI64>,
+    available: Arc<AtomicI64>,
+    registered: Arc<AtomicI64>,
+    duplicates: Arc<AtomicI64>,
+    wildcard: Arc<AtomicI64>,
+    retry_pending: Arc<AtomicI64>,
+    retried: Arc<AtomicI64>,
+    failed: Arc<AtomicI64>,
+    // candidates in the check queue, and sends that found it full
+    queued: Arc<AtomicI64>,
+    queue_full: Arc<AtomicI64>,
+    // indexed by FailureKind
+    errors: Arc<[AtomicI64; FailureKind::ALL.len()]>,
     total_planned: Arc<AtomicI64>,
+    latency: Arc<Latencies>,
+    stages: Arc<StageTimes>,
+    tlds: Arc<RwLock<HashMap<String, Arc<TldCounters>>>>,
 }
 
 impl Progress {
@@ -18,17 +57,178 @@ impl Progress {
             enqueued: Arc::new(AtomicI64::new(0)),
             checked: Arc::new(AtomicI64::new(0)),
             found: Arc::new(AtomicI64::new(0)),
+            available: Arc::new(AtomicI64::new(0)),
+            registered: Arc::new(AtomicI64::new(0)),
+            duplicates: Arc::new(AtomicI64::new(0)),
+            wildcard: Arc::new(AtomicI64::new(0)),
+            retry_pending: Arc::new(AtomicI64::new(0)),
+            retried: Arc::new(AtomicI64::new(0)),
+            failed: Arc::new(AtomicI64::new(0)),
+            queued: Arc::new(AtomicI64::new(0)),
+            queue_full: Arc::new(AtomicI64::new(0)),
+            errors: Arc::new(Default::default()),
             total_planned: Arc::new(AtomicI64::new(total_planned.max(0))),
+            latency: Arc::new(Latencies::default()),
+            stages: Arc::new(StageTimes::default()),
+            tlds: Arc::new(RwLock::new(HashMap::new())),
         }
     }
-    pub fn inc_enqueued(&self) {
+    pub fn inc_enqueued(&self, domain: &str) {
         self.enqueued.fetch_add(1, Ordering::Relaxed);
+        if let Some(t) = self.tld(domain) {
+            t.enqueued.fetch_add(1, Ordering::Relaxed);
+        }
     }
-    pub fn inc_checked(&self) {
+    pub fn inc_checked(&self, domain: &str) {
         self.checked.fetch_add(1, Ordering::Relaxed);
+        if let Some(t) = self.tld(domain) {
+            t.checked.fetch_add(1, Ordering::Relaxed);
+            t.lengths.lock().entry(label_len(domain)).or_default()[0] += 1;
+        }
     }
-    pub fn inc_found(&self) {
+    pub fn inc_found(&self, domain: &str) {
         self.found.fetch_add(1, Ordering::Relaxed);
+        if let Some(t) = self.tld(domain) {
+            t.found.fetch_add(1, Ordering::Relaxed);
+            t.lengths.lock().entry(label_len(domain)).or_default()[1] += 1;
+        }
+    }
+    fn tld(&self, domain: &str) -> Option<Arc<TldCounters>> {
+        let tld = crate::store::extract_tld(domain)?;
+        if let Some(t) = self.tlds.read().get(tld) {
+            return Some(t.clone());
+        }
+        Some(self.tlds.write().entry(tld.to_string()).or_default().clone())
+    }
+    // per-TLD (enqueued, checked, found), sorted by TLD; counted since process start
+    pub fn tld_snapshot(&self) -> Vec<(String, i64, i64, i64)> {
+        let mut out: Vec<_> = self
+            .tlds
+            .read()
+            .iter()
+            .map(|(tld, c)| {
+                (
+                    tld.clone(),
+                    c.enqueued.load(Ordering::Relaxed),
+                    c.checked.load(Ordering::Relaxed),
+                    c.found.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+        out.sort();
+        out
+    }
+    // per-TLD and label length (checked, found), sorted; counted since process start
+    pub fn length_snapshot(&self) -> Vec<(String, usize, i64, i64)> {
+        let mut out: Vec<_> = self
+            .tlds
+            .read()
+            .iter()
+            .flat_map(|(tld, c)| c.lengths.lock().iter().map(|(len, [chk, fnd])| (tld.clone(), *len, *chk, *fnd)).collect::<Vec<_>>())
+            .collect();
+        out.sort();
+        out
+    }
+    pub fn inc_available(&self) {
+        self.available.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn inc_registered(&self) {
+        self.registered.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn inc_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+    // candidates and records skipped by the dedup layer
+    pub fn duplicates(&self) -> i64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+    pub fn inc_wildcard(&self) {
+        self.wildcard.fetch_add(1, Ordering::Relaxed);
+    }
+    // candidates that resolved only to their zone's wildcard addresses
+    pub fn wildcard(&self) -> i64 {
+        self.wildcard.load(Ordering::Relaxed)
+    }
+    pub fn inc_retry_scheduled(&self) {
+        self.retried.fetch_add(1, Ordering::Relaxed);
+        self.retry_pending.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn sub_retry_pending(&self, n: i64) {
+        self.retry_pending.fetch_sub(n, Ordering::Relaxed);
+    }
+    pub fn inc_failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+    // retry counters: (waiting in the queue, re-checks scheduled, given up)
+    pub fn retries(&self) -> (i64, i64, i64) {
+        (
+            self.retry_pending.load(Ordering::Relaxed),
+            self.retried.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+        )
+    }
+    pub fn inc_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn add_queued(&self, n: i64) {
+        self.queued.fetch_add(n, Ordering::Relaxed);
+    }
+    pub fn dec_queued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+    pub fn inc_queue_full(&self) {
+        self.queue_full.fetch_add(1, Ordering::Relaxed);
+    }
+    // check queue: (candidates waiting for a worker, sends that had to wait for room)
+    pub fn queue(&self) -> (i64, i64) {
+        (
+            self.queued.load(Ordering::Relaxed),
+            self.queue_full.load(Ordering::Relaxed),
+        )
+    }
+    pub fn inc_error(&self, kind: FailureKind) {
+        self.errors[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+    // failed check attempts by cause, re-checks included; every label is present
+    pub fn errors(&self) -> Vec<(&'static str, i64)> {
+        FailureKind::ALL
+            .iter()
+            .map(|&k| (k.as_str(), self.errors[k as usize].load(Ordering::Relaxed)))
+            .collect()
+    }
+    pub fn observe(&self, t: Timing) {
+        self.latency.observe(t);
+        let phase = match t.stage {
+            Stage::Dns => Phase::Dns,
+            Stage::Http => Phase::Http,
+        };
+        self.stages.add(phase, t.elapsed);
+    }
+    pub fn add_stage_time(&self, phase: Phase, elapsed: Duration) {
+        self.stages.add(phase, elapsed);
+    }
+    // counts the check as running until dropped
+    pub fn time_check(&self) -> CheckTimer {
+        self.stages.start_check()
+    }
+    // worker slots of the scan, for the stage utilization
+    pub fn set_workers(&self, workers: usize) {
+        self.stages.set_workers(workers);
+    }
+    // time per stage since process start, with utilization of the worker slots
+    pub fn stages(&self) -> BTreeMap<String, StageSummary> {
+        self.stages.summary()
+    }
+    // DNS and HTTP answer times since process start
+    pub fn latency(&self) -> &Latencies {
+        &self.latency
+    }
+    // RDAP classification counters: (available, registered)
+    pub fn availability(&self) -> (i64, i64) {
+        (
+            self.available.load(Ordering::Relaxed),
+            self.registered.load(Ordering::Relaxed),
+        )
     }
     pub fn snapshot(&self) -> (i64, i64, i64, Duration) {
         (
@@ -41,6 +241,33 @@ impl Progress {
     pub fn total_planned(&self) -> i64 {
         self.total_planned.load(Ordering::Relaxed)
     }
+    // Speed and what is left of the plan; remaining is -1 without max_candidates
+    pub fn estimate(&self) -> Estimate {
+        let (_, chk, fnd, elapsed) = self.snapshot();
+        let elapsed_sec = elapsed.as_secs_f64();
+        let speed = if elapsed_sec > 0.0 { (chk as f64) / elapsed_sec } else { 0.0 };
+        let total_planned = self.total_planned();
+        let mut est = Estimate {
+            speed_per_sec: speed,
+            remaining: -1,
+            eta: Duration::from_secs(0),
+            percent: 0.0,
+            efficiency_percent: if chk > 0 { (fnd as f64) / (chk as f64) * 100.0 } else { 0.0 },
+        };
+        if total_planned > 0 {
+            if chk >= total_planned {
+                est.remaining = 0;
+                est.percent = 100.0;
+            } else {
+                est.remaining = total_planned - chk;
+                if speed > 0.0 {
+                    est.eta = Duration::from_secs_f64((est.remaining as f64) / speed);
+                }
+                est.percent = (100.0 * (chk as f64) / (total_planned as f64)).min(100.0);
+            }
+        }
+        est
+    }
 
     // Initialize counters from persisted state
     pub fn set_initial(&self, enqueued: i64, checked: i64, found: i64, total_planned: i64) {
@@ -49,4 +276,14 @@ impl Progress {
         self.found.store(found, Ordering::Relaxed);
         self.total_planned.store(total_planned.max(0), Ordering::Relaxed);
     }
-}
\ No newline at end of file
+}
+
+// Characters of the first label, as generated (before punycode)
+fn label_len(domain: &str) -> usize {
+    let label = domain.split('.').next().unwrap_or_default();
+    if label.starts_with("xn--") {
+        idna::domain_to_unicode(label).0.chars().count()
+    } else {
+        label.chars().count()
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::SendError, error::TrySendError};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+use crate::config::{QueueConfig, QueueKind};
+use crate::domain::Domain;
+
+/// Sending half of the queue between a candidate source and the checks.
+#[derive(Clone)]
+pub enum QueueSender {
+    Memory(mpsc::Sender<Domain>),
+    Disk(Arc<DiskQueue>),
+}
+
+/// Receiving half; disk entries come with a `Taken` to mark done once checked.
+pub enum QueueReceiver {
+    Memory(mpsc::Receiver<Domain>),
+    Disk(Arc<DiskQueue>),
+}
+
+/// A queue of `storage.queue.kind`; the disk queue is also returned for
+/// resume and commits. `keep` replays what a previous run left queued.
+pub fn open(cfg: &QueueConfig, keep: bool) -> anyhow::Result<(QueueSender, QueueReceiver, Option<Arc<DiskQueue>>)> {
+    match cfg.kind {
+        QueueKind::Memory => {
+            let (tx, rx) = memory(cfg.capacity);
+            Ok((tx, rx, None))
+        }
+        QueueKind::Disk => {
+            let q = Arc::new(DiskQueue::open(Path::new(&cfg.dir), cfg.capacity, cfg.segment_size as u64, keep)?);
+            Ok((QueueSender::Disk(q.clone()), QueueReceiver::Disk(q.clone()), Some(q)))
+        }
+    }
+}
+
+/// An in-memory queue of `capacity` candidates.
+pub fn memory(capacity: usize) -> (QueueSender, QueueReceiver) {
+    let (tx, rx) = mpsc::channel(capacity.max(1));
+    (QueueSender::Memory(tx), QueueReceiver::Memory(rx))
+}
+
+impl QueueSender {
+    pub fn try_send(&self, domain: Domain) -> Result<(), TrySendError<Domain>> {
+        match self {
+            QueueSender::Memory(tx) => tx.try_send(domain),
+            QueueSender::Disk(q) => match q.push(&domain) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(TrySendError::Full(domain)),
+                Err(e) => {
+                    error!("queue: {e}");
+                    Err(TrySendError::Closed(domain))
+                }
+            },
+        }
+    }
+
+    /// Wait for room, then queue
+    pub async fn send(&self, domain: Domain) -> Result<(), SendError<Domain>> {
+        match self {
+            QueueSender::Memory(tx) => tx.send(domain).await,
+            QueueSender::Disk(q) => {
+                let mut domain = domain;
+                loop {
+                    let room = q.room.notified();
+                    match self.try_send(domain) {
+                        Err(TrySendError::Full(d)) => {
+                            domain = d;
+                            room.await;
+                        }
+                        Err(TrySendError::Closed(d)) => return Err(SendError(d)),
+                        Ok(()) => return Ok(()),
+                    }
+                }
+            }
+        }
+    }
+
+    /// No more candidates; the receiver ends once it has taken the rest.
+    pub fn close(self) {
+        if let QueueSender::Disk(q) = self {
+            q.close();
+        }
+    }
+}
+
+impl QueueReceiver {
+    /// Next candidate, None once the queue is closed and empty.
+    pub async fn recv(&mut self) -> Option<(Domain, Option<Taken>)> {
+        match self {
+            QueueReceiver::Memory(rx) => rx.recv().await.map(|d| (d, None)),
+            QueueReceiver::Disk(q) => q
+                .pop()
+                .await
+                .map(|(d, pos)| (Domain::from_normalized(d), Some(Taken { queue: q.clone(), pos }))),
+        }
+    }
+}
+
+/// A disk queue entry being checked; a restart replays it until it is `done`.
+pub struct Taken {
+    queue: Arc<DiskQueue>,
+    pos: Position,
+}
+
+impl Taken {
+    pub fn done(self) {
+        self.queue.inner.lock().taken.remove(&self.pos);
+    }
+}
+
+// where an entry starts: segment number and byte offset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Position {
+    segment: u64,
+    offset: u64,
+}
+
+// the consumer position as of the last commit
+#[derive(Default, Serialize, Deserialize)]
+struct Cursor {
+    #[serde(flatten)]
+    at: Position,
+    // last candidate queued, for when its segment is gone
+    last: String,
+}
+
+// Queue of candidates on disk, for `storage.queue.kind: disk`.
+//
+// Candidates are appended one per line to numbered segment files; a new
+// segment starts once the current one reaches `segment_size`. The cursor file
+// holds the position of the oldest candidate taken but not yet checked, so a
+// restart replays everything from there: what was queued and what was in
+// flight. Segments wholly before the cursor are deleted on commit.
+pub struct DiskQueue {
+    dir: PathBuf,
+    capacity: usize,
+    segment_size: u64,
+    inner: Mutex<Inner>,
+    // an entry was appended or the queue closed
+    ready: Notify,
+    // an entry was taken
+    room: Notify,
+}
+
+struct Inner {
+    head_seq: u64,
+    head: File,
+    head_len: u64,
+    reader: BufReader<File>,
+    read_at: Position,
+    // queued, not taken yet
+    len: usize,
+    // taken, not checked yet
+    taken: BTreeSet<Position>,
+    last: String,
+    saved: Position,
+    closed: bool,
+}
+
+impl DiskQueue {
+    pub fn open(dir: &Path, capacity: usize, segment_size: u64, keep: bool) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        if !keep {
+            Self::reset(dir)?;
+        }
+        let cursor_path = dir.join("cursor.json");
+        let mut segments = list_segments(dir)?;
+        let cursor: Cursor = match std::fs::read(&cursor_path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(_) => Cursor {
+                at: Position { segment: segments.first().copied().unwrap_or(0), offset: 0 },
+                last: String::new(),
+            },
+        };
+        for seq in segments.iter().filter(|s| **s < cursor.at.segment) {
+            std::fs::remove_file(segment_path(dir, *seq))?;
+        }
+        segments.retain(|s| *s >= cursor.at.segment);
+        let head_seq = segments.last().copied().unwrap_or(cursor.at.segment);
+
+        // a crash can leave half a line at the end
+        let head_path = segment_path(dir, head_seq);
+        let head = OpenOptions::new().create(true).read(true).append(true).open(&head_path)?;
+        let mut data = Vec::new();
+        (&head).read_to_end(&mut data)?;
+        let head_len = data.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1) as u64;
+        head.set_len(head_len)?;
+
+        let mut len = 0;
+        let mut last = cursor.last.clone();
+        for &seq in &segments {
+            let mut data = std::fs::read(segment_path(dir, seq))?;
+            data.truncate(if seq == head_seq { head_len as usize } else { data.len() });
+            let from = if seq == cursor.at.segment { (cursor.at.offset as usize).min(data.len()) } else { 0 };
+            len += data[from..].iter().filter(|b| **b == b'\n').count();
+            if let Some(line) = data.split(|b| *b == b'\n').rfind(|l| !l.is_empty()) {
+                last = String::from_utf8_lossy(line).into_owned();
+            }
+        }
+        let mut reader = BufReader::new(File::open(segment_path(dir, cursor.at.segment))?);
+        reader.seek(SeekFrom::Start(cursor.at.offset))?;
+        if len > 0 {
+            info!("queue: {} candidates left queued in {}", len, dir.display());
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            capacity: capacity.max(1),
+            segment_size,
+            inner: Mutex::new(Inner {
+                head_seq,
+                head,
+                head_len,
+                reader,
+                read_at: cursor.at,
+                len,
+                taken: BTreeSet::new(),
+                last,
+                saved: cursor.at,
+                closed: false,
+            }),
+            ready: Notify::new(),
+            room: Notify::new(),
+        })
+    }
+
+    /// Delete the segments and cursor in `dir`, leaving other files alone.
+    pub fn reset(dir: &Path) -> anyhow::Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        for seq in list_segments(dir)? {
+            std::fs::remove_file(segment_path(dir, seq))?;
+        }
+        let _ = std::fs::remove_file(dir.join("cursor.json"));
+        Ok(())
+    }
+
+    /// Candidates queued and not taken yet.
+    pub fn len(&self) -> usize {
+        self.inner.lock().len
+    }
+
+    /// The candidate queued last, in this run or before; the source resumes after it.
+    pub fn last(&self) -> String {
+        self.inner.lock().last.clone()
+    }
+
+    // Ok(false) when full
+    fn push(&self, domain: &str) -> anyhow::Result<bool> {
+        let mut g = self.inner.lock();
+        if g.closed {
+            anyhow::bail!("closed");
+        }
+        if g.len >= self.capacity {
+            return Ok(false);
+        }
+        let line = format!("{domain}\n");
+        g.head.write_all(line.as_bytes())?;
+        g.head_len += line.len() as u64;
+        g.len += 1;
+        g.last = domain.to_string();
+        if g.head_len >= self.segment_size {
+            let seq = g.head_seq + 1;
+            g.head = OpenOptions::new().create(true).append(true).open(segment_path(&self.dir, seq))?;
+            g.head_seq = seq;
+            g.head_len = 0;
+            // the cursor keeps the last candidate once its segment is deleted
+            self.save(&mut g)?;
+        }
+        drop(g);
+        self.ready.notify_one();
+        Ok(true)
+    }
+
+    async fn pop(&self) -> Option<(String, Position)> {
+        loop {
+            let ready = self.ready.notified();
+            match self.take() {
+                Ok(Some(entry)) => {
+                    self.room.notify_one();
+                    return Some(entry);
+                }
+                Ok(None) if self.inner.lock().closed => return None,
+                Ok(None) => ready.await,
+                Err(e) => {
+                    error!("queue: cannot read {}: {e}", self.dir.display());
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn take(&self) -> anyhow::Result<Option<(String, Position)>> {
+        let mut g = self.inner.lock();
+        if g.len == 0 {
+            return Ok(None);
+        }
+        loop {
+            let mut line = String::new();
+            let n = g.reader.read_line(&mut line)?;
+            if n == 0 {
+                // end of a full segment, on to the next
+                if g.read_at.segment >= g.head_seq {
+                    anyhow::bail!("segment {} ends early", g.read_at.segment);
+                }
+                let seq = g.read_at.segment + 1;
+                g.reader = BufReader::new(File::open(segment_path(&self.dir, seq))?);
+                g.read_at = Position { segment: seq, offset: 0 };
+                continue;
+            }
+            let pos = g.read_at;
+            g.read_at.offset += n as u64;
+            g.len -= 1;
+            g.taken.insert(pos);
+            return Ok(Some((line.trim_end().to_string(), pos)));
+        }
+    }
+
+    fn close(&self) {
+        self.inner.lock().closed = true;
+        self.ready.notify_one();
+    }
+
+    /// Save the cursor and delete the segments wholly before it.
+    pub fn commit(&self) -> anyhow::Result<()> {
+        let mut g = self.inner.lock();
+        self.save(&mut g)
+    }
+
+    fn save(&self, g: &mut Inner) -> anyhow::Result<()> {
+        let at = g.taken.first().copied().unwrap_or(g.read_at);
+        let cursor = Cursor { at, last: g.last.clone() };
+        let path = self.dir.join("cursor.json");
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&cursor)?)?;
+        std::fs::rename(&tmp, &path)?;
+        for seq in g.saved.segment..at.segment {
+            if let Err(e) = std::fs::remove_file(segment_path(&self.dir, seq)) {
+                warn!("queue: cannot delete segment {seq}: {e}");
+            }
+        }
+        g.saved = at;
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{seq:010}.seg"))
+}
+
+// segment numbers in dir, in order
+fn list_segments(dir: &Path) -> anyhow::Result<Vec<u64>> {
+    let mut v = Vec::new();
+    for e in std::fs::read_dir(dir)? {
+        let name = e?.file_name();
+        if let Some(seq) = name.to_str().and_then(|n| n.strip_suffix(".seg")).and_then(|n| n.parse().ok()) {
+            v.push(seq);
+        }
+    }
+    v.sort_unstable();
+    Ok(v)
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use tracing::{debug, info};
+
+use crate::bandwidth::{self, Channel};
+use crate::config::{RdapConfig, StorageConfig};
+use crate::store::DomainStore;
+
+pub const DEFAULT_BOOTSTRAP_URL: &str = "https://data.iana.org/rdap/dns.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Availability {
+    Registered,
+    Available,
+    Unknown,
+}
+
+// Where RDAP classifications are written, separate from live (found) domains
+#[derive(Clone)]
+pub struct AvailabilityStores {
+    pub available: DomainStore,
+    pub registered: DomainStore,
+}
+
+impl AvailabilityStores {
+    /// Open `<storage.dir>/available` and `<storage.dir>/registered` with the configured backend.
+    pub fn open(storage: &StorageConfig) -> anyhow::Result<Self> {
+        let sub = |name: &str| {
+            let mut sc = storage.clone();
+            sc.dir = std::path::Path::new(&storage.dir).join(name).to_string_lossy().to_string();
+            sc.sqlite_path = String::new();
+            sc.s3 = crate::store::S3Backend::sub_config(&storage.s3, name);
+            DomainStore::new(&sc)
+        };
+        Ok(Self {
+            available: sub("available")?,
+            registered: sub("registered")?,
+        })
+    }
+}
+
+// IANA RDAP bootstrap file (RFC 9224)
+#[derive(Deserialize)]
+struct Bootstrap {
+    services: Vec<(Vec<String>, Vec<String>)>,
+}
+
+// RDAP lookups with per-TLD endpoints discovered from the IANA bootstrap registry
+pub struct RdapClient {
+    client: Client,
+    cfg: RdapConfig,
+    endpoints: HashMap<String, String>,
+    permits: Semaphore,
+}
+
+impl RdapClient {
+    pub async fn bootstrap(client: Client, cfg: &RdapConfig) -> anyhow::Result<Arc<Self>> {
+        let url = if cfg.bootstrap_url.trim().is_empty() {
+            DEFAULT_BOOTSTRAP_URL
+        } else {
+            cfg.bootstrap_url.trim()
+        };
+        info!("rdap: loading bootstrap from {}", url);
+        let bs: Bootstrap = client
+            .get(url)
+            .timeout(cfg.timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut endpoints = HashMap::new();
+        for (tlds, urls) in bs.services {
+            // prefer https endpoints
+            let Some(base) = urls
+                .iter()
+                .find(|u| u.starts_with("https://"))
+                .or_else(|| urls.first())
+            else {
+                continue;
+            };
+            let base = if base.ends_with('/') {
+                base.clone()
+            } else {
+                format!("{base}/")
+            };
+            for t in tlds {
+                endpoints.insert(t.to_lowercase(), base.clone());
+            }
+        }
+        info!("rdap: {} TLDs have an RDAP service", endpoints.len());
+        Ok(Arc::new(Self {
+            client,
+            cfg: cfg.clone(),
+            endpoints,
+            permits: Semaphore::new(cfg.max_in_flight.max(1) as usize),
+        }))
+    }
+
+    /// Bootstrap when enabled; a failed bootstrap disables the stage instead of the scan.
+    pub async fn maybe_bootstrap(client: &Client, cfg: &RdapConfig) -> Option<Arc<Self>> {
+        if !cfg.enabled {
+            return None;
+        }
+        match Self::bootstrap(client.clone(), cfg).await {
+            Ok(r) => Some(r),
+            Err(e) => {
+                tracing::error!("rdap: bootstrap failed, availability stage disabled: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn only_unresolved(&self) -> bool {
+        self.cfg.only_unresolved
+    }
+
+    pub async fn lookup(&self, domain: &str) -> Availability {
+        let Some(tld) = domain.rsplit('.').next() else {
+            return Availability::Unknown;
+        };
+        let Some(base) = self.endpoints.get(&tld.to_lowercase()) else {
+            return Availability::Unknown;
+        };
+        let Ok(_permit) = self.permits.acquire().await else {
+            return Availability::Unknown;
+        };
+        let url = format!("{base}domain/{domain}");
+        let req = self.client.get(&url).header("Accept", "application/rdap+json").timeout(self.cfg.timeout);
+        let resp = bandwidth::send(req, Channel::Rdap).await;
+        match resp {
+            Ok(r) if r.status().is_success() => Availability::Registered,
+            Ok(r) if r.status() == StatusCode::NOT_FOUND => Availability::Available,
+            Ok(r) => {
+                debug!("rdap: {} answered {}", url, r.status());
+                Availability::Unknown
+            }
+            Err(e) => {
+                debug!("rdap: request error for {}: {}", url, e);
+                Availability::Unknown
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use crate::config::RetryConfig;
+use crate::domain::Domain;
+use crate::progress::Progress;
+
+// Domains waiting for another attempt, ordered by when they are due
+pub struct RetryQueue {
+    heap: Mutex<BinaryHeap<Reverse<(Instant, Domain, u32)>>>,
+    notify: Notify,
+    prog: Progress,
+}
+
+impl RetryQueue {
+    pub fn new(prog: Progress) -> Self {
+        Self {
+            heap: Mutex::new(BinaryHeap::new()),
+            notify: Notify::new(),
+            prog,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.lock().is_empty()
+    }
+
+    /// Queue the re-check following `attempt` (0 = first check). Returns false
+    /// when the policy gives up on the domain.
+    pub fn schedule(&self, cfg: &RetryConfig, domain: Domain, attempt: u32) -> bool {
+        if !cfg.enabled || attempt >= cfg.max_attempts {
+            return false;
+        }
+        let mut heap = self.heap.lock();
+        if heap.len() >= cfg.max_queued {
+            return false;
+        }
+        heap.push(Reverse((Instant::now() + backoff(cfg, attempt), domain, attempt + 1)));
+        drop(heap);
+        self.prog.inc_retry_scheduled();
+        self.notify.notify_one();
+        true
+    }
+
+    /// Wait for the next due domain and its attempt number.
+    pub async fn next_due(&self) -> (Domain, u32) {
+        loop {
+            let due = {
+                let mut heap = self.heap.lock();
+                match heap.peek() {
+                    Some(Reverse((at, _, _))) if *at <= Instant::now() => {
+                        let Reverse((_, domain, attempt)) = heap.pop().expect("peeked");
+                        self.prog.sub_retry_pending(1);
+                        return (domain, attempt);
+                    }
+                    Some(Reverse((at, _, _))) => Some(*at),
+                    None => None,
+                }
+            };
+            // an earlier entry may be scheduled while we sleep
+            match due {
+                Some(at) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(at.into()) => {}
+                        _ = self.notify.notified() => {}
+                    }
+                }
+                None => self.notify.notified().await,
+            }
+        }
+    }
+
+    /// Drop everything still waiting; returns how many were dropped.
+    pub fn clear(&self) -> usize {
+        let n = std::mem::take(&mut *self.heap.lock()).len();
+        self.prog.sub_retry_pending(n as i64);
+        n
+    }
+}
+
+// base_delay * 2^attempt, capped at max_delay
+fn backoff(cfg: &RetryConfig, attempt: u32) -> Duration {
+    cfg.base_delay.saturating_mul(1u32 << attempt.min(16)).min(cfg.max_delay)
+}
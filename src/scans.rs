@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_yaml as yaml;
+use tokio::task::JoinHandle;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::config::{validate_generator, ProfileConfig};
+use crate::error::ApiError;
+use crate::namespace::{self, Namespaces};
+use crate::progress::Progress;
+use crate::service::{run_profile_scan, ScanControl, ScanState, ServiceContext};
+use crate::store::DomainStore;
+
+// finished scans kept around for GET /scans/
+const KEEP_FINISHED: usize = 50;
+
+#[derive(Deserialize, ToSchema)]
+pub struct StartScanReq {
+    /// Name of a profile from the `profiles` config section; omit it to give
+    /// the scan's settings in this request instead
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Namespace to write to instead of the profile's `namespace`
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Without a profile: generator keys over the top-level generator section
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub generator: Option<yaml::Mapping>,
+    /// Without a profile: candidates for the run; 0 means limits.max_candidates
+    #[serde(default)]
+    pub max_candidates: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ScanInfo {
+    id: u64,
+    /// Absent for a scan started with its own settings
+    #[serde(skip_serializing_if = "String::is_empty")]
+    profile: String,
+    /// Where its found domains go (`/domain/{namespace}/...`); absent for the main store
+    #[serde(skip_serializing_if = "String::is_empty")]
+    namespace: String,
+    state: ScanState,
+    /// The run is over: all candidates checked, or stopped and drained
+    finished: bool,
+    started_at_unix: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at_unix: Option<u64>,
+    elapsed_secs: u64,
+    generated: i64,
+    checked: i64,
+    found: i64,
+    total_planned: i64,
+    percent: f64,
+    speed_per_sec: f64,
+    retry_pending: i64,
+    failed_permanent: i64,
+    wildcard_skipped: i64,
+    last_domain: String,
+}
+
+struct Scan {
+    id: u64,
+    profile: String,
+    namespace: String,
+    prog: Progress,
+    store: DomainStore,
+    control: ScanControl,
+    last_domain: Arc<RwLock<String>>,
+    started_at_unix: u64,
+    finished_at_unix: Mutex<Option<u64>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Scan {
+    fn finished(&self) -> bool {
+        self.finished_at_unix.lock().is_some()
+    }
+
+    fn info(&self) -> ScanInfo {
+        let (enq, chk, fnd, elapsed) = self.prog.snapshot();
+        let (retry_pending, _, failed) = self.prog.retries();
+        let total = self.prog.total_planned();
+        let finished_at = *self.finished_at_unix.lock();
+        // a finished scan's clock stops with it
+        let elapsed_secs = match finished_at {
+            Some(t) => t.saturating_sub(self.started_at_unix),
+            None => elapsed.as_secs(),
+        };
+        ScanInfo {
+            id: self.id,
+            profile: self.profile.clone(),
+            namespace: self.namespace.clone(),
+            state: self.control.state(),
+            finished: finished_at.is_some(),
+            started_at_unix: self.started_at_unix,
+            finished_at_unix: finished_at,
+            elapsed_secs,
+            generated: enq,
+            checked: chk,
+            found: fnd,
+            total_planned: total,
+            percent: if total > 0 {
+                (100.0 * chk as f64 / total as f64).min(100.0)
+            } else {
+                0.0
+            },
+            speed_per_sec: if elapsed_secs > 0 { chk as f64 / elapsed_secs as f64 } else { 0.0 },
+            retry_pending,
+            failed_permanent: failed,
+            wildcard_skipped: self.prog.wildcard(),
+            last_domain: self.last_domain.read().clone(),
+        }
+    }
+}
+
+// Scans started next to the main one, each with its own config, progress and control
+pub struct ScanManager {
+    ctx: ServiceContext,
+    namespaces: Namespaces,
+    scans: Mutex<BTreeMap<u64, Arc<Scan>>>,
+    next_id: AtomicU64,
+}
+
+impl ScanManager {
+    /// `ctx` supplies the shared pieces: live config, storage, client, rate limit.
+    pub fn new(ctx: ServiceContext, namespaces: Namespaces) -> Arc<Self> {
+        Arc::new(Self {
+            ctx,
+            namespaces,
+            scans: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    pub fn profiles(&self) -> Vec<String> {
+        self.ctx.config.current().profiles.keys().cloned().collect()
+    }
+
+    pub async fn start(&self, req: &StartScanReq) -> Result<ScanInfo, ApiError> {
+        let base = self.ctx.config.current();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (profile, cfg, namespace) = match (&req.profile, &req.generator) {
+            (Some(_), Some(_)) => return Err(ApiError::bad_request("give either a profile or generator settings, not both")),
+            (Some(profile), None) => {
+                let Some(p) = base.profiles.get(profile) else {
+                    return Err(ApiError::not_found(format!("no profile named {profile:?}")));
+                };
+                let cfg = p
+                    .resolve(&base)
+                    .map_err(|e| ApiError::bad_request(format!("profile {profile:?}: {e:#}")))?;
+                (profile.as_str(), cfg, p.namespace.clone())
+            }
+            (None, generator) => {
+                if req.max_candidates < 0 {
+                    return Err(ApiError::bad_request("max_candidates must be >= 0"));
+                }
+                let p = ProfileConfig {
+                    generator: generator.clone().unwrap_or_default(),
+                    max_candidates: req.max_candidates,
+                    namespace: String::new(),
+                };
+                let cfg = p
+                    .resolve(&base)
+                    .context("generator")
+                    .and_then(|c| validate_generator(&c.generator, "generator").map(|()| c))
+                    .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+                // its results never mix with another scan's
+                ("", cfg, format!("scan-{id}"))
+            }
+        };
+        let namespace = req.namespace.as_deref().unwrap_or(&namespace).trim().to_string();
+        if !namespace.is_empty() && !namespace::valid_name(&namespace) {
+            return Err(ApiError::bad_request(format!(
+                "namespace {namespace:?} may only contain letters, digits, '-' and '_'"
+            )));
+        }
+        let (store, found_kind) = if namespace.is_empty() {
+            (self.ctx.store.clone(), "found".to_string())
+        } else {
+            let st = self
+                .namespaces
+                .open(&namespace)
+                .await
+                .map_err(|e| ApiError::internal(format!("namespace {namespace:?}: {e:#}")))?;
+            (st, namespace::dedup_kind(&namespace))
+        };
+
+        let mut scans = self.scans.lock();
+        if !profile.is_empty() && scans.values().any(|s| s.profile == profile && !s.finished()) {
+            return Err(ApiError::conflict(format!("a scan of profile {profile:?} is already running")));
+        }
+        let scan = Arc::new(Scan {
+            id,
+            profile: profile.to_string(),
+            namespace: namespace.clone(),
+            prog: Progress::new((cfg.limits.max_candidates as i64).max(0)),
+            store: store.clone(),
+            control: ScanControl::new(),
+            last_domain: Arc::new(RwLock::new(String::new())),
+            started_at_unix: now_unix(),
+            finished_at_unix: Mutex::new(None),
+            task: Mutex::new(None),
+        });
+        let ctx = ServiceContext {
+            config: self.ctx.config.fork(cfg),
+            store,
+            prog: scan.prog.clone(),
+            control: scan.control.clone(),
+            ..self.ctx.clone()
+        };
+        match (profile, namespace.as_str()) {
+            ("", ns) => info!("scan {}: starting with its own settings in namespace '{}'", id, ns),
+            (p, "") => info!("scan {}: starting profile '{}'", id, p),
+            (p, ns) => info!("scan {}: starting profile '{}' in namespace '{}'", id, p, ns),
+        }
+        let task = tokio::spawn({
+            let scan = scan.clone();
+            async move {
+                run_profile_scan(ctx, scan.id, found_kind, scan.last_domain.clone()).await;
+                scan.control.stop();
+                *scan.finished_at_unix.lock() = Some(now_unix());
+                let (_, checked, found, _) = scan.prog.snapshot();
+                info!("scan {}: finished, checked={} found={}", scan.id, checked, found);
+            }
+        });
+        *scan.task.lock() = Some(task);
+        scans.insert(id, scan.clone());
+
+        // forget the oldest finished scans
+        let mut finished: Vec<u64> = scans.values().filter(|s| s.finished()).map(|s| s.id).collect();
+        if finished.len() > KEEP_FINISHED {
+            finished.truncate(finished.len() - KEEP_FINISHED);
+            for id in finished {
+                scans.remove(&id);
+            }
+        }
+        Ok(scan.info())
+    }
+
+    pub fn list(&self) -> Vec<ScanInfo> {
+        self.scans.lock().values().map(|s| s.info()).collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<ScanInfo> {
+        self.scans.lock().get(&id).map(|s| s.info())
+    }
+
+    pub fn control(&self, id: u64) -> Option<ScanControl> {
+        self.scans.lock().get(&id).map(|s| s.control.clone())
+    }
+
+    /// Progress, result store and control of one scan, for its stats and domain endpoints.
+    pub fn parts(&self, id: u64) -> Option<(Progress, DomainStore, ScanControl)> {
+        self.scans.lock().get(&id).map(|s| (s.prog.clone(), s.store.clone(), s.control.clone()))
+    }
+
+    /// Stop every scan and wait for them to drain and flush.
+    pub async fn shutdown(&self) {
+        let tasks: Vec<JoinHandle<()>> = self
+            .scans
+            .lock()
+            .values()
+            .filter_map(|s| {
+                s.control.stop();
+                s.task.lock().take()
+            })
+            .collect();
+        for t in tasks {
+            let _ = t.await;
+        }
+    }
+}
+
+fn now_unix() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
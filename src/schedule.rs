@@ -0,0 +1,246 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::select;
+use tokio::sync::Notify;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::cluster::ClusterRole;
+use crate::config::{LiveConfig, ScheduleConfig};
+
+// upcoming start times listed by GET /schedule/
+const SHOW_NEXT: usize = 5;
+
+/// Why a scheduled run ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunEnd {
+    /// The generator or source had nothing left
+    Finished,
+    MaxDuration,
+    MaxCandidates,
+    /// Stopped via the control API or shut down
+    Stopped,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct RunInfo {
+    pub started_at_unix: u64,
+    /// When max_duration cuts the run off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline_unix: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at_unix: Option<u64>,
+    /// Candidates this run may queue; 0 for no limit
+    pub max_candidates: i64,
+    /// Candidates queued, known once the run ends (not for stopped runs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_by: Option<RunEnd>,
+}
+
+/// GET /schedule/
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScheduleStatus {
+    /// Cron expression (UTC); empty when scans are not scheduled
+    pub cron: String,
+    pub max_duration_secs: u64,
+    pub max_candidates: i64,
+    /// Set through PUT /schedule/; a config reload that changes the schedule section replaces it
+    pub overridden: bool,
+    /// Upcoming start times, RFC 3339 in UTC
+    pub next_runs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_run: Option<RunInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<RunInfo>,
+}
+
+/// Limits of the run that was just started
+pub struct Run {
+    pub deadline: Option<Instant>,
+    /// 0 for no limit
+    pub max_candidates: i64,
+}
+
+impl Run {
+    /// Why a run that queued `sent` candidates is over.
+    pub fn end(&self, sent: i64) -> RunEnd {
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            RunEnd::MaxDuration
+        } else if self.max_candidates > 0 && sent >= self.max_candidates {
+            RunEnd::MaxCandidates
+        } else {
+            RunEnd::Finished
+        }
+    }
+}
+
+// Start times for the main scan (cron), replaceable at runtime
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    // the schedule was replaced; waiters recompute their start time
+    changed: Notify,
+}
+
+struct State {
+    cfg: ScheduleConfig,
+    schedule: Option<cron::Schedule>,
+    overridden: bool,
+    current: Option<RunInfo>,
+    last: Option<RunInfo>,
+}
+
+impl Scheduler {
+    /// `cfg` has passed config validation.
+    pub fn new(cfg: &ScheduleConfig) -> Self {
+        let schedule = cfg.parse().unwrap_or_else(|e| {
+            warn!("schedule: {e:#}");
+            None
+        });
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    cfg: cfg.clone(),
+                    schedule,
+                    overridden: false,
+                    current: None,
+                    last: None,
+                }),
+                changed: Notify::new(),
+            }),
+        }
+    }
+
+    /// Take the schedule section over from config reloads that change it.
+    pub fn follow(&self, live: &LiveConfig) {
+        let mut rx = live.subscribe();
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut seen = rx.borrow_and_update().schedule.clone();
+            while rx.changed().await.is_ok() {
+                let cfg = rx.borrow_and_update().schedule.clone();
+                if cfg != seen {
+                    seen = cfg.clone();
+                    // validated with the rest of the reloaded config
+                    if let Err(e) = this.replace(cfg, false) {
+                        warn!("schedule: {e:#}");
+                    }
+                }
+            }
+        });
+    }
+
+    /// Replace the schedule at runtime (PUT /schedule/).
+    pub fn set(&self, cfg: ScheduleConfig, role: ClusterRole) -> anyhow::Result<()> {
+        if !cfg.cron.trim().is_empty() && role != ClusterRole::Standalone {
+            anyhow::bail!("schedule is not supported in cluster mode");
+        }
+        cfg.validate()?;
+        self.replace(cfg, true)
+    }
+
+    fn replace(&self, cfg: ScheduleConfig, overridden: bool) -> anyhow::Result<()> {
+        let schedule = cfg.parse()?;
+        info!("schedule: cron={:?} max_duration={:?} max_candidates={}", cfg.cron, cfg.max_duration, cfg.max_candidates);
+        let mut st = self.inner.state.lock();
+        st.cfg = cfg;
+        st.schedule = schedule;
+        st.overridden = overridden;
+        drop(st);
+        self.inner.changed.notify_waiters();
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.inner.state.lock().schedule.is_some()
+    }
+
+    /// Sleep until the next start time. Returns false right away, or as soon
+    /// as the schedule is removed, when there is none.
+    pub async fn wait_next(&self) -> bool {
+        loop {
+            // registered before reading the state so a concurrent change is not missed
+            let changed = self.inner.changed.notified();
+            let next = match &self.inner.state.lock().schedule {
+                Some(s) => s.upcoming(Utc).next(),
+                None => return false,
+            };
+            let Some(next) = next else {
+                return false;
+            };
+            info!("schedule: next run at {}", next.to_rfc3339());
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            select! {
+                _ = time::sleep(wait) => return true,
+                _ = changed => {}
+            }
+        }
+    }
+
+    /// Record the start of a run; `default_budget` is limits.max_candidates.
+    pub fn start_run(&self, default_budget: i64) -> Run {
+        let mut st = self.inner.state.lock();
+        let max_candidates = if st.cfg.max_candidates > 0 { st.cfg.max_candidates } else { default_budget };
+        let limit = st.cfg.max_duration;
+        let now = now_unix();
+        st.current = Some(RunInfo {
+            started_at_unix: now,
+            deadline_unix: (!limit.is_zero()).then(|| now + limit.as_secs()),
+            finished_at_unix: None,
+            max_candidates,
+            candidates: None,
+            ended_by: None,
+        });
+        info!("schedule: run started, max_duration={:?} max_candidates={}", limit, max_candidates);
+        Run {
+            deadline: (!limit.is_zero()).then(|| Instant::now() + limit),
+            max_candidates,
+        }
+    }
+
+    /// Record the end of the current run, if one is running.
+    pub fn finish_run(&self, candidates: Option<i64>, ended_by: RunEnd) {
+        let mut st = self.inner.state.lock();
+        if let Some(mut run) = st.current.take() {
+            run.finished_at_unix = Some(now_unix());
+            run.candidates = candidates;
+            run.ended_by = Some(ended_by);
+            info!("schedule: run ended ({:?}), candidates={:?}", ended_by, candidates);
+            st.last = Some(run);
+        }
+    }
+
+    pub fn status(&self) -> ScheduleStatus {
+        let st = self.inner.state.lock();
+        ScheduleStatus {
+            cron: st.cfg.cron.clone(),
+            max_duration_secs: st.cfg.max_duration.as_secs(),
+            max_candidates: st.cfg.max_candidates,
+            overridden: st.overridden,
+            next_runs: st
+                .schedule
+                .iter()
+                .flat_map(|s| s.upcoming(Utc).take(SHOW_NEXT))
+                .map(|t| t.to_rfc3339())
+                .collect(),
+            current_run: st.current.clone(),
+            last_run: st.last.clone(),
+        }
+    }
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::fs;
+
+use tracing::info;
+
+use crate::config::{GeneratorConfig, ScoreWeights};
+
+// dictionary words shorter than this match by accident too often to count
+const MIN_WORD: usize = 3;
+
+/// Rates generated labels from 0 to 100 (`generator.scoring`): pronounceable,
+/// made of dictionary words, short, without digits and hyphens. Used to put
+/// good labels first with `order: priority` and to drop labels under `min_score`.
+pub struct Scorer {
+    dictionary: HashSet<String>,
+    // longest dictionary word, in chars
+    max_word: usize,
+    weights: ScoreWeights,
+    min_score: f64,
+    min_length: usize,
+    max_length: usize,
+}
+
+impl Scorer {
+    /// Read the dictionaries (paths or URLs); called at the start of each pass.
+    pub async fn load(gen: &GeneratorConfig) -> anyhow::Result<Self> {
+        let mut dictionary = HashSet::new();
+        for src in gen.scoring.dictionary.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let body = if src.starts_with("http://") || src.starts_with("https://") {
+                reqwest::get(src).await?.error_for_status()?.text().await?
+            } else {
+                fs::read_to_string(src)?
+            };
+            let before = dictionary.len();
+            for line in body.lines() {
+                let w = line.trim().to_lowercase();
+                if w.starts_with('#') || w.chars().count() < MIN_WORD {
+                    continue;
+                }
+                dictionary.insert(w);
+            }
+            info!("scoring dictionary {}: {} new words", src, dictionary.len() - before);
+        }
+        Ok(Self {
+            max_word: dictionary.iter().map(|w| w.chars().count()).max().unwrap_or(0),
+            dictionary,
+            weights: gen.scoring.weights.clone(),
+            min_score: gen.scoring.min_score,
+            min_length: gen.min_length.max(1) as usize,
+            max_length: gen.max_length.max(1) as usize,
+        })
+    }
+
+    /// Whether the label reaches `min_score`.
+    pub fn passes(&self, label: &str) -> bool {
+        self.min_score <= 0.0 || self.score(label) >= self.min_score
+    }
+
+    pub fn min_score(&self) -> f64 {
+        self.min_score
+    }
+
+    pub fn score(&self, label: &str) -> f64 {
+        let chars: Vec<char> = label.chars().collect();
+        if chars.is_empty() {
+            return 0.0;
+        }
+        let w = &self.weights;
+        let total = w.pronounceable + w.dictionary + w.length + w.digits + w.hyphens;
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let sum = w.pronounceable * pronounceable(&chars)
+            + w.dictionary * self.dictionary_coverage(&chars)
+            + w.length * self.shortness(chars.len())
+            + w.digits * if chars.iter().any(char::is_ascii_digit) { 0.0 } else { 1.0 }
+            + w.hyphens * if chars.contains(&'-') { 0.0 } else { 1.0 };
+        100.0 * sum / total
+    }
+
+    // share of the label covered by dictionary words that do not overlap
+    fn dictionary_coverage(&self, chars: &[char]) -> f64 {
+        if self.dictionary.is_empty() {
+            return 0.0;
+        }
+        let word: String = chars.iter().collect();
+        if self.dictionary.contains(&word) {
+            return 1.0;
+        }
+        // best[i]: most chars of the first i that words can cover
+        let mut best = vec![0usize; chars.len() + 1];
+        for i in 0..chars.len() {
+            best[i + 1] = best[i + 1].max(best[i]);
+            for j in (i + MIN_WORD)..=(i + self.max_word).min(chars.len()) {
+                let part: String = chars[i..j].iter().collect();
+                if self.dictionary.contains(&part) {
+                    best[j] = best[j].max(best[i] + j - i);
+                }
+            }
+        }
+        best[chars.len()] as f64 / chars.len() as f64
+    }
+
+    // 1 at min_length down to 0 at max_length
+    fn shortness(&self, len: usize) -> f64 {
+        if self.max_length <= self.min_length {
+            return 1.0;
+        }
+        let over = len.saturating_sub(self.min_length).min(self.max_length - self.min_length);
+        1.0 - over as f64 / (self.max_length - self.min_length) as f64
+    }
+}
+
+// How easy the label is to say: runs of more than two consonants or vowels
+// cost a point per extra letter, and three or more letters without a vowel
+// score 0. Only Latin letters are judged; other scripts get a neutral 0.5.
+fn pronounceable(chars: &[char]) -> f64 {
+    if chars.iter().any(|c| !c.is_ascii()) {
+        return 0.5;
+    }
+    let letters = chars.iter().filter(|c| c.is_ascii_lowercase()).count();
+    if letters == 0 {
+        return 0.0;
+    }
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    if letters >= 3 && !chars.iter().any(|c| is_vowel(*c)) {
+        return 0.0;
+    }
+    let mut penalty = 0;
+    // current run: vowels or not, and its length; digits and hyphens end it
+    let mut run: Option<(bool, usize)> = None;
+    for &c in chars {
+        if !c.is_ascii_lowercase() {
+            run = None;
+            continue;
+        }
+        let v = is_vowel(c);
+        let len = match run {
+            Some((rv, n)) if rv == v => n + 1,
+            _ => 1,
+        };
+        if len > 2 {
+            penalty += 1;
+        }
+        run = Some((v, len));
+    }
+    1.0 - penalty as f64 / letters as f64
+}
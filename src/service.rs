@@ -1,23 +1,47 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use futures_util::StreamExt;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use once_cell::sync::OnceCell;
-use reqwest::{Client, Method};
-use tokio::{select, sync::mpsc, time};
-use tracing::{error, info, debug};
-use hickory_resolver::{TokioAsyncResolver, config::{ResolverConfig, ResolverOpts}};
+use reqwest::Client;
+use tokio::{
+    select,
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+    task::{JoinHandle, JoinSet},
+    time,
+};
+use tracing::{debug, error, info, warn};
 
-use crate::config::{Config, GeneratorConfig, HTTPCheckConfig};
+use crate::bandwidth;
+use crate::budget::{Budget, BudgetState};
+use crate::checker::{check_clients, Probe};
+use crate::checkpoint::{Checkpoint, CheckpointLog, Cursors};
+use crate::cluster::{ClusterClient, ClusterRole};
+use crate::config::{Config, GeneratorConfig, LiveConfig, TldLimitConfig};
+use crate::dedup::Dedup;
+use crate::dns::{NegativeCache, PositiveCache, ResolverPool};
+use crate::domain::{normalize_tld, Domain};
+use crate::generator::{label_lengths, GenerationOrder};
+use crate::health::Health;
+use crate::ingest::CandidateQueue;
+use crate::journal::Journal;
+use crate::latency::Phase;
+use crate::notify::Notifier;
 use crate::progress::Progress;
-use crate::store::DomainStore;
+use crate::queue::{self, DiskQueue, QueueReceiver, QueueSender};
+use crate::rdap::{Availability, AvailabilityStores, RdapClient};
+use crate::retry::RetryQueue;
+use crate::schedule::{RunEnd, Scheduler};
+use crate::sources::{candidate_suffixes, generate_candidates, read_zones, tail_logs, Feed, ResumePoint, SourceKind};
+use crate::store::{DomainStore, FoundDomain};
+use crate::wildcard::WildcardFilter;
 
 // Public shutdown signal used by main.rs
 #[derive(Clone)]
@@ -41,6 +65,157 @@ impl ShutdownSignal {
     }
 }
 
+// Runtime scan state driven by the control API
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanState {
+    Running,
+    Paused,
+    Stopped,
+}
+
+impl ScanState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanState::Running => "running",
+            ScanState::Paused => "paused",
+            ScanState::Stopped => "stopped",
+        }
+    }
+}
+
+// Control channel shared by the HTTP handlers, generator and workers
+#[derive(Clone)]
+pub struct ScanControl {
+    tx: Arc<watch::Sender<ScanState>>,
+}
+
+impl ScanControl {
+    pub fn new() -> Self {
+        let (tx, _rx) = watch::channel(ScanState::Running);
+        Self { tx: Arc::new(tx) }
+    }
+
+    pub fn state(&self) -> ScanState {
+        *self.tx.borrow()
+    }
+
+    /// Pause a running scan. Returns the state after the call.
+    pub fn pause(&self) -> ScanState {
+        self.tx.send_if_modified(|st| {
+            if *st == ScanState::Running {
+                *st = ScanState::Paused;
+                true
+            } else {
+                false
+            }
+        });
+        self.state()
+    }
+
+    /// Resume a paused scan. Returns the state after the call.
+    pub fn resume(&self) -> ScanState {
+        self.tx.send_if_modified(|st| {
+            if *st == ScanState::Paused {
+                *st = ScanState::Running;
+                true
+            } else {
+                false
+            }
+        });
+        self.state()
+    }
+
+    /// Stop the scan; this is terminal.
+    pub fn stop(&self) -> ScanState {
+        self.tx.send_replace(ScanState::Stopped);
+        ScanState::Stopped
+    }
+
+    /// Resolves once the scan is stopped.
+    pub async fn stopped(&self) {
+        let mut rx = self.tx.subscribe();
+        let _ = rx.wait_for(|st| *st == ScanState::Stopped).await;
+    }
+
+    /// Block while paused. Returns false once the scan is stopped.
+    pub async fn wait_running(&self) -> bool {
+        let mut rx = self.tx.subscribe();
+        loop {
+            match *rx.borrow_and_update() {
+                ScanState::Running => return true,
+                ScanState::Stopped => return false,
+                ScanState::Paused => {}
+            }
+            if rx.changed().await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+// Token-bucket rate limiter gating DNS+HTTP checks
+pub struct RateLimiter {
+    rate: AtomicU64,
+    burst: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// burst == 0 means "same as rate"
+    pub fn new(rate_per_second: u64, burst: u64) -> Self {
+        let rate = rate_per_second.max(1);
+        let burst = if burst == 0 { rate } else { burst };
+        Self {
+            rate: AtomicU64::new(rate),
+            burst: AtomicU64::new(burst),
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn set_rate(&self, rate_per_second: u64) {
+        self.rate.store(rate_per_second.max(1), Ordering::Relaxed);
+    }
+
+    pub fn set_burst(&self, burst: u64) {
+        let burst = if burst == 0 { self.rate.load(Ordering::Relaxed) } else { burst };
+        self.burst.store(burst, Ordering::Relaxed);
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.rate.load(Ordering::Relaxed)
+    }
+
+    /// Wait until a token is available and take it.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let rate = self.rate.load(Ordering::Relaxed) as f64;
+                let burst = self.burst.load(Ordering::Relaxed) as f64;
+                let mut b = self.bucket.lock();
+                let now = Instant::now();
+                let elapsed = now.duration_since(b.last).as_secs_f64();
+                b.tokens = (b.tokens + elapsed * rate).min(burst);
+                b.last = now;
+                if b.tokens >= 1.0 {
+                    b.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - b.tokens) / rate)
+            };
+            time::sleep(wait).await;
+        }
+    }
+}
+
 // In-memory last domain for resume tracking
 static LAST_DOMAIN: OnceCell<Arc<RwLock<String>>> = OnceCell::new();
 
@@ -48,70 +223,329 @@ fn last_domain_cell() -> Arc<RwLock<String>> {
     LAST_DOMAIN.get_or_init(|| Arc::new(RwLock::new(String::new()))).clone()
 }
 
-pub async fn run_service(
-    cfg: Config,
+// Handles shared between the background service and the HTTP API
+#[derive(Clone)]
+pub struct ServiceContext {
+    pub config: LiveConfig,
+    pub store: DomainStore,
+    pub prog: Progress,
+    pub client: Client,
+    pub limiter: Arc<RateLimiter>,
+    /// limits.per_tld caps
+    pub tld_limits: Arc<TldLimits>,
+    /// Pipeline liveness for /healthz and /readyz (main scan only)
+    pub health: Health,
+    pub control: ScanControl,
+    pub avail_stores: Option<AvailabilityStores>,
+    pub dedup: Option<Arc<Dedup>>,
+    /// dns.negative_cache, shared by the main and profile scans
+    pub negative_cache: Option<Arc<NegativeCache>>,
+    /// dns.positive_cache, shared by the scans and the verify rounds
+    pub positive_cache: Option<Arc<PositiveCache>>,
+    pub notifier: Notifier,
+    /// Candidates posted to /candidates/; only the main scan takes from it
+    pub candidates: Arc<CandidateQueue>,
+    /// Start times of the main scan
+    pub schedule: Scheduler,
+}
+
+// Semaphore whose capacity can change at runtime
+struct WorkerLimit {
+    sem: Arc<Semaphore>,
+    size: Mutex<usize>,
+}
+
+impl WorkerLimit {
+    fn new(size: usize) -> Self {
+        Self {
+            sem: Arc::new(Semaphore::new(size)),
+            size: Mutex::new(size),
+        }
+    }
+
+    fn resize(&self, size: usize) {
+        let mut cur = self.size.lock();
+        if size > *cur {
+            self.sem.add_permits(size - *cur);
+        } else if size < *cur {
+            // retire permits as running checks finish
+            let sem = self.sem.clone();
+            let n = (*cur - size) as u32;
+            tokio::spawn(async move {
+                if let Ok(p) = sem.acquire_many_owned(n).await {
+                    p.forget();
+                }
+            });
+        }
+        *cur = size;
+    }
+}
+
+// Checks of one capped TLD that may wait for its caps; beyond this the dispatcher blocks
+const TLD_BACKLOG: usize = 10_000;
+
+// How often checks held by limits.bandwidth look at the limit again
+const BANDWIDTH_RECHECK: Duration = Duration::from_secs(10);
+
+// Caps of one limits.per_tld entry
+struct TldGate {
+    workers: Option<WorkerLimit>,
+    limiter: Option<RateLimiter>,
+    // checks waiting for this gate, bounded so a slow TLD cannot pile up without limit
+    backlog: Arc<Semaphore>,
+}
+
+impl TldGate {
+    fn new(l: &TldLimitConfig) -> Self {
+        Self {
+            workers: (l.concurrency > 0).then(|| WorkerLimit::new(l.concurrency as usize)),
+            limiter: (l.rate_per_second > 0).then(|| RateLimiter::new(l.rate_per_second as u64, l.burst.max(0) as u64)),
+            backlog: Arc::new(Semaphore::new(TLD_BACKLOG)),
+        }
+    }
+
+    // same kinds of caps, so a reload can adjust it in place
+    fn fits(&self, l: &TldLimitConfig) -> bool {
+        self.workers.is_some() == (l.concurrency > 0) && self.limiter.is_some() == (l.rate_per_second > 0)
+    }
+
+    fn update(&self, l: &TldLimitConfig) {
+        if let Some(w) = &self.workers {
+            w.resize(l.concurrency as usize);
+        }
+        if let Some(r) = &self.limiter {
+            r.set_rate(l.rate_per_second as u64);
+            r.set_burst(l.burst.max(0) as u64);
+        }
+    }
+
+    // wait for a concurrency slot and a token; the permit is held for the check
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.workers {
+            Some(w) => w.sem.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        if let Some(r) = &self.limiter {
+            r.acquire().await;
+        }
+        permit
+    }
+}
+
+/// Per-TLD caps from limits.per_tld, applied on top of the global ones.
+/// Shared by the main and profile scans, and follows config reloads.
+#[derive(Default)]
+pub struct TldLimits {
+    // keyed by ACE TLD without the leading dot ("io", "co.uk")
+    gates: RwLock<HashMap<String, Arc<TldGate>>>,
+}
+
+impl TldLimits {
+    pub fn new(per_tld: &BTreeMap<String, TldLimitConfig>) -> Self {
+        let limits = Self::default();
+        limits.apply(per_tld);
+        limits
+    }
+
+    /// Adopt a (reloaded) limits.per_tld; checks already waiting keep the caps they queued for.
+    pub fn apply(&self, per_tld: &BTreeMap<String, TldLimitConfig>) {
+        let mut gates = self.gates.write();
+        let mut next = HashMap::new();
+        for (tld, l) in per_tld {
+            let Some(key) = normalize_tld(tld) else { continue };
+            let gate = match gates.remove(&key) {
+                Some(g) if g.fits(l) => {
+                    g.update(l);
+                    g
+                }
+                _ => Arc::new(TldGate::new(l)),
+            };
+            info!("limits: .{} concurrency={} rate={}/s", key, l.concurrency, l.rate_per_second);
+            next.insert(key, gate);
+        }
+        *gates = next;
+    }
+
+    // gate of the longest configured suffix of `domain`
+    fn gate(&self, domain: &str) -> Option<Arc<TldGate>> {
+        let gates = self.gates.read();
+        if gates.is_empty() {
+            return None;
+        }
+        domain.match_indices('.').find_map(|(i, _)| gates.get(&domain[i + 1..]).cloned())
+    }
+}
+
+// Everything a single check needs: Probe (the `checks` pipeline) -> Store
+struct Worker {
+    config: LiveConfig,
     store: DomainStore,
     prog: Progress,
-    client: Client,
-    shutdown: ShutdownSignal,
-) {
-    // Increase channel size for buffering
-    let (tx, rx) = mpsc::channel::<String>(10000);
-
-    // DNS Resolver
-    let resolver = TokioAsyncResolver::tokio(
-        ResolverConfig::google(),
-        ResolverOpts::default(),
-    );
-    let resolver = Arc::new(resolver);
+    probe: Probe,
+    limiter: Arc<RateLimiter>,
+    tld_limits: Arc<TldLimits>,
+    control: ScanControl,
+    avail_stores: Option<AvailabilityStores>,
+    dedup: Option<Arc<Dedup>>,
+    cluster: Option<Arc<ClusterClient>>,
+    retries: Arc<RetryQueue>,
+    notifier: Notifier,
+    health: Option<Health>,
+    last_domain: Arc<RwLock<String>>,
+    journal: Option<Arc<Journal>>,
+    /// Stamped on stored records: the /scans/ id, 0 for the main scan
+    scan_id: u64,
+    /// Dedup key space of `store`: "found", or one per namespace
+    found_kind: String,
+}
 
-    // Concurrency limiter
-    let concurrency = cfg.limits.concurrency.max(1) as usize;
-    info!("concurrency: {} workers", concurrency);
+impl Worker {
+    // append unless the dedup layer has seen this record before; true when stored
+    async fn store_once(&self, store: &DomainStore, kind: &str, rec: FoundDomain) -> bool {
+        if let Some(d) = &self.dedup {
+            if !d.first_store(kind, &rec.domain) {
+                self.prog.inc_duplicate();
+                return false;
+            }
+        }
+        let started = Instant::now();
+        store.add(rec).await;
+        self.prog.add_stage_time(Phase::Store, started.elapsed());
+        true
+    }
 
-    // Pipeline: Generator -> Channel -> Stream -> DNS -> HTTP -> Store
-    {
-        let store = store.clone();
-        let prog = prog.clone();
-        let client = client.clone();
-        let hc = cfg.http_check.clone();
-        let resolver = resolver.clone();
-        
-        // Convert receiver to stream
-        let stream = tokio_stream::wrappers::ReceiverStream::new(rx);
-        
-        // Process stream with concurrency
-        let process_fut = stream.for_each_concurrent(concurrency, move |domain: String| {
-            let store = store.clone();
-            let prog = prog.clone();
-            let client = client.clone();
-            let hc = hc.clone();
-            let resolver = resolver.clone();
-            
-            async move {
-                // 1. DNS Resolve (Fast Filter)
-                let has_ip = match resolver.lookup_ip(&domain).await {
-                    Ok(ips) => ips.iter().next().is_some(),
-                    Err(_) => false,
-                };
+    // attempt 0 is the first check, later ones come from the retry queue;
+    // `generated` is false for posted candidates, which do not move the resume position.
+    // False when the candidate is not done with: dropped on stop, or a re-check is scheduled
+    async fn check(&self, domain: Domain, attempt: u32, generated: bool) -> bool {
+        // hold queued candidates while paused, drop them once stopped
+        if !self.control.wait_running().await {
+            return false;
+        }
+        let _timer = self.prog.time_check();
+        let _busy = self.health.as_ref().map(Health::busy);
+        let waited = Instant::now();
+        self.limiter.acquire().await;
+        // limits.bandwidth: hold the check until the next hour, reading the
+        // limit again now and then so a reload can lift it
+        while let Some(wait) = bandwidth::over_limit(&self.config.current().limits.bandwidth) {
+            select! {
+                _ = self.control.stopped() => return false,
+                _ = time::sleep(wait.min(BANDWIDTH_RECHECK)) => {}
+            }
+        }
+        self.prog.add_stage_time(Phase::RateLimit, waited.elapsed());
+        let cfg = self.config.current();
 
-                if has_ip {
-                    // 2. HTTP Check (Slow Check)
-                    if let Ok(ok) = check_domain(&client, &domain, &hc).await {
-                        if ok {
-                            store.add(&domain);
-                            prog.inc_found();
-                        }
-                    }
+        let v = self.probe.evaluate(&domain, &cfg.http_check).await;
+        for t in &v.timings {
+            self.prog.observe(*t);
+        }
+        if v.wildcard {
+            self.prog.inc_wildcard();
+        }
+        if let Some(kind) = v.failure {
+            self.prog.inc_error(kind);
+        }
+        if !v.found && v.transient {
+            if self.retries.schedule(&cfg.retry, domain.clone(), attempt) {
+                debug!("retry: {} failed transiently (attempt {}), re-check scheduled", domain, attempt + 1);
+                return false;
+            }
+            self.prog.inc_failed();
+        }
+        if let Some(mut rec) = v.found_record() {
+            rec.scan_id = self.scan_id;
+            if let Some(c) = &self.cluster {
+                c.report(rec.clone());
+            }
+            let notify = rec.clone();
+            if self.store_once(&self.store, &self.found_kind, rec).await {
+                self.notifier.found(&notify);
+            }
+            self.prog.inc_found(&domain);
+        }
+        if let Some(stores) = self.avail_stores.as_ref() {
+            match v.availability {
+                Some(Availability::Available) => {
+                    self.store_once(&stores.available, "available", FoundDomain::new(&domain, v.ips)).await;
+                    self.prog.inc_available();
+                }
+                Some(Availability::Registered) => {
+                    self.store_once(&stores.registered, "registered", FoundDomain::new(&domain, v.ips)).await;
+                    self.prog.inc_registered();
                 }
-                
-                prog.inc_checked();
-                *last_domain_cell().write() = domain.clone();
+                _ => {}
             }
-        });
+        }
+
+        self.prog.inc_checked(&domain);
+        if let Some(j) = &self.journal {
+            j.done(&domain);
+        }
+        if let Some(c) = &self.cluster {
+            c.checked(&domain);
+        }
+        // re-checks are behind the generator position, keep it from moving back
+        if attempt == 0 && generated {
+            *self.last_domain.write() = domain.into();
+        }
+        true
+    }
+}
+
+pub async fn run_service(ctx: ServiceContext, shutdown: ShutdownSignal) {
+    let ServiceContext {
+        config,
+        store,
+        prog,
+        client,
+        limiter,
+        tld_limits,
+        health,
+        control,
+        avail_stores,
+        dedup,
+        negative_cache,
+        positive_cache,
+        notifier,
+        candidates,
+        schedule,
+    } = ctx;
+    let cfg = config.current();
+
+    // DNS resolver, optional RDAP availability stage and wildcard filter
+    let probe = match build_probe(&cfg, &client, avail_stores.is_some(), negative_cache.clone(), positive_cache.clone()).await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("dns: invalid resolver config: {e}");
+            return;
+        }
+    };
+
+    // Cluster: workers lease shards from a coordinator, the coordinator only collects
+    let role = cfg.cluster.role;
+    let source = cfg.source.kind;
+    let cluster = (role == ClusterRole::Worker).then(|| ClusterClient::new(&cfg, client.clone()));
 
-        // Spawn processor
-        tokio::spawn(process_fut);
+    // Dedup: never append a stored domain twice
+    if let Some(d) = &dedup {
+        d.seed(&store, "found").await;
+        if let Some(st) = &avail_stores {
+            d.seed(&st.available, "available").await;
+            d.seed(&st.registered, "registered").await;
+        }
+    }
+
+    // Concurrency limiter
+    let concurrency = cfg.limits.concurrency.max(1) as usize;
+    info!("concurrency: {} workers, rate limit: {}/s", concurrency, limiter.rate());
+    let workers = Arc::new(WorkerLimit::new(concurrency));
+    let retries = Arc::new(RetryQueue::new(prog.clone()));
+    health.set_workers(concurrency);
+    prog.set_workers(concurrency);
+    if role != ClusterRole::Coordinator {
+        health.spawn_resolver_probe(&cfg);
     }
 
     // Resume state management
@@ -120,16 +554,47 @@ pub async fn run_service(
     } else {
         PathBuf::from(&cfg.storage.state_file)
     };
-    info!("resume: enabled={}, state_file={}", cfg.storage.resume, state_path.display());
+    // shards replace the resume position in cluster mode
+    let resume = cfg.storage.resume && role == ClusterRole::Standalone;
+    info!("resume: enabled={}, state_file={}", resume, state_path.display());
+    // Queue between the source and the checks; on disk it outlives a crash
+    let (tx, rx, disk) = match queue::open(&cfg.storage.queue, resume) {
+        Ok(q) => q,
+        Err(e) => {
+            error!("queue: cannot open {}: {e}", cfg.storage.queue.dir);
+            return;
+        }
+    };
+    if let Some(q) = &disk {
+        prog.add_queued(q.len() as i64);
+        let q = q.clone();
+        tokio::spawn(async move {
+            let mut ticker = time::interval(Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                if let Err(e) = q.commit() {
+                    warn!("queue: cannot save cursor: {e}");
+                }
+            }
+        });
+    }
     let last = last_domain_cell();
-    if cfg.storage.resume {
+    let budget = Arc::new(Budget::new(config.clone()));
+    let mut journal = None;
+    let mut checkpoint = None;
+    let mut cursors = Cursors::default();
+    let mut positions = None;
+    if resume {
         if let Ok(s) = std::fs::read_to_string(&state_path) {
             if let Ok(st) = serde_json::from_str::<ResumeState>(&s) {
                 let ld = st.last_domain.trim().to_string();
                 if !ld.is_empty() {
                     info!("resume: loaded last='{}'", ld);
                 }
+                cursors = st.cursors(&cfg.generator);
                 *last.write() = ld;
+                checkpoint = st.checkpoint;
+                budget.restore(st.budget);
                 // restore progress counters if present
                 if st.enqueued > 0 || st.checked > 0 || st.found > 0 || st.total_planned > 0 {
                     let tp = if st.total_planned > 0 { st.total_planned } else { prog.total_planned() };
@@ -138,52 +603,234 @@ pub async fn run_service(
                 }
             }
         }
+        positions = Some(Arc::new(CheckpointLog::with_cursors(cursors.clone())));
+        // CT names arrive in log order and keep their own per-log positions;
+        // a disk queue already keeps everything not checked yet
+        if cfg.storage.journal && source != SourceKind::Ct && disk.is_none() {
+            match Journal::open(Path::new(&cfg.storage.journal_file), last.read().clone()) {
+                Ok(j) => journal = Some(Arc::new(j)),
+                Err(e) => warn!("journal: cannot open {}: {e}; resuming from the last position only", cfg.storage.journal_file),
+            }
+        }
         // periodic saver
         let state_path_clone = state_path.clone();
         let last_for_saver = last.clone();
+        let journal_for_saver = journal.clone();
+        let disk_for_saver = disk.clone();
+        let positions_for_saver = positions.clone();
+        let config_for_saver = config.clone();
         let prog_for_saver = prog.clone();
+        let budget_for_saver = budget.clone();
         tokio::spawn(async move {
             let mut prev = String::new();
             let mut ticker = time::interval(Duration::from_secs(5)); // Save every 5s
             loop {
                 ticker.tick().await;
-                let cur = last_for_saver.read().clone();
+                let cur = resume_position(&last_for_saver, journal_for_saver.as_deref(), disk_for_saver.as_deref());
                 if !cur.is_empty() && cur != prev {
-                    let _ = save_resume(&state_path_clone, &cur, &prog_for_saver);
+                    let cp = position_of(&cur, positions_for_saver.as_deref(), &config_for_saver);
+                    if let Err(e) = save_resume(&state_path_clone, &cur, cp, &prog_for_saver, budget_for_saver.snapshot()) {
+                        warn!("resume: cannot save state: {e}");
+                        continue;
+                    }
                     debug!("resume: saved last='{}'", cur);
                     prev = cur;
                 }
+                // entries at or below the saved position are no longer needed
+                if let Some(j) = &journal_for_saver {
+                    if let Err(e) = j.compact() {
+                        warn!("journal: compaction failed: {e}");
+                    }
+                }
+            }
+        });
+    }
+
+    // Pipeline: Generator -> Channel -> Worker (DNS -> HTTP -> Store)
+    let worker = Arc::new(Worker {
+        config: config.clone(),
+        store: store.clone(),
+        prog: prog.clone(),
+        probe,
+        limiter: limiter.clone(),
+        tld_limits: tld_limits.clone(),
+        control: control.clone(),
+        avail_stores: avail_stores.clone(),
+        dedup: dedup.clone(),
+        cluster: cluster.clone(),
+        retries,
+        notifier: notifier.clone(),
+        health: Some(health.clone()),
+        last_domain: last_domain_cell(),
+        journal: journal.clone(),
+        scan_id: 0,
+        found_kind: "found".to_string(),
+    });
+    let mut dispatcher = spawn_dispatcher(worker, workers.clone(), rx, Some(candidates));
+
+    // Apply reloaded limits to the running pipeline
+    {
+        let mut cfg_rx = config.subscribe();
+        let workers = workers.clone();
+        let limiter = limiter.clone();
+        let tld_limits = tld_limits.clone();
+        let health = health.clone();
+        let prog = prog.clone();
+        tokio::spawn(async move {
+            while cfg_rx.changed().await.is_ok() {
+                let c = cfg_rx.borrow_and_update().clone();
+                workers.resize(c.limits.concurrency.max(1) as usize);
+                health.set_workers(c.limits.concurrency.max(1) as usize);
+                prog.set_workers(c.limits.concurrency.max(1) as usize);
+                limiter.set_rate(c.limits.rate_per_second.max(1) as u64);
+                limiter.set_burst(c.limits.burst.max(0) as u64);
+                tld_limits.apply(&c.limits.per_tld);
+                info!(
+                    "config applied: concurrency={} rate={}/s burst={}",
+                    c.limits.concurrency, c.limits.rate_per_second, c.limits.burst
+                );
             }
         });
     }
 
     // Generator Loop
     info!("service entering main loop");
+    health.set_started();
+    let mut interrupted = false;
+    let mut first_pass = true;
     loop {
-        let cfg_gen = cfg.generator.clone();
+        // scheduled scans wait for their start time
+        let mut run = None;
+        if schedule.enabled() {
+            select! {
+                _ = shutdown.wait() => {
+                    interrupted = true;
+                    break;
+                }
+                _ = control.stopped() => {
+                    info!("scan stopped via control API");
+                    interrupted = true;
+                    break;
+                }
+                due = schedule.wait_next() => {
+                    if due {
+                        run = Some(schedule.start_run(config.current().limits.max_candidates as i64));
+                    } else if !first_pass && !config.current().run.loop_ {
+                        // the schedule was removed while waiting: done, as without one
+                        break;
+                    }
+                }
+            }
+        }
+        first_pass = false;
+        let deadline = run.as_ref().and_then(|r| r.deadline);
         let tx_gen = tx.clone();
         let last_for_gen = last_domain_cell();
+        // only the first pass starts from the saved checkpoint and cursors
+        let checkpoint = checkpoint.take();
+        let mut cursors = std::mem::take(&mut cursors);
 
         select! {
             _ = shutdown.wait() => {
+                if run.is_some() {
+                    schedule.finish_run(None, RunEnd::Stopped);
+                }
+                interrupted = true;
+                break;
+            }
+            _ = control.stopped() => {
+                info!("scan stopped via control API");
+                if run.is_some() {
+                    schedule.finish_run(None, RunEnd::Stopped);
+                }
+                interrupted = true;
                 break;
             }
             res = async {
-                let resume_from = last_for_gen.read().clone();
-                info!("generator start: resume_from='{}'", resume_from);
-                generate_candidates(
-                    cfg_gen,
-                    resume_from,
-                    &tx_gen,
-                    &prog,
-                    cfg.limits.max_candidates as i64,
-                ).await
+                let _generating = health.generating();
+                match &cluster {
+                    Some(c) => run_shard(c, &config, &tx_gen, &prog, &control, dedup.as_deref()).await,
+                    // the coordinator serves leases and reports until shut down
+                    None if role == ClusterRole::Coordinator => std::future::pending().await,
+                    None => {
+                        // everything up to the last queued candidate is on the disk queue
+                        let resume_from = disk.as_ref().map_or_else(|| last_for_gen.read().clone(), |q| q.last());
+                        let cur = config.current();
+                        let mut feed = Feed {
+                            tx: &tx_gen,
+                            prog: &prog,
+                            control: &control,
+                            dedup: dedup.as_deref(),
+                            journal: journal.as_deref(),
+                            budget: Some(budget.as_ref()),
+                            shard: None,
+                            max_candidates: run.as_ref().map_or(cur.limits.max_candidates as i64, |r| r.max_candidates),
+                            // a scheduled run has its own budget; a resumed pass goes on counting
+                            sent: if run.is_some() { 0 } else { budget.pass_sent() },
+                        };
+                        let pass = async {
+                            match source {
+                                SourceKind::Generator => {
+                                    info!("generator start: resume_from='{}'", resume_from);
+                                    // the disk queue may have got further than the last save
+                                    if !cursors.is_empty() && disk.is_some() {
+                                        cursors.advance(&resume_from, &candidate_suffixes(&cur.generator), label_lengths(&cur.generator));
+                                    }
+                                    let from = ResumePoint { from: resume_from, checkpoint, cursors, positions: positions.as_deref() };
+                                    generate_candidates(&config, from, &mut feed, None).await
+                                }
+                                SourceKind::Zone => {
+                                    info!("zone source start: {} files, resume_from='{}'", cur.source.zone.files.len(), resume_from);
+                                    read_zones(&cur.source.zone.files, &cur.generator.tlds, &resume_from, &mut feed).await
+                                }
+                                SourceKind::Ct => {
+                                    info!("ct source start: {} logs", cur.source.ct.logs.len());
+                                    tail_logs(&config, &client, &mut feed).await
+                                }
+                            }
+                        };
+                        match deadline {
+                            // what is queued still gets checked; the next run continues after it
+                            Some(d) => time::timeout_at(d, pass).await.unwrap_or_else(|_| Ok(feed.sent)),
+                            None => pass.await,
+                        }.map(Some)
+                    }
+                }
             } => {
                 match res {
-                    Ok(sent) => info!("generator finished: enqueued_sent={}", sent),
-                    Err(e) => error!("generator error: {e}"),
+                    Ok(Some(sent)) => {
+                        info!("generator finished: enqueued_sent={}", sent);
+                        if let Some(r) = &run {
+                            schedule.finish_run(Some(sent), r.end(sent));
+                        }
+                    }
+                    Ok(None) => {
+                        info!("cluster: no shards left");
+                        break;
+                    }
+                    Err(e) => {
+                        error!("generator error: {e}");
+                        if run.is_some() {
+                            schedule.finish_run(None, RunEnd::Finished);
+                        }
+                        if cluster.is_some() {
+                            // coordinator unreachable or busy; retry
+                            time::sleep(Duration::from_secs(5)).await;
+                        }
+                    }
+                }
+                if let Some(d) = &dedup {
+                    d.new_pass();
                 }
-                if !cfg.run.loop_ {
+                if let Some(j) = &journal {
+                    j.new_pass();
+                }
+                budget.new_pass();
+                if budget.exhausted() {
+                    info!("budget: limits.budget.total of {} candidates used up, scan ends", config.current().limits.budget.total);
+                    break;
+                }
+                if cluster.is_none() && !schedule.enabled() && !config.current().run.loop_ {
                     break;
                 }
                 time::sleep(Duration::from_millis(250)).await;
@@ -191,155 +838,354 @@ pub async fn run_service(
         }
     }
 
+    // Drain: close the queue, let checks finish, flush storage, save resume state
+    tx.close();
+    if !interrupted {
+        // the generator is done; check everything still queued unless asked to quit
+        select! {
+            _ = &mut dispatcher => {}
+            _ = shutdown.wait() => interrupted = true,
+        }
+    }
+    if interrupted {
+        // queued candidates are dropped and regenerated on resume, or stay on
+        // the disk queue; in-flight checks finish
+        control.stop();
+        drain(&mut dispatcher, config.current().run.drain_timeout).await;
+    }
+    store.flush().await;
+    if let Some(q) = &disk {
+        if let Err(e) = q.commit() {
+            warn!("queue: cannot save cursor: {e}");
+        }
+    }
+    if let Some(n) = &negative_cache {
+        n.save();
+    }
+    if let Some(c) = &cluster {
+        c.flush().await;
+    }
+    notifier.flush().await;
+    if let Some(st) = &avail_stores {
+        st.available.flush().await;
+        st.registered.flush().await;
+    }
+
     // final save resume
-    if cfg.storage.resume {
-        let cur = last_domain_cell().read().clone();
-        let _ = save_resume(&state_path, &cur, &prog);
+    if resume {
+        let cur = resume_position(&last_domain_cell(), journal.as_deref(), disk.as_deref());
+        let cp = position_of(&cur, positions.as_deref(), &config);
+        let saved = save_resume(&state_path, &cur, cp, &prog, budget.snapshot()).is_ok();
+        if let Some(j) = journal.as_ref().filter(|_| saved) {
+            if let Err(e) = j.compact() {
+                warn!("journal: compaction failed: {e}");
+            }
+        }
     }
 
     info!("service stopped");
 }
 
-async fn check_domain(client: &Client, domain: &str, hc: &HTTPCheckConfig) -> anyhow::Result<bool> {
-    let method = if hc.method.trim().is_empty() {
-        Method::GET
-    } else {
-        Method::from_bytes(hc.method.as_bytes()).unwrap_or(Method::GET)
-    };
-    let schemes = if hc.try_https_first {
-        ["https", "http"]
+/// The configured checks (RDAP only when the availability stores are open) for one scan
+pub async fn build_probe(
+    cfg: &Config,
+    client: &Client,
+    with_rdap: bool,
+    negative: Option<Arc<NegativeCache>>,
+    positive: Option<Arc<PositiveCache>>,
+) -> anyhow::Result<Probe> {
+    let resolver = ResolverPool::from_config(&cfg.dns)?
+        .with_negative_cache(negative)
+        .with_positive_cache(positive);
+    let rdap = if with_rdap {
+        RdapClient::maybe_bootstrap(client, &cfg.rdap).await
     } else {
-        ["http", "https"]
+        None
     };
+    Ok(Probe::new(cfg, check_clients(cfg)?, resolver, rdap, WildcardFilter::from_config(&cfg.wildcard)))
+}
 
-    for _attempt in 0..=hc.retry {
-        for scheme in schemes {
-            let url = format!("{scheme}://{domain}/");
-            // Short timeout for connection
-            let req = client.request(method.clone(), &url).build()?;
-            let resp = client.execute(req).await;
-            match resp {
-                Ok(resp) => {
-                    let status = resp.status().as_u16() as i32;
-                    // Just check status, don't read body if not needed
-                    if status >= hc.accept_status_min && status <= hc.accept_status_max {
-                        debug!("reachable: {} status={}", url, status);
-                        return Ok(true);
+// Feed due re-checks, posted candidates and queued candidates (in that order
+// of priority) to the worker, at most `workers` at a time; finishes once the
+// queue is closed and drained, or on stop
+fn spawn_dispatcher(
+    worker: Arc<Worker>,
+    workers: Arc<WorkerLimit>,
+    mut rx: QueueReceiver,
+    posted: Option<Arc<CandidateQueue>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let retries = worker.retries.clone();
+        let control = worker.control.clone();
+        let mut running = JoinSet::new();
+        let mut open = true;
+        loop {
+            // generator done: finish once no re-check is waiting or can still be scheduled
+            if !open && retries.is_empty() && posted.as_ref().is_none_or(|q| q.is_empty()) && running.is_empty() {
+                break;
+            }
+            let Ok(permit) = workers.sem.clone().acquire_owned().await else {
+                break;
+            };
+            let next = select! {
+                biased;
+                _ = control.stopped() => break,
+                (domain, attempt) = retries.next_due() => Some((domain, attempt, true, None)),
+                domain = next_posted(posted.as_deref()) => Some((domain, 0, false, None)),
+                msg = rx.recv(), if open => match msg {
+                    Some((domain, taken)) => {
+                        worker.prog.dec_queued();
+                        Some((domain, 0, true, taken))
                     }
+                    None => {
+                        open = false;
+                        None
+                    }
+                },
+                Some(_) = running.join_next(), if !open => None,
+            };
+            let Some((domain, attempt, generated, taken)) = next else { continue };
+            let worker = worker.clone();
+            let Some(gate) = worker.tld_limits.gate(&domain) else {
+                running.spawn(async move {
+                    // the disk queue's cursor stays before a candidate that is not done with
+                    let done = worker.check(domain, attempt, generated).await;
+                    drop(permit);
+                    if let Some(t) = taken.filter(|_| done) {
+                        t.done();
+                    }
+                });
+                continue;
+            };
+            // a capped TLD waits for its caps without holding one of the workers
+            drop(permit);
+            let queued = select! {
+                _ = control.stopped() => break,
+                p = gate.backlog.clone().acquire_owned() => p,
+            };
+            let (workers, control) = (workers.clone(), control.clone());
+            running.spawn(async move {
+                let _tld_permit = select! {
+                    _ = control.stopped() => return,
+                    p = gate.acquire() => p,
+                };
+                drop(queued);
+                let Ok(permit) = workers.sem.clone().acquire_owned().await else { return };
+                let done = worker.check(domain, attempt, generated).await;
+                drop(permit);
+                if let Some(t) = taken.filter(|_| done) {
+                    t.done();
                 }
-                Err(e) => {
-                    debug!("request error for {}: {}", url, e);
-                }
-            }
+            });
+            while running.try_join_next().is_some() {}
+        }
+        let dropped = retries.clear();
+        if dropped > 0 {
+            info!("retry: dropped {} pending re-checks", dropped);
         }
+        let dropped = posted.as_ref().map_or(0, |q| q.clear());
+        if dropped > 0 {
+            info!("candidates: dropped {} posted candidates", dropped);
+        }
+        // wait for the checks already started
+        while running.join_next().await.is_some() {}
+    })
+}
+
+// next posted candidate; never ready for scans without a queue
+async fn next_posted(posted: Option<&CandidateQueue>) -> Domain {
+    match posted {
+        Some(q) => q.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+// wait for in-flight checks of a stopped scan, up to the drain timeout
+async fn drain(dispatcher: &mut JoinHandle<()>, timeout: Duration) {
+    info!("draining in-flight checks (timeout {:?})", timeout);
+    if time::timeout(timeout, &mut *dispatcher).await.is_err() {
+        warn!("drain timeout reached, abandoning in-flight checks");
+        dispatcher.abort();
     }
-    Ok(false)
 }
 
-// generate labels and domains; resume_from is lexicographic full domain to start after
-async fn generate_candidates(
-    gen: GeneratorConfig,
-    resume_from: String,
-    tx: &mpsc::Sender<String>,
+/// One pass over a profile's candidates next to the main scan. `ctx` carries
+/// the profile's config snapshot, the scan's own progress and control, and
+/// the store of its namespace; dedup, notifications and the rate limit are
+/// shared. Records are stamped with `scan_id` and deduplicated under `found_kind`.
+pub async fn run_profile_scan(ctx: ServiceContext, scan_id: u64, found_kind: String, last_domain: Arc<RwLock<String>>) {
+    let cfg = ctx.config.current();
+    let probe = match build_probe(&cfg, &ctx.client, ctx.avail_stores.is_some(), ctx.negative_cache.clone(), ctx.positive_cache.clone()).await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("scan: invalid resolver config: {e}");
+            return;
+        }
+    };
+    let (tx, rx) = queue::memory(cfg.storage.queue.capacity);
+    let worker = Arc::new(Worker {
+        config: ctx.config.clone(),
+        store: ctx.store.clone(),
+        prog: ctx.prog.clone(),
+        probe,
+        limiter: ctx.limiter.clone(),
+        tld_limits: ctx.tld_limits.clone(),
+        control: ctx.control.clone(),
+        avail_stores: ctx.avail_stores.clone(),
+        dedup: ctx.dedup.clone(),
+        cluster: None,
+        retries: Arc::new(RetryQueue::new(ctx.prog.clone())),
+        notifier: ctx.notifier.clone(),
+        health: None,
+        last_domain,
+        journal: None,
+        scan_id,
+        found_kind,
+    });
+    let workers = Arc::new(WorkerLimit::new(cfg.limits.concurrency.max(1) as usize));
+    ctx.prog.set_workers(cfg.limits.concurrency.max(1) as usize);
+    let mut dispatcher = spawn_dispatcher(worker, workers, rx, None);
+
+    // the main scan's checked set does not apply: a profile checks all of its candidates
+    let mut feed = Feed {
+        tx: &tx,
+        prog: &ctx.prog,
+        control: &ctx.control,
+        dedup: None,
+        journal: None,
+        budget: None,
+        shard: None,
+        max_candidates: cfg.limits.max_candidates as i64,
+        sent: 0,
+    };
+    match generate_candidates(&ctx.config, ResumePoint::default(), &mut feed, None).await {
+        Ok(sent) => info!("scan: generator finished: enqueued_sent={}", sent),
+        Err(e) => {
+            error!("scan: generator error: {e}");
+            ctx.control.stop();
+        }
+    }
+    tx.close();
+    select! {
+        _ = &mut dispatcher => {}
+        _ = ctx.control.stopped() => drain(&mut dispatcher, cfg.run.drain_timeout).await,
+    }
+    ctx.store.flush().await;
+    if let Some(st) = &ctx.avail_stores {
+        st.available.flush().await;
+        st.registered.flush().await;
+    }
+}
+
+// Lease a shard, enqueue its candidates and report it done once each of them
+// is checked (re-checks included); Ok(None) when the coordinator has nothing
+// left to hand out.
+async fn run_shard(
+    cluster: &ClusterClient,
+    config: &LiveConfig,
+    tx: &QueueSender,
     prog: &Progress,
-    max_candidates: i64,
-) -> anyhow::Result<i64> {
-    let alpha = if gen.alphabet.is_empty() {
-        "abcdefghijklmnopqrstuvwxyz0123456789-".to_string()
-    } else {
-        gen.alphabet.clone()
+    control: &ScanControl,
+    dedup: Option<&Dedup>,
+) -> anyhow::Result<Option<i64>> {
+    let Some(lease) = cluster.lease().await? else {
+        return Ok(None);
     };
-    let alpha_chars: Vec<char> = alpha.chars().collect();
-    let allowed: std::collections::HashSet<char> = alpha_chars.iter().copied().collect();
-    let is_allowed = |c: char| allowed.contains(&c);
-    let resume = resume_from.to_lowercase();
-    let mut started = resume.is_empty();
-    let mut sent: i64 = 0;
-
-    for ln in gen.min_length..=gen.max_length {
-        let ln = ln as usize;
-        let mut idx = vec![0usize; ln];
-        loop {
-            // build label
-            let mut valid = true;
-            let mut prev_hyphen = false;
-            let mut label = String::with_capacity(ln);
-            for i in 0..ln {
-                let r = alpha_chars[idx[i]];
-                if !is_allowed(r) {
-                    valid = false;
-                    break;
-                }
-                if r == '-' {
-                    if !gen.allow_hyphen
-                        || (gen.forbid_leading_hyphen && i == 0)
-                        || (gen.forbid_trailing_hyphen && i == ln - 1)
-                        || (gen.forbid_double_hyphen && prev_hyphen)
-                    {
-                        valid = false;
-                        break;
-                    }
-                    prev_hyphen = true;
-                } else {
-                    prev_hyphen = false;
-                }
-                label.push(r);
-            }
-            if valid {
-                for tld in &gen.tlds {
-                    let t = tld.trim().to_lowercase();
-                    if t.is_empty() || !t.starts_with('.') {
-                        continue;
-                    }
-                    let domain = format!("{label}{t}");
-                    let dl = domain.to_lowercase();
-                    if !started {
-                        if dl <= resume {
-                            if dl == resume {
-                                started = true; 
-                            }
-                            continue;
-                        }
-                        started = true; 
-                    }
-                    
-                    if tx.send(domain.clone()).await.is_ok() {
-                        prog.inc_enqueued();
-                        sent += 1;
-                        if max_candidates > 0 && sent >= max_candidates {
-                            return Ok(sent);
-                        }
-                    } else {
-                        return Ok(sent);
-                    }
-                }
-            }
-            // increment odometer
-            let mut carry = 1usize;
-            let mut i = ln as isize - 1;
-            while i >= 0 && carry > 0 {
-                let ii = i as usize;
-                idx[ii] += carry;
-                if idx[ii] >= alpha_chars.len() {
-                    idx[ii] = 0;
-                    carry = 1;
-                } else {
-                    carry = 0;
-                }
-                i -= 1;
-            }
-            if carry > 0 {
-                break;
-            }
-            // cooperative yield
-            tokio::task::yield_now().await;
+    info!("cluster: scanning shard {}/{}", lease.shard, lease.shards);
+    let mut feed = Feed {
+        tx,
+        prog,
+        control,
+        dedup,
+        journal: None,
+        budget: None,
+        shard: Some(cluster),
+        max_candidates: config.current().limits.max_candidates as i64,
+        sent: 0,
+    };
+    let sent = generate_candidates(config, ResumePoint::default(), &mut feed, Some((lease.shard, lease.shards))).await?;
+    loop {
+        if control.state() == ScanState::Stopped {
+            // leave the shard to expire and be re-leased
+            return Ok(Some(sent));
+        }
+        if cluster.shard_pending() == 0 {
+            break;
+        }
+        time::sleep(Duration::from_millis(500)).await;
+    }
+    cluster.complete(lease.shard).await?;
+    Ok(Some(sent))
+}
+
+/// What a scan would queue first, for GET /preview/candidates and `preview`.
+pub struct Preview {
+    /// Where the pass starts: after this domain, empty from the beginning
+    pub from: String,
+    /// ACE form, in queue order
+    pub candidates: Vec<String>,
+    /// The source ran out before `count`
+    pub exhausted: bool,
+}
+
+/// Run the candidate source of `config` into a throwaway queue and return
+/// the first `count` names, starting after `from` or else at the saved resume
+/// point. Nothing is checked and no state is touched; names already checked
+/// are listed too, as the preview does not consult dedup.
+pub async fn preview_candidates(config: &LiveConfig, count: usize, from: Option<Domain>) -> anyhow::Result<Preview> {
+    let cur = config.current();
+    let (from, checkpoint, cursors) = match from {
+        Some(f) => (f.into(), None, Cursors::default()),
+        None if cur.storage.resume => match std::fs::read_to_string(&cur.storage.state_file) {
+            Ok(s) => serde_json::from_str::<ResumeState>(&s)
+                .map(|st| (st.last_domain.trim().to_string(), st.checkpoint.clone(), st.cursors(&cur.generator)))
+                .unwrap_or_default(),
+            Err(_) => Default::default(),
+        },
+        None => Default::default(),
+    };
+    if count == 0 {
+        return Ok(Preview { from, candidates: Vec::new(), exhausted: false });
+    }
+    // room for every candidate, so the source never waits
+    let (tx, mut rx) = queue::memory(count);
+    let (prog, control) = (Progress::new(0), ScanControl::new());
+    let mut feed = Feed {
+        tx: &tx,
+        prog: &prog,
+        control: &control,
+        dedup: None,
+        journal: None,
+        budget: None,
+        shard: None,
+        max_candidates: count as i64,
+        sent: 0,
+    };
+    let sent = match cur.source.kind {
+        SourceKind::Generator => {
+            let resume = ResumePoint { from: from.clone(), checkpoint, cursors, positions: None };
+            generate_candidates(config, resume, &mut feed, None).await?
         }
+        SourceKind::Zone => read_zones(&cur.source.zone.files, &cur.generator.tlds, &from, &mut feed).await?,
+        SourceKind::Ct => anyhow::bail!("source.kind ct follows live logs and has nothing to preview"),
+    };
+    drop(tx);
+    let mut candidates = Vec::with_capacity(sent.max(0) as usize);
+    while let Some((domain, _)) = rx.recv().await {
+        candidates.push(domain.into());
     }
-    Ok(sent)
+    Ok(Preview { from, exhausted: candidates.len() < count, candidates })
 }
 
+// v1 files hold one position (last_domain, checkpoint); v2 adds cursors per
+// label length and TLD
+const RESUME_FORMAT: u32 = 2;
+
 #[derive(serde::Serialize, serde::Deserialize, Default)]
 struct ResumeState {
+    /// Absent in v1
+    #[serde(default)]
+    version: u32,
     last_domain: String,
     updated_at_unix: u64,
     #[serde(default)]
@@ -350,9 +1196,61 @@ struct ResumeState {
     found: i64,
     #[serde(default)]
     total_planned: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checkpoint: Option<Checkpoint>,
+    /// `label` order only
+    #[serde(default, skip_serializing_if = "Cursors::is_empty")]
+    cursors: Cursors,
+    /// Candidates queued toward limits.max_candidates and limits.budget
+    #[serde(default)]
+    budget: BudgetState,
 }
 
-fn save_resume(path: &Path, last: &str, prog: &Progress) -> anyhow::Result<()> {
+impl ResumeState {
+    // a v1 position becomes the cursor of every length and TLD of the current settings
+    fn cursors(&self, gen: &GeneratorConfig) -> Cursors {
+        let last = self.last_domain.trim();
+        if self.version >= 2 || last.is_empty() || gen.order != GenerationOrder::Label {
+            return self.cursors.clone();
+        }
+        info!("resume: migrating state file from format v{} to v{}", self.version.max(1), RESUME_FORMAT);
+        Cursors::at(last, &candidate_suffixes(gen), label_lengths(gen))
+    }
+}
+
+// with a journal the position only moves past fully checked candidates; with
+// a disk queue it is the last queued one, as the queue keeps the rest
+fn resume_position(last: &RwLock<String>, journal: Option<&Journal>, disk: Option<&DiskQueue>) -> String {
+    match (journal, disk) {
+        (_, Some(q)) => q.last(),
+        (Some(j), None) => j.watermark(),
+        (None, None) => last.read().clone(),
+    }
+}
+
+// generator position of the resume domain, when its label is still tracked,
+// and the cursors moved to it in `label` order
+fn position_of(domain: &str, positions: Option<&CheckpointLog>, config: &LiveConfig) -> (Option<Checkpoint>, Cursors) {
+    let gen = &config.current().generator;
+    let tlds = candidate_suffixes(gen);
+    let Some(p) = positions else {
+        return (None, Cursors::default());
+    };
+    let cursors = if gen.order == GenerationOrder::Label {
+        p.cursors(domain, &tlds, label_lengths(gen))
+    } else {
+        Cursors::default()
+    };
+    (p.lookup(domain, &tlds), cursors)
+}
+
+fn save_resume(
+    path: &Path,
+    last: &str,
+    (checkpoint, cursors): (Option<Checkpoint>, Cursors),
+    prog: &Progress,
+    budget: BudgetState,
+) -> anyhow::Result<()> {
     if last.trim().is_empty() {
         return Ok(());
     }
@@ -362,12 +1260,16 @@ fn save_resume(path: &Path, last: &str, prog: &Progress) -> anyhow::Result<()> {
     let tmp = path.with_extension("json.tmp");
     let (enq, chk, fnd, _elapsed) = prog.snapshot();
     let st = ResumeState {
+        version: RESUME_FORMAT,
         last_domain: last.trim().to_string(),
         updated_at_unix: now_unix(),
         enqueued: enq,
         checked: chk,
         found: fnd,
         total_planned: prog.total_planned(),
+        checkpoint,
+        cursors,
+        budget,
     };
     let data = serde_json::to_vec(&st)?;
     std::fs::write(&tmp, data)?;
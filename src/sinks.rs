@@ -0,0 +1,329 @@
+mod kafka;
+mod nats;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::config::SinksConfig;
+use crate::store::FoundDomain;
+
+// how long a sink that gave up waits before it tries the spool again
+const REPLAY_BACKOFF: Duration = Duration::from_secs(60);
+// records per batch when the spool is replayed
+const REPLAY_BATCH: usize = 5000;
+
+/// Message brokers the store writer publishes each written batch of found
+/// domains to (`sinks`), besides or instead of the storage backend. Every
+/// sink publishes from its own task, so a broker that is down never holds
+/// up the writer: batches it cannot take wait in a spool file under
+/// storage.dir and go out once it is back.
+#[derive(Clone, Default)]
+pub struct Sinks {
+    queues: Arc<Vec<Queue>>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    stop: Option<Arc<watch::Sender<bool>>>,
+    store: bool,
+}
+
+// the writer's end of one sink
+struct Queue {
+    name: &'static str,
+    tx: mpsc::Sender<Vec<FoundDomain>>,
+    spool: Arc<Spool>,
+}
+
+enum Sink {
+    Kafka(kafka::KafkaSink),
+    Nats(nats::NatsSink),
+}
+
+impl Sink {
+    fn name(&self) -> &'static str {
+        match self {
+            Sink::Kafka(_) => "kafka",
+            Sink::Nats(_) => "nats",
+        }
+    }
+
+    async fn publish(&self, batch: &[FoundDomain]) -> anyhow::Result<()> {
+        match self {
+            Sink::Kafka(s) => s.publish(batch).await,
+            Sink::Nats(s) => s.publish(batch).await,
+        }
+    }
+
+    // one attempt, in a span
+    async fn attempt(&self, batch: &[FoundDomain], attempt: u32) -> bool {
+        let span = info_span!("sink.publish", sink = self.name(), records = batch.len(), attempt);
+        match self.publish(batch).instrument(span).await {
+            Ok(()) => {
+                debug!("{}: published {} domains", self.name(), batch.len());
+                true
+            }
+            Err(e) => {
+                warn!("{}: publishing {} domains failed: {e:#}", self.name(), batch.len());
+                false
+            }
+        }
+    }
+
+    // retrying with backoff; false when it gave up or shutdown cut it short
+    async fn publish_retrying(&self, batch: &[FoundDomain], retry: u32, stop: &mut watch::Receiver<bool>) -> bool {
+        let mut attempt = 0;
+        loop {
+            if self.attempt(batch, attempt).await {
+                return true;
+            }
+            if attempt >= retry {
+                return false;
+            }
+            tokio::select! {
+                _ = time::sleep(Duration::from_secs(1 << attempt.min(6))) => {}
+                _ = stop.wait_for(|s| *s) => return false,
+            }
+            attempt += 1;
+        }
+    }
+}
+
+impl Sinks {
+    /// Connections are made on the first publish, so a broker that is down
+    /// at startup does not keep the service from starting. Spool files of
+    /// the sinks live in `dir`.
+    pub fn new(cfg: &SinksConfig, dir: &Path) -> anyhow::Result<Self> {
+        let mut sinks = Vec::new();
+        if cfg.kafka.enabled() {
+            sinks.push(Sink::Kafka(kafka::KafkaSink::new(&cfg.kafka, cfg.timeout)?));
+        }
+        if cfg.nats.enabled() {
+            sinks.push(Sink::Nats(nats::NatsSink::new(&cfg.nats, cfg.timeout)?));
+        }
+        if !cfg.store && sinks.is_empty() {
+            anyhow::bail!("sinks.store is off but no sink is configured; found domains would be lost");
+        }
+        let (stop, _) = watch::channel(false);
+        let mut queues = Vec::new();
+        let mut tasks = Vec::new();
+        for sink in sinks {
+            let sink_name = sink.name();
+            let spool = Arc::new(Spool::open(dir.join(format!("sink-{}.spool", sink.name()))));
+            let (tx, rx) = mpsc::channel(cfg.queue.max(1));
+            tasks.push(tokio::spawn(run(sink, rx, spool.clone(), cfg.retry, stop.subscribe())));
+            queues.push(Queue { name: sink_name, tx, spool });
+        }
+        Ok(Self {
+            queues: Arc::new(queues),
+            tasks: Arc::new(Mutex::new(tasks)),
+            stop: Some(Arc::new(stop)),
+            store: cfg.store,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+
+    /// Whether the storage backend keeps the domains too; no sinks means it does.
+    pub fn keep_stored(&self) -> bool {
+        self.store || self.queues.is_empty()
+    }
+
+    /// Hand the batch to every sink's task, or to its spool file when the
+    /// task is behind; false when a spool file cannot be written.
+    pub fn publish(&self, batch: &[FoundDomain]) -> bool {
+        let mut ok = true;
+        for q in self.queues.iter() {
+            if let Err(e) = q.tx.try_send(batch.to_vec()) {
+                let batch = match e {
+                    mpsc::error::TrySendError::Full(b) | mpsc::error::TrySendError::Closed(b) => b,
+                };
+                debug!("{}: behind, spooling {} domains", q.name, batch.len());
+                ok &= q.spool.append(&batch);
+            }
+        }
+        ok
+    }
+
+    /// Stop the sink tasks: batches still queued get one attempt each, and
+    /// once a broker fails the rest are spooled for the next start.
+    pub async fn close(&self) {
+        if let Some(stop) = &self.stop {
+            let _ = stop.send(true);
+        }
+        let tasks = std::mem::take(&mut *self.tasks.lock());
+        for t in tasks {
+            let _ = t.await;
+        }
+    }
+}
+
+async fn run(sink: Sink, mut rx: mpsc::Receiver<Vec<FoundDomain>>, spool: Arc<Spool>, retry: u32, mut stop: watch::Receiver<bool>) {
+    let name = sink.name();
+    let mut replay_at = Instant::now();
+    loop {
+        // older records first, once the broker takes batches again
+        if spool.has_records() && Instant::now() >= replay_at && !*stop.borrow() {
+            if !replay(&sink, &spool, retry, &mut stop).await {
+                replay_at = Instant::now() + REPLAY_BACKOFF;
+            }
+            continue;
+        }
+        let batch = tokio::select! {
+            b = rx.recv() => b,
+            _ = stop.wait_for(|s| *s) => None,
+            _ = time::sleep_until(replay_at), if spool.has_records() && !*stop.borrow() => continue,
+        };
+        let Some(batch) = batch else { break };
+        // the broker failed lately: behind the spool, which tries it again
+        if spool.has_records() && Instant::now() < replay_at {
+            spool.append(&batch);
+            continue;
+        }
+        if sink.publish_retrying(&batch, retry, &mut stop).await {
+            replay_at = Instant::now();
+        } else {
+            warn!("{name}: keeping {} domains in {} until it is back", batch.len(), spool.path.display());
+            spool.append(&batch);
+            replay_at = Instant::now() + REPLAY_BACKOFF;
+        }
+    }
+    rx.close();
+    let mut up = true;
+    while let Ok(batch) = rx.try_recv() {
+        up = up && sink.attempt(&batch, 0).await;
+        if !up {
+            spool.append(&batch);
+        }
+    }
+}
+
+// publish what the spool kept; false when the sink gave up on part of it,
+// which stays in the spool
+async fn replay(sink: &Sink, spool: &Arc<Spool>, retry: u32, stop: &mut watch::Receiver<bool>) -> bool {
+    let s = spool.clone();
+    let records = match tokio::task::spawn_blocking(move || s.take()).await {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => {
+            warn!("{}: cannot read {}: {e}", sink.name(), spool.path.display());
+            return false;
+        }
+        Err(_) => return false,
+    };
+    let total = records.len();
+    for (i, batch) in records.chunks(REPLAY_BATCH).enumerate() {
+        if !sink.publish_retrying(batch, retry, stop).await {
+            let rest = &records[i * REPLAY_BATCH..];
+            // failing that, the whole file is replayed again
+            if let Err(e) = spool.keep(rest) {
+                warn!("{}: cannot rewrite {}: {e}", sink.name(), spool.replaying.display());
+            }
+            return false;
+        }
+    }
+    spool.replayed();
+    info!("{}: published {total} spooled domains", sink.name());
+    true
+}
+
+// Batches a sink could not take, as JSON lines. Replaying moves the file
+// aside first, so appends go on meanwhile and a crash replays it again
+// (a record can then be published twice, never lost).
+struct Spool {
+    path: PathBuf,
+    replaying: PathBuf,
+    // appends and the move aside
+    lock: Mutex<()>,
+    records: AtomicBool,
+}
+
+impl Spool {
+    fn open(path: PathBuf) -> Self {
+        let replaying = path.with_extension("spool.replay");
+        let records = path.exists() || replaying.exists();
+        Self { path, replaying, lock: Mutex::new(()), records: AtomicBool::new(records) }
+    }
+
+    fn has_records(&self) -> bool {
+        self.records.load(Ordering::Relaxed)
+    }
+
+    fn append(&self, batch: &[FoundDomain]) -> bool {
+        let _g = self.lock.lock();
+        let file = OpenOptions::new().create(true).append(true).open(&self.path);
+        match file.and_then(|f| write_records(f, batch)) {
+            Ok(_) => {
+                self.records.store(true, Ordering::Relaxed);
+                true
+            }
+            Err(e) => {
+                warn!("sinks: cannot spool {} domains to {}: {e}", batch.len(), self.path.display());
+                false
+            }
+        }
+    }
+
+    // the records to replay: a replay cut short before, else the spool
+    fn take(&self) -> std::io::Result<Vec<FoundDomain>> {
+        {
+            let _g = self.lock.lock();
+            if !self.replaying.exists() && self.path.exists() {
+                fs::rename(&self.path, &self.replaying)?;
+            }
+        }
+        if !self.replaying.exists() {
+            return Ok(Vec::new());
+        }
+        let mut out = Vec::new();
+        for line in BufReader::new(File::open(&self.replaying)?).lines() {
+            let line = line?;
+            // a line cut off by a crash
+            match serde_json::from_str(&line) {
+                Ok(rec) => out.push(rec),
+                Err(_) if line.trim().is_empty() => {}
+                Err(e) => warn!("sinks: skipping a bad line of {}: {e}", self.replaying.display()),
+            }
+        }
+        Ok(out)
+    }
+
+    // what is left of a replay, for the next one
+    fn keep(&self, rest: &[FoundDomain]) -> std::io::Result<()> {
+        let tmp = self.replaying.with_extension("tmp");
+        write_records(File::create(&tmp)?, rest)?.sync_data()?;
+        fs::rename(&tmp, &self.replaying)
+    }
+
+    fn replayed(&self) {
+        let _g = self.lock.lock();
+        let _ = fs::remove_file(&self.replaying);
+        self.records.store(self.path.exists(), Ordering::Relaxed);
+    }
+}
+
+fn write_records(file: File, recs: &[FoundDomain]) -> std::io::Result<File> {
+    let mut w = BufWriter::new(file);
+    for rec in recs {
+        serde_json::to_writer(&mut w, rec)?;
+        w.write_all(b"\n")?;
+    }
+    w.into_inner().map_err(|e| e.into_error())
+}
+
+/// Per-message metadata, as Kafka record headers and NATS message headers.
+fn headers(rec: &FoundDomain) -> [(&'static str, String); 3] {
+    [
+        ("tld", rec.tld.clone()),
+        ("scan_id", rec.scan_id.to_string()),
+        ("found_at", rec.found_at_unix.to_string()),
+    ]
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::{ClientBuilder, Credentials, SaslConfig};
+use rskafka::record::Record;
+use rskafka::BackoffConfig;
+use tokio::sync::Mutex;
+use tokio::time;
+use tracing::info;
+
+use crate::certs;
+use crate::config::{KafkaSaslMechanism, KafkaSinkConfig};
+use crate::dedup::fnv1a;
+use crate::store::FoundDomain;
+
+/// Producer for `sinks.kafka`: one record per domain, keyed by the name so
+/// re-finds of a domain land on the same partition.
+pub struct KafkaSink {
+    cfg: KafkaSinkConfig,
+    timeout: Duration,
+    tls: Option<Arc<rustls::ClientConfig>>,
+    // one client per partition of the topic; dropped after an error
+    partitions: Mutex<Vec<PartitionClient>>,
+}
+
+impl KafkaSink {
+    pub fn new(cfg: &KafkaSinkConfig, timeout: Duration) -> anyhow::Result<Self> {
+        if cfg.topic.trim().is_empty() {
+            anyhow::bail!("sinks.kafka.topic is required");
+        }
+        let tls = match cfg.tls {
+            true => Some(Arc::new(certs::plain_client_tls()?)),
+            false => None,
+        };
+        Ok(Self {
+            cfg: cfg.clone(),
+            timeout,
+            tls,
+            partitions: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub async fn publish(&self, batch: &[FoundDomain]) -> anyhow::Result<()> {
+        let mut partitions = self.partitions.lock().await;
+        if partitions.is_empty() {
+            *partitions = time::timeout(self.timeout, self.connect())
+                .await
+                .map_err(|_| anyhow::anyhow!("connect timed out"))??;
+        }
+        let mut per_partition: BTreeMap<usize, Vec<Record>> = BTreeMap::new();
+        for rec in batch {
+            let at = (fnv1a(rec.domain.as_bytes()) % partitions.len() as u64) as usize;
+            per_partition.entry(at).or_default().push(record(rec)?);
+        }
+        for (at, records) in per_partition {
+            let res = time::timeout(self.timeout, partitions[at].produce(records, Compression::NoCompression)).await;
+            let err = match res {
+                Ok(Ok(_)) => continue,
+                Ok(Err(e)) => anyhow::Error::new(e),
+                Err(_) => anyhow::anyhow!("produce timed out"),
+            };
+            // leader changes and dead brokers: look the partitions up again next time
+            partitions.clear();
+            return Err(err).with_context(|| format!("topic {} partition {at}", self.cfg.topic));
+        }
+        Ok(())
+    }
+
+    async fn connect(&self) -> anyhow::Result<Vec<PartitionClient>> {
+        let mut builder = ClientBuilder::new(self.cfg.brokers.clone())
+            .client_id(self.cfg.client_id.as_str())
+            // give up inside the publish timeout instead of retrying for minutes
+            .backoff_config(BackoffConfig {
+                deadline: Some(self.timeout),
+                ..Default::default()
+            });
+        if let Some(tls) = &self.tls {
+            builder = builder.tls_config(tls.clone());
+        }
+        if !self.cfg.username.is_empty() {
+            let creds = Credentials::new(self.cfg.username.clone(), self.cfg.password.clone());
+            builder = builder.sasl_config(match self.cfg.sasl_mechanism {
+                KafkaSaslMechanism::Plain => SaslConfig::Plain(creds),
+                KafkaSaslMechanism::ScramSha256 => SaslConfig::ScramSha256(creds),
+                KafkaSaslMechanism::ScramSha512 => SaslConfig::ScramSha512(creds),
+            });
+        }
+        let client = builder.build().await.context("connect to brokers")?;
+        let topics = client.list_topics().await.context("list topics")?;
+        let partitions = topics
+            .into_iter()
+            .find(|t| t.name == self.cfg.topic)
+            .map(|t| t.partitions)
+            .filter(|p| !p.is_empty())
+            .with_context(|| format!("topic {} does not exist", self.cfg.topic))?;
+        let mut clients = Vec::with_capacity(partitions.len());
+        for p in partitions {
+            clients.push(client.partition_client(self.cfg.topic.clone(), p, UnknownTopicHandling::Error).await?);
+        }
+        info!("kafka: publishing to {} ({} partitions)", self.cfg.topic, clients.len());
+        Ok(clients)
+    }
+}
+
+fn record(rec: &FoundDomain) -> anyhow::Result<Record> {
+    Ok(Record {
+        key: Some(rec.domain.as_bytes().to_vec()),
+        value: Some(serde_json::to_vec(rec)?),
+        headers: super::headers(rec).into_iter().map(|(k, v)| (k.to_string(), v.into_bytes())).collect(),
+        timestamp: chrono::DateTime::from_timestamp(rec.found_at_unix as i64, 0).unwrap_or_else(chrono::Utc::now),
+    })
+}
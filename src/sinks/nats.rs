@@ -0,0 +1,201 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time;
+use tracing::info;
+
+use crate::certs;
+use crate::config::NatsSinkConfig;
+use crate::store::FoundDomain;
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+// what we use of the server's INFO
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ServerInfo {
+    headers: bool,
+    tls_required: bool,
+    max_payload: usize,
+}
+
+struct Conn {
+    io: BufReader<Box<dyn Stream>>,
+    info: ServerInfo,
+}
+
+/// Publisher for `sinks.nats` over the plain NATS client protocol: one
+/// message per domain, then a PING whose PONG confirms the server has them.
+pub struct NatsSink {
+    cfg: NatsSinkConfig,
+    timeout: Duration,
+    host: String,
+    port: u16,
+    tls: bool,
+    // user:pass or token from the URL (as written, not percent-decoded), when the config does not set them
+    url_user: String,
+    url_pass: String,
+    conn: Mutex<Option<Conn>>,
+}
+
+impl NatsSink {
+    pub fn new(cfg: &NatsSinkConfig, timeout: Duration) -> anyhow::Result<Self> {
+        let url = reqwest::Url::parse(&cfg.url).context("sinks.nats.url")?;
+        let tls = match url.scheme() {
+            "nats" => false,
+            "tls" => true,
+            other => anyhow::bail!("sinks.nats.url: unsupported scheme {other:?}, use nats:// or tls://"),
+        };
+        if cfg.subject.trim().is_empty() {
+            anyhow::bail!("sinks.nats.subject is required");
+        }
+        Ok(Self {
+            cfg: cfg.clone(),
+            timeout,
+            host: url.host_str().context("sinks.nats.url: no host")?.to_string(),
+            port: url.port().unwrap_or(4222),
+            tls,
+            url_user: url.username().to_string(),
+            url_pass: url.password().unwrap_or_default().to_string(),
+            conn: Mutex::new(None),
+        })
+    }
+
+    pub async fn publish(&self, batch: &[FoundDomain]) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().await;
+        let res = time::timeout(self.timeout, async {
+            if conn.is_none() {
+                *conn = Some(self.connect().await?);
+            }
+            let c = conn.as_mut().expect("connected above");
+            for rec in batch {
+                c.publish(&subject(&self.cfg.subject, rec), rec).await?;
+            }
+            c.io.write_all(b"PING\r\n").await?;
+            c.io.flush().await?;
+            c.pong().await
+        })
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out")));
+        if res.is_err() {
+            // start over on a fresh connection; the server may have dropped this one
+            *conn = None;
+        }
+        res.with_context(|| format!("{}:{}", self.host, self.port))
+    }
+
+    async fn connect(&self) -> anyhow::Result<Conn> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port)).await.context("connect")?;
+        let mut io = BufReader::new(Box::new(tcp) as Box<dyn Stream>);
+        let line = read_line(&mut io).await?;
+        let info: ServerInfo = match line.strip_prefix("INFO ") {
+            Some(json) => serde_json::from_str(json).context("server INFO")?,
+            None => anyhow::bail!("expected INFO, server sent {line:?}"),
+        };
+        // TLS starts right after INFO, before CONNECT
+        if self.tls || info.tls_required {
+            let tcp = io.into_inner();
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(certs::plain_client_tls()?));
+            let name = rustls::pki_types::ServerName::try_from(self.host.clone()).context("sinks.nats.url host")?;
+            io = BufReader::new(Box::new(connector.connect(name, tcp).await.context("TLS handshake")?));
+        }
+        let user = if self.cfg.user.is_empty() { &self.url_user } else { &self.cfg.user };
+        let pass = if self.cfg.password.is_empty() { &self.url_pass } else { &self.cfg.password };
+        let mut connect = json!({
+            "verbose": false,
+            "pedantic": false,
+            "lang": "rust",
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "headers": info.headers,
+            "no_responders": false,
+        });
+        if !self.cfg.token.is_empty() {
+            connect["auth_token"] = json!(self.cfg.token);
+        } else if !user.is_empty() && pass.is_empty() {
+            // nats://token@host
+            connect["auth_token"] = json!(user);
+        } else if !user.is_empty() {
+            connect["user"] = json!(user);
+            connect["pass"] = json!(pass);
+        }
+        io.write_all(format!("CONNECT {connect}\r\nPING\r\n").as_bytes()).await?;
+        io.flush().await?;
+        let mut c = Conn { io, info };
+        // a refused login answers the PING with -ERR
+        c.pong().await.context("CONNECT")?;
+        info!("nats: publishing to {} on {}:{}", self.cfg.subject, self.host, self.port);
+        Ok(c)
+    }
+}
+
+impl Conn {
+    async fn publish(&mut self, subject: &str, rec: &FoundDomain) -> anyhow::Result<()> {
+        let payload = serde_json::to_vec(rec)?;
+        let mut msg = Vec::with_capacity(payload.len() + 128);
+        if self.info.headers {
+            let mut hdr = String::from("NATS/1.0\r\n");
+            for (k, v) in super::headers(rec) {
+                hdr.push_str(&format!("{k}: {v}\r\n"));
+            }
+            hdr.push_str("\r\n");
+            let total = hdr.len() + payload.len();
+            self.check_size(total, rec)?;
+            msg.extend_from_slice(format!("HPUB {subject} {} {total}\r\n{hdr}", hdr.len()).as_bytes());
+        } else {
+            self.check_size(payload.len(), rec)?;
+            msg.extend_from_slice(format!("PUB {subject} {}\r\n", payload.len()).as_bytes());
+        }
+        msg.extend_from_slice(&payload);
+        msg.extend_from_slice(b"\r\n");
+        self.io.write_all(&msg).await?;
+        Ok(())
+    }
+
+    // the server closes the connection on an oversized message
+    fn check_size(&self, len: usize, rec: &FoundDomain) -> anyhow::Result<()> {
+        if self.info.max_payload > 0 && len > self.info.max_payload {
+            anyhow::bail!("message for {} is {len} bytes, the server takes {}", rec.domain, self.info.max_payload);
+        }
+        Ok(())
+    }
+
+    // wait for PONG, answering the server's own PINGs
+    async fn pong(&mut self) -> anyhow::Result<()> {
+        loop {
+            let line = read_line(&mut self.io).await?;
+            match line.as_str() {
+                "PONG" => return Ok(()),
+                "PING" => {
+                    self.io.write_all(b"PONG\r\n").await?;
+                    self.io.flush().await?;
+                }
+                "+OK" => {}
+                l if l.starts_with("INFO ") => {}
+                l if l.starts_with("-ERR") => anyhow::bail!("server answered {}", l.trim_start_matches("-ERR").trim()),
+                l => anyhow::bail!("unexpected {l:?} from server"),
+            }
+        }
+    }
+}
+
+async fn read_line(io: &mut BufReader<Box<dyn Stream>>) -> anyhow::Result<String> {
+    let mut line = String::new();
+    if io.read_line(&mut line).await? == 0 {
+        anyhow::bail!("server closed the connection");
+    }
+    Ok(line.trim_end().to_string())
+}
+
+// "{tld}" in the configured subject becomes the domain's TLD
+fn subject(template: &str, rec: &FoundDomain) -> String {
+    let tld = if rec.tld.is_empty() { "unknown" } else { rec.tld.as_str() };
+    template.replace("{tld}", tld)
+}
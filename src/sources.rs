@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::info;
+
+use crate::budget::{Allowance, Budget};
+use crate::cluster::ClusterClient;
+use crate::config::GeneratorConfig;
+use crate::dedup::Dedup;
+use crate::domain::Domain;
+use crate::generator::to_ace;
+use crate::journal::Journal;
+use crate::progress::Progress;
+use crate::queue::QueueSender;
+use crate::service::ScanControl;
+
+mod ct;
+mod generated;
+mod zone;
+
+pub use ct::tail_logs;
+pub use generated::{generate_candidates, ResumePoint};
+pub use zone::read_zones;
+
+/// Where the main scan's candidates come from (`source.kind`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// Labels from the generator combined with every TLD
+    #[default]
+    Generator,
+    /// Names newly logged to Certificate Transparency logs
+    Ct,
+    /// Names delegated in zone files (e.g. from ICANN CZDS)
+    Zone,
+}
+
+// how often a scan waiting for tomorrow's budget looks at the config again
+const BUDGET_RECHECK: Duration = Duration::from_secs(60);
+
+// Hands candidates from any source to the check queue, applying dedup, the
+// journal, pause/stop, limits.max_candidates and limits.budget
+pub struct Feed<'a> {
+    pub tx: &'a QueueSender,
+    pub prog: &'a Progress,
+    pub control: &'a ScanControl,
+    pub dedup: Option<&'a Dedup>,
+    pub journal: Option<&'a Journal>,
+    /// Main scan only: counts what is queued across restarts
+    pub budget: Option<&'a Budget>,
+    /// Cluster worker: the leased shard's candidates, tracked until checked
+    pub shard: Option<&'a ClusterClient>,
+    /// 0 for no limit
+    pub max_candidates: i64,
+    /// Already queued in this pass, e.g. before a restart
+    pub sent: i64,
+}
+
+impl Feed<'_> {
+    /// Queue one candidate. Returns false once the source should
+    /// stop: the scan was stopped, the queue closed or max_candidates reached.
+    pub async fn offer(&mut self, domain: Domain) -> bool {
+        // a pass resumed at its limit
+        if self.max_candidates > 0 && self.sent >= self.max_candidates {
+            return false;
+        }
+        if self.dedup.is_some_and(|d| !d.first_check(&domain)) {
+            self.prog.inc_duplicate();
+            return true;
+        }
+        // checked before the restart, out of order
+        if self.journal.is_some_and(|j| !j.sent(&domain)) {
+            return true;
+        }
+        if !self.control.wait_running().await {
+            return false;
+        }
+        if let Some(b) = self.budget {
+            if !self.within_budget(b).await {
+                return false;
+            }
+        }
+        // counted before the send so the worker can never take it off the gauge first
+        self.prog.inc_queued();
+        if let Some(c) = self.shard {
+            c.queued(&domain);
+        }
+        let sent = match self.tx.try_send(domain.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(d)) => {
+                // the workers are behind: wait for room instead of buffering more
+                self.prog.inc_queue_full();
+                self.tx.send(d).await.is_ok()
+            }
+            Err(TrySendError::Closed(_)) => false,
+        };
+        if !sent {
+            self.prog.dec_queued();
+            if let Some(c) = self.shard {
+                c.checked(&domain);
+            }
+            return false;
+        }
+        self.prog.inc_enqueued(&domain);
+        self.sent += 1;
+        if let Some(b) = self.budget {
+            b.record();
+        }
+        !(self.max_candidates > 0 && self.sent >= self.max_candidates)
+    }
+
+    // false once limits.budget.total is used up or the scan stopped while
+    // waiting for the next day's budget
+    async fn within_budget(&self, budget: &Budget) -> bool {
+        let mut waiting = false;
+        loop {
+            match budget.check() {
+                Allowance::Go => return true,
+                Allowance::Exhausted => return false,
+                Allowance::Wait(left) => {
+                    if !waiting {
+                        info!("budget: limits.budget.per_day used up, waiting {}s for the next UTC day", left.as_secs());
+                        waiting = true;
+                    }
+                    tokio::time::sleep(left.min(BUDGET_RECHECK)).await;
+                    if !self.control.wait_running().await {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lowercase, in Unicode form so that `.xn--p1ai` and `.рф` generate the same names.
+pub fn normalize_tlds(tlds: &[String]) -> Vec<String> {
+    tlds.iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty() && t.starts_with('.'))
+        .map(|t| format!(".{}", idna::domain_to_unicode(&t[1..]).0))
+        .collect()
+}
+
+/// What generated labels are combined with: the TLDs, then the parent zones
+/// as ".example.com", in the same Unicode form.
+pub fn candidate_suffixes(gen: &GeneratorConfig) -> Vec<String> {
+    let zones: Vec<String> = gen
+        .parent_zones
+        .iter()
+        .map(|z| format!(".{}", z.trim().trim_matches('.')))
+        .collect();
+    let mut out = normalize_tlds(&gen.tlds);
+    out.extend(normalize_tlds(&zones));
+    out
+}
+
+// configured TLDs in ACE form, longest first so ".co.uk" wins over ".uk"
+fn ace_tlds(tlds: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = normalize_tlds(tlds).iter().filter_map(|t| to_ace(t)).collect();
+    out.sort_by_key(|t| std::cmp::Reverse(t.len()));
+    out
+}
+
+// The registered name under one of `tlds` (ACE, longest first) that `name`
+// belongs to: "www.shop.example.co.uk" -> "example.co.uk"; None outside them
+fn registered_name(name: &str, tlds: &[String]) -> Option<Domain> {
+    let name = Domain::parse(name.trim_start_matches("*."))?;
+    let tld = tlds.iter().find(|t| name.len() > t.len() && name.ends_with(t.as_str()))?;
+    let label = name[..name.len() - tld.len()].rsplit('.').next()?;
+    (!label.is_empty()).then(|| Domain::from_normalized(format!("{label}{tld}")))
+}
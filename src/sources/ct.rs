@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tokio::time;
+use tracing::{debug, info, warn};
+use x509_parser::prelude::*;
+
+use super::{ace_tlds, registered_name, Feed};
+use crate::config::{Config, CtConfig, LiveConfig};
+
+// get-entries can be slow on large logs; the HTTP check timeout is too short for it
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct SignedTreeHead {
+    tree_size: u64,
+}
+
+#[derive(Deserialize)]
+struct Entries {
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    leaf_input: String,
+}
+
+// Next entry to read per log URL, persisted so a restart continues where it stopped
+struct Positions {
+    path: PathBuf,
+    next: BTreeMap<String, u64>,
+}
+
+impl Positions {
+    fn load(path: PathBuf) -> Self {
+        let next = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        Self { path, next }
+    }
+
+    fn set(&mut self, log: &str, next: u64) -> anyhow::Result<()> {
+        self.next.insert(log.to_string(), next);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&self.next)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Poll the configured RFC 6962 logs and queue the names of newly logged
+/// certificates, reduced to the registered name under a configured TLD.
+/// Runs until the scan is stopped or limits.max_candidates is reached.
+pub async fn tail_logs(config: &LiveConfig, client: &Client, feed: &mut Feed<'_>) -> anyhow::Result<i64> {
+    let mut positions = Positions::load(positions_path(&config.current()));
+    loop {
+        // logs, TLDs and the interval follow config reloads
+        let cfg = config.current();
+        let tlds = ace_tlds(&cfg.generator.tlds);
+        for log in &cfg.source.ct.logs {
+            let log = log.trim_end_matches('/');
+            match poll_log(client, log, &cfg.source.ct, &tlds, &mut positions, feed).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(feed.sent),
+                // one unreachable log must not hold up the others
+                Err(e) => warn!("ct: {}: {e:#}", log),
+            }
+        }
+        time::sleep(cfg.source.ct.poll_interval).await;
+    }
+}
+
+fn positions_path(cfg: &Config) -> PathBuf {
+    if cfg.source.ct.state_file.trim().is_empty() {
+        Path::new(&cfg.storage.dir).join("ct_positions.json")
+    } else {
+        PathBuf::from(&cfg.source.ct.state_file)
+    }
+}
+
+// Read the entries added to `log` since the last poll; false once the feed is done
+async fn poll_log(
+    client: &Client,
+    log: &str,
+    ct: &CtConfig,
+    tlds: &[String],
+    positions: &mut Positions,
+    feed: &mut Feed<'_>,
+) -> anyhow::Result<bool> {
+    let sth: SignedTreeHead = get_json(client, &format!("{log}/ct/v1/get-sth")).await?;
+    // a log seen for the first time is read from its current head
+    let mut next = match positions.next.get(log) {
+        Some(&n) => n,
+        None => {
+            info!("ct: {} starts at entry {}", log, sth.tree_size);
+            positions.set(log, sth.tree_size)?;
+            sth.tree_size
+        }
+    };
+    while next < sth.tree_size {
+        let end = (next + ct.batch_size.max(1) - 1).min(sth.tree_size - 1);
+        let page: Entries = get_json(client, &format!("{log}/ct/v1/get-entries?start={next}&end={end}")).await?;
+        // logs may return fewer entries than asked for, but never none
+        if page.entries.is_empty() {
+            bail!("get-entries returned no entries for {next}..={end}");
+        }
+        // the same names repeat across a certificate's CN and SANs and its precertificate
+        let mut seen = HashSet::new();
+        for (i, e) in page.entries.iter().enumerate() {
+            let names = match leaf_names(&e.leaf_input) {
+                Ok(n) => n,
+                Err(err) => {
+                    debug!("ct: {} entry {}: {err:#}", log, next + i as u64);
+                    continue;
+                }
+            };
+            for domain in names.iter().filter_map(|n| registered_name(n, tlds)) {
+                if seen.insert(domain.clone()) && !feed.offer(domain).await {
+                    return Ok(false);
+                }
+            }
+        }
+        next += page.entries.len() as u64;
+        positions.set(log, next)?;
+    }
+    Ok(true)
+}
+
+async fn get_json<T: DeserializeOwned>(client: &Client, url: &str) -> anyhow::Result<T> {
+    Ok(client.get(url).timeout(REQUEST_TIMEOUT).send().await?.error_for_status()?.json().await?)
+}
+
+// Subject CN and DNS SANs of a log entry's certificate or precertificate
+fn leaf_names(leaf_input: &str) -> anyhow::Result<Vec<String>> {
+    let leaf = STANDARD.decode(leaf_input)?;
+    // MerkleTreeLeaf: version(1) leaf_type(1) timestamp(8) entry_type(2) entry
+    let entry_type = leaf.get(10..12).map(|b| u16::from_be_bytes([b[0], b[1]])).context("truncated leaf")?;
+    let tbs = match entry_type {
+        // x509_entry: ASN.1Cert<1..2^24-1>
+        0 => X509Certificate::from_der(opaque24(&leaf, 12)?)?.1.tbs_certificate,
+        // precert_entry: issuer_key_hash[32], TBSCertificate<1..2^24-1>
+        1 => TbsCertificate::from_der(opaque24(&leaf, 12 + 32)?)?.1,
+        t => bail!("unknown entry type {t}"),
+    };
+    let mut names: Vec<String> = tbs
+        .subject()
+        .iter_common_name()
+        .filter_map(|cn| cn.as_str().ok())
+        .map(str::to_string)
+        .collect();
+    if let Ok(Some(san)) = tbs.subject_alternative_name() {
+        for n in &san.value.general_names {
+            if let GeneralName::DNSName(d) = n {
+                names.push(d.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+// TLS opaque value with a 24-bit length prefix at `at`
+fn opaque24(buf: &[u8], at: usize) -> anyhow::Result<&[u8]> {
+    let len = buf
+        .get(at..at + 3)
+        .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]) as usize)
+        .context("truncated leaf")?;
+    buf.get(at + 3..at + 3 + len).context("truncated leaf")
+}
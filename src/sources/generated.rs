@@ -0,0 +1,465 @@
+use tracing::{debug, info, warn};
+
+use super::{candidate_suffixes, Feed};
+use crate::checkpoint::{BlockPosition, Checkpoint, CheckpointLog, CursorSkip, Cursors};
+use crate::cluster::shard_of;
+use crate::config::{GeneratorConfig, LiveConfig};
+use crate::domain::Domain;
+use crate::exclude::Exclusions;
+use crate::generator::{build_generator, label_lengths, CandidateGenerator, GenerationOrder};
+use crate::score::Scorer;
+
+/// Where a pass starts: after the full domain `from` (at `checkpoint` when it
+/// is saved for it). In `label` order `cursors`, when set, replace `from`.
+/// Generated label positions go to `positions` for the next save.
+#[derive(Default)]
+pub struct ResumePoint<'a> {
+    pub from: String,
+    pub checkpoint: Option<Checkpoint>,
+    pub cursors: Cursors,
+    pub positions: Option<&'a CheckpointLog>,
+}
+
+/// Generate labels and domains, starting after the resume point; only labels
+/// of `shard` (index, count) when set.
+pub async fn generate_candidates(
+    config: &LiveConfig,
+    resume: ResumePoint<'_>,
+    feed: &mut Feed<'_>,
+    shard: Option<(u32, u32)>,
+) -> anyhow::Result<i64> {
+    let cur = config.current();
+    match cur.generator.order {
+        GenerationOrder::Label => {}
+        GenerationOrder::Priority => return generate_scored(config, resume, feed, shard).await,
+        _ => return generate_blocks(config, resume, feed, shard).await,
+    }
+    let mut cfg_rx = config.subscribe();
+    let (mut labels, filter) = build_labels(&cur.generator, shard).await?;
+    let exclude = Exclusions::load(&cur.generator).await?;
+    let scorer = Scorer::load(&cur.generator).await?;
+    let (mut excluded, mut below) = (0u64, 0u64);
+    let mut tlds = candidate_suffixes(&cur.generator);
+    let ResumePoint { from, checkpoint, cursors, positions } = resume;
+    let lengths = label_lengths(&cur.generator);
+    let mut cursors = if cursors.is_empty() { Cursors::at(&from, &tlds, lengths.clone()) } else { cursors };
+    // a cursor at a label the generator no longer produces would skip its pairs for good
+    let start = labels.checkpoint();
+    for label in cursors.labels() {
+        if !labels.seek(&label) {
+            warn!("resume: '{}' is not produced by the current generator settings, its lengths and TLDs start from the beginning", label);
+            cursors.forget(&label);
+        }
+    }
+    if !start.is_empty() && !labels.restore(&start) {
+        labels = build_labels(&cur.generator, shard).await?.0;
+    }
+    // the resume label, already taken from a restored generator, with its position
+    let mut first: Option<(Vec<usize>, String)> = None;
+    match cursors.common_label(&tlds, lengths).map(str::to_string) {
+        Some(label) => match checkpoint.filter(|cp| cp.label == label) {
+            Some(cp) if labels.restore(&cp.index) && labels.next_label().as_deref() == Some(label.as_str()) => {
+                info!("resume: generator restored at label '{}' from checkpoint", label);
+                first = Some((cp.index, label));
+            }
+            cp => {
+                // a checkpoint that did not fit may have moved the generator
+                if cp.is_some() {
+                    labels = build_labels(&cur.generator, shard).await?.0;
+                }
+                labels.seek(&label);
+                info!("resume: generator positioned at label '{}'", label);
+            }
+        },
+        None if !cursors.is_empty() => {
+            info!("resume: TLDs or lengths changed since the last run, replaying the pass past the saved cursors");
+        }
+        None => {}
+    }
+    let mut skip = CursorSkip::new(cursors, positions);
+
+    loop {
+        let (at, label) = match first.take() {
+            Some(f) => f,
+            None => {
+                let at = if positions.is_some() { labels.checkpoint() } else { Vec::new() };
+                match labels.next_label() {
+                    Some(label) => (at, label),
+                    None => break,
+                }
+            }
+        };
+        // pick up a reloaded TLD list without restarting the pass
+        if cfg_rx.has_changed().unwrap_or(false) {
+            tlds = candidate_suffixes(&cfg_rx.borrow_and_update().generator);
+            info!("generator: TLD list updated, {} TLDs", tlds.len());
+        }
+        // taken for every label, so the cursors are reached even at skipped ones
+        let covered = skip.covered(&label, &tlds);
+        if filter.is_some_and(|(i, n)| shard_of(&label, n) != i) {
+            continue;
+        }
+        if exclude.skips_label(&label) {
+            excluded += tlds.len() as u64;
+            continue;
+        }
+        if !scorer.passes(&label) {
+            below += tlds.len() as u64;
+            continue;
+        }
+        if let Some(p) = positions {
+            p.push(Checkpoint { label: label.clone(), index: at, ..Default::default() });
+        }
+        for (ti, t) in tlds.iter().enumerate() {
+            if covered.get(ti) == Some(&true) {
+                continue;
+            }
+            let domain = format!("{label}{t}");
+            if exclude.skips_domain(&domain) {
+                excluded += 1;
+                continue;
+            }
+            let Some(domain) = Domain::parse(&domain) else {
+                debug!("generator: skipping {}: not a valid domain", domain);
+                continue;
+            };
+            if !feed.offer(domain).await {
+                return Ok(feed.sent);
+            }
+        }
+        // cooperative yield
+        tokio::task::yield_now().await;
+    }
+    log_excluded(&exclude, excluded, &scorer, below);
+    Ok(feed.sent)
+}
+
+// The other orders: a block of consecutive labels goes out with one TLD, then
+// again from its first label with the next TLD. A position is the label plus
+// its block, so resume restarts the remaining TLDs from the block start.
+async fn generate_blocks(
+    config: &LiveConfig,
+    resume: ResumePoint<'_>,
+    feed: &mut Feed<'_>,
+    shard: Option<(u32, u32)>,
+) -> anyhow::Result<i64> {
+    let mut cfg_rx = config.subscribe();
+    let cur = config.current();
+    let (order, batch) = (cur.generator.order, cur.generator.order_batch);
+    let (mut labels, filter) = build_labels(&cur.generator, shard).await?;
+    let exclude = Exclusions::load(&cur.generator).await?;
+    let scorer = Scorer::load(&cur.generator).await?;
+    let (mut excluded, mut below) = (0u64, 0u64);
+    let mut tlds = candidate_suffixes(&cur.generator);
+    let ResumePoint { from, checkpoint, positions, .. } = resume;
+    let resume = idna::domain_to_unicode(&from.to_lowercase()).0;
+
+    // generator position of the block's first label
+    let mut block = labels.checkpoint();
+    let mut block_len: Option<usize> = None;
+    let mut ti = 0;
+    // where the resume TLD goes on inside the block, and the labels of the block before that
+    let mut cont: Option<(Vec<usize>, usize)> = None;
+    if let Some((label, tld)) = resume.split_once('.') {
+        let tld_pos = tlds.iter().position(|t| t.strip_prefix('.') == Some(tld));
+        let restored = match checkpoint.filter(|cp| cp.label == label) {
+            Some(Checkpoint { index, tld: cp_tld, block: Some(b), .. })
+                if b.fits(order, batch) && labels.restore(&index) && labels.next_label().as_deref() == Some(label) =>
+            {
+                info!("resume: generator restored at label '{}' from checkpoint", label);
+                let tld_at = if tlds.get(cp_tld).is_some_and(|t| t.strip_prefix('.') == Some(tld)) {
+                    Some(cp_tld)
+                } else {
+                    tld_pos
+                };
+                Some((b, tld_at))
+            }
+            _ => {
+                labels = build_labels(&cur.generator, shard).await?.0;
+                match locate(labels.as_mut(), order, batch, label) {
+                    Some(b) => {
+                        info!("resume: generator replayed up to label '{}'", label);
+                        Some((b, tld_pos))
+                    }
+                    None => {
+                        warn!("resume: '{}' is not produced by the current generator settings, starting from the beginning", resume);
+                        labels = build_labels(&cur.generator, shard).await?.0;
+                        block = labels.checkpoint();
+                        None
+                    }
+                }
+            }
+        };
+        if let Some((b, tld_at)) = restored {
+            block_len = Some(label.chars().count());
+            match tld_at {
+                Some(t) => {
+                    // an empty position: the labels ran out after the resume label
+                    cont = Some((labels.checkpoint(), b.n + 1));
+                    ti = t;
+                }
+                // the resume TLD is gone, so the block starts over
+                None => warn!("resume: TLD of '{}' is no longer listed, repeating its block", resume),
+            }
+            block = b.start;
+        }
+    }
+
+    while !block.is_empty() {
+        let mut next = None;
+        while ti < tlds.len() {
+            let (start, mut n) = cont.take().unwrap_or_else(|| (block.clone(), 0));
+            if start.is_empty() {
+                ti += 1;
+                continue;
+            }
+            if !labels.restore(&start) {
+                anyhow::bail!("generator cannot return to its position {:?}", start);
+            }
+            loop {
+                let at = labels.checkpoint();
+                let Some(label) = labels.next_label() else {
+                    next = None;
+                    break;
+                };
+                if order.ends_block(batch, n, block_len, &label) {
+                    next = Some(at);
+                    break;
+                }
+                block_len.get_or_insert(label.chars().count());
+                n += 1;
+                if filter.is_some_and(|(i, n)| shard_of(&label, n) != i) {
+                    continue;
+                }
+                if exclude.skips_label(&label) {
+                    excluded += 1;
+                    continue;
+                }
+                if !scorer.passes(&label) {
+                    below += 1;
+                    continue;
+                }
+                if let Some(p) = positions {
+                    p.push(Checkpoint {
+                        label: label.clone(),
+                        index: at,
+                        tld: 0,
+                        block: Some(BlockPosition { start: block.clone(), n: n - 1, order, batch }),
+                    });
+                }
+                let domain = format!("{label}{}", tlds[ti]);
+                if exclude.skips_domain(&domain) {
+                    excluded += 1;
+                    continue;
+                }
+                let Some(domain) = Domain::parse(&domain) else {
+                    debug!("generator: skipping {}: not a valid domain", domain);
+                    continue;
+                };
+                if !feed.offer(domain).await {
+                    return Ok(feed.sent);
+                }
+                if n % 256 == 0 {
+                    // cooperative yield
+                    tokio::task::yield_now().await;
+                }
+            }
+            ti += 1;
+        }
+        let Some(start) = next else {
+            break;
+        };
+        block = start;
+        block_len = None;
+        ti = 0;
+        // pick up a reloaded TLD list at block boundaries
+        if cfg_rx.has_changed().unwrap_or(false) {
+            tlds = candidate_suffixes(&cfg_rx.borrow_and_update().generator);
+            info!("generator: TLD list updated, {} TLDs", tlds.len());
+        }
+    }
+    log_excluded(&exclude, excluded, &scorer, below);
+    Ok(feed.sent)
+}
+
+// order: priority. A window of `order_batch` consecutive labels is scored and
+// sorted, best first, and each label goes out with every TLD before the next.
+// A position is the window start plus the label, so resume sorts the window
+// again and goes on after the label.
+async fn generate_scored(
+    config: &LiveConfig,
+    resume: ResumePoint<'_>,
+    feed: &mut Feed<'_>,
+    shard: Option<(u32, u32)>,
+) -> anyhow::Result<i64> {
+    let mut cfg_rx = config.subscribe();
+    let cur = config.current();
+    let (order, batch) = (GenerationOrder::Priority, cur.generator.order_batch);
+    let (mut labels, filter) = build_labels(&cur.generator, shard).await?;
+    let exclude = Exclusions::load(&cur.generator).await?;
+    let scorer = Scorer::load(&cur.generator).await?;
+    let (mut excluded, mut below) = (0u64, 0u64);
+    let mut tlds = candidate_suffixes(&cur.generator);
+    let ResumePoint { from, checkpoint, positions, .. } = resume;
+    let resume = idna::domain_to_unicode(&from.to_lowercase()).0;
+
+    // the resume label and the TLD it got to
+    let mut skip: Option<(String, Option<usize>)> = None;
+    if let Some((label, tld)) = resume.split_once('.') {
+        let tld_pos = tlds.iter().position(|t| t.strip_prefix('.') == Some(tld));
+        let found = match checkpoint.filter(|cp| cp.label == label) {
+            Some(Checkpoint { index, tld: cp_tld, block: Some(b), .. })
+                if b.fits(order, batch) && labels.restore(&index) && labels.next_label().as_deref() == Some(label) =>
+            {
+                info!("resume: generator restored at label '{}' from checkpoint", label);
+                let tld_at = if tlds.get(cp_tld).is_some_and(|t| t.strip_prefix('.') == Some(tld)) {
+                    Some(cp_tld)
+                } else {
+                    tld_pos
+                };
+                Some((b.start, tld_at))
+            }
+            _ => {
+                labels = build_labels(&cur.generator, shard).await?.0;
+                let b = locate(labels.as_mut(), order, batch, label);
+                if b.is_some() {
+                    info!("resume: generator replayed up to label '{}'", label);
+                }
+                b.map(|b| (b.start, tld_pos))
+            }
+        };
+        match found {
+            Some((start, tld_at)) => {
+                if !labels.restore(&start) {
+                    anyhow::bail!("generator cannot return to its position {:?}", start);
+                }
+                skip = Some((label.to_string(), tld_at));
+            }
+            None => {
+                warn!("resume: '{}' is not produced by the current generator settings, starting from the beginning", resume);
+                labels = build_labels(&cur.generator, shard).await?.0;
+            }
+        }
+    }
+
+    let mut more = true;
+    while more {
+        let start = labels.checkpoint();
+        let mut window = Vec::with_capacity(batch);
+        for _ in 0..batch {
+            let at = if positions.is_some() { labels.checkpoint() } else { Vec::new() };
+            let Some(label) = labels.next_label() else {
+                more = false;
+                break;
+            };
+            if filter.is_some_and(|(i, n)| shard_of(&label, n) != i) {
+                continue;
+            }
+            if exclude.skips_label(&label) {
+                excluded += tlds.len() as u64;
+                continue;
+            }
+            let score = scorer.score(&label);
+            if score < scorer.min_score() {
+                below += tlds.len() as u64;
+                continue;
+            }
+            window.push((score, at, label));
+        }
+        // stable, so equal scores keep the generator order
+        window.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let (mut first, mut skip_tlds) = (0, None);
+        if let Some((label, tld_at)) = skip.take() {
+            match window.iter().position(|(_, _, l)| *l == label) {
+                Some(i) => {
+                    if tld_at.is_none() {
+                        warn!("resume: TLD of '{}' is no longer listed, repeating its label", resume);
+                    }
+                    (first, skip_tlds) = (i, tld_at);
+                }
+                None => warn!("resume: '{}' did not score the same, repeating its window", resume),
+            }
+        }
+        for (n, (_, at, label)) in window.into_iter().enumerate().skip(first) {
+            if let Some(p) = positions {
+                p.push(Checkpoint {
+                    label: label.clone(),
+                    index: at,
+                    tld: 0,
+                    block: Some(BlockPosition { start: start.clone(), n, order, batch }),
+                });
+            }
+            let skip = skip_tlds.take();
+            for (ti, t) in tlds.iter().enumerate() {
+                if skip.is_some_and(|s| ti <= s) {
+                    continue;
+                }
+                let domain = format!("{label}{t}");
+                if exclude.skips_domain(&domain) {
+                    excluded += 1;
+                    continue;
+                }
+                let Some(domain) = Domain::parse(&domain) else {
+                    debug!("generator: skipping {}: not a valid domain", domain);
+                    continue;
+                };
+                if !feed.offer(domain).await {
+                    return Ok(feed.sent);
+                }
+            }
+            // cooperative yield
+            tokio::task::yield_now().await;
+        }
+        // pick up a reloaded TLD list between windows
+        if cfg_rx.has_changed().unwrap_or(false) {
+            tlds = candidate_suffixes(&cfg_rx.borrow_and_update().generator);
+            info!("generator: TLD list updated, {} TLDs", tlds.len());
+        }
+    }
+    log_excluded(&exclude, excluded, &scorer, below);
+    Ok(feed.sent)
+}
+
+// The generator of a pass. A shard is walked as a range of the keyspace when
+// the generator can split itself; otherwise the returned filter picks the
+// shard's labels out of all of them.
+async fn build_labels(
+    gen: &GeneratorConfig,
+    shard: Option<(u32, u32)>,
+) -> anyhow::Result<(Box<dyn CandidateGenerator>, Option<(u32, u32)>)> {
+    let mut labels = build_generator(gen).await?;
+    let filter = shard.filter(|(i, n)| !labels.shard(*i, *n));
+    Ok((labels, filter))
+}
+
+fn log_excluded(exclude: &Exclusions, excluded: u64, scorer: &Scorer, below: u64) {
+    if !exclude.is_empty() {
+        info!("generator: {} candidates excluded this pass", excluded);
+    }
+    if scorer.min_score() > 0.0 {
+        info!("generator: {} candidates under scoring.min_score this pass", below);
+    }
+}
+
+// Walk a fresh generator up to `label`, tracking the blocks; the generator is
+// left right after it.
+fn locate(labels: &mut dyn CandidateGenerator, order: GenerationOrder, batch: usize, label: &str) -> Option<BlockPosition> {
+    let mut start = labels.checkpoint();
+    let mut n = 0;
+    let mut block_len = None;
+    loop {
+        let at = labels.checkpoint();
+        let l = labels.next_label()?;
+        if order.ends_block(batch, n, block_len, &l) {
+            start = at;
+            n = 0;
+            block_len = None;
+        }
+        block_len.get_or_insert(l.chars().count());
+        if l == label {
+            return Some(BlockPosition { start, n, order, batch });
+        }
+        n += 1;
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+
+use anyhow::Context;
+use flate2::read::MultiGzDecoder;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use super::{ace_tlds, registered_name, Feed};
+use crate::ingest::owner_name;
+
+// owner names read ahead of the check queue
+const READ_AHEAD: usize = 4096;
+
+/// Queue the names delegated in zone files (plain or .gz, absolute owner
+/// names as in CZDS files), reduced to the registered name under one of
+/// `tlds`, starting after `from` when it is found.
+pub async fn read_zones(files: &[String], tlds: &[String], from: &str, feed: &mut Feed<'_>) -> anyhow::Result<i64> {
+    let tlds = ace_tlds(tlds);
+    // the zone may have changed since the resume point was saved
+    let mut skip_until = (!from.is_empty()).then(|| from.to_string());
+    loop {
+        let (tx, mut rx) = mpsc::channel::<String>(READ_AHEAD);
+        let paths = files.to_vec();
+        let reader = tokio::task::spawn_blocking(move || read_owners(&paths, &tx));
+        let (mut last, mut outside) = (String::new(), 0u64);
+        while let Some(owner) = rx.recv().await {
+            let Some(domain) = registered_name(&owner, &tlds) else {
+                outside += 1;
+                continue;
+            };
+            // records of one name are adjacent; NS + DS sets repeat the owner
+            if *domain == *last {
+                continue;
+            }
+            last.clear();
+            last.push_str(&domain);
+            if let Some(s) = &skip_until {
+                if *s == *domain {
+                    info!("resume: zone source positioned after '{}'", s);
+                    skip_until = None;
+                }
+                continue;
+            }
+            if !feed.offer(domain).await {
+                return Ok(feed.sent);
+            }
+        }
+        reader.await??;
+        if outside > 0 {
+            info!("zone: {} records outside the configured TLDs skipped", outside);
+        }
+        match skip_until.take() {
+            Some(s) => warn!("resume: '{}' is not in the zone files, reading them from the beginning", s),
+            None => return Ok(feed.sent),
+        }
+    }
+}
+
+// Send the owner names of every file in order; stops early when the receiver is gone
+fn read_owners(paths: &[String], tx: &mpsc::Sender<String>) -> anyhow::Result<()> {
+    for path in paths {
+        let file = File::open(path).with_context(|| format!("open zone file {path}"))?;
+        let reader: Box<dyn Read> = if path.ends_with(".gz") {
+            Box::new(MultiGzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        info!("zone: reading {}", path);
+        for line in BufReader::new(reader).lines() {
+            let line = line.with_context(|| format!("read zone file {path}"))?;
+            if let Some(owner) = owner_name(&line) {
+                if tx.blocking_send(owner.to_string()).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
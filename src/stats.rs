@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::{self, AlertStatus};
+use crate::bandwidth::{self, BandwidthStats};
+use crate::dns::{self, PositiveCacheStats, ResolverStats};
+use crate::http_pool::{self, PoolStats};
+use crate::latency::{LatencySummary, Stage, StageSummary};
+use crate::progress::Progress;
+use crate::service::{ScanControl, ScanState};
+use crate::store::DomainStore;
+
+/// Layout of the /stats/ payload: `v1` is the flat Go-compatible one,
+/// `v2` groups the counters and drops the Go-only fields.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsSchema {
+    #[default]
+    V1,
+    V2,
+}
+
+/// The /stats/ payload in the requested schema
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum Stats {
+    V1(StatsV1),
+    V2(StatsV2),
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StatsV1 {
+    state: ScanState,
+    elapsed: String,
+    eta: String,
+    found: i64,
+    available: i64,
+    registered: i64,
+    duplicates_skipped: i64,
+    wildcard_skipped: i64,
+    retry_pending: i64,
+    retried: i64,
+    failed_permanent: i64,
+    /// Failed check attempts by cause (re-checks included): dns_nxdomain,
+    /// dns_no_records, dns_servfail, dns_refused, dns_timeout, dns_other,
+    /// http_connect, http_tls, http_timeout, http_status, http_content, http_other
+    errors: BTreeMap<String, i64>,
+    /// Items waiting between pipeline stages: candidates (generator/source to
+    /// checkers) and store (checkers to the store writer)
+    queue_depth: BTreeMap<String, i64>,
+    /// Times a producer found the next stage's queue full and had to wait, per queue
+    backpressure_events: BTreeMap<String, i64>,
+    /// Approximate size of the found domains queued for or buffered by the store writer
+    store_pending_bytes: usize,
+    /// Answer times since start: dns (address lookups) and http (requests, redirects included)
+    latency: BTreeMap<String, LatencySummary>,
+    /// Where the worker slots' time went since start: generation_wait (no
+    /// candidate to check), rate_limit, dns, http, store and other
+    stages: BTreeMap<String, StageSummary>,
+    /// Connections of the HTTP checks since start, over all scans
+    http_pool: PoolStats,
+    /// Answers of each dns.consensus resolver since start; empty without it
+    dns_resolvers: BTreeMap<String, ResolverStats>,
+    /// Lookups answered by dns.positive_cache since start, and the others
+    dns_cache: PositiveCacheStats,
+    /// Estimated network traffic of the checks since start, over all scans,
+    /// and limits.bandwidth
+    bandwidth: BandwidthStats,
+    /// Rules of alerts.rules by name: ok, pending or firing
+    alerts: BTreeMap<String, AlertStatus>,
+    remaining: i64,
+    speed_per_sec: f64,
+    efficiency_percent: f64,
+    percent: f64,
+    generated: i64,
+    checked: i64,
+    total_planned: i64,
+    domains_memory_bytes: u64,
+    domains_memory_human: String,
+    /// Kept for clients of the Go service; always 0
+    go_mem_alloc_bytes: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StatsV2 {
+    /// Always 2
+    schema_version: u32,
+    state: ScanState,
+    progress: ProgressV2,
+    /// RDAP classification of the candidates
+    availability: AvailabilityV2,
+    /// Candidates dropped before a check
+    skipped: SkippedV2,
+    retries: RetriesV2,
+    /// Failed check attempts by stage (dns, http, other) and cause, re-checks included
+    errors: BTreeMap<String, BTreeMap<String, i64>>,
+    /// Sum of `errors`
+    errors_total: i64,
+    /// Queues between pipeline stages: candidates and store
+    queues: BTreeMap<String, QueueV2>,
+    /// Answer times since start: dns and http
+    latency: BTreeMap<String, LatencySummary>,
+    /// Worker time per stage: generation_wait, rate_limit, dns, http, store, other
+    stages: BTreeMap<String, StageSummary>,
+    http_pool: PoolStats,
+    /// Answers of each dns.consensus resolver; empty without it
+    dns_resolvers: BTreeMap<String, ResolverStats>,
+    /// Hits and misses of dns.positive_cache
+    dns_cache: PositiveCacheStats,
+    bandwidth: BandwidthStats,
+    /// Rules of alerts.rules by name
+    alerts: BTreeMap<String, AlertStatus>,
+    /// Approximate memory held by the found domains index
+    domains_memory_bytes: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProgressV2 {
+    generated: i64,
+    checked: i64,
+    found: i64,
+    /// limits.max_candidates; 0 for none
+    total_planned: i64,
+    /// Absent without a total_planned
+    remaining: Option<i64>,
+    percent: f64,
+    speed_per_sec: f64,
+    efficiency_percent: f64,
+    elapsed_secs: f64,
+    /// Absent without a total_planned
+    eta_secs: Option<f64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AvailabilityV2 {
+    available: i64,
+    registered: i64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SkippedV2 {
+    duplicates: i64,
+    wildcard: i64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RetriesV2 {
+    pending: i64,
+    retried: i64,
+    failed_permanent: i64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct QueueV2 {
+    /// Items waiting for the next stage
+    depth: i64,
+    /// Times a producer found the queue full and had to wait
+    backpressure_events: i64,
+    /// Approximate size of the waiting items; store only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending_bytes: Option<usize>,
+}
+
+/// The /stats/ payload of one scan, also pushed by /ws/stats.
+pub fn snapshot(prog: &Progress, store: &DomainStore, control: &ScanControl, schema: StatsSchema) -> Stats {
+    match schema {
+        StatsSchema::V1 => Stats::V1(v1(prog, store, control)),
+        StatsSchema::V2 => Stats::V2(v2(prog, store, control)),
+    }
+}
+
+fn v1(prog: &Progress, store: &DomainStore, control: &ScanControl) -> StatsV1 {
+    let (enq, chk, fnd, elapsed) = prog.snapshot();
+    let (avail, regd) = prog.availability();
+    let (retry_pending, retried, failed) = prog.retries();
+    let est = prog.estimate();
+    let dom_bytes = store.approx_bytes();
+    let (queued, queue_full) = prog.queue();
+    StatsV1 {
+        state: control.state(),
+        elapsed: crate::fmt_duration(elapsed),
+        eta: if est.remaining >= 0 {
+            crate::fmt_duration(est.eta)
+        } else {
+            "-".to_string()
+        },
+        found: fnd,
+        available: avail,
+        registered: regd,
+        duplicates_skipped: prog.duplicates(),
+        wildcard_skipped: prog.wildcard(),
+        retry_pending,
+        retried,
+        failed_permanent: failed,
+        errors: prog.errors().into_iter().map(|(k, n)| (k.to_string(), n)).collect(),
+        queue_depth: [("candidates", queued), ("store", store.writer_status().0 as i64)]
+            .into_iter()
+            .map(|(k, n)| (k.to_string(), n))
+            .collect(),
+        backpressure_events: [("candidates", queue_full), ("store", store.backpressure_events() as i64)]
+            .into_iter()
+            .map(|(k, n)| (k.to_string(), n))
+            .collect(),
+        store_pending_bytes: store.pending_bytes(),
+        latency: latency(prog),
+        stages: prog.stages(),
+        http_pool: http_pool::stats(),
+        dns_resolvers: dns::consensus_stats(),
+        dns_cache: dns::positive_cache_stats(),
+        bandwidth: bandwidth::stats(),
+        alerts: alerts::stats(),
+        remaining: est.remaining,
+        speed_per_sec: est.speed_per_sec,
+        efficiency_percent: est.efficiency_percent,
+        percent: est.percent,
+        generated: enq,
+        checked: chk,
+        total_planned: prog.total_planned(),
+        domains_memory_bytes: dom_bytes,
+        domains_memory_human: crate::human_bytes(dom_bytes),
+        go_mem_alloc_bytes: 0,
+    }
+}
+
+fn v2(prog: &Progress, store: &DomainStore, control: &ScanControl) -> StatsV2 {
+    let (enq, chk, fnd, elapsed) = prog.snapshot();
+    let (available, registered) = prog.availability();
+    let (pending, retried, failed_permanent) = prog.retries();
+    let est = prog.estimate();
+    let (queued, queue_full) = prog.queue();
+    let mut errors: BTreeMap<String, BTreeMap<String, i64>> = BTreeMap::new();
+    let mut errors_total = 0;
+    for (kind, n) in prog.errors() {
+        let (stage, cause) = match kind.split_once('_') {
+            Some((stage @ ("dns" | "http"), cause)) => (stage, cause),
+            _ => ("other", kind),
+        };
+        errors.entry(stage.to_string()).or_default().insert(cause.to_string(), n);
+        errors_total += n;
+    }
+    let queues = [
+        ("candidates", QueueV2 { depth: queued, backpressure_events: queue_full, pending_bytes: None }),
+        (
+            "store",
+            QueueV2 {
+                depth: store.writer_status().0 as i64,
+                backpressure_events: store.backpressure_events() as i64,
+                pending_bytes: Some(store.pending_bytes()),
+            },
+        ),
+    ];
+    let planned = est.remaining >= 0;
+    StatsV2 {
+        schema_version: 2,
+        state: control.state(),
+        progress: ProgressV2 {
+            generated: enq,
+            checked: chk,
+            found: fnd,
+            total_planned: prog.total_planned(),
+            remaining: planned.then_some(est.remaining),
+            percent: est.percent,
+            speed_per_sec: est.speed_per_sec,
+            efficiency_percent: est.efficiency_percent,
+            elapsed_secs: elapsed.as_secs_f64(),
+            eta_secs: planned.then_some(est.eta.as_secs_f64()),
+        },
+        availability: AvailabilityV2 { available, registered },
+        skipped: SkippedV2 {
+            duplicates: prog.duplicates(),
+            wildcard: prog.wildcard(),
+        },
+        retries: RetriesV2 {
+            pending,
+            retried,
+            failed_permanent,
+        },
+        errors,
+        errors_total,
+        queues: queues.into_iter().map(|(k, q)| (k.to_string(), q)).collect(),
+        latency: latency(prog),
+        stages: prog.stages(),
+        http_pool: http_pool::stats(),
+        dns_resolvers: dns::consensus_stats(),
+        dns_cache: dns::positive_cache_stats(),
+        bandwidth: bandwidth::stats(),
+        alerts: alerts::stats(),
+        domains_memory_bytes: store.approx_bytes(),
+    }
+}
+
+fn latency(prog: &Progress) -> BTreeMap<String, LatencySummary> {
+    Stage::ALL
+        .iter()
+        .map(|&st| (st.as_str().to_string(), prog.latency().stage(st).summary()))
+        .collect()
+}
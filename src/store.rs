@@ -1,60 +1,454 @@
+mod ip_index;
+mod s3;
+mod sqlite;
+
+pub use ip_index::IpIndex;
+pub use s3::S3Backend;
+
 use std::{
-    path::{Path, PathBuf},
-    sync::Arc,
-    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    collections::{BTreeSet, HashMap, HashSet},
 };
-use tokio::sync::mpsc;
+use std::io::Write;
+use flate2::read::MultiGzDecoder;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot, Notify};
 use tokio::time::{self, Duration};
-use tokio::io::AsyncWriteExt;
+use tracing::Instrument;
+
+use crate::certs::CertInfo;
+use crate::config::{RotationConfig, RotationPeriod, StorageConfig, StoreBufferConfig};
+use crate::dedup::fnv1a;
+use crate::domain::normalize_tld;
+use crate::sinks::Sinks;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendKind {
+    #[default]
+    Text,
+    Sqlite,
+    S3,
+}
+
+// A domain that passed the checks, with what we learned about it
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(default)]
+pub struct FoundDomain {
+    /// ACE form, as looked up (`xn--` labels for IDNs)
+    pub domain: String,
+    /// Unicode form of an IDN; empty when it is the same as `domain`
+    pub unicode: String,
+    pub tld: String,
+    pub ips: Vec<String>,
+    pub http_status: u16,
+    pub scheme: String,
+    /// Every scheme whose answer was accepted, with http_check.scheme_policy all
+    pub schemes: Vec<String>,
+    /// URL after following redirects
+    pub final_url: String,
+    /// `Server` response header
+    pub server: String,
+    pub response_ms: u64,
+    pub found_at_unix: u64,
+    /// Page `<title>`, when http_check.match.extract_title is on
+    pub title: String,
+    /// dns.record_types the domain matched ("a", "mx", ...)
+    pub record_types: Vec<String>,
+    /// Tag of the http_check.status_rules entry that stored it (`store_with_tag`)
+    pub tag: String,
+    /// TLS certificate of the answer, when http_check.tls.inspect is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<CertInfo>,
+    /// /scans/ id of the run that found it; 0 for the main scan
+    pub scan_id: u64,
+    /// Parking provider whose fingerprint matched (parking check); empty for other sites
+    pub parked_by: String,
+    /// Address families the HTTP check succeeded over ("ipv4", "ipv6"), with http_check.per_family
+    pub http_families: Vec<String>,
+    /// "allowed" or "disallowed": robots.txt on crawling the site, with http_check.robots; empty when not read
+    pub robots: String,
+    /// ISO country code of the addresses (geoip check); empty when unknown
+    pub country: String,
+    /// Autonomous system of the addresses (geoip check); 0 when unknown
+    pub asn: u32,
+    /// Organization of `asn`
+    pub as_org: String,
+}
+
+impl FoundDomain {
+    /// Rough heap and inline size of the record, for storage.buffer.
+    pub fn approx_bytes(&self) -> usize {
+        let strs = |v: &[String]| v.iter().map(|s| s.len() + std::mem::size_of::<String>()).sum::<usize>();
+        let cert = self
+            .cert
+            .as_ref()
+            .map_or(0, |c| std::mem::size_of::<CertInfo>() + c.subject.len() + c.issuer.len() + c.error.len() + strs(&c.sans));
+        std::mem::size_of::<Self>()
+            + self.domain.len()
+            + self.unicode.len()
+            + self.tld.len()
+            + self.scheme.len()
+            + self.final_url.len()
+            + self.server.len()
+            + self.title.len()
+            + self.tag.len()
+            + self.parked_by.len()
+            + self.robots.len()
+            + self.country.len()
+            + self.as_org.len()
+            + strs(&self.ips)
+            + strs(&self.record_types)
+            + strs(&self.http_families)
+            + strs(&self.schemes)
+            + cert
+    }
+
+    pub fn new(domain: &str, ips: Vec<String>) -> Self {
+        let unicode = idna::domain_to_unicode(domain).0;
+        Self {
+            domain: domain.to_string(),
+            unicode: if unicode == domain { String::new() } else { unicode },
+            tld: extract_tld(domain).unwrap_or_default().to_string(),
+            ips,
+            found_at_unix: now_unix(),
+            ..Default::default()
+        }
+    }
+}
+
+// Filter and window for paged listing
+#[derive(Clone, Debug, Default)]
+pub struct ListQuery {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub prefix: String,
+    pub contains: String,
+    /// Only domains this expression matches (GET /search/?regex=true)
+    pub pattern: Option<regex::Regex>,
+    /// Only parked (true) or only other (false) domains; needs the stored metadata
+    pub parked: Option<bool>,
+    /// Only domains hosted in one of these countries (geoip check), upper case
+    pub countries: Vec<String>,
+    /// Only domains in this autonomous system (geoip check)
+    pub asn: Option<u32>,
+}
+
+impl ListQuery {
+    fn matches(&self, domain: &str) -> bool {
+        domain.starts_with(&self.prefix)
+            && domain.contains(&self.contains)
+            && self.pattern.as_ref().is_none_or(|p| p.is_match(domain))
+    }
+
+    fn matches_record(&self, rec: &FoundDomain) -> bool {
+        self.matches(&rec.domain)
+            && self.parked.is_none_or(|p| p != rec.parked_by.is_empty())
+            && (self.countries.is_empty() || self.countries.contains(&rec.country))
+            && self.asn.is_none_or(|a| a == rec.asn)
+    }
+
+    /// Lists every stored domain: no filter and no window.
+    pub fn is_unfiltered(&self) -> bool {
+        self.offset == 0
+            && self.limit.is_none()
+            && self.prefix.is_empty()
+            && self.contains.is_empty()
+            && self.pattern.is_none()
+            && !self.needs_metadata()
+    }
+
+    // filters on more than the name, so listing goes through scan_detailed
+    fn needs_metadata(&self) -> bool {
+        self.parked.is_some() || !self.countries.is_empty() || self.asn.is_some()
+    }
+
+    fn normalized(&self) -> Self {
+        Self {
+            prefix: self.prefix.to_lowercase(),
+            contains: self.contains.to_lowercase(),
+            ..self.clone()
+        }
+    }
+}
+
+// Walks the matches of a query, deciding which fall inside [offset, offset+limit)
+struct Window<'a> {
+    q: &'a ListQuery,
+    end: Option<usize>,
+    seen: usize,
+}
+
+enum Slot {
+    Skip,
+    Take,
+    // past the end of the window, stop scanning
+    Done,
+}
+
+impl<'a> Window<'a> {
+    fn new(q: &'a ListQuery) -> Self {
+        Self {
+            q,
+            end: q.limit.map(|l| q.offset.saturating_add(l)),
+            seen: 0,
+        }
+    }
+
+    fn slot(&mut self, matched: bool) -> Slot {
+        if !matched {
+            return Slot::Skip;
+        }
+        let i = self.seen;
+        self.seen += 1;
+        if i < self.q.offset {
+            Slot::Skip
+        } else if self.end.is_some_and(|e| i >= e) {
+            Slot::Done
+        } else {
+            Slot::Take
+        }
+    }
+}
+
+// Persistence layer behind DomainStore. Calls are blocking; the writer task
+// runs write_batch on the blocking pool.
+pub trait StorageBackend: Send + Sync {
+    fn write_batch(&self, batch: Vec<FoundDomain>) -> anyhow::Result<()>;
+    /// Visit stored domains of one TLD (or all when None) in storage order
+    /// until `visit` returns false.
+    fn scan(&self, tld: Option<&str>, visit: &mut dyn FnMut(&str) -> bool);
+    /// Like `scan`, with the stored metadata of each domain.
+    fn scan_detailed(&self, tld: Option<&str>, visit: &mut dyn FnMut(FoundDomain) -> bool);
+    fn list_range(&self, from_unix: u64, to_unix: u64, tld: Option<&str>) -> anyhow::Result<Vec<FoundDomain>>;
+    fn approx_bytes(&self) -> u64;
+    /// Changes whenever the stored domains of one TLD (or all) may have;
+    /// None when the backend cannot tell without reading them.
+    fn version(&self, tld: Option<&str>) -> Option<Version>;
+    fn reset(&self) -> anyhow::Result<()>;
+    /// Remove the stored domains of one TLD that `matches` accepts; returns
+    /// the removed names, sorted and each once. Only called from the writer.
+    fn delete(&self, tld: &str, matches: &dyn Fn(&str) -> bool) -> anyhow::Result<Vec<String>>;
+    /// Plain files holding the names of one TLD (or all) one per line, in
+    /// `scan` order, for byte range reads; None when there are none.
+    fn text_files(&self, _tld: Option<&str>) -> Option<Vec<PathBuf>> {
+        None
+    }
+    /// Sort the stored names of one TLD (or all) and drop repeated ones.
+    /// Only called from the writer.
+    fn compact(&self, _tld: Option<&str>) -> anyhow::Result<Compacted> {
+        anyhow::bail!("compaction requires storage.backend: text")
+    }
+}
+
+/// What `DomainStore::compact` did
+#[derive(Clone, Debug, Default, Serialize, utoipa::ToSchema)]
+pub struct Compacted {
+    /// TLDs (or parent zones) with stored files
+    pub tlds: usize,
+    /// Files rewritten; those already sorted and without repeats are left alone
+    pub files_rewritten: usize,
+    /// Repeated names dropped, each with its metadata line
+    pub duplicates_removed: usize,
+    /// Names kept
+    pub domains: usize,
+}
+
+/// Picks the domains `DomainStore::delete` removes.
+pub type DomainMatcher = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// The state of stored data a list response was read from, for ETag and Last-Modified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub tag: u64,
+    pub modified_unix: u64,
+}
+
+impl Version {
+    /// From the size and modification time of the files holding the data.
+    pub fn of_files<'a>(paths: impl IntoIterator<Item = &'a std::path::Path>) -> Self {
+        let mut key = String::new();
+        let mut modified_unix = 0;
+        for path in paths {
+            let Ok(md) = std::fs::metadata(path) else { continue };
+            let modified = md.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).unwrap_or_default();
+            modified_unix = modified_unix.max(modified.as_secs());
+            key.push_str(&format!("{}|{}|{}\n", path.display(), md.len(), modified.as_nanos()));
+        }
+        Self {
+            tag: fnv1a(key.as_bytes()),
+            modified_unix,
+        }
+    }
+}
+
+enum WriterMsg {
+    Record(Box<FoundDomain>),
+    // write out everything buffered so far, then acknowledge
+    Flush(oneshot::Sender<()>),
+    // write out the buffer, then remove matching domains of a TLD
+    Delete(String, DomainMatcher, oneshot::Sender<anyhow::Result<Vec<String>>>),
+    // write out the buffer, then sort and dedup the files of a TLD (or all)
+    Compact(Option<String>, oneshot::Sender<anyhow::Result<Compacted>>),
+}
 
 #[derive(Clone)]
 pub struct DomainStore {
-    dir: Arc<PathBuf>,
-    tx: mpsc::Sender<String>,
+    backend: Arc<dyn StorageBackend>,
+    tx: mpsc::Sender<WriterMsg>,
+    // unix seconds of the writer's last successful flush or idle tick
+    flushed_at: Arc<AtomicU64>,
+    // adds that found the writer queue full and had to wait
+    queue_full: Arc<AtomicU64>,
+    pending: Arc<Pending>,
+    ips: Arc<IpIndex>,
+}
+
+// Size of the records queued for or buffered by the writer, against
+// storage.buffer.hard_limit
+struct Pending {
+    bytes: AtomicUsize,
+    hard_limit: usize,
+    drained: Notify,
+}
+
+impl Pending {
+    fn release(&self, bytes: usize) {
+        self.bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.drained.notify_waiters();
+    }
+
+    // wait until `n` more bytes fit; a record alone over the limit goes through
+    // once nothing else is pending
+    async fn reserve(&self, n: usize) -> bool {
+        let mut waited = false;
+        loop {
+            let drained = self.drained.notified();
+            tokio::pin!(drained);
+            drained.as_mut().enable();
+            let cur = self.bytes.load(Ordering::Relaxed);
+            if self.hard_limit == 0 || cur == 0 || cur + n <= self.hard_limit {
+                self.bytes.fetch_add(n, Ordering::Relaxed);
+                return waited;
+            }
+            waited = true;
+            drained.await;
+        }
+    }
 }
 
 impl DomainStore {
-    pub fn new<P: AsRef<Path>>(dir: P) -> anyhow::Result<Self> {
-        let p = dir.as_ref().to_path_buf();
-        std::fs::create_dir_all(&p)?;
-        let dir_arc = Arc::new(p);
+    pub fn new(cfg: &StorageConfig) -> anyhow::Result<Self> {
+        Self::with_sinks(cfg, Sinks::default())
+    }
 
-        let (tx, mut rx) = mpsc::channel::<String>(10000);
-        let dir_clone = dir_arc.clone();
+    /// A store whose writer also publishes every written batch to `sinks`.
+    pub fn with_sinks(cfg: &StorageConfig, sinks: Sinks) -> anyhow::Result<Self> {
+        let dir = PathBuf::from(&cfg.dir);
+        std::fs::create_dir_all(&dir)?;
+        let backend: Arc<dyn StorageBackend> = match cfg.backend {
+            StorageBackendKind::Text => Arc::new(TextBackend::new(dir, cfg.rotation.clone())),
+            StorageBackendKind::Sqlite => {
+                let path = if cfg.sqlite_path.trim().is_empty() {
+                    dir.join("domains.sqlite")
+                } else {
+                    PathBuf::from(&cfg.sqlite_path)
+                };
+                Arc::new(sqlite::SqliteBackend::open(&path)?)
+            }
+            StorageBackendKind::S3 => Arc::new(s3::S3Backend::open(&cfg.s3)?),
+        };
+
+        let (tx, mut rx) = mpsc::channel::<WriterMsg>(10000);
+        let writer = backend.clone();
+        let StoreBufferConfig { soft_limit, hard_limit } = cfg.buffer;
+        let pending = Arc::new(Pending {
+            bytes: AtomicUsize::new(0),
+            hard_limit,
+            drained: Notify::new(),
+        });
+        let released = pending.clone();
+        let flushed_at = Arc::new(AtomicU64::new(now_unix()));
+        let flushed = flushed_at.clone();
+        // idle ticks count as flushes only while writes succeed
+        let mark = move |wrote: Option<bool>, failing: &mut bool| {
+            *failing = wrote.map_or(*failing, |ok| !ok);
+            if !*failing {
+                flushed.store(now_unix(), Ordering::Relaxed);
+            }
+        };
 
         tokio::spawn(async move {
-            let mut buffer: HashMap<String, Vec<String>> = HashMap::new();
+            let mut buffer: Vec<FoundDomain> = Vec::new();
+            // approx_bytes of `buffer`, reserved by `add`
+            let mut buffered = 0;
             let mut last_flush = time::Instant::now();
+            let mut failing = false;
             // Flush every 2 seconds or if buffer is large
             let flush_interval = Duration::from_secs(2);
-            
+
             loop {
                 let timeout = time::sleep_until(last_flush + flush_interval);
-                
+
                 tokio::select! {
                     msg = rx.recv() => {
                         match msg {
-                            Some(domain) => {
-                                if let Some(tld) = Self::extract_tld(&domain) {
-                                    buffer.entry(tld).or_default().push(domain);
-                                }
-                                // Soft limit to trigger flush
-                                if buffer.len() > 500 || buffer.values().map(|v| v.len()).sum::<usize>() > 5000 {
-                                    Self::flush_buffer(&dir_clone, &mut buffer).await;
+                            Some(WriterMsg::Record(rec)) => {
+                                buffered += rec.approx_bytes();
+                                buffer.push(*rec);
+                                // Soft limits to trigger flush
+                                if buffer.len() > 5000 || (soft_limit > 0 && buffered >= soft_limit) {
+                                    mark(Self::flush_buffer(&writer, &sinks, &mut buffer).await, &mut failing);
+                                    released.release(std::mem::take(&mut buffered));
                                     last_flush = time::Instant::now();
                                 }
                             }
+                            Some(WriterMsg::Flush(ack)) => {
+                                mark(Self::flush_buffer(&writer, &sinks, &mut buffer).await, &mut failing);
+                                released.release(std::mem::take(&mut buffered));
+                                last_flush = time::Instant::now();
+                                let _ = ack.send(());
+                            }
+                            Some(WriterMsg::Delete(tld, matches, ack)) => {
+                                mark(Self::flush_buffer(&writer, &sinks, &mut buffer).await, &mut failing);
+                                released.release(std::mem::take(&mut buffered));
+                                last_flush = time::Instant::now();
+                                // no append runs while the files are rewritten
+                                let backend = writer.clone();
+                                let res = tokio::task::spawn_blocking(move || backend.delete(&tld, &*matches))
+                                    .await
+                                    .unwrap_or_else(|e| Err(anyhow::anyhow!("store writer panicked: {e}")));
+                                let _ = ack.send(res);
+                            }
+                            Some(WriterMsg::Compact(tld, ack)) => {
+                                mark(Self::flush_buffer(&writer, &sinks, &mut buffer).await, &mut failing);
+                                released.release(std::mem::take(&mut buffered));
+                                last_flush = time::Instant::now();
+                                let backend = writer.clone();
+                                let res = tokio::task::spawn_blocking(move || backend.compact(tld.as_deref()))
+                                    .await
+                                    .unwrap_or_else(|e| Err(anyhow::anyhow!("store writer panicked: {e}")));
+                                let _ = ack.send(res);
+                            }
                             None => {
                                 // Channel closed
-                                Self::flush_buffer(&dir_clone, &mut buffer).await;
+                                Self::flush_buffer(&writer, &sinks, &mut buffer).await;
+                                released.release(buffered);
                                 break;
                             }
                         }
                     }
                     _ = timeout => {
-                        if !buffer.is_empty() {
-                            Self::flush_buffer(&dir_clone, &mut buffer).await;
-                        }
+                        mark(Self::flush_buffer(&writer, &sinks, &mut buffer).await, &mut failing);
+                        released.release(std::mem::take(&mut buffered));
                         last_flush = time::Instant::now();
                     }
                 }
@@ -62,119 +456,724 @@ impl DomainStore {
         });
 
         Ok(Self {
-            dir: dir_arc,
+            backend,
             tx,
+            flushed_at,
+            queue_full: Arc::new(AtomicU64::new(0)),
+            pending,
+            ips: Arc::new(IpIndex::default()),
         })
     }
 
-    fn extract_tld(domain: &str) -> Option<String> {
-        let idx = domain.rfind('.')?;
-        if idx == 0 || idx == domain.len() - 1 {
+    // whether the batch was written (or published, when the backend keeps
+    // nothing); None when there was nothing to write
+    async fn flush_buffer(backend: &Arc<dyn StorageBackend>, sinks: &Sinks, buffer: &mut Vec<FoundDomain>) -> Option<bool> {
+        if buffer.is_empty() {
             return None;
         }
-        Some(domain[idx + 1..].to_string())
-    }
-
-    async fn flush_buffer(dir: &Path, buffer: &mut HashMap<String, Vec<String>>) {
-        for (tld, domains) in buffer.drain() {
-            let path = dir.join(format!("{}.txt", tld));
-            // Use tokio fs for async writing
-            let res = tokio::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&path)
-                .await;
-                
-            match res {
-                Ok(mut f) => {
-                    let mut chunk = String::with_capacity(domains.len() * 20);
-                    for d in domains {
-                        chunk.push_str(&d);
-                        chunk.push('\n');
-                    }
-                    if let Err(e) = f.write_all(chunk.as_bytes()).await {
-                        tracing::error!("failed to write to {}: {}", path.display(), e);
+        let batch = std::mem::take(buffer);
+        if !sinks.keep_stored() {
+            return Some(sinks.publish(&batch));
+        }
+        if sinks.is_empty() {
+            return Some(Self::write(backend.clone(), batch).await);
+        }
+        // a batch the sinks cannot keep is logged; the backend decides whether the flush counts
+        sinks.publish(&batch);
+        Some(Self::write(backend.clone(), batch).await)
+    }
+
+    async fn write(backend: Arc<dyn StorageBackend>, batch: Vec<FoundDomain>) -> bool {
+        let span = tracing::info_span!("store.write", records = batch.len());
+        match tokio::task::spawn_blocking(move || backend.write_batch(batch)).instrument(span).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                tracing::error!("failed to write domains: {}", e);
+                false
+            }
+            Err(e) => {
+                tracing::error!("store writer panicked: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Records queued for the writer and the unix time of its last successful flush.
+    pub fn writer_status(&self) -> (usize, u64) {
+        (self.tx.max_capacity() - self.tx.capacity(), self.flushed_at.load(Ordering::Relaxed))
+    }
+
+    /// Size of the found domains queued for or buffered by the writer.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending.bytes.load(Ordering::Relaxed)
+    }
+
+    /// How often `add` found the writer queue full (or storage.buffer.hard_limit
+    /// reached) and had to wait.
+    pub fn backpressure_events(&self) -> u64 {
+        self.queue_full.load(Ordering::Relaxed)
+    }
+
+    /// Queue a record for the writer; waits for room when the writer is behind,
+    /// so a slow backend slows the checks down instead of piling up records.
+    pub async fn add(&self, rec: FoundDomain) {
+        self.ips.add(&rec);
+        if self.pending.reserve(rec.approx_bytes()).await {
+            self.queue_full.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Err(mpsc::error::TrySendError::Full(msg)) = self.tx.try_send(WriterMsg::Record(Box::new(rec))) {
+            self.queue_full.fetch_add(1, Ordering::Relaxed);
+            let _ = self.tx.send(msg).await;
+        }
+    }
+
+    /// Write all records added so far to the backend.
+    pub async fn flush(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.tx.send(WriterMsg::Flush(ack)).await.is_ok() {
+            let _ = done.await;
+        }
+    }
+
+    /// Remove the stored domains of `tld` that `matches` accepts, once the
+    /// records added before are written; returns the removed names. The
+    /// dedup filter still knows them, so a re-find is not stored again
+    /// before a restart.
+    pub async fn delete(&self, tld: &str, matches: DomainMatcher) -> anyhow::Result<Vec<String>> {
+        let tld = normalize_tld(tld).unwrap_or_default();
+        let (ack, done) = oneshot::channel();
+        self.tx
+            .send(WriterMsg::Delete(tld, matches, ack))
+            .await
+            .map_err(|_| anyhow::anyhow!("store writer stopped"))?;
+        let removed = done.await.map_err(|_| anyhow::anyhow!("store writer stopped"))??;
+        self.ips.remove(&removed);
+        Ok(removed)
+    }
+
+    /// Sort the stored names of `tld` (all when None) and drop repeated ones,
+    /// once the records added before are written; appends wait meanwhile.
+    pub async fn compact(&self, tld: Option<&str>) -> anyhow::Result<Compacted> {
+        let tld = tld.and_then(normalize_tld);
+        let (ack, done) = oneshot::channel();
+        self.tx
+            .send(WriterMsg::Compact(tld, ack))
+            .await
+            .map_err(|_| anyhow::anyhow!("store writer stopped"))?;
+        done.await.map_err(|_| anyhow::anyhow!("store writer stopped"))?
+    }
+
+    /// The reverse index of stored domains by address; read on first use.
+    pub async fn ip_index(&self) -> anyhow::Result<&IpIndex> {
+        let _build = self.ips.build.lock().await;
+        if !self.ips.is_ready() {
+            self.ips.start();
+            // records added before are in the backend after this, later ones are kept by `add`
+            self.flush().await;
+            let backend = self.backend.clone();
+            let index = tokio::task::spawn_blocking(move || {
+                let mut index = HashMap::new();
+                backend.scan_detailed(None, &mut |rec| {
+                    ip_index::insert(&mut index, &rec);
+                    true
+                });
+                index
+            })
+            .await?;
+            tracing::info!("ip index built: {} addresses", index.len());
+            self.ips.finish(index);
+        }
+        Ok(&self.ips)
+    }
+
+    /// Number of stored domains matching the filter, regardless of offset/limit (blocking).
+    pub fn count(&self, tld: Option<&str>, q: &ListQuery) -> usize {
+        let q = q.normalized();
+        let mut n = 0;
+        if q.needs_metadata() {
+            self.scan_detailed(tld, &mut |rec| {
+                n += q.matches_record(&rec) as usize;
+                true
+            });
+        } else {
+            self.scan(tld, &mut |d| {
+                n += q.matches(d) as usize;
+                true
+            });
+        }
+        n
+    }
+
+    /// Visit the matching names inside the query window in storage order,
+    /// until `visit` returns false (blocking).
+    pub fn scan_window(&self, tld: Option<&str>, q: &ListQuery, visit: &mut dyn FnMut(&str) -> bool) {
+        let q = q.normalized();
+        let mut win = Window::new(&q);
+        if q.needs_metadata() {
+            self.scan_detailed(tld, &mut |rec| match win.slot(q.matches_record(&rec)) {
+                Slot::Skip => true,
+                Slot::Take => visit(&rec.domain),
+                Slot::Done => false,
+            });
+            return;
+        }
+        self.scan(tld, &mut |d| match win.slot(q.matches(d)) {
+            Slot::Skip => true,
+            Slot::Take => visit(d),
+            Slot::Done => false,
+        });
+    }
+
+    /// Same as `scan_window` with stored metadata (blocking).
+    pub fn scan_window_detailed(&self, tld: Option<&str>, q: &ListQuery, visit: &mut dyn FnMut(FoundDomain) -> bool) {
+        let q = q.normalized();
+        let mut win = Window::new(&q);
+        self.scan_detailed(tld, &mut |rec| match win.slot(q.matches_record(&rec)) {
+            Slot::Skip => true,
+            Slot::Take => visit(rec),
+            Slot::Done => false,
+        });
+    }
+
+    /// Visit stored names of one TLD (or all) until `visit` returns false (blocking).
+    pub fn scan(&self, tld: Option<&str>, visit: &mut dyn FnMut(&str) -> bool) {
+        let t = tld.map(|t| normalize_tld(t).unwrap_or_default());
+        self.backend.scan(t.as_deref(), visit);
+    }
+
+    /// Same as `scan` with stored metadata (blocking).
+    pub fn scan_detailed(&self, tld: Option<&str>, visit: &mut dyn FnMut(FoundDomain) -> bool) {
+        let t = tld.map(|t| normalize_tld(t).unwrap_or_default());
+        self.backend.scan_detailed(t.as_deref(), visit);
+    }
+
+    /// Visit every stored domain (blocking).
+    pub fn scan_all(&self, visit: &mut dyn FnMut(&str)) {
+        self.backend.scan(None, &mut |d| {
+            visit(d);
+            true
+        });
+    }
+
+    pub fn list_range(&self, from_unix: u64, to_unix: u64, tld: Option<&str>) -> anyhow::Result<Vec<FoundDomain>> {
+        let t = tld.map(|t| normalize_tld(t).unwrap_or_default());
+        self.backend.list_range(from_unix, to_unix, t.as_deref().filter(|t| !t.is_empty()))
+    }
+
+    pub fn approx_bytes(&self) -> u64 {
+        self.backend.approx_bytes()
+    }
+
+    /// The files of `scan` for one TLD (or all) with their length up to the
+    /// last complete line, which a write may be adding to; None when the
+    /// backend keeps no plain files (blocking).
+    pub fn text_files(&self, tld: Option<&str>) -> Option<Vec<(PathBuf, u64)>> {
+        let t = tld.map(|t| normalize_tld(t).unwrap_or_default());
+        let files = self.backend.text_files(t.as_deref())?;
+        Some(
+            files
+                .into_iter()
+                .map(|p| {
+                    let len = whole_lines(&p);
+                    (p, len)
+                })
+                .collect(),
+        )
+    }
+
+    /// Version of the stored domains of one TLD (or all), None if unknown (blocking).
+    pub fn version(&self, tld: Option<&str>) -> Option<Version> {
+        let t = tld.map(|t| normalize_tld(t).unwrap_or_default());
+        self.backend.version(t.as_deref())
+    }
+
+    pub fn reset(&self, state_file: &str) -> anyhow::Result<()> {
+        self.backend.reset()?;
+        self.ips.clear();
+        if !state_file.trim().is_empty() {
+            let _ = std::fs::remove_file(state_file);
+        }
+        Ok(())
+    }
+}
+
+// -------- plain text backend: one "<tld>.txt" file per TLD --------
+// Metadata goes to a "<tld>.meta.jsonl" sidecar so the .txt files stay plain lists.
+// With storage.rotation the pair is rolled into "segments/<tld>/<day>.<seq>.txt"
+// (and .meta.jsonl, gzipped with compress); reads go through the segments in
+// order, then the open files.
+
+const SEGMENTS: &str = "segments";
+
+struct TextBackend {
+    dir: PathBuf,
+    rotation: RotationConfig,
+}
+
+impl TextBackend {
+    fn new(dir: PathBuf, rotation: RotationConfig) -> Self {
+        Self { dir, rotation }
+    }
+
+    // Files holding one TLD (or all), oldest first; `ext` is "txt" or "meta.jsonl"
+    fn files(&self, tld: Option<&str>, ext: &str) -> Vec<PathBuf> {
+        let tlds = match tld {
+            Some(t) => vec![t.to_string()],
+            None => self.tlds(),
+        };
+        let mut out = Vec::new();
+        for t in tlds {
+            out.extend(segment_files(&self.dir.join(SEGMENTS).join(&t), ext));
+            let open = self.dir.join(format!("{t}.{ext}"));
+            if open.exists() {
+                out.push(open);
+            }
+        }
+        out
+    }
+
+    fn tlds(&self) -> Vec<String> {
+        let mut tlds: Vec<String> = std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| e.file_name().to_str()?.strip_suffix(".txt").map(str::to_string))
+            // the negative DNS cache of older versions, not a list
+            .filter(|t| t != "negative_cache")
+            .chain(
+                std::fs::read_dir(self.dir.join(SEGMENTS))
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter_map(|e| e.file_name().to_str().map(str::to_string)),
+            )
+            .collect();
+        tlds.sort();
+        tlds.dedup();
+        tlds
+    }
+
+    // Move a TLD's open files into a new segment when the day changed since
+    // they were last written or they grew past max_size.
+    fn roll_if_due(&self, tld: &str) -> anyhow::Result<()> {
+        let names = self.dir.join(format!("{tld}.txt"));
+        let meta = self.dir.join(format!("{tld}.meta.jsonl"));
+        let Ok(md) = std::fs::metadata(&meta) else {
+            return Ok(());
+        };
+        let size = md.len() + std::fs::metadata(&names).map_or(0, |m| m.len());
+        let written = md
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let new_day = self.rotation.period == RotationPeriod::Daily && written / 86400 != now_unix() / 86400;
+        let too_big = self.rotation.max_size > 0 && size >= self.rotation.max_size as u64;
+        if !new_day && !too_big {
+            return Ok(());
+        }
+        let seg_dir = self.dir.join(SEGMENTS).join(tld);
+        std::fs::create_dir_all(&seg_dir)?;
+        let day = chrono::DateTime::from_timestamp(written as i64, 0)
+            .unwrap_or_default()
+            .format("%Y-%m-%d")
+            .to_string();
+        let base = (1..)
+            .map(|seq| seg_dir.join(format!("{day}.{seq:04}")))
+            .find(|b| !with_ext(b, "txt").exists() && !with_ext(b, "txt.gz").exists())
+            .unwrap_or_default();
+        if names.exists() {
+            std::fs::rename(&names, with_ext(&base, "txt"))?;
+        }
+        std::fs::rename(&meta, with_ext(&base, "meta.jsonl"))?;
+        tracing::info!("storage: rolled {} into {}", tld, base.display());
+        if self.rotation.compress {
+            // earlier segments too, e.g. from before compress was turned on
+            for ext in ["txt", "meta.jsonl"] {
+                for path in segment_files(&seg_dir, ext) {
+                    if !path.to_string_lossy().ends_with(".gz") {
+                        gzip(&path)?;
                     }
                 }
-                Err(e) => {
-                    tracing::error!("failed to open {}: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for TextBackend {
+    fn write_batch(&self, batch: Vec<FoundDomain>) -> anyhow::Result<()> {
+        let mut by_tld: HashMap<String, (String, String)> = HashMap::new();
+        for rec in batch {
+            if rec.tld.is_empty() {
+                continue;
+            }
+            let (names, meta) = by_tld.entry(rec.tld.clone()).or_default();
+            names.push_str(&rec.domain);
+            names.push('\n');
+            meta.push_str(&serde_json::to_string(&rec)?);
+            meta.push('\n');
+        }
+        for (tld, (names, meta)) in by_tld {
+            if self.rotation.enabled() {
+                // keep writing to the open files when rolling fails
+                if let Err(e) = self.roll_if_due(&tld) {
+                    tracing::error!("storage: rolling {} failed: {:#}", tld, e);
                 }
             }
+            append(&self.dir.join(format!("{}.txt", tld)), &names);
+            append(&self.dir.join(format!("{}.meta.jsonl", tld)), &meta);
         }
+        Ok(())
     }
 
-    pub fn add(&self, domain: &str) {
-        let d = domain.to_string();
-        let tx = self.tx.clone();
-        // We spawn a task to send to channel to avoid blocking the caller
-        tokio::spawn(async move {
-            let _ = tx.send(d).await;
-        });
+    fn scan(&self, tld: Option<&str>, visit: &mut dyn FnMut(&str) -> bool) {
+        for path in self.files(tld, "txt") {
+            if !read_lines(&path, &mut |line| visit(line)) {
+                return;
+            }
+        }
+    }
+
+    fn scan_detailed(&self, tld: Option<&str>, visit: &mut dyn FnMut(FoundDomain) -> bool) {
+        for path in self.files(tld, "meta.jsonl") {
+            let more = read_lines(&path, &mut |line| match serde_json::from_str::<FoundDomain>(line) {
+                Ok(rec) => visit(rec),
+                Err(_) => true,
+            });
+            if !more {
+                return;
+            }
+        }
+    }
+
+    fn list_range(&self, _from_unix: u64, _to_unix: u64, _tld: Option<&str>) -> anyhow::Result<Vec<FoundDomain>> {
+        anyhow::bail!("time range queries require storage.backend: sqlite")
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        fn size(dir: &std::path::Path) -> u64 {
+            let Ok(entries) = std::fs::read_dir(dir) else { return 0 };
+            let mut total = 0u64;
+            for e in entries.flatten() {
+                match e.metadata() {
+                    Ok(md) if md.is_dir() => total += size(&e.path()),
+                    Ok(md) => total += md.len(),
+                    Err(_) => {}
+                }
+            }
+            total
+        }
+        size(&self.dir)
+    }
+
+    fn version(&self, tld: Option<&str>) -> Option<Version> {
+        let mut files = self.files(tld, "txt");
+        files.extend(self.files(tld, "meta.jsonl"));
+        Some(Version::of_files(files.iter().map(PathBuf::as_path)))
     }
 
-    pub fn list(&self, tld: &str) -> Vec<String> {
-        let t = tld.trim().to_lowercase();
-        if t.is_empty() {
-            return vec![];
+    fn reset(&self) -> anyhow::Result<()> {
+        let entries = std::fs::read_dir(&self.dir)?;
+        for ent in entries.flatten() {
+            let p = ent.path();
+            if p.extension().and_then(|s| s.to_str()) == Some("txt") || p.to_string_lossy().ends_with(".meta.jsonl") {
+                let _ = std::fs::remove_file(p);
+            }
+        }
+        match std::fs::remove_dir_all(self.dir.join(SEGMENTS)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
         }
-        let path = self.dir.join(format!("{}.txt", t));
-        if !path.exists() {
-            return vec![];
+    }
+
+    fn text_files(&self, tld: Option<&str>) -> Option<Vec<PathBuf>> {
+        let files = self.files(tld, "txt");
+        // gzipped segments cannot be read from an offset
+        files.iter().all(|p| p.extension().is_none_or(|e| e != "gz")).then_some(files)
+    }
+
+    fn delete(&self, tld: &str, matches: &dyn Fn(&str) -> bool) -> anyhow::Result<Vec<String>> {
+        let mut removed = BTreeSet::new();
+        for path in self.files(Some(tld), "txt") {
+            rewrite_lines(&path, &mut |line| {
+                let hit = matches(line);
+                if hit {
+                    removed.insert(line.to_string());
+                }
+                !hit
+            })?;
         }
-        if let Ok(f) = std::fs::File::open(path) {
-             use std::io::BufRead;
-             let reader = std::io::BufReader::new(f);
-             return reader.lines().filter_map(|l| l.ok()).collect();
+        for path in self.files(Some(tld), "meta.jsonl") {
+            rewrite_lines(&path, &mut |line| match serde_json::from_str::<FoundDomain>(line) {
+                Ok(rec) if matches(&rec.domain) => {
+                    removed.insert(rec.domain);
+                    false
+                }
+                _ => true,
+            })?;
         }
-        vec![]
+        Ok(removed.into_iter().collect())
     }
 
-    pub fn list_all(&self) -> Vec<String> {
-        let mut out = Vec::new();
-        if let Ok(entries) = std::fs::read_dir(&*self.dir) {
-            for e in entries.flatten() {
-                let path = e.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("txt") {
-                    if let Ok(f) = std::fs::File::open(&path) {
-                        use std::io::BufRead;
-                        let reader = std::io::BufReader::new(f);
-                        for line in reader.lines().flatten() {
-                            out.push(line);
-                            if out.len() >= 100_000 { // Safety limit
-                                return out;
+    // A name repeated across files stays in the oldest one; its metadata
+    // keeps the first found_at_unix and the latest of the rest, as sqlite does
+    fn compact(&self, tld: Option<&str>) -> anyhow::Result<Compacted> {
+        let tlds = match tld {
+            Some(t) => vec![t.to_string()],
+            None => self.tlds(),
+        };
+        let mut out = Compacted::default();
+        for tld in &tlds {
+            if self.files(Some(tld), "txt").is_empty() && self.files(Some(tld), "meta.jsonl").is_empty() {
+                continue;
+            }
+            out.tlds += 1;
+            let mut seen = HashSet::new();
+            for path in self.files(Some(tld), "txt") {
+                let mut names = Vec::new();
+                read_lines(&path, &mut |line| {
+                    if !line.is_empty() {
+                        names.push(line.to_string());
+                    }
+                    true
+                });
+                let total = names.len();
+                names.retain(|n| seen.insert(n.clone()));
+                names.sort_unstable();
+                out.duplicates_removed += total - names.len();
+                out.domains += names.len();
+                if names.len() < total || !holds_lines(&path, &names) {
+                    write_lines(&path, names.iter().map(String::as_str))?;
+                    out.files_rewritten += 1;
+                }
+            }
+            // domain -> (file it stays in, merged record)
+            let files = self.files(Some(tld), "meta.jsonl");
+            let mut records: HashMap<String, (usize, FoundDomain)> = HashMap::new();
+            let mut torn: Vec<Vec<String>> = vec![Vec::new(); files.len()];
+            let mut lines = vec![0; files.len()];
+            for (i, path) in files.iter().enumerate() {
+                read_lines(path, &mut |line| {
+                    lines[i] += 1;
+                    match serde_json::from_str::<FoundDomain>(line) {
+                        Ok(rec) => match records.get_mut(&rec.domain) {
+                            Some((_, kept)) => {
+                                let found_at_unix = kept.found_at_unix;
+                                *kept = FoundDomain { found_at_unix, ..rec };
                             }
-                        }
+                            None => {
+                                records.insert(rec.domain.clone(), (i, rec));
+                            }
+                        },
+                        // a torn line is kept as it is, after the records
+                        Err(_) if !line.is_empty() => torn[i].push(line.to_string()),
+                        Err(_) => {}
                     }
+                    true
+                });
+            }
+            let mut by_file: Vec<Vec<FoundDomain>> = vec![Vec::new(); files.len()];
+            for (i, rec) in records.into_values() {
+                by_file[i].push(rec);
+            }
+            for (i, path) in files.iter().enumerate() {
+                let mut recs = std::mem::take(&mut by_file[i]);
+                recs.sort_unstable_by(|a, b| a.domain.cmp(&b.domain));
+                let mut body = Vec::with_capacity(recs.len() + torn[i].len());
+                for rec in &recs {
+                    body.push(serde_json::to_string(rec)?);
+                }
+                body.append(&mut torn[i]);
+                if body.len() < lines[i] || !holds_lines(path, &body) {
+                    write_lines(path, body.iter().map(String::as_str))?;
+                    out.files_rewritten += 1;
                 }
             }
         }
-        out
+        Ok(out)
     }
+}
 
-    pub fn approx_bytes(&self) -> u64 {
-        let Ok(entries) = std::fs::read_dir(&*self.dir) else { return 0 };
-        let mut total = 0u64;
-        for e in entries.flatten() {
-            if let Ok(md) = e.metadata() {
-                total += md.len();
-            }
+// Whether the file holds exactly `lines`, in this order.
+fn holds_lines(path: &std::path::Path, lines: &[String]) -> bool {
+    let mut i = 0;
+    let same = read_lines(path, &mut |line| {
+        let ok = lines.get(i).is_some_and(|l| l == line);
+        i += 1;
+        ok
+    });
+    same && i == lines.len()
+}
+
+// Segments of one TLD directory in order; a plain file whose gzipped copy is
+// complete (compression was interrupted) is left out.
+fn segment_files(dir: &std::path::Path, ext: &str) -> Vec<PathBuf> {
+    let (plain, gz) = (format!(".{ext}"), format!(".{ext}.gz"));
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = p.to_string_lossy();
+            name.ends_with(&gz) || (name.ends_with(&plain) && !with_ext(p, "gz").exists())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+// `base` with ".<ext>" appended
+fn with_ext(base: &std::path::Path, ext: &str) -> PathBuf {
+    let mut s = base.as_os_str().to_owned();
+    s.push(".");
+    s.push(ext);
+    PathBuf::from(s)
+}
+
+// Replace a file with its gzipped copy.
+fn gzip(path: &std::path::Path) -> anyhow::Result<()> {
+    let tmp = with_ext(path, "gz.tmp");
+    let mut src = std::fs::File::open(path)?;
+    let mut enc = GzEncoder::new(std::io::BufWriter::new(std::fs::File::create(&tmp)?), Compression::default());
+    std::io::copy(&mut src, &mut enc)?;
+    enc.finish()?.flush()?;
+    std::fs::rename(&tmp, with_ext(path, "gz"))?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+// Feed the lines of a plain or gzipped file to `visit`; false once it stopped.
+fn read_lines(path: &std::path::Path, visit: &mut dyn FnMut(&str) -> bool) -> bool {
+    use std::io::BufRead;
+    let Ok(f) = std::fs::File::open(path) else { return true };
+    let reader: Box<dyn std::io::Read> = if path.extension().is_some_and(|e| e == "gz") {
+        Box::new(MultiGzDecoder::new(f))
+    } else {
+        Box::new(f)
+    };
+    std::io::BufReader::new(reader).lines().map_while(Result::ok).all(|line| visit(&line))
+}
+
+// Length of a plain file up to and with its last newline; 0 when missing.
+// Names are short, so the last complete line is in the final few KiB
+fn whole_lines(path: &std::path::Path) -> u64 {
+    use std::io::{Read, Seek, SeekFrom};
+    let Ok(mut f) = std::fs::File::open(path) else { return 0 };
+    let Ok(len) = f.metadata().map(|m| m.len()) else { return 0 };
+    let from = len.saturating_sub(4096);
+    let mut tail = Vec::new();
+    if f.seek(SeekFrom::Start(from)).is_err() || f.take(len - from).read_to_end(&mut tail).is_err() {
+        return 0;
+    }
+    tail.iter().rposition(|&b| b == b'\n').map_or(0, |i| from + i as u64 + 1)
+}
+
+// Drop the lines of a plain or gzipped file that `keep` rejects, through a
+// temporary file renamed over it; a file left empty is removed.
+fn rewrite_lines(path: &std::path::Path, keep: &mut dyn FnMut(&str) -> bool) -> anyhow::Result<()> {
+    let mut kept = String::new();
+    let mut dropped = false;
+    read_lines(path, &mut |line| {
+        if keep(line) {
+            kept.push_str(line);
+            kept.push('\n');
+        } else {
+            dropped = true;
         }
-        total
+        true
+    });
+    if !dropped {
+        return Ok(());
     }
+    write_lines(path, kept.lines())
+}
 
-    pub fn reset(&self, state_file: &str) -> anyhow::Result<()> {
-        let entries = std::fs::read_dir(&*self.dir)?;
-        for e in entries {
-            if let Ok(ent) = e {
-                let p = ent.path();
-                if p.extension().and_then(|s| s.to_str()) == Some("txt") {
-                    let _ = std::fs::remove_file(p);
-                }
+// Replace a plain or gzipped file with `lines` through a temporary file
+// renamed over it, so readers see the old or the new one whole; no lines
+// removes it.
+fn write_lines<'a>(path: &std::path::Path, lines: impl Iterator<Item = &'a str>) -> anyhow::Result<()> {
+    let mut body = String::new();
+    for line in lines {
+        body.push_str(line);
+        body.push('\n');
+    }
+    if body.is_empty() {
+        std::fs::remove_file(path)?;
+        return Ok(());
+    }
+    let tmp = with_ext(path, "tmp");
+    let out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+    if path.extension().is_some_and(|e| e == "gz") {
+        let mut enc = GzEncoder::new(out, Compression::default());
+        enc.write_all(body.as_bytes())?;
+        enc.finish()?.flush()?;
+    } else {
+        let mut out = out;
+        out.write_all(body.as_bytes())?;
+        out.flush()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn append(path: &std::path::Path, chunk: &str) {
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path);
+    match res {
+        Ok(mut f) => {
+            if let Err(e) = f.write_all(chunk.as_bytes()) {
+                tracing::error!("failed to write to {}: {}", path.display(), e);
             }
         }
-        if !state_file.trim().is_empty() {
-            let _ = std::fs::remove_file(state_file);
+        Err(e) => {
+            tracing::error!("failed to open {}: {}", path.display(), e);
         }
-        Ok(())
     }
-}
\ No newline at end of file
+}
+
+// generator.parent_zones in ACE form, longest first
+static PARENT_ZONES: Lazy<RwLock<Vec<String>>> = Lazy::new(Default::default);
+
+/// Configure the zones whose subdomains are stored under the zone instead of
+/// the TLD (`generator.parent_zones`); replaces the previous list.
+pub fn set_parent_zones(zones: &[String]) {
+    let mut ace: Vec<String> = zones
+        .iter()
+        .map(|z| z.trim().trim_matches('.').to_lowercase())
+        .filter_map(|z| crate::generator::to_ace(&z))
+        .filter(|z| !z.is_empty())
+        .collect();
+    ace.sort_by_key(|z| std::cmp::Reverse(z.len()));
+    ace.dedup();
+    *PARENT_ZONES.write() = ace;
+}
+
+/// The configured parent zone `domain` is a subdomain of, as part of `domain`.
+pub fn parent_zone(domain: &str) -> Option<&str> {
+    let zones = PARENT_ZONES.read();
+    let zone = zones
+        .iter()
+        .find(|z| domain.len() > z.len() + 1 && domain.ends_with(z.as_str()) && domain.as_bytes()[domain.len() - z.len() - 1] == b'.')?;
+    Some(&domain[domain.len() - zone.len()..])
+}
+
+/// What a domain is filed under: its parent zone ("example.com" for
+/// "www.example.com" with that zone configured), else its last label.
+pub fn extract_tld(domain: &str) -> Option<&str> {
+    if let Some(zone) = parent_zone(domain) {
+        return Some(zone);
+    }
+    let idx = domain.rfind('.')?;
+    if idx == 0 || idx == domain.len() - 1 {
+        return None;
+    }
+    Some(&domain[idx + 1..])
+}
+
+fn now_unix() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,115 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+
+use parking_lot::Mutex;
+
+use super::FoundDomain;
+
+// domains by address, ACE form
+type Index = HashMap<IpAddr, BTreeSet<String>>;
+
+enum State {
+    Unbuilt,
+    // records added and names deleted while the stored ones are read, applied when done
+    Building(Vec<FoundDomain>, Vec<String>),
+    Ready(Index),
+}
+
+/// Reverse index of the stored domains by resolved address, for GET /ips/.
+/// Built from the store on first use, then kept current by `DomainStore::add`.
+pub struct IpIndex {
+    state: Mutex<State>,
+    // one build at a time; later callers wait for it
+    pub(super) build: tokio::sync::Mutex<()>,
+}
+
+impl Default for IpIndex {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State::Unbuilt),
+            build: tokio::sync::Mutex::new(()),
+        }
+    }
+}
+
+impl IpIndex {
+    pub(super) fn is_ready(&self) -> bool {
+        matches!(*self.state.lock(), State::Ready(_))
+    }
+
+    // from here on `add` keeps records for the index being built
+    pub(super) fn start(&self) {
+        *self.state.lock() = State::Building(Vec::new(), Vec::new());
+    }
+
+    pub(super) fn finish(&self, mut index: Index) {
+        let mut st = self.state.lock();
+        if let State::Building(pending, deleted) = &mut *st {
+            remove(&mut index, deleted);
+            for rec in pending.drain(..) {
+                insert(&mut index, &rec);
+            }
+        }
+        *st = State::Ready(index);
+    }
+
+    pub(super) fn add(&self, rec: &FoundDomain) {
+        match &mut *self.state.lock() {
+            State::Unbuilt => {}
+            State::Building(pending, _) => pending.push(rec.clone()),
+            State::Ready(index) => insert(index, rec),
+        }
+    }
+
+    pub(super) fn remove(&self, domains: &[String]) {
+        match &mut *self.state.lock() {
+            State::Unbuilt => {}
+            State::Building(pending, deleted) => {
+                pending.retain(|rec| !domains.contains(&rec.domain));
+                deleted.extend_from_slice(domains);
+            }
+            State::Ready(index) => remove(index, domains),
+        }
+    }
+
+    pub(super) fn clear(&self) {
+        *self.state.lock() = State::Unbuilt;
+    }
+
+    /// Domains that resolved to `ip`, by name.
+    pub fn domains(&self, ip: &IpAddr) -> Vec<String> {
+        match &*self.state.lock() {
+            State::Ready(index) => index.get(ip).map(|d| d.iter().cloned().collect()).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The `top` addresses shared by the most domains, most first, and the
+    /// number of addresses indexed.
+    pub fn top(&self, top: usize) -> (Vec<(IpAddr, usize)>, usize) {
+        let State::Ready(index) = &*self.state.lock() else {
+            return (Vec::new(), 0);
+        };
+        let mut v: Vec<(IpAddr, usize)> = index.iter().map(|(ip, d)| (*ip, d.len())).collect();
+        v.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        v.truncate(top);
+        (v, index.len())
+    }
+}
+
+pub(super) fn insert(index: &mut Index, rec: &FoundDomain) {
+    for ip in rec.ips.iter().filter_map(|ip| ip.parse::<IpAddr>().ok()) {
+        index.entry(ip).or_default().insert(rec.domain.clone());
+    }
+}
+
+fn remove(index: &mut Index, domains: &[String]) {
+    if domains.is_empty() {
+        return;
+    }
+    let gone: BTreeSet<&str> = domains.iter().map(String::as_str).collect();
+    index.retain(|_, names| {
+        names.retain(|d| !gone.contains(d.as_str()));
+        !names.is_empty()
+    });
+}
@@ -0,0 +1,427 @@
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use parking_lot::Mutex;
+use reqwest::{Client, Method};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+use super::{now_unix, FoundDomain, StorageBackend, Version};
+use crate::config::S3Config;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+}
+
+// The object a TLD's results are appended to until it is rolled over
+struct Shard {
+    key: String,
+    body: Vec<u8>,
+    // bytes of `body` the stored object has
+    uploaded: usize,
+    opened: Instant,
+}
+
+// S3 backend: every TLD gets dated JSONL shard objects under
+// "<prefix><tld>/". S3 cannot append, so the open shard is uploaded whole on
+// every flush and replaced by a new one once it is large or old enough; a
+// lost instance loses at most the records of one flush interval.
+pub struct S3Backend {
+    cfg: S3Config,
+    prefix: String,
+    creds: Credentials,
+    client: Client,
+    handle: Handle,
+    // keeps this run's shards apart from those of earlier runs and other instances
+    run_id: String,
+    seq: AtomicU64,
+    shards: Mutex<HashMap<String, Shard>>,
+    // shard object sizes, for approx_bytes without listing the bucket
+    sizes: Mutex<HashMap<String, u64>>,
+}
+
+impl S3Backend {
+    /// Connect to the bucket; listing the prefix checks the bucket and credentials up front.
+    pub fn open(cfg: &S3Config) -> anyhow::Result<Self> {
+        let pick = |configured: &str, var: &str| {
+            if configured.is_empty() {
+                std::env::var(var).unwrap_or_default()
+            } else {
+                configured.to_string()
+            }
+        };
+        let creds = Credentials {
+            access_key_id: pick(&cfg.access_key_id, "AWS_ACCESS_KEY_ID"),
+            secret_access_key: pick(&cfg.secret_access_key.0, "AWS_SECRET_ACCESS_KEY"),
+            session_token: pick(&cfg.session_token.0, "AWS_SESSION_TOKEN"),
+        };
+        if creds.access_key_id.is_empty() || creds.secret_access_key.is_empty() {
+            bail!("storage.s3: no credentials, set access_key_id and secret_access_key or AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY");
+        }
+        let mut id = [0u8; 4];
+        SystemRandom::new().fill(&mut id).map_err(|_| anyhow!("storage.s3: no random source"))?;
+        let backend = Self {
+            cfg: cfg.clone(),
+            prefix: key_prefix(&cfg.prefix),
+            creds,
+            client: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            handle: Handle::current(),
+            run_id: hex(&id),
+            seq: AtomicU64::new(0),
+            shards: Mutex::new(HashMap::new()),
+            sizes: Mutex::new(HashMap::new()),
+        };
+        let objects = backend.list(&backend.prefix)?;
+        let sizes: HashMap<String, u64> = objects.into_iter().filter(|(k, _)| backend.is_shard(k)).collect();
+        info!("storage: s3://{}/{} has {} shard objects", cfg.bucket, backend.prefix, sizes.len());
+        *backend.sizes.lock() = sizes;
+        Ok(backend)
+    }
+
+    /// Same bucket, objects under "<prefix><sub>/" (the RDAP stores).
+    pub fn sub_config(cfg: &S3Config, sub: &str) -> S3Config {
+        S3Config {
+            prefix: format!("{}{sub}/", key_prefix(&cfg.prefix)),
+            ..cfg.clone()
+        }
+    }
+
+    // Backend calls are blocking: they come from the writer's blocking pool
+    // or from request handlers, all on the multi-threaded runtime
+    fn block_on<F: Future>(&self, f: F) -> F::Output {
+        tokio::task::block_in_place(|| self.handle.block_on(f))
+    }
+
+    // "<prefix><tld>/<shard>.jsonl"; other stores of the bucket live deeper
+    fn is_shard(&self, key: &str) -> bool {
+        key.strip_prefix(&self.prefix)
+            .is_some_and(|rest| rest.matches('/').count() == 1 && rest.ends_with(".jsonl"))
+    }
+
+    fn new_shard(&self, tld: &str) -> Shard {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let (_, stamp) = amz_dates(now_unix());
+        Shard {
+            key: format!("{}{tld}/{stamp}-{}-{seq:04}.jsonl", self.prefix, self.run_id),
+            body: Vec::new(),
+            uploaded: 0,
+            opened: Instant::now(),
+        }
+    }
+
+    // shard keys of one TLD (or all), oldest first within a TLD
+    fn shard_keys(&self, tld: Option<&str>) -> anyhow::Result<Vec<String>> {
+        let prefix = match tld {
+            Some(t) => format!("{}{t}/", self.prefix),
+            None => self.prefix.clone(),
+        };
+        let mut keys: Vec<String> = self.list(&prefix)?.into_iter().map(|(k, _)| k).filter(|k| self.is_shard(k)).collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    // (key, size) of every object under `prefix` (ListObjectsV2)
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<(String, u64)>> {
+        self.block_on(async {
+            let mut out = Vec::new();
+            let mut token = String::new();
+            loop {
+                let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+                if !token.is_empty() {
+                    query.push(("continuation-token", &token));
+                }
+                let body = self.request(Method::GET, "", &query, Vec::new()).await?.text().await?;
+                for c in elements(&body, "Contents") {
+                    let key = elements(c, "Key").first().map(|k| unescape(k)).unwrap_or_default();
+                    let size = elements(c, "Size").first().and_then(|s| s.parse().ok()).unwrap_or(0);
+                    out.push((key, size));
+                }
+                let next = elements(&body, "NextContinuationToken").first().map(|t| unescape(t));
+                match next {
+                    Some(t) if elements(&body, "IsTruncated").first() == Some(&"true") => token = t,
+                    _ => return Ok(out),
+                }
+            }
+        })
+    }
+
+    // Signed request (AWS Signature Version 4) for `key`, or the bucket when empty
+    async fn request(&self, method: Method, key: &str, query: &[(&str, &str)], body: Vec<u8>) -> anyhow::Result<reqwest::Response> {
+        let base = if self.cfg.endpoint.trim().is_empty() {
+            format!("https://s3.{}.amazonaws.com", self.cfg.region)
+        } else {
+            self.cfg.endpoint.trim().trim_end_matches('/').to_string()
+        };
+        let base = reqwest::Url::parse(&base)?;
+        let host = base.host_str().ok_or_else(|| anyhow!("storage.s3.endpoint has no host"))?;
+        let (host, path) = if self.cfg.path_style {
+            (host.to_string(), format!("/{}/{}", uri_encode(&self.cfg.bucket, false), uri_encode(key, true)))
+        } else {
+            (format!("{}.{host}", self.cfg.bucket), format!("/{}", uri_encode(key, true)))
+        };
+        let host = match base.port() {
+            Some(p) => format!("{host}:{p}"),
+            None => host,
+        };
+        let mut pairs: Vec<(String, String)> = query.iter().map(|(k, v)| (uri_encode(k, false), uri_encode(v, false))).collect();
+        pairs.sort();
+        let query = pairs.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>().join("&");
+        let url = if query.is_empty() {
+            format!("{}://{host}{path}", base.scheme())
+        } else {
+            format!("{}://{host}{path}?{query}", base.scheme())
+        };
+
+        let payload_hash = hex(digest::digest(&digest::SHA256, &body).as_ref());
+        let (date, stamp) = amz_dates(now_unix());
+        // sorted by name, as the canonical request needs them
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", stamp.clone()),
+        ];
+        if !self.creds.session_token.is_empty() {
+            headers.push(("x-amz-security-token", self.creds.session_token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{k}:{}\n", v.trim())).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+        let canonical = format!("{method}\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}");
+        let scope = format!("{date}/{}/s3/aws4_request", self.cfg.region);
+        let to_sign = format!(
+            "AWS4-HMAC-SHA256\n{stamp}\n{scope}\n{}",
+            hex(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref())
+        );
+        let mut signing_key = hmac_sha256(format!("AWS4{}", self.creds.secret_access_key).as_bytes(), date.as_bytes());
+        for part in [self.cfg.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&signing_key, to_sign.as_bytes()));
+        let auth = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.creds.access_key_id
+        );
+
+        let mut req = self.client.request(method.clone(), &url).header("authorization", auth).body(body);
+        // reqwest sets Host from the URL
+        for (k, v) in headers.iter().filter(|(k, _)| *k != "host") {
+            req = req.header(*k, v);
+        }
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            let code = elements(&text, "Code").first().copied().unwrap_or_default().to_string();
+            bail!("s3 {method} /{key}: {status} {code}");
+        }
+        Ok(resp)
+    }
+}
+
+impl StorageBackend for S3Backend {
+    fn write_batch(&self, batch: Vec<FoundDomain>) -> anyhow::Result<()> {
+        let mut by_tld: HashMap<String, String> = HashMap::new();
+        for rec in batch {
+            if rec.tld.is_empty() {
+                continue;
+            }
+            let lines = by_tld.entry(rec.tld.clone()).or_default();
+            lines.push_str(&serde_json::to_string(&rec)?);
+            lines.push('\n');
+        }
+        let mut shards = self.shards.lock();
+        let mut failed = None;
+        for (tld, lines) in by_tld {
+            // roll over only once everything in the old shard is stored
+            let full = shards.get(&tld).is_some_and(|s| {
+                s.uploaded == s.body.len() && (s.body.len() >= self.cfg.shard_size || s.opened.elapsed() >= self.cfg.shard_interval)
+            });
+            if full {
+                shards.remove(&tld);
+            }
+            let shard = shards.entry(tld.clone()).or_insert_with(|| self.new_shard(&tld));
+            shard.body.extend_from_slice(lines.as_bytes());
+            let put = self.request(Method::PUT, &shard.key, &[], shard.body.clone());
+            match self.block_on(put) {
+                Ok(_) => {
+                    shard.uploaded = shard.body.len();
+                    self.sizes.lock().insert(shard.key.clone(), shard.body.len() as u64);
+                }
+                // still in the shard, uploaded again with the next batch
+                Err(e) => failed = Some(e),
+            }
+        }
+        failed.map_or(Ok(()), Err)
+    }
+
+    fn scan(&self, tld: Option<&str>, visit: &mut dyn FnMut(&str) -> bool) {
+        self.scan_detailed(tld, &mut |rec| visit(&rec.domain));
+    }
+
+    fn scan_detailed(&self, tld: Option<&str>, visit: &mut dyn FnMut(FoundDomain) -> bool) {
+        let keys = match self.shard_keys(tld) {
+            Ok(k) => k,
+            Err(e) => {
+                error!("storage: s3 list failed: {e:#}");
+                return;
+            }
+        };
+        for key in keys {
+            let body = match self.block_on(async { self.request(Method::GET, &key, &[], Vec::new()).await?.text().await.map_err(anyhow::Error::from) }) {
+                Ok(b) => b,
+                Err(e) => {
+                    error!("storage: s3 read of {} failed: {e:#}", key);
+                    continue;
+                }
+            };
+            for line in body.lines() {
+                let Ok(rec) = serde_json::from_str::<FoundDomain>(line) else { continue };
+                if !visit(rec) {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn list_range(&self, _from_unix: u64, _to_unix: u64, _tld: Option<&str>) -> anyhow::Result<Vec<FoundDomain>> {
+        anyhow::bail!("time range queries require storage.backend: sqlite")
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        self.sizes.lock().values().sum()
+    }
+
+    // other writers to the prefix would go unnoticed, so no caching
+    fn version(&self, _tld: Option<&str>) -> Option<Version> {
+        None
+    }
+
+    fn reset(&self) -> anyhow::Result<()> {
+        let mut shards = self.shards.lock();
+        for key in self.shard_keys(None)? {
+            self.block_on(self.request(Method::DELETE, &key, &[], Vec::new()))?;
+        }
+        shards.clear();
+        self.sizes.lock().clear();
+        Ok(())
+    }
+
+    // S3 cannot edit an object either: every shard holding a match is
+    // downloaded, filtered and uploaded again, or deleted once empty
+    fn delete(&self, tld: &str, matches: &dyn Fn(&str) -> bool) -> anyhow::Result<Vec<String>> {
+        let mut shards = self.shards.lock();
+        let mut removed = BTreeSet::new();
+        for key in self.shard_keys(Some(tld))? {
+            let open = shards.get_mut(tld).filter(|s| s.key == key);
+            let body = match &open {
+                Some(shard) => shard.body.clone(),
+                None => self.block_on(async { self.request(Method::GET, &key, &[], Vec::new()).await?.bytes().await.map_err(anyhow::Error::from) })?.to_vec(),
+            };
+            let mut kept = Vec::with_capacity(body.len());
+            let mut dropped = false;
+            for line in body.split_inclusive(|b| *b == b'\n') {
+                match serde_json::from_slice::<FoundDomain>(line) {
+                    Ok(rec) if matches(&rec.domain) => {
+                        removed.insert(rec.domain);
+                        dropped = true;
+                    }
+                    _ => kept.extend_from_slice(line),
+                }
+            }
+            if !dropped {
+                continue;
+            }
+            if kept.is_empty() {
+                self.block_on(self.request(Method::DELETE, &key, &[], Vec::new()))?;
+                self.sizes.lock().remove(&key);
+            } else {
+                self.block_on(self.request(Method::PUT, &key, &[], kept.clone()))?;
+                self.sizes.lock().insert(key.clone(), kept.len() as u64);
+            }
+            if let Some(shard) = open {
+                shard.uploaded = kept.len();
+                shard.body = kept;
+            }
+        }
+        Ok(removed.into_iter().collect())
+    }
+}
+
+// "scans/eu-1" -> "scans/eu-1/"; empty stays empty (bucket root)
+fn key_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim().trim_start_matches('/');
+    if prefix.is_empty() || prefix.ends_with('/') {
+        prefix.to_string()
+    } else {
+        format!("{prefix}/")
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// RFC 3986 unreserved characters stay, everything else is %XX (SigV4 rules)
+fn uri_encode(s: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+// ("YYYYMMDD", "YYYYMMDDTHHMMSSZ") in UTC
+fn amz_dates(unix: u64) -> (String, String) {
+    // days to civil date (Howard Hinnant's algorithm)
+    let z = (unix / 86400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let secs = unix % 86400;
+    let date = format!("{year:04}{month:02}{day:02}");
+    let stamp = format!("{date}T{:02}{:02}{:02}Z", secs / 3600, secs / 60 % 60, secs % 60);
+    (date, stamp)
+}
+
+// text of every <name>...</name> element, in document order
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+    let mut out = Vec::new();
+    let mut rest = xml;
+    while let Some(i) = rest.find(&open) {
+        let after = &rest[i + open.len()..];
+        let Some(j) = after.find(&close) else { break };
+        out.push(&after[..j]);
+        rest = &after[j + close.len()..];
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
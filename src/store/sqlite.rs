@@ -0,0 +1,274 @@
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OpenFlags};
+
+use super::{FoundDomain, StorageBackend, Version};
+
+const COLUMNS: &str = "domain, tld, ips, http_status, scheme, final_url, server, response_ms, found_at, title, unicode, record_types, tag, cert, scan_id, parked_by, http_families, country, asn, as_org, schemes, robots";
+
+// columns added after the first schema; older databases get them on open
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('domains')")?;
+    let have = stmt
+        .query_map([], |r| r.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (col, ddl) in [
+        ("final_url", "TEXT NOT NULL DEFAULT ''"),
+        ("server", "TEXT NOT NULL DEFAULT ''"),
+        ("response_ms", "INTEGER NOT NULL DEFAULT 0"),
+        ("title", "TEXT NOT NULL DEFAULT ''"),
+        ("unicode", "TEXT NOT NULL DEFAULT ''"),
+        ("record_types", "TEXT NOT NULL DEFAULT ''"),
+        ("tag", "TEXT NOT NULL DEFAULT ''"),
+        ("cert", "TEXT NOT NULL DEFAULT ''"),
+        ("scan_id", "INTEGER NOT NULL DEFAULT 0"),
+        ("parked_by", "TEXT NOT NULL DEFAULT ''"),
+        ("http_families", "TEXT NOT NULL DEFAULT ''"),
+        ("country", "TEXT NOT NULL DEFAULT ''"),
+        ("asn", "INTEGER NOT NULL DEFAULT 0"),
+        ("as_org", "TEXT NOT NULL DEFAULT ''"),
+        ("schemes", "TEXT NOT NULL DEFAULT ''"),
+        ("robots", "TEXT NOT NULL DEFAULT ''"),
+    ] {
+        if !have.iter().any(|c| c == col) {
+            conn.execute(&format!("ALTER TABLE domains ADD COLUMN {col} {ddl}"), [])?;
+        }
+    }
+    Ok(())
+}
+
+fn row_to_found(r: &rusqlite::Row) -> rusqlite::Result<FoundDomain> {
+    let ips: String = r.get(2)?;
+    let types: String = r.get(11)?;
+    // JSON of the certificate, empty without one
+    let cert: String = r.get(13)?;
+    let families: String = r.get(16)?;
+    let schemes: String = r.get(20)?;
+    Ok(FoundDomain {
+        domain: r.get(0)?,
+        tld: r.get(1)?,
+        ips: ips.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+        http_status: r.get(3)?,
+        scheme: r.get(4)?,
+        schemes: schemes.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+        final_url: r.get(5)?,
+        server: r.get(6)?,
+        response_ms: r.get::<_, i64>(7)?.max(0) as u64,
+        found_at_unix: r.get::<_, i64>(8)?.max(0) as u64,
+        title: r.get(9)?,
+        unicode: r.get(10)?,
+        record_types: types.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+        tag: r.get(12)?,
+        cert: serde_json::from_str(&cert).ok(),
+        scan_id: r.get::<_, i64>(14)?.max(0) as u64,
+        parked_by: r.get(15)?,
+        http_families: families.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+        country: r.get(17)?,
+        asn: r.get::<_, i64>(18)?.clamp(0, u32::MAX as i64) as u32,
+        as_org: r.get(19)?,
+        robots: r.get(21)?,
+    })
+}
+
+// SQLite backend: one row per domain with the metadata collected by the checks
+pub struct SqliteBackend {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteBackend {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             CREATE TABLE IF NOT EXISTS domains (
+                 domain      TEXT PRIMARY KEY,
+                 tld         TEXT NOT NULL,
+                 ips         TEXT NOT NULL DEFAULT '',
+                 http_status INTEGER NOT NULL DEFAULT 0,
+                 scheme      TEXT NOT NULL DEFAULT '',
+                 found_at    INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_domains_tld ON domains (tld);
+             CREATE INDEX IF NOT EXISTS idx_domains_found_at ON domains (found_at);",
+        )?;
+        migrate(&conn)?;
+        tracing::info!("sqlite storage opened: {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // Scans may feed a slow consumer (a streamed HTTP response), so they get
+    // their own read-only connection instead of holding the writer's lock; WAL
+    // lets them proceed alongside writes.
+    fn reader(&self) -> rusqlite::Result<Connection> {
+        Connection::open_with_flags(&self.path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+    }
+
+    // SQLite names the log after the whole file: foo.db -> foo.db-wal
+    fn wal_path(&self) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push("-wal");
+        PathBuf::from(name)
+    }
+}
+
+impl StorageBackend for SqliteBackend {
+    fn write_batch(&self, batch: Vec<FoundDomain>) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let txn = conn.transaction()?;
+        {
+            // keep the first discovery time, refresh the rest
+            let mut stmt = txn.prepare_cached(
+                "INSERT INTO domains (domain, tld, ips, http_status, scheme, final_url, server, response_ms, found_at, title, unicode, record_types, tag, cert, scan_id, parked_by, http_families, country, asn, as_org, schemes, robots)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+                 ON CONFLICT(domain) DO UPDATE SET
+                     ips = excluded.ips,
+                     http_status = excluded.http_status,
+                     scheme = excluded.scheme,
+                     final_url = excluded.final_url,
+                     server = excluded.server,
+                     response_ms = excluded.response_ms,
+                     title = excluded.title,
+                     record_types = excluded.record_types,
+                     tag = excluded.tag,
+                     cert = excluded.cert,
+                     scan_id = excluded.scan_id,
+                     parked_by = excluded.parked_by,
+                     http_families = excluded.http_families,
+                     country = excluded.country,
+                     asn = excluded.asn,
+                     as_org = excluded.as_org,
+                     schemes = excluded.schemes,
+                     robots = excluded.robots",
+            )?;
+            for rec in &batch {
+                stmt.execute(params![
+                    rec.domain,
+                    rec.tld,
+                    rec.ips.join(","),
+                    rec.http_status,
+                    rec.scheme,
+                    rec.final_url,
+                    rec.server,
+                    rec.response_ms as i64,
+                    rec.found_at_unix as i64,
+                    rec.title,
+                    rec.unicode,
+                    rec.record_types.join(","),
+                    rec.tag,
+                    rec.cert.as_ref().map(serde_json::to_string).transpose()?.unwrap_or_default(),
+                    rec.scan_id as i64,
+                    rec.parked_by,
+                    rec.http_families.join(","),
+                    rec.country,
+                    rec.asn as i64,
+                    rec.as_org,
+                    rec.schemes.join(","),
+                    rec.robots,
+                ])?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn scan(&self, tld: Option<&str>, visit: &mut dyn FnMut(&str) -> bool) {
+        let res = self.reader().and_then(|conn| {
+            let mut stmt = conn.prepare("SELECT domain FROM domains WHERE (?1 IS NULL OR tld = ?1) ORDER BY rowid")?;
+            let mut rows = stmt.query(params![tld])?;
+            while let Some(row) = rows.next()? {
+                let d: String = row.get(0)?;
+                if !visit(&d) {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = res {
+            tracing::error!("sqlite scan failed: {}", e);
+        }
+    }
+
+    fn scan_detailed(&self, tld: Option<&str>, visit: &mut dyn FnMut(FoundDomain) -> bool) {
+        let sql = format!("SELECT {COLUMNS} FROM domains WHERE (?1 IS NULL OR tld = ?1) ORDER BY rowid");
+        let res = self.reader().and_then(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query(params![tld])?;
+            while let Some(row) = rows.next()? {
+                if !visit(row_to_found(row)?) {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = res {
+            tracing::error!("sqlite scan failed: {}", e);
+        }
+    }
+
+    fn list_range(&self, from_unix: u64, to_unix: u64, tld: Option<&str>) -> anyhow::Result<Vec<FoundDomain>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {COLUMNS} FROM domains
+             WHERE found_at >= ?1 AND found_at <= ?2 AND (?3 IS NULL OR tld = ?3)
+             ORDER BY found_at, rowid"
+        ))?;
+        let rows = stmt.query_map(
+            params![from_unix as i64, to_unix.min(i64::MAX as u64) as i64, tld],
+            row_to_found,
+        )?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        let wal = self.wal_path();
+        [self.path.as_path(), wal.as_path()]
+            .iter()
+            .filter_map(|p| std::fs::metadata(p).ok())
+            .map(|md| md.len())
+            .sum()
+    }
+
+    // the whole database, whichever TLD is asked for
+    fn version(&self, _tld: Option<&str>) -> Option<Version> {
+        let wal = self.wal_path();
+        Some(Version::of_files([self.path.as_path(), wal.as_path()]))
+    }
+
+    fn reset(&self) -> anyhow::Result<()> {
+        let conn = self.conn.lock();
+        conn.execute("DELETE FROM domains", [])?;
+        Ok(())
+    }
+
+    fn delete(&self, tld: &str, matches: &dyn Fn(&str) -> bool) -> anyhow::Result<Vec<String>> {
+        let mut conn = self.conn.lock();
+        let txn = conn.transaction()?;
+        let mut removed = Vec::new();
+        {
+            let mut stmt = txn.prepare_cached("SELECT domain FROM domains WHERE tld = ?1")?;
+            let names = stmt.query_map(params![tld], |r| r.get::<_, String>(0))?;
+            for name in names {
+                let name = name?;
+                if matches(&name) {
+                    removed.push(name);
+                }
+            }
+            let mut del = txn.prepare_cached("DELETE FROM domains WHERE domain = ?1")?;
+            for name in &removed {
+                del.execute(params![name])?;
+            }
+        }
+        txn.commit()?;
+        removed.sort();
+        Ok(removed)
+    }
+}
@@ -0,0 +1,86 @@
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use axum::body::{Body, Bytes};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+const CHUNK_BYTES: usize = 64 * 1024;
+
+// Collects output of a blocking producer into chunks for a streamed body.
+// Every write returns false once the client has gone away.
+pub struct ChunkWriter {
+    tx: mpsc::Sender<Result<Bytes, Infallible>>,
+    buf: Vec<u8>,
+}
+
+impl ChunkWriter {
+    pub fn write(&mut self, data: &[u8]) -> bool {
+        self.buf.extend_from_slice(data);
+        self.buf.len() < CHUNK_BYTES || self.flush()
+    }
+
+    pub fn write_json<T: Serialize>(&mut self, value: &T) -> bool {
+        // serializing plain records into a Vec cannot fail
+        let _ = serde_json::to_writer(&mut self.buf, value);
+        self.buf.len() < CHUNK_BYTES || self.flush()
+    }
+
+    fn flush(&mut self) -> bool {
+        if self.buf.is_empty() {
+            return !self.tx.is_closed();
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_BYTES)));
+        self.tx.blocking_send(Ok(chunk)).is_ok()
+    }
+}
+
+/// Response body fed by `produce` on the blocking pool; sent chunked, with at
+/// most a few chunks buffered ahead of the client.
+pub fn blocking_body(produce: impl FnOnce(&mut ChunkWriter) + Send + 'static) -> Body {
+    let (tx, rx) = mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let mut w = ChunkWriter {
+            tx,
+            buf: Vec::with_capacity(CHUNK_BYTES),
+        };
+        produce(&mut w);
+        w.flush();
+    });
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+/// Bytes `start..=end` of the files laid end to end, each read up to the
+/// length given with it. A file that shrank or went away ends the body early.
+pub fn file_body(files: Vec<(PathBuf, u64)>, start: u64, end: u64) -> Body {
+    blocking_body(move |w| {
+        let mut at = 0;
+        for (path, len) in files {
+            let (from, to) = (start.max(at), (end + 1).min(at + len));
+            let file_start = at;
+            at += len;
+            if from >= to {
+                continue;
+            }
+            let Ok(mut f) = File::open(&path) else { return };
+            if f.seek(SeekFrom::Start(from - file_start)).is_err() {
+                return;
+            }
+            let mut left = to - from;
+            let mut buf = vec![0; CHUNK_BYTES];
+            while left > 0 {
+                let n = match f.read(&mut buf[..left.min(CHUNK_BYTES as u64) as usize]) {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => n,
+                };
+                if !w.write(&buf[..n]) {
+                    return;
+                }
+                left -= n as u64;
+            }
+        }
+    })
+}
@@ -0,0 +1,157 @@
+use std::net::TcpListener;
+use std::time::Duration;
+
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::health::Health;
+use crate::service::ScanControl;
+use crate::store::DomainStore;
+
+// systemd passes activated sockets from this descriptor on (SD_LISTEN_FDS_START)
+const LISTEN_FDS_START: i32 = 3;
+
+/// sd_notify(3) messages to the service manager, for `--systemd`; a no-op
+/// when NOTIFY_SOCKET is not set (not started by systemd, or Type=simple).
+#[derive(Clone)]
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: Option<std::sync::Arc<(std::os::unix::net::UnixDatagram, std::os::unix::net::SocketAddr)>>,
+}
+
+impl Notifier {
+    pub fn from_env() -> Self {
+        #[cfg(unix)]
+        {
+            let socket = std::env::var("NOTIFY_SOCKET").ok().and_then(|path| match connect(&path) {
+                Ok(s) => Some(std::sync::Arc::new(s)),
+                Err(e) => {
+                    warn!("systemd: cannot use NOTIFY_SOCKET {path}: {e}");
+                    None
+                }
+            });
+            Self { socket }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    /// Send newline-separated `KEY=VALUE` assignments.
+    pub fn notify(&self, state: &str) {
+        #[cfg(unix)]
+        if let Some(s) = &self.socket {
+            if let Err(e) = s.0.send_to_addr(state.as_bytes(), &s.1) {
+                warn!("systemd: notify failed: {e}");
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = state;
+    }
+
+    pub fn ready(&self, status: &str) {
+        self.notify(&format!("READY=1\nSTATUS={status}"));
+    }
+
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1\nSTATUS=draining");
+    }
+
+    /// Ping the watchdog at half of WatchdogSec while /healthz would pass, so
+    /// a store writer stuck for minutes gets the service restarted.
+    pub fn spawn_watchdog(&self, health: Health, store: DomainStore, control: ScanControl) {
+        let Some(interval) = watchdog_interval() else {
+            return;
+        };
+        info!("systemd: watchdog every {:?}", interval);
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let report = health.liveness(&store, &control);
+                if report.is_ok() {
+                    this.notify(&format!("WATCHDOG=1\nSTATUS=scan {}", report.scan_state));
+                } else {
+                    warn!("systemd: skipping watchdog ping: {}", report.problems.join("; "));
+                }
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+fn connect(path: &str) -> std::io::Result<(std::os::unix::net::UnixDatagram, std::os::unix::net::SocketAddr)> {
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+    // "@name" is a socket in the abstract namespace
+    let addr = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name)?
+        }
+        _ => SocketAddr::from_pathname(path)?,
+    };
+    Ok((UnixDatagram::unbound()?, addr))
+}
+
+// WATCHDOG_USEC / 2, when the watchdog is on for this process
+fn watchdog_interval() -> Option<Duration> {
+    if !for_this_process("WATCHDOG_PID") {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+// `var` names this process, or is unset
+fn for_this_process(var: &str) -> bool {
+    std::env::var(var).map_or(true, |pid| pid.parse() == Ok(std::process::id()))
+}
+
+/// The socket passed for the HTTP API by socket activation: the one named
+/// "http" in LISTEN_FDNAMES (FileDescriptorName=), else the first. None when
+/// the process was not socket-activated.
+pub fn take_listener() -> anyhow::Result<Option<TcpListener>> {
+    if std::env::var("LISTEN_PID").map_or(true, |pid| pid.parse() != Ok(std::process::id())) {
+        return Ok(None);
+    }
+    let count: i32 = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse().ok()).unwrap_or(0);
+    // left set: LISTEN_PID keeps them from applying to any other process, and
+    // clearing the environment is unsound once the runtime threads run
+    let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+    if count < 1 {
+        return Ok(None);
+    }
+    let at = names.split(':').position(|n| n == "http").filter(|i| (*i as i32) < count).unwrap_or(0);
+    listener_at(LISTEN_FDS_START + at as i32).map(Some)
+}
+
+#[cfg(unix)]
+fn listener_at(fd: i32) -> anyhow::Result<TcpListener> {
+    use std::os::fd::FromRawFd;
+    // a datagram socket (ListenDatagram=) or a FIFO fails here instead of at the first accept
+    let mut ty: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: ty and len are valid for writes of the sizes given
+    let rc = unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_TYPE, (&mut ty as *mut libc::c_int).cast(), &mut len) };
+    if rc != 0 {
+        let e = std::io::Error::last_os_error();
+        anyhow::bail!("socket activation: descriptor {fd} is not a socket: {e}");
+    }
+    if ty != libc::SOCK_STREAM {
+        anyhow::bail!("socket activation: descriptor {fd} is not a stream socket");
+    }
+    // SAFETY: systemd hands this descriptor to the process and nothing else owns it
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+    // a unix stream socket fails here
+    listener
+        .local_addr()
+        .map_err(|e| anyhow::anyhow!("socket activation: descriptor {fd} is not a TCP listener: {e}"))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+#[cfg(not(unix))]
+fn listener_at(_fd: i32) -> anyhow::Result<TcpListener> {
+    anyhow::bail!("socket activation is only supported on unix")
+}
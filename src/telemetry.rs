@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig, WithHttpConfig, WithTonicConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::field::Empty;
+use tracing::{info_span, warn, Instrument, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::{filter_fn, LevelFilter, Targets};
+use tracing_subscriber::layer::Identity;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::config::{TelemetryConfig, TelemetryProtocol};
+
+/// Span export to an OTLP collector (`telemetry`). Spans of the checks, the
+/// store writer and the API are only recorded while it is on.
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    pub fn new(cfg: &TelemetryConfig) -> anyhow::Result<Option<Self>> {
+        if !cfg.enabled {
+            return Ok(None);
+        }
+        let exporter = match cfg.protocol {
+            TelemetryProtocol::Grpc => {
+                let mut metadata = tonic::metadata::MetadataMap::new();
+                for (k, v) in &cfg.headers {
+                    let key = tonic::metadata::MetadataKey::from_bytes(k.to_ascii_lowercase().as_bytes())
+                        .with_context(|| format!("telemetry.headers: {k:?}"))?;
+                    metadata.insert(key, v.parse().with_context(|| format!("telemetry.headers.{k}"))?);
+                }
+                let mut builder = SpanExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(cfg.endpoint(TelemetryProtocol::Grpc))
+                    .with_timeout(cfg.timeout)
+                    .with_metadata(metadata);
+                if cfg.endpoint(TelemetryProtocol::Grpc).starts_with("https://") {
+                    builder = builder.with_tls_config(tonic::transport::ClientTlsConfig::new().with_native_roots());
+                }
+                builder.build()
+            }
+            TelemetryProtocol::Http => SpanExporter::builder()
+                .with_http()
+                .with_endpoint(cfg.endpoint(TelemetryProtocol::Http))
+                .with_timeout(cfg.timeout)
+                .with_headers(cfg.headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect::<HashMap<_, _>>())
+                .build(),
+        }
+        .context("telemetry exporter")?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(cfg.sample_ratio))))
+            .with_resource(Resource::new([KeyValue::new("service.name", cfg.service_name.clone())]))
+            .build();
+        Ok(Some(Self { provider }))
+    }
+
+    /// Export what is still buffered; called on shutdown.
+    pub fn shutdown(self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("telemetry: flushing spans failed: {e}");
+        }
+    }
+}
+
+/// The tracing layer that turns this crate's spans into OpenTelemetry spans;
+/// dependencies are left out so the exporter does not trace itself. Without
+/// telemetry it rejects every span, so creating one costs next to nothing.
+pub fn layer<S>(telemetry: Option<&Telemetry>) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    match telemetry {
+        Some(t) => tracing_opentelemetry::layer()
+            .with_tracer(t.provider.tracer(env!("CARGO_PKG_NAME")))
+            .with_filter(Targets::new().with_target(env!("CARGO_PKG_NAME"), LevelFilter::INFO))
+            .boxed(),
+        None => Identity::new().with_filter(filter_fn(|_| false)).boxed(),
+    }
+}
+
+/// API middleware: one server span per request, a child of the caller's
+/// trace when it sends a W3C `traceparent` header.
+pub async fn trace_request(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path().to_string(), |p| p.as_str().to_string());
+    let span = info_span!(
+        "http.request",
+        otel.name = %format!("{} {route}", req.method()),
+        otel.kind = "server",
+        http.request.method = %req.method(),
+        http.route = %route,
+        http.response.status_code = Empty,
+    );
+    if !span.is_disabled() {
+        let parent = TraceContextPropagator::new().extract(&HeaderExtractor(req.headers()));
+        span.set_parent(parent);
+    }
+    let resp = next.run(req).instrument(span.clone()).await;
+    span.record("http.response.status_code", resp.status().as_u16());
+    resp
+}
+
+struct HeaderExtractor<'a>(&'a axum::http::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::config::{is_url, load_tlds_from_url, LiveConfig};
+
+// how often a disabled refresh looks again whether a reload turned it on
+const IDLE_RECHECK: Duration = Duration::from_secs(60);
+// names listed in the log for each side of a diff
+const LOGGED_NAMES: usize = 20;
+
+/// Re-fetch `generator.tlds_file` every `generator.tlds_refresh` when it is a
+/// URL and publish a changed list as a live config update; the generator
+/// picks it up at its next label. A failed or empty fetch keeps the current
+/// list, and a config reload fetches the file again anyway.
+pub fn spawn_refresh(live: LiveConfig) {
+    tokio::spawn(async move {
+        loop {
+            let cur = live.current();
+            let (src, every) = (cur.generator.tlds_file.trim().to_string(), cur.generator.tlds_refresh);
+            if !is_url(&src) || every.is_zero() {
+                time::sleep(IDLE_RECHECK).await;
+                continue;
+            }
+            time::sleep(every).await;
+            let fetched = match load_tlds_from_url(&src).await {
+                Ok(t) if t.is_empty() => {
+                    warn!("tlds refresh: no TLDs parsed from {}, keeping the current list", src);
+                    continue;
+                }
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("tlds refresh: fetching {} failed, keeping the current list: {e:#}", src);
+                    continue;
+                }
+            };
+            let cur = live.current();
+            let old: BTreeSet<&str> = cur.generator.tlds.iter().map(String::as_str).collect();
+            let new: BTreeSet<&str> = fetched.iter().map(String::as_str).collect();
+            let added: Vec<&str> = new.difference(&old).copied().collect();
+            let removed: Vec<&str> = old.difference(&new).copied().collect();
+            if added.is_empty() && removed.is_empty() {
+                info!("tlds refresh: {} unchanged, {} TLDs", src, new.len());
+                continue;
+            }
+            info!(
+                "tlds refresh: {} TLDs from {}, {} added{}, {} removed{}",
+                new.len(),
+                src,
+                added.len(),
+                names(&added),
+                removed.len(),
+                names(&removed)
+            );
+            // a reload while fetching may have pointed tlds_file elsewhere
+            let res = live.update(|c| {
+                if c.generator.tlds_file.trim() == src {
+                    c.generator.tlds = fetched;
+                }
+            });
+            if let Err(e) = res {
+                warn!("tlds refresh: keeping the current list: {e:#}");
+            }
+        }
+    });
+}
+
+fn names(list: &[&str]) -> String {
+    match list.len() {
+        0 => String::new(),
+        n if n <= LOGGED_NAMES => format!(" ({})", list.join(" ")),
+        n => format!(" ({} and {} more)", list[..LOGGED_NAMES].join(" "), n - LOGGED_NAMES),
+    }
+}
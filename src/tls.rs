@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::{info, warn};
+
+// PEM certificate chain and private key for the API listener
+#[derive(Clone, Debug)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    pub async fn load(&self) -> anyhow::Result<RustlsConfig> {
+        RustlsConfig::from_pem_file(&self.cert, &self.key)
+            .await
+            .with_context(|| format!("load TLS cert {} / key {}", self.cert.display(), self.key.display()))
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let m = |p: &Path| std::fs::metadata(p).and_then(|md| md.modified()).ok();
+        Some((m(&self.cert)?, m(&self.key)?))
+    }
+
+    /// Reload the certificate into the running listener whenever either file
+    /// changes (e.g. a certbot renewal); a broken pair keeps the old one.
+    pub fn watch(self, config: RustlsConfig, every: Duration) {
+        tokio::spawn(async move {
+            let mut seen = self.modified();
+            loop {
+                tokio::time::sleep(every).await;
+                let now = self.modified();
+                if now.is_none() || now == seen {
+                    continue;
+                }
+                // cert and key are often replaced one after the other
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                match config.reload_from_pem_file(&self.cert, &self.key).await {
+                    Ok(()) => info!("tls: reloaded certificate from {}", self.cert.display()),
+                    Err(e) => warn!("tls: reload failed, keeping the current certificate: {e}"),
+                }
+                // a failed pair is retried once the files change again
+                seen = self.modified();
+            }
+        });
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::progress::Progress;
+use crate::service::{ScanControl, ScanState};
+use crate::store::FoundDomain;
+
+// redraw and key poll interval
+const TICK: Duration = Duration::from_millis(250);
+// checked-per-second samples behind the speed sparkline
+const SPEED_SAMPLES: usize = 120;
+const MAX_FINDS: usize = 500;
+const MAX_LOG_LINES: usize = 1000;
+
+/// Log output for `--tui`: written through to stderr until the dashboard
+/// takes the terminal, kept for its log pane while it runs.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capture: Arc<AtomicBool>,
+}
+
+impl LogBuffer {
+    fn set_capture(&self, on: bool) {
+        self.capture.store(on, Ordering::Relaxed);
+    }
+
+    fn tail(&self, n: usize) -> Vec<String> {
+        let g = self.lines.lock();
+        g.iter().skip(g.len().saturating_sub(n)).cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.capture.load(Ordering::Relaxed) {
+            return io::stderr().write(buf);
+        }
+        let mut g = self.lines.lock();
+        for line in String::from_utf8_lossy(buf).lines().filter(|l| !l.is_empty()) {
+            if g.len() >= MAX_LOG_LINES {
+                g.pop_front();
+            }
+            g.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// The running terminal dashboard; it owns the terminal until closed.
+pub struct Dashboard {
+    task: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    logs: LogBuffer,
+}
+
+impl Dashboard {
+    pub fn spawn(prog: Progress, control: ScanControl, finds: broadcast::Receiver<FoundDomain>, logs: LogBuffer) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        logs.set_capture(true);
+        // counters may start from a resumed state
+        let checked = prog.snapshot().1;
+        let mut view = View {
+            prog,
+            control,
+            finds,
+            logs: logs.clone(),
+            recent: VecDeque::new(),
+            selected: ListState::default(),
+            speed: VecDeque::new(),
+            last_sample: (Instant::now(), checked),
+        };
+        let stop_flag = stop.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let mut terminal = ratatui::init();
+            let res = view.run(&mut terminal, &stop_flag);
+            ratatui::restore();
+            if let Err(e) = res {
+                eprintln!("tui: {e}");
+            }
+        });
+        Self { task: Some(task), stop, logs }
+    }
+
+    /// Resolves once the user quit the dashboard (q, Esc or Ctrl-C).
+    pub async fn quit(&mut self) {
+        match &mut self.task {
+            Some(t) => {
+                let _ = t.await;
+                self.task = None;
+                self.logs.set_capture(false);
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Give the terminal back; logs go to stderr again.
+    pub async fn close(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(t) = self.task.take() {
+            let _ = t.await;
+        }
+        self.logs.set_capture(false);
+    }
+}
+
+struct View {
+    prog: Progress,
+    control: ScanControl,
+    finds: broadcast::Receiver<FoundDomain>,
+    logs: LogBuffer,
+    // newest first
+    recent: VecDeque<FoundDomain>,
+    selected: ListState,
+    // checked per second, oldest first
+    speed: VecDeque<u64>,
+    last_sample: (Instant, i64),
+}
+
+impl View {
+    fn run(&mut self, terminal: &mut DefaultTerminal, stop: &AtomicBool) -> io::Result<()> {
+        while !stop.load(Ordering::Relaxed) {
+            self.collect();
+            terminal.draw(|f| self.draw(f))?;
+            if !event::poll(TICK)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                // raw mode swallows SIGINT
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Char('p') | KeyCode::Char(' ') => {
+                    match self.control.state() {
+                        ScanState::Running => self.control.pause(),
+                        _ => self.control.resume(),
+                    };
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.selected.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.selected.select_previous(),
+                KeyCode::Home | KeyCode::Char('g') => self.selected.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.selected.select_last(),
+                KeyCode::Char('c') => {
+                    self.recent.clear();
+                    self.selected.select(None);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // new finds, and one speed sample per elapsed second
+    fn collect(&mut self) {
+        loop {
+            match self.finds.try_recv() {
+                Ok(f) => {
+                    if self.recent.len() >= MAX_FINDS {
+                        self.recent.pop_back();
+                    }
+                    self.recent.push_front(f);
+                    // keep the selected find in place as new ones arrive on top
+                    if let Some(i) = self.selected.selected() {
+                        self.selected.select(Some((i + 1).min(MAX_FINDS - 1)));
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+        let (at, before) = self.last_sample;
+        let secs = at.elapsed().as_secs();
+        if secs >= 1 {
+            let (_, checked, _, _) = self.prog.snapshot();
+            let per_sec = (checked - before).max(0) as u64 / secs;
+            for _ in 0..secs.min(SPEED_SAMPLES as u64) {
+                if self.speed.len() >= SPEED_SAMPLES {
+                    self.speed.pop_front();
+                }
+                self.speed.push_back(per_sec);
+            }
+            self.last_sample = (Instant::now(), checked);
+        }
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let [header, gauge, counters, speed, middle, logs, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Length(5),
+            Constraint::Min(6),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(f.area());
+        let (enq, chk, fnd, elapsed) = self.prog.snapshot();
+        let est = self.prog.estimate();
+        let state = self.control.state();
+        let state_style = match state {
+            ScanState::Running => Style::new().fg(Color::Green),
+            ScanState::Paused => Style::new().fg(Color::Yellow),
+            ScanState::Stopped => Style::new().fg(Color::Red),
+        };
+        let current = self.speed.back().copied().unwrap_or(0);
+        f.render_widget(
+            Line::from(vec![
+                Span::styled(format!(" {} ", state.as_str().to_uppercase()), state_style.add_modifier(Modifier::REVERSED)),
+                Span::raw(format!(
+                    "  elapsed {}  speed {}/s (avg {:.1}/s)  eta {}",
+                    crate::fmt_duration(elapsed),
+                    current,
+                    est.speed_per_sec,
+                    if est.remaining >= 0 { crate::fmt_duration(est.eta) } else { "-".to_string() },
+                )),
+            ]),
+            header,
+        );
+
+        let planned = self.prog.total_planned();
+        let label = if planned > 0 {
+            format!("{chk} / {planned} checked ({:.1}%)", est.percent)
+        } else {
+            format!("{chk} checked (no limits.max_candidates)")
+        };
+        f.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(" progress "))
+                .gauge_style(Style::new().fg(Color::Cyan))
+                .ratio((est.percent / 100.0).clamp(0.0, 1.0))
+                .label(label),
+            gauge,
+        );
+
+        let (avail, regd) = self.prog.availability();
+        let (retry_pending, retried, failed) = self.prog.retries();
+        let (queued, _) = self.prog.queue();
+        let counters_text = vec![
+            Line::from(format!(
+                "generated {enq}  checked {chk}  found {fnd} ({:.2}%)  available {avail}  registered {regd}",
+                est.efficiency_percent
+            )),
+            Line::from(format!(
+                "queued {queued}  duplicates {}  wildcard {}  retry pending {retry_pending}  retried {retried}  failed {failed}",
+                self.prog.duplicates(),
+                self.prog.wildcard()
+            )),
+        ];
+        f.render_widget(Paragraph::new(counters_text).block(Block::bordered().title(" counters ")), counters);
+
+        let samples: Vec<u64> = self.speed.iter().copied().collect();
+        let width = speed.width.saturating_sub(2) as usize;
+        f.render_widget(
+            Sparkline::default()
+                .block(Block::bordered().title(format!(" checked/s, last {}s ", samples.len().min(width))))
+                .data(&samples[samples.len().saturating_sub(width)..])
+                .style(Style::new().fg(Color::Green)),
+            speed,
+        );
+
+        let [finds, errors] = Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(middle);
+        self.draw_finds(f, finds);
+        let mut errs: Vec<(&str, i64)> = self.prog.errors().into_iter().filter(|(_, n)| *n > 0).collect();
+        errs.sort_by_key(|e| std::cmp::Reverse(e.1));
+        let err_lines: Vec<Line> = if errs.is_empty() {
+            vec![Line::from("none").dim()]
+        } else {
+            errs.into_iter().map(|(k, n)| Line::from(format!("{n:>10}  {k}"))).collect()
+        };
+        f.render_widget(Paragraph::new(err_lines).block(Block::bordered().title(" errors ")), errors);
+
+        let lines: Vec<Line> = self
+            .logs
+            .tail(logs.height.saturating_sub(2) as usize)
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        f.render_widget(Paragraph::new(lines).block(Block::new().borders(Borders::TOP).title(" log ")), logs);
+
+        f.render_widget(
+            Line::from(" q quit  p pause/resume  ↑/↓ select find  g/G first/last  c clear finds").dim(),
+            help,
+        );
+    }
+
+    fn draw_finds(&mut self, f: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .recent
+            .iter()
+            .map(|d| {
+                let name = if d.unicode.is_empty() { &d.domain } else { &d.unicode };
+                let mut spans = vec![Span::styled(format!("{name:<32}"), Style::new().bold())];
+                if d.http_status > 0 {
+                    spans.push(Span::raw(format!(" {} {:>5}ms", d.http_status, d.response_ms)));
+                }
+                if !d.title.is_empty() {
+                    spans.push(Span::raw(format!("  {}", d.title)).dim());
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" recent finds ({}) ", self.recent.len())))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, area, &mut self.selected);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::config::LiveConfig;
+use crate::dns::PositiveCache;
+use crate::service::{build_probe, RateLimiter};
+use crate::store::DomainStore;
+
+// how often a sleeping verifier looks at the config again
+const RECHECK_CONFIG: Duration = Duration::from_secs(60);
+
+/// A found domain that stopped passing the checks.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StaleDomain {
+    pub domain: String,
+    /// Last time it was known to pass: when it was found or last re-checked
+    pub last_seen_unix: u64,
+    /// Start of the round that first saw it fail
+    pub stale_since_unix: u64,
+}
+
+/// GET /verify/
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct VerifyStatus {
+    /// A round is in progress
+    pub running: bool,
+    /// Start of the last finished round; 0 before the first one
+    pub last_round_unix: u64,
+    pub next_round_unix: u64,
+    /// Domains re-checked in the current or last round
+    pub checked: u64,
+    /// Still passing
+    pub alive: u64,
+    /// Failed transiently (timeout, SERVFAIL); left as they were
+    pub unknown: u64,
+    /// Newly stale
+    pub went_stale: u64,
+    /// Stale before, passing again
+    pub revived: u64,
+    /// Stale domains now
+    pub stale: usize,
+}
+
+// what survives a restart, in <storage.dir>/verify.json
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    last_round_unix: u64,
+    stale: BTreeMap<String, StaleDomain>,
+}
+
+enum Recheck {
+    Alive,
+    Unknown,
+    Failed,
+}
+
+/// Re-checks the found domains every `verify.interval` and keeps the list of
+/// ones that stopped responding; the domains stay in the store.
+#[derive(Clone)]
+pub struct Verifier {
+    config: LiveConfig,
+    store: DomainStore,
+    client: Client,
+    limiter: Arc<RateLimiter>,
+    // dns.positive_cache of the scans
+    dns_cache: Option<Arc<PositiveCache>>,
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+    status: Arc<Mutex<VerifyStatus>>,
+}
+
+impl Verifier {
+    pub fn new(config: LiveConfig, store: DomainStore, client: Client, limiter: Arc<RateLimiter>, dns_cache: Option<Arc<PositiveCache>>) -> Self {
+        let cfg = config.current();
+        let path = Self::state_path(&cfg.storage.dir);
+        let state: State = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("verify: ignoring unreadable {}: {e}", path.display());
+                State::default()
+            }),
+            Err(_) => State::default(),
+        };
+        let status = VerifyStatus {
+            last_round_unix: state.last_round_unix,
+            next_round_unix: state.last_round_unix + cfg.verify.interval.as_secs(),
+            stale: state.stale.len(),
+            ..Default::default()
+        };
+        Self {
+            config,
+            store,
+            client,
+            limiter,
+            dns_cache,
+            path,
+            state: Arc::new(Mutex::new(state)),
+            status: Arc::new(Mutex::new(status)),
+        }
+    }
+
+    /// Where the state is kept, for --reset.
+    pub fn state_path(storage_dir: &str) -> PathBuf {
+        PathBuf::from(storage_dir).join("verify.json")
+    }
+
+    pub fn status(&self) -> VerifyStatus {
+        self.status.lock().clone()
+    }
+
+    /// Stale domains, by name.
+    pub fn stale(&self) -> Vec<StaleDomain> {
+        self.state.lock().stale.values().cloned().collect()
+    }
+
+    /// Run rounds until the process exits; the first one is due an interval
+    /// after the last round of a previous run, or right away.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            loop {
+                let cfg = self.config.current();
+                let due = self.state.lock().last_round_unix + cfg.verify.interval.as_secs();
+                self.status.lock().next_round_unix = due;
+                let now = now_unix();
+                if now < due {
+                    tokio::time::sleep(Duration::from_secs(due - now).min(RECHECK_CONFIG)).await;
+                    continue;
+                }
+                if let Err(e) = self.round().await {
+                    error!("verify: round failed: {e:#}");
+                    tokio::time::sleep(RECHECK_CONFIG).await;
+                }
+            }
+        });
+    }
+
+    async fn round(&self) -> anyhow::Result<()> {
+        let cfg = self.config.current();
+        let probe = Arc::new(build_probe(&cfg, &self.client, false, None, self.dns_cache.clone()).await?);
+        let started = now_unix();
+        let previous = self.state.lock().last_round_unix;
+        let store = self.store.clone();
+        let domains = tokio::task::spawn_blocking(move || {
+            let mut v = Vec::new();
+            store.scan_detailed(None, &mut |rec| {
+                v.push((rec.domain, rec.found_at_unix));
+                true
+            });
+            v
+        })
+        .await?;
+        info!("verify: re-checking {} found domains", domains.len());
+        *self.status.lock() = VerifyStatus {
+            running: true,
+            last_round_unix: previous,
+            next_round_unix: started + cfg.verify.interval.as_secs(),
+            stale: self.state.lock().stale.len(),
+            ..Default::default()
+        };
+
+        let hc = Arc::new(cfg.http_check.clone());
+        let slots = Arc::new(Semaphore::new(cfg.verify.concurrency.max(1)));
+        let mut running = JoinSet::new();
+        for (domain, found_at) in domains {
+            let permit = slots.clone().acquire_owned().await?;
+            let (probe, limiter, hc) = (probe.clone(), self.limiter.clone(), hc.clone());
+            running.spawn(async move {
+                let _permit = permit;
+                limiter.acquire().await;
+                let v = probe.evaluate(&domain, &hc).await;
+                let outcome = if v.found {
+                    Recheck::Alive
+                } else if v.transient {
+                    Recheck::Unknown
+                } else {
+                    Recheck::Failed
+                };
+                (domain, found_at, outcome)
+            });
+            while let Some(done) = running.try_join_next() {
+                self.record(done?, started, previous);
+            }
+        }
+        while let Some(done) = running.join_next().await {
+            self.record(done?, started, previous);
+        }
+
+        let stale = {
+            let mut st = self.state.lock();
+            st.last_round_unix = started;
+            self.save(&st)?;
+            st.stale.len()
+        };
+        let mut status = self.status.lock();
+        status.running = false;
+        status.last_round_unix = started;
+        status.stale = stale;
+        info!(
+            "verify: round done: {} checked, {} alive, {} unknown, {} went stale, {} revived, {} stale",
+            status.checked, status.alive, status.unknown, status.went_stale, status.revived, stale
+        );
+        Ok(())
+    }
+
+    // `previous` is the start of the last round, which the domain passed unless it was stale
+    fn record(&self, (domain, found_at, outcome): (String, u64, Recheck), started: u64, previous: u64) {
+        let mut st = self.state.lock();
+        let mut status = self.status.lock();
+        status.checked += 1;
+        match outcome {
+            Recheck::Alive => {
+                status.alive += 1;
+                if st.stale.remove(&domain).is_some() {
+                    info!("verify: {} responds again", domain);
+                    status.revived += 1;
+                }
+            }
+            Recheck::Unknown => status.unknown += 1,
+            Recheck::Failed if !st.stale.contains_key(&domain) => {
+                status.went_stale += 1;
+                st.stale.insert(
+                    domain.clone(),
+                    StaleDomain {
+                        last_seen_unix: found_at.max(previous),
+                        stale_since_unix: started,
+                        domain,
+                    },
+                );
+            }
+            Recheck::Failed => {}
+        }
+        status.stale = st.stale.len();
+    }
+
+    fn save(&self, st: &State) -> anyhow::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(st)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+use crate::config::WildcardConfig;
+use crate::dns::ResolverPool;
+
+// Wildcard DNS detection: a zone that answers for random nonsense labels answers
+// for every candidate, so names resolving only to those addresses are not real.
+pub struct WildcardFilter {
+    probes: usize,
+    // parent zone ("com", "co.uk") -> addresses its wildcard answers with (empty: none)
+    zones: Mutex<HashMap<String, Arc<OnceCell<HashSet<IpAddr>>>>>,
+    seed: RandomState,
+}
+
+impl WildcardFilter {
+    pub fn from_config(cfg: &WildcardConfig) -> Option<Self> {
+        cfg.enabled.then(|| Self {
+            probes: cfg.probes.max(1),
+            zones: Mutex::new(HashMap::new()),
+            seed: RandomState::new(),
+        })
+    }
+
+    /// True when every address in `ips` is one the parent zone of `domain`
+    /// returns for random labels. The zone is probed on first use.
+    pub async fn is_wildcard_answer(&self, domain: &str, ips: &[IpAddr], resolver: &ResolverPool) -> bool {
+        let Some((_, zone)) = domain.split_once('.') else {
+            return false;
+        };
+        let cell = self.zones.lock().entry(zone.to_string()).or_default().clone();
+        let wild = match cell.get_or_try_init(|| self.probe(zone, resolver)).await {
+            Ok(w) => w,
+            // probes failed (resolver trouble); try again with a later candidate
+            Err(()) => return false,
+        };
+        !wild.is_empty() && ips.iter().all(|ip| wild.contains(ip))
+    }
+
+    async fn probe(&self, zone: &str, resolver: &ResolverPool) -> Result<HashSet<IpAddr>, ()> {
+        let mut wild = HashSet::new();
+        let mut answered = 0;
+        for i in 0..self.probes {
+            let name = format!("{}.{}", self.random_label(zone, i), zone);
+            match resolver.lookup_ip(&name).await {
+                Ok(ips) => {
+                    answered += 1;
+                    wild.extend(ips);
+                }
+                Err(e) if matches!(e.kind(), hickory_resolver::error::ResolveErrorKind::NoRecordsFound { .. }) => {
+                    answered += 1;
+                }
+                Err(e) => warn!("wildcard: probe {} failed: {}", name, e),
+            }
+        }
+        if answered == 0 {
+            return Err(());
+        }
+        if !wild.is_empty() {
+            let mut list: Vec<String> = wild.iter().map(|ip| ip.to_string()).collect();
+            list.sort();
+            info!("wildcard: .{} answers random labels with {}; matching candidates are skipped", zone, list.join(", "));
+        }
+        Ok(wild)
+    }
+
+    // 20 random letters/digits, unlikely to be registered anywhere
+    fn random_label(&self, zone: &str, i: usize) -> String {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let mut h = self.seed.hash_one((zone, i));
+        (0..20)
+            .map(|n| {
+                if n % 12 == 0 {
+                    h = self.seed.hash_one((h, n));
+                }
+                let c = CHARS[(h % CHARS.len() as u64) as usize];
+                h /= CHARS.len() as u64;
+                c as char
+            })
+            .collect()
+    }
+}